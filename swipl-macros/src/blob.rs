@@ -15,6 +15,14 @@ pub fn arc_blob_macro(
 ) -> proc_macro::TokenStream {
     let crt = crate_token();
     let attr_def = parse_macro_input!(attr as ArcBlobAttr);
+    if let Some(serde) = attr_def.serde {
+        return syn::Error::new(
+            serde.span,
+            "arc blobs are shared through an Arc, use the swipl::serde::blob adapter on Arc fields instead",
+        )
+        .to_compile_error()
+        .into();
+    }
 
     let name_lit = attr_def.name;
     let name = name_lit.value();
@@ -363,6 +371,24 @@ pub fn clone_blob_macro(
         quote! {}
     };
 
+    let serde_implementation = if attr_def.serde.is_some() {
+        quote! {
+            impl #crt::__serde::Serialize for #item_name {
+                fn serialize<S: #crt::__serde::Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+                    #crt::serde::blob::serialize_clone_blob(self, serializer)
+                }
+            }
+
+            impl<'de> #crt::__serde::Deserialize<'de> for #item_name {
+                fn deserialize<D: #crt::__serde::Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+                    #crt::serde::blob::deserialize_clone_blob(deserializer)
+                }
+            }
+        }
+    } else {
+        quote! {}
+    };
+
     let result = quote! {
         #item_def
 
@@ -482,6 +508,8 @@ pub fn clone_blob_macro(
         }
 
         #default_implementation
+
+        #serde_implementation
     };
 
     result.into()
@@ -520,21 +548,30 @@ pub fn wrapped_clone_blob_macro(item: proc_macro::TokenStream) -> proc_macro::To
 struct ArcBlobAttr {
     name: LitStr,
     defaults: bool,
+    serde: Option<kw::serde>,
 }
 
 impl Parse for ArcBlobAttr {
     fn parse(input: ParseStream) -> Result<Self> {
         let name = input.parse()?;
 
-        let defaults = if input.peek(Token![,]) {
+        let mut defaults = false;
+        let mut serde = None;
+        while input.peek(Token![,]) {
             input.parse::<Token![,]>()?;
-            input.parse::<kw::defaults>()?;
-            true
-        } else {
-            false
-        };
+            if input.peek(kw::serde) {
+                serde = Some(input.parse::<kw::serde>()?);
+            } else {
+                input.parse::<kw::defaults>()?;
+                defaults = true;
+            }
+        }
 
-        Ok(Self { name, defaults })
+        Ok(Self {
+            name,
+            defaults,
+            serde,
+        })
     }
 }
 
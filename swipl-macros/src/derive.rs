@@ -0,0 +1,134 @@
+use proc_macro2::{Span, TokenStream};
+use quote::quote;
+use syn::{parse_macro_input, Data, DeriveInput, Error, Fields, Index, LitStr, Member};
+
+use crate::util::*;
+
+/// Turn a struct name like `MyPoint` into a functor name like `my_point`.
+fn functor_name(name: &str) -> String {
+    let mut result = String::with_capacity(name.len());
+    for (i, c) in name.chars().enumerate() {
+        if c.is_uppercase() {
+            if i != 0 {
+                result.push('_');
+            }
+            result.extend(c.to_lowercase());
+        } else {
+            result.push(c);
+        }
+    }
+
+    result
+}
+
+struct StructInfo {
+    functor_name: LitStr,
+    arity: u16,
+    members: Vec<Member>,
+}
+
+fn struct_info(input: &DeriveInput) -> Result<StructInfo, Error> {
+    let fields = match &input.data {
+        Data::Struct(s) => &s.fields,
+        _ => {
+            return Err(Error::new_spanned(
+                &input.ident,
+                "only structs can be derived to and from terms",
+            ))
+        }
+    };
+
+    let members: Vec<Member> = match fields {
+        Fields::Named(fields) => fields
+            .named
+            .iter()
+            .map(|f| Member::Named(f.ident.clone().unwrap()))
+            .collect(),
+        Fields::Unnamed(fields) => (0..fields.unnamed.len())
+            .map(|i| Member::Unnamed(Index::from(i)))
+            .collect(),
+        Fields::Unit => Vec::new(),
+    };
+
+    if members.len() > u16::MAX as usize {
+        return Err(Error::new_spanned(&input.ident, "too many fields"));
+    }
+
+    Ok(StructInfo {
+        functor_name: LitStr::new(&functor_name(&input.ident.to_string()), Span::call_site()),
+        arity: members.len() as u16,
+        members,
+    })
+}
+
+pub fn from_term_macro(stream: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = parse_macro_input!(stream as DeriveInput);
+    let info = match struct_info(&input) {
+        Ok(info) => info,
+        Err(e) => return e.to_compile_error().into(),
+    };
+    let crt = crate_token();
+
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    let functor_name = &info.functor_name;
+    let arity = info.arity;
+    let type_name = LitStr::new(&name.to_string(), Span::call_site());
+    let getters = info.members.iter().enumerate().map(|(i, member)| {
+        let index = i + 1;
+        quote! {#member: term.get_arg(#index).ok()?}
+    });
+
+    let result: TokenStream = quote! {
+        unsafe impl #impl_generics #crt::term::TermGetable for #name #ty_generics #where_clause {
+            fn get(term: &#crt::term::Term) -> Option<Self> {
+                term.assert_term_handling_possible();
+                let functor: #crt::functor::Functor = term.get().ok()?;
+                if functor != #crt::functor::Functor::new(#functor_name, #arity) {
+                    return None;
+                }
+
+                Some(Self {
+                    #(#getters),*
+                })
+            }
+
+            fn name() -> &'static str {
+                #type_name
+            }
+        }
+    };
+
+    result.into()
+}
+
+pub fn into_term_macro(stream: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = parse_macro_input!(stream as DeriveInput);
+    let info = match struct_info(&input) {
+        Ok(info) => info,
+        Err(e) => return e.to_compile_error().into(),
+    };
+    let crt = crate_token();
+
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    let functor_name = &info.functor_name;
+    let arity = info.arity;
+    let unifiers = info.members.iter().enumerate().map(|(i, member)| {
+        let index = i + 1;
+        quote! {term.unify_arg(#index, &self.#member).is_ok()}
+    });
+
+    let result: TokenStream = quote! {
+        unsafe impl #impl_generics #crt::term::Unifiable for #name #ty_generics #where_clause {
+            fn unify(&self, term: &#crt::term::Term) -> bool {
+                term.assert_term_handling_possible();
+
+                term.unify(#crt::functor::Functor::new(#functor_name, #arity)).is_ok()
+                    #(&& #unifiers)*
+            }
+        }
+    };
+
+    result.into()
+}
@@ -1,6 +1,8 @@
 use syn::custom_keyword;
+custom_keyword!(det);
 custom_keyword!(semidet);
 custom_keyword!(nondet);
 custom_keyword!(setup);
 custom_keyword!(call);
 custom_keyword!(defaults);
+custom_keyword!(serde);
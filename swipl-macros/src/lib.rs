@@ -6,6 +6,7 @@ mod util;
 
 mod atom;
 mod blob;
+mod derive;
 mod functor;
 mod pred;
 mod predicate;
@@ -30,10 +31,23 @@ use proc_macro::TokenStream;
 /// attribute, which specifies what prolog module this predicate is
 /// in.  When omitted, no module is assumed.
 ///
+/// Each function may be annotated with an `#[arity(N)]` attribute,
+/// which declares the arity of the predicate in prolog. The arity is
+/// checked against the number of arguments at compile time, so a
+/// declaration that does not match the predicate it wraps results in
+/// a compile error rather than a panic at runtime.
+///
 /// For each function declaration, a function will be generated which
 /// takes a context argument, followed by all declared arguments, and
 /// returns a query opened in that context.
 ///
+/// A declaration may also list output arguments, using the format
+/// `fn <predicate>(..args..) -> (..outputs..);`. The outputs are
+/// passed to the predicate after the other arguments. The generated
+/// function then calls the predicate once, and returns the outputs
+/// deserialized into a tuple. This requires the `serde` feature of
+/// the swipl crate.
+///
 /// Example:
 /// ```ignore
 /// prolog! {
@@ -41,8 +55,12 @@ use proc_macro::TokenStream;
 ///     #[name("nl")]
 ///     fn print_a_newline();
 ///     #[module("zlib")]
+///     #[arity(3)]
 ///     pub fn zopen(stream, zstream, options);
+///     fn divmod(dividend, divisor) -> (quotient, remainder);
 /// }
+///
+/// let (q, r): (u64, u64) = divmod(&context, &dividend, &divisor)?;
 /// ```
 #[proc_macro]
 pub fn prolog(stream: TokenStream) -> TokenStream {
@@ -75,7 +93,7 @@ pub fn pred(stream: TokenStream) -> TokenStream {
 /// Define foreign predicates written in rust for use in prolog.
 ///
 /// The `predicates!` macro takes an arbitrary amount of predicate
-/// definitions. These definitions may be semidet or
+/// definitions. These definitions may be det, semidet or
 /// nondet. Optionally, a visibility specifier like `pub` may be used
 /// to change the visibility of the generated functions. These
 /// definitions look somewhat like ordinary rust functions. However,
@@ -103,9 +121,7 @@ pub fn pred(stream: TokenStream) -> TokenStream {
 /// The first kind of predicate that you can define is a semidet
 /// predicate. Semidet, or semideterministic, means that this
 /// predicate is only going to have one result, and it could either be
-/// success or failure. Note that this also covers the deterministic
-/// case - to implement a deterministic predicate, just ensure that
-/// your predicate does not fail.
+/// success or failure.
 ///
 /// Semidet predicates return a `PrologResult<()>`, which also happens
 /// to be the type returned by most of the functions in the `swipl`
@@ -150,6 +166,26 @@ pub fn pred(stream: TokenStream) -> TokenStream {
 /// register_throw_if_not_42();
 /// ```
 ///
+/// # Deterministic predicates
+/// A det, or deterministic, predicate is a semidet predicate that is
+/// not supposed to fail. It is defined in the same way, using `det`
+/// instead of `semidet`. If the body returns a failure anyway, a
+/// `determinism_error` is raised instead, just like SWI-Prolog does
+/// for predicates declared with `det/1`. Exceptions are passed on
+/// as usual.
+///
+/// ## Examples
+/// ```ignore
+/// predicates! {
+///     // raises a determinism error if term is not an integer
+///     det fn double(_context, term, result) {
+///         let num: i64 = term.get()?;
+///
+///         result.unify(num * 2)
+///     }
+/// }
+/// ```
+///
 /// # Nondeterministic predicates
 /// Nondet or nondeterministic predicates are a bit more complex to
 /// implement. Instead of just one block which returns success or
@@ -319,3 +355,38 @@ pub fn atom(item: TokenStream) -> TokenStream {
 pub fn functor(item: TokenStream) -> TokenStream {
     functor::functor_macro(item)
 }
+
+/// Derive [TermGetable](https://terminusdb-labs.github.io/swipl-rs/swipl/term/trait.TermGetable.html) for a struct.
+///
+/// The struct is read from a compound term whose name is the struct
+/// name in snake case, and whose arguments are the struct fields in
+/// declaration order. Each field type must itself be `TermGetable`.
+///
+/// # Examples
+/// ```ignore
+/// #[derive(FromTerm, IntoTerm)]
+/// struct Point {
+///     x: i64,
+///     y: i64,
+/// }
+///
+/// // retrieves a Point from a term like point(1, 2)
+/// let point: Point = term.get()?;
+/// ```
+#[proc_macro_derive(FromTerm)]
+pub fn from_term(item: TokenStream) -> TokenStream {
+    derive::from_term_macro(item)
+}
+
+/// Derive [Unifiable](https://terminusdb-labs.github.io/swipl-rs/swipl/term/trait.Unifiable.html) for a struct.
+///
+/// The struct is unified with a compound term whose name is the
+/// struct name in snake case, and whose arguments are the struct
+/// fields in declaration order. Each field type must itself be
+/// `Unifiable`.
+///
+/// See [FromTerm](derive.FromTerm.html) for an example.
+#[proc_macro_derive(IntoTerm)]
+pub fn into_term(item: TokenStream) -> TokenStream {
+    derive::into_term_macro(item)
+}
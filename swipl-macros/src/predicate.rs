@@ -33,7 +33,9 @@ impl AttributedForeignPredicateDefinition {
         // - a register function
         // - TODO a documented frontend for calling from rust as if this is a query
         let def = self.predicate.generate_definition();
-        let (trampoline_name, trampoline) = self.predicate.generate_trampoline();
+        let (trampoline_name, trampoline) = self
+            .predicate
+            .generate_trampoline(self.predicate_name.as_ref());
         let registration = self.predicate.generate_registration(
             &trampoline_name,
             &self.visibility,
@@ -76,7 +78,7 @@ impl Parse for AttributedForeignPredicateDefinition {
 
 trait ForeignPredicateDefinitionImpl {
     fn generate_definition(&self) -> TokenStream;
-    fn generate_trampoline(&self) -> (Ident, TokenStream);
+    fn generate_trampoline(&self, name: Option<&LitStr>) -> (Ident, TokenStream);
     fn generate_registration(
         &self,
         trampoline_name: &Ident,
@@ -100,10 +102,10 @@ impl ForeignPredicateDefinitionImpl for ForeignPredicateDefinition {
         }
     }
 
-    fn generate_trampoline(&self) -> (Ident, TokenStream) {
+    fn generate_trampoline(&self, name: Option<&LitStr>) -> (Ident, TokenStream) {
         match self {
-            Self::Semidet(d) => d.generate_trampoline(),
-            Self::Nondet(d) => d.generate_trampoline(),
+            Self::Semidet(d) => d.generate_trampoline(name),
+            Self::Nondet(d) => d.generate_trampoline(name),
         }
     }
 
@@ -127,7 +129,7 @@ impl ForeignPredicateDefinitionImpl for ForeignPredicateDefinition {
 
 impl Parse for ForeignPredicateDefinition {
     fn parse(input: ParseStream) -> Result<Self> {
-        if input.peek(kw::semidet) {
+        if input.peek(kw::semidet) || input.peek(kw::det) {
             Ok(Self::Semidet(input.parse()?))
         } else if input.peek(kw::nondet) {
             Ok(Self::Nondet(input.parse()?))
@@ -138,6 +140,7 @@ impl Parse for ForeignPredicateDefinition {
 }
 
 struct SemidetForeignPredicateDefinition {
+    det: bool,
     predicate_rust_name: Ident,
     params: Vec<Ident>,
     body: Block,
@@ -145,7 +148,13 @@ struct SemidetForeignPredicateDefinition {
 
 impl Parse for SemidetForeignPredicateDefinition {
     fn parse(input: ParseStream) -> Result<Self> {
-        input.parse::<kw::semidet>()?;
+        let det = if input.peek(kw::det) {
+            input.parse::<kw::det>()?;
+            true
+        } else {
+            input.parse::<kw::semidet>()?;
+            false
+        };
 
         input.parse::<Token![fn]>()?;
         let name: Ident = input.parse()?;
@@ -165,6 +174,7 @@ impl Parse for SemidetForeignPredicateDefinition {
         let body = input.parse()?;
 
         Ok(Self {
+            det,
             predicate_rust_name: name,
             params,
             body,
@@ -190,7 +200,7 @@ impl ForeignPredicateDefinitionImpl for SemidetForeignPredicateDefinition {
         }
     }
 
-    fn generate_trampoline(&self) -> (Ident, TokenStream) {
+    fn generate_trampoline(&self, name: Option<&LitStr>) -> (Ident, TokenStream) {
         let crt = crate_token();
         let definition_name = semidet_definition_name(&self.predicate_rust_name);
         let trampoline_name = Ident::new(
@@ -199,6 +209,25 @@ impl ForeignPredicateDefinitionImpl for SemidetForeignPredicateDefinition {
         );
         let known_arity = self.params.len() - 1;
         let term_args = (0..known_arity).map(|i| quote! {&terms[#i]});
+        let call = quote! {
+            #definition_name(&context,
+                             #(#term_args),*)
+        };
+        let call = if self.det {
+            let rust_name = format!("{}", self.predicate_rust_name);
+            let name_lit = match name {
+                None => quote! {#rust_name},
+                Some(n) => quote! {#n},
+            };
+            quote! {
+                match #call {
+                    Err(#crt::result::PrologError::Failure) => #crt::context::raise_determinism_error(&context, #name_lit, #known_arity),
+                    result => result,
+                }
+            }
+        } else {
+            call
+        };
         (
             trampoline_name.clone(),
             quote! {
@@ -225,8 +254,7 @@ impl ForeignPredicateDefinitionImpl for SemidetForeignPredicateDefinition {
 
                         let terms: [#crt::term::Term;#known_arity] = std::mem::transmute(terms);
 
-                        #definition_name(&context,
-                                         #(#term_args),*)
+                        #call
                     });
 
                     match result {
@@ -386,7 +414,7 @@ impl ForeignPredicateDefinitionImpl for NondetForeignPredicateDefinition {
         }
     }
 
-    fn generate_trampoline(&self) -> (Ident, TokenStream) {
+    fn generate_trampoline(&self, _name: Option<&LitStr>) -> (Ident, TokenStream) {
         let crt = crate_token();
         let trampoline_name = Ident::new(
             &format!("__{}_trampoline", self.predicate_rust_name),
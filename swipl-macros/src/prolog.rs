@@ -3,7 +3,7 @@ use proc_macro2::{self, Span, TokenStream};
 use quote::quote;
 use syn::parse::{Parse, ParseStream, Result};
 use syn::punctuated::Punctuated;
-use syn::{parenthesized, parse_macro_input, Attribute, Ident, LitStr, Token, Visibility};
+use syn::{parenthesized, parse_macro_input, Attribute, Ident, LitInt, LitStr, Token, Visibility};
 
 pub fn prolog_macro(stream: proc_macro::TokenStream) -> proc_macro::TokenStream {
     let predicates = parse_macro_input!(stream as PrologPredicateBlock);
@@ -22,6 +22,7 @@ struct PrologPredicate {
     doc: Option<Attribute>,
     visibility: Visibility,
     params: Vec<Ident>,
+    outputs: Option<Vec<Ident>>,
 }
 
 impl Parse for PrologPredicate {
@@ -30,6 +31,7 @@ impl Parse for PrologPredicate {
         let mut doc = None;
         let mut predicate_name = None;
         let mut predicate_module = None;
+        let mut arity: Option<LitInt> = None;
         for attr in attrs {
             if attr.path().is_ident("doc") {
                 doc = Some(attr);
@@ -37,6 +39,8 @@ impl Parse for PrologPredicate {
                 predicate_name = Some(attr.parse_args()?);
             } else if attr.path().is_ident("module") {
                 predicate_module = Some(attr.parse_args()?);
+            } else if attr.path().is_ident("arity") {
+                arity = Some(attr.parse_args()?);
             }
         }
         let visibility = input.parse()?;
@@ -47,6 +51,30 @@ impl Parse for PrologPredicate {
         let params_punct: Punctuated<Ident, Token![,]> =
             Punctuated::parse_terminated(&params_stream)?;
         let params: Vec<_> = params_punct.into_iter().collect();
+        let outputs: Option<Vec<Ident>> = if input.peek(Token![->]) {
+            input.parse::<Token![->]>()?;
+            let outputs_stream;
+            parenthesized!(outputs_stream in input);
+            let outputs_punct: Punctuated<Ident, Token![,]> =
+                Punctuated::parse_terminated(&outputs_stream)?;
+            Some(outputs_punct.into_iter().collect())
+        } else {
+            None
+        };
+
+        if let Some(arity) = arity {
+            let expected: usize = arity.base10_parse()?;
+            let actual = params.len() + outputs.as_ref().map(|o| o.len()).unwrap_or(0);
+            if expected != actual {
+                return Err(syn::Error::new(
+                    arity.span(),
+                    format!(
+                        "predicate {} is declared with arity {}, but takes {} arguments",
+                        name, expected, actual
+                    ),
+                ));
+            }
+        }
 
         Ok(Self {
             predicate_rust_name: name,
@@ -55,6 +83,7 @@ impl Parse for PrologPredicate {
             doc,
             visibility,
             params,
+            outputs,
         })
     }
 }
@@ -69,7 +98,8 @@ impl PrologPredicate {
         );
         let rust_name = self.predicate_rust_name;
         let params = self.params;
-        let params_len = params.len();
+        let outputs = self.outputs.unwrap_or_default();
+        let params_len = params.len() + outputs.len();
         let doc = self.doc;
         let predicate_name = match self.predicate_name {
             Some(name) => name.value(),
@@ -80,6 +110,20 @@ impl PrologPredicate {
             None => "user".to_string(),
         };
         let visibility = self.visibility;
+        if !outputs.is_empty() {
+            return output_definition(
+                &crt,
+                &pred_static_ident,
+                &predicate_module,
+                &predicate_name,
+                doc,
+                visibility,
+                rust_name,
+                params,
+                outputs,
+            );
+        }
+
         let gen = quote! {
             #[allow(clippy::non_upper_case_globals)]
             static #pred_static_ident: #crt::callable::LazyCallablePredicate<#params_len> = #crt::callable::LazyCallablePredicate::new(Some(#predicate_module), #predicate_name);
@@ -99,6 +143,58 @@ impl PrologPredicate {
     }
 }
 
+/// Generate a function which calls the predicate once, returning
+/// the deserialized output arguments as a tuple.
+#[allow(clippy::too_many_arguments)]
+fn output_definition(
+    crt: &TokenStream,
+    pred_static_ident: &Ident,
+    predicate_module: &str,
+    predicate_name: &str,
+    doc: Option<Attribute>,
+    visibility: Visibility,
+    rust_name: Ident,
+    params: Vec<Ident>,
+    outputs: Vec<Ident>,
+) -> TokenStream {
+    let params_len = params.len() + outputs.len();
+    let output_types: Vec<Ident> = outputs
+        .iter()
+        .map(|o| Ident::new(&format!("{}Out", camel_case(&o.to_string())), o.span()))
+        .collect();
+
+    quote! {
+        #[allow(clippy::non_upper_case_globals)]
+        static #pred_static_ident: #crt::callable::LazyCallablePredicate<#params_len> = #crt::callable::LazyCallablePredicate::new(Some(#predicate_module), #predicate_name);
+
+        #doc
+        #visibility fn #rust_name<'a, T:#crt::context::QueryableContextType, #(#output_types: #crt::__serde::de::DeserializeOwned),*>(swipl_context: &'a #crt::context::Context<'a, T>, #(#params: &#crt::term::Term<'a>),*) -> #crt::result::PrologResult<(#(#output_types,)*)> {
+            swipl_context.assert_activated();
+            let swipl_frame = swipl_context.open_frame();
+            #(let #outputs = swipl_frame.new_term_ref();)*
+            let swipl_call_args = [#(#params,)* #(&#outputs),*];
+
+            swipl_frame.call_once(#pred_static_ident.as_callable(), swipl_call_args)?;
+            let swipl_result = (#(swipl_frame.try_or_die_generic(#crt::term::de::from_term::<_, #output_types>(&swipl_frame, &#outputs))?,)*);
+            swipl_frame.close();
+
+            Ok(swipl_result)
+        }
+    }
+}
+
+/// Turn a name like `some_output` into `SomeOutput`.
+fn camel_case(name: &str) -> String {
+    name.split('_')
+        .filter(|part| !part.is_empty())
+        .map(|part| {
+            let mut chars = part.chars();
+            let first = chars.next().unwrap();
+            first.to_uppercase().chain(chars).collect::<String>()
+        })
+        .collect()
+}
+
 struct PrologPredicateBlock {
     predicates: Vec<PrologPredicate>,
 }
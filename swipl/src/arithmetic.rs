@@ -0,0 +1,160 @@
+//! Arithmetic functions implemented in rust.
+//!
+//! SWI-Prolog allows extending arithmetic with user-defined functions
+//! through `library(arithmetic)`. Such a function `name/N` is backed
+//! by a predicate `name/N+1`, which receives the evaluated arguments
+//! and unifies its last argument with the result. Arithmetic in code
+//! that is loaded after the function was registered is rewritten to
+//! call this predicate, so `X is double(21)` calls into rust.
+//!
+//! Note that this rewriting happens through goal expansion. Calls to
+//! `is/2` which were not expanded, such as goals constructed at
+//! runtime and called with `call/1`, do not know about these
+//! functions. Such goals can be expanded first using
+//! `expand_goal/2`.
+use crate::atom::*;
+use crate::context::*;
+use crate::functor::*;
+use crate::module::*;
+use crate::result::*;
+use crate::term::*;
+
+use lazy_static::*;
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use swipl_macros::{pred, predicates, term};
+
+type ArithmeticFunction =
+    Arc<dyn for<'a> Fn(&[Term<'a>], &Term<'a>) -> PrologResult<()> + Send + Sync>;
+
+lazy_static! {
+    static ref FUNCTIONS: RwLock<HashMap<(String, usize), ArithmeticFunction>> =
+        RwLock::new(HashMap::new());
+}
+
+predicates! {
+    #[name("$swipl_rs_arithmetic_function")]
+    semidet fn swipl_rs_arithmetic_function(context, name, args, result) {
+        let mut values = Vec::new();
+        for arg in context.term_list_iter(args) {
+            values.push(context.eval(&arg)?);
+        }
+
+        let key = name.get_atom_name(|name| name.map(|name| (name.to_string(), values.len())))?;
+        let function = key.and_then(|key| FUNCTIONS.read().unwrap().get(&key).cloned());
+        match function {
+            Some(function) => function(&values, result),
+            None => Err(PrologError::Failure),
+        }
+    }
+}
+
+/// Module in which the predicates backing arithmetic functions are defined.
+const ARITHMETIC_MODULE: &str = "$swipl_rs_arithmetic";
+
+impl<'a, T: QueryableContextType> Context<'a, T> {
+    /// Register a rust function as the arithmetic function `name/arity`.
+    ///
+    /// The function is given the evaluated arguments, and is to
+    /// unify the given result term with a number. This defines the
+    /// predicate `name/arity+1` in a private module, and declares it
+    /// as an arithmetic function using the `arithmetic_function/1`
+    /// directive from `library(arithmetic)`. Registering a function
+    /// for a name and arity that was registered before replaces the
+    /// previous function.
+    ///
+    /// See the [module documentation](crate::arithmetic) for the
+    /// limitations of user-defined arithmetic functions.
+    pub fn register_arithmetic_function<
+        F: for<'b> Fn(&[Term<'b>], &Term<'b>) -> PrologResult<()> + Send + Sync + 'static,
+    >(
+        &self,
+        name: &str,
+        arity: u16,
+        function: F,
+    ) -> PrologResult<()> {
+        FUNCTIONS
+            .write()
+            .unwrap()
+            .insert((name.to_string(), arity as usize), Arc::new(function));
+        register_swipl_rs_arithmetic_function();
+
+        let frame = self.open_frame();
+        let args = frame.new_term_refs_vec(arity as usize);
+        let [name_term, arity_term, head, arg_list, result, call, clause] = frame.new_term_refs();
+        let [indicator, declaration, use_arithmetic, source, id] = frame.new_term_refs();
+        name_term.unify(Atomable::from(name))?;
+        arity_term.unify(arity as u64)?;
+
+        // name(Arg1, ..., ArgN, Result) :-
+        //     '$swipl_rs_arithmetic_function'(name, [Arg1, ..., ArgN], Result).
+        head.unify(Functor::new(name, arity + 1))?;
+        for (i, arg) in args.iter().enumerate() {
+            head.unify_arg(i + 1, arg)?;
+        }
+        head.unify_arg(arity as usize + 1, &result)?;
+        arg_list.unify(args.as_slice())?;
+        call.unify(Functor::new("$swipl_rs_arithmetic_function", 3))?;
+        call.unify_arg(1, &name_term)?;
+        call.unify_arg(2, &arg_list)?;
+        call.unify_arg(3, &result)?;
+        clause.unify(Functor::new(":-", 2))?;
+        clause.unify_arg(1, &head)?;
+        clause.unify_arg(2, &call)?;
+
+        // The arithmetic_function/1 directive only works while
+        // loading source, so the declaration and the predicate are
+        // loaded from a string. Each function gets its own source,
+        // so that reloading it only replaces this function.
+        indicator.unify(Functor::new("/", 2))?;
+        indicator.unify_arg(1, &name_term)?;
+        indicator.unify_arg(2, &arity_term)?;
+        let library = term! {frame: use_module(library(arithmetic))}?;
+        use_arithmetic.unify(Functor::new(":-", 1))?;
+        use_arithmetic.unify_arg(1, &library)?;
+        let arithmetic_function = term! {frame: arithmetic_function(#&indicator)}?;
+        declaration.unify(Functor::new(":-", 1))?;
+        declaration.unify_arg(1, &arithmetic_function)?;
+        let directives = term! {frame: [#&use_arithmetic, #&declaration, #&clause]}?;
+        let write = term! {frame: with_output_to(string(#&source), forall(member(Clause, #&directives), portray_clause(Clause)))}?;
+        frame.call_once(pred!("call/1"), [&write])?;
+
+        id.unify(Atomable::from(
+            format!("{}/{}/{}", ARITHMETIC_MODULE, name, arity).as_str(),
+        ))?;
+        let load = term! {frame: setup_call_cleanup(open_string(#&source, Stream), load_files(#&id, [stream(Stream)]), close(Stream))}?;
+        let query = frame.open_with_module(
+            pred!("call/1"),
+            Some(Module::new(ARITHMETIC_MODULE)),
+            [&load],
+        );
+        query.next_solution()?;
+        query.cut();
+        frame.close();
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::*;
+
+    #[test]
+    fn register_and_evaluate_arithmetic_function() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        context.register_arithmetic_function("double", 1, |args, result| {
+            result.unify(args[0].get::<i64>()? * 2)
+        })?;
+
+        let goal =
+            context.term_from_string("(expand_goal((X is double(21)), G), call(G), X == 42)")?;
+        context.call_term_once(&goal)?;
+
+        Ok(())
+    }
+}
@@ -15,10 +15,13 @@
 use super::context::*;
 use super::engine::*;
 use super::fli::*;
+use super::functor::*;
 use super::init::*;
 use super::result::*;
 use super::term::*;
 use crate::{term_getable, term_putable, unifiable};
+#[cfg(test)]
+use std::cell::Cell;
 use std::convert::TryInto;
 use std::os::raw::c_char;
 use std::sync::atomic::{AtomicUsize, Ordering};
@@ -27,6 +30,11 @@ use std::sync::atomic::{AtomicUsize, Ordering};
 ///
 /// When created, the underlying atom will have its reference count
 /// increased. When dropped, the reference count will decrease.
+///
+/// As atoms are interned, two atoms with the same text are the same
+/// atom. Equality and hashing therefore work on the underlying
+/// `atom_t` handle rather than on the text, which makes them cheap
+/// and makes atoms suitable as keys in a map.
 #[derive(PartialEq, Eq, Hash, Debug)]
 pub struct Atom {
     atom: atom_t,
@@ -81,11 +89,29 @@ impl Atom {
         unsafe { Atom::wrap(atom) }
     }
 
+    /// Returns true if this is the same atom as the other.
+    ///
+    /// This compares the underlying `atom_t` handles, just like `==`
+    /// does. No text is compared.
+    pub fn same_atom(&self, other: &Atom) -> bool {
+        self.atom == other.atom
+    }
+
     /// Return the underlying `atom_t` which SWI-Prolog uses to refer to the atom.
     pub fn atom_ptr(&self) -> atom_t {
         self.atom
     }
 
+    /// Create a functor with this atom as its name and the given arity.
+    ///
+    /// Unlike `Functor::new(atom.name(), arity)`, this does not
+    /// retrieve and re-intern the name of the atom.
+    ///
+    /// This will panic if no prolog engine is active on this thread.
+    pub fn as_functor(&self, arity: u16) -> Functor {
+        Functor::new(self, arity)
+    }
+
     /// Retrieve the name of this atom, that is, the string with which it was created.
     ///
     /// This will panic if no prolog engine is active on this thread.
@@ -109,12 +135,75 @@ impl Atom {
         name.unwrap()
     }
 
+    /// Compare the text of this atom with the given string.
+    ///
+    /// Unlike comparing against `Atom::new(s)`, this does not create
+    /// a new atom, and for most atoms does not allocate either. Atoms
+    /// that are not text atoms, such as blobs, never compare equal.
+    ///
+    /// This will panic if no prolog engine is active on this thread.
+    pub fn eq_str(&self, s: &str) -> bool {
+        assert_some_engine_is_active();
+
+        let mut len = 0;
+        let ptr = unsafe { PL_atom_nchars(self.atom, &mut len) };
+        if !ptr.is_null() {
+            // text is ISO latin-1, so each byte is exactly one character
+            let text = unsafe { std::slice::from_raw_parts(ptr as *const u8, len) };
+            return s.chars().eq(text.iter().map(|&c| c as char));
+        }
+
+        let ptr = unsafe { PL_atom_wchars(self.atom, &mut len) };
+        if ptr.is_null() {
+            return false;
+        }
+
+        let text = unsafe { std::slice::from_raw_parts(ptr, len) };
+        #[cfg(not(windows))]
+        let result = s
+            .chars()
+            .map(|c| c as u32)
+            .eq(text.iter().map(|&c| c as u32));
+        #[cfg(windows)]
+        let result = s.encode_utf16().eq(text.iter().map(|&c| c as u16));
+
+        result
+    }
+
     /// Increase the reference counter for this atom.
     pub(crate) fn increment_refcount(&self) {
-        unsafe { PL_register_atom(self.atom) }
+        unsafe { register_atom(self.atom) }
     }
 }
 
+#[cfg(test)]
+thread_local! {
+    /// The number of reference count operations done on this thread,
+    /// so tests can check that a code path leaves them alone.
+    pub(crate) static REFCOUNT_OPERATIONS: Cell<usize> = Cell::new(0);
+}
+
+/// Increase the reference counter of the given atom.
+///
+/// # Safety
+/// This is unsafe because the atom has to be valid.
+pub(crate) unsafe fn register_atom(atom: atom_t) {
+    #[cfg(test)]
+    REFCOUNT_OPERATIONS.with(|ops| ops.set(ops.get() + 1));
+    PL_register_atom(atom)
+}
+
+/// Decrease the reference counter of the given atom.
+///
+/// # Safety
+/// This is unsafe because the atom has to be valid, and it must have
+/// been registered before.
+pub(crate) unsafe fn unregister_atom(atom: atom_t) {
+    #[cfg(test)]
+    REFCOUNT_OPERATIONS.with(|ops| ops.set(ops.get() + 1));
+    PL_unregister_atom(atom)
+}
+
 impl ToString for Atom {
     fn to_string(&self) -> String {
         self.name()
@@ -130,7 +219,7 @@ impl From<Atom> for String {
 impl Clone for Atom {
     fn clone(&self) -> Self {
         assert_some_engine_is_active();
-        unsafe { PL_register_atom(self.atom) };
+        unsafe { register_atom(self.atom) };
         Atom { atom: self.atom }
     }
 }
@@ -139,9 +228,82 @@ impl Drop for Atom {
     fn drop(&mut self) {
         assert_some_engine_is_active();
         unsafe {
-            PL_unregister_atom(self.atom);
+            unregister_atom(self.atom);
+        }
+    }
+}
+
+/// An atom which is borrowed without holding a reference count.
+///
+/// Unlike [Atom], creating or dropping a `BorrowedAtom` does not
+/// touch the reference count of the underlying atom. This makes it
+/// cheaper for transient use, such as deserializing into a structure
+/// which is only inspected and then thrown away.
+///
+/// Through `Deref`, a `BorrowedAtom` can be used as an `&Atom`.
+///
+/// # Lifetime constraints
+/// Without a reference count, the atom is only guaranteed to stay
+/// alive while something else keeps it alive. When deserialized from
+/// a term, that is the term itself. The lifetime `'a` ties the atom
+/// to the context it was retrieved in, but it is up to the user to
+/// not use the atom after the term it came from was modified, such
+/// as by rewinding or discarding a frame. If the atom needs to
+/// outlive that, turn it into an owned atom with
+/// [to_atom](BorrowedAtom::to_atom).
+pub struct BorrowedAtom<'a> {
+    atom: std::mem::ManuallyDrop<Atom>,
+    _lifetime: std::marker::PhantomData<&'a ()>,
+}
+
+impl<'a> BorrowedAtom<'a> {
+    /// Wrap an `atom_t` without increasing its reference count.
+    ///
+    /// # Safety
+    /// The caller has to ensure that the atom stays alive for as long
+    /// as this wrapper is in use.
+    pub unsafe fn wrap(atom: atom_t) -> BorrowedAtom<'a> {
+        BorrowedAtom {
+            atom: std::mem::ManuallyDrop::new(Atom::wrap(atom)),
+            _lifetime: std::marker::PhantomData,
         }
     }
+
+    /// Turn this into an owned atom, increasing its reference count.
+    pub fn to_atom(&self) -> Atom {
+        (*self.atom).clone()
+    }
+}
+
+impl<'a> std::ops::Deref for BorrowedAtom<'a> {
+    type Target = Atom;
+
+    fn deref(&self) -> &Atom {
+        &self.atom
+    }
+}
+
+impl<'a> PartialEq for BorrowedAtom<'a> {
+    fn eq(&self, other: &BorrowedAtom) -> bool {
+        self.atom_ptr() == other.atom_ptr()
+    }
+}
+
+impl<'a> PartialEq<Atom> for BorrowedAtom<'a> {
+    fn eq(&self, other: &Atom) -> bool {
+        self.atom_ptr() == other.atom_ptr()
+    }
+}
+
+impl<'a> Eq for BorrowedAtom<'a> {}
+
+impl<'a> std::fmt::Debug for BorrowedAtom<'a> {
+    fn fmt(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        formatter
+            .debug_tuple("BorrowedAtom")
+            .field(&self.atom_ptr())
+            .finish()
+    }
 }
 
 unifiable! {
@@ -520,6 +682,22 @@ mod tests {
         assert_eq!(a1, a3);
     }
 
+    #[test]
+    fn compare_atom_with_str() {
+        let engine = Engine::new();
+        let _activation = engine.activate();
+
+        let atom = Atom::new("foo");
+        assert!(atom.eq_str("foo"));
+        assert!(!atom.eq_str("bar"));
+        assert!(!atom.eq_str("fo"));
+        assert!(!atom.eq_str("fooo"));
+
+        let atom = Atom::new("naïve ☃");
+        assert!(atom.eq_str("naïve ☃"));
+        assert!(!atom.eq_str("naive ☃"));
+    }
+
     #[test]
     fn clone_atom() {
         let engine = Engine::new();
@@ -681,4 +859,20 @@ mod tests {
         let a2 = "bar".as_atom();
         assert_eq!(a1, a2);
     }
+
+    #[test]
+    fn equal_atoms_are_the_same_atom() {
+        let engine = Engine::new();
+        let _activation = engine.activate();
+
+        let foo1 = Atom::new("foo");
+        let foo2 = Atom::new("foo");
+        let bar = Atom::new("bar");
+
+        assert_eq!(foo1.atom_ptr(), foo2.atom_ptr());
+        assert!(foo1.same_atom(&foo2));
+        assert_eq!(foo1, foo2);
+        assert!(!foo1.same_atom(&bar));
+        assert_ne!(foo1, bar);
+    }
 }
@@ -121,6 +121,11 @@
 //! This type of blob copies its contents to and from SWI-Prolog when
 //! getting, putting or unifying terms with the data.
 //!
+//! With the additional keyword 'serde', the macro also implements
+//! serde's `Serialize` and `Deserialize` for the type, storing it as
+//! a blob whenever it is serialized into a prolog term. See
+//! [crate::serde::blob] for details.
+//!
 //! ## Examples
 //! ```
 //! # use swipl::prelude::*;
@@ -292,9 +297,11 @@ pub trait ArcBlobBase {
 pub trait ArcBlobImpl: ArcBlobBase + Send + Sync + Unpin {
     /// Compare two values, returning an Ordering.
     ///
-    /// This is used from SWI-Prolog when sorting lists. The default
-    /// implementation returns `Ordering::Equal`, effectively
-    /// providing no sorting information.
+    /// This is used from SWI-Prolog to order two blobs of this type
+    /// in the standard order of terms, such as in `compare/3` and
+    /// when sorting lists. The default implementation returns
+    /// `Ordering::Equal`, effectively providing no sorting
+    /// information.
     fn compare(&self, _other: &Self) -> Ordering {
         Ordering::Equal
     }
@@ -600,9 +607,11 @@ pub trait WrappedArcBlobBase {
 pub trait WrappedArcBlobImpl: WrappedArcBlobBase {
     /// Compare two values, returning an Ordering.
     ///
-    /// This is used from SWI-Prolog when sorting lists. The default
-    /// implementation returns `Ordering::Equal`, effectively
-    /// providing no sorting information.
+    /// This is used from SWI-Prolog to order two blobs of this type
+    /// in the standard order of terms, such as in `compare/3` and
+    /// when sorting lists. The default implementation returns
+    /// `Ordering::Equal`, effectively providing no sorting
+    /// information.
     fn compare(_this: &Self::Inner, _other: &Self::Inner) -> Ordering {
         Ordering::Equal
     }
@@ -653,9 +662,11 @@ pub trait CloneBlobBase {
 pub trait CloneBlobImpl: CloneBlobBase + Sized + Sync + Clone {
     /// Compare two values, returning an Ordering.
     ///
-    /// This is used from SWI-Prolog when sorting lists. The default
-    /// implementation returns `Ordering::Equal`, effectively
-    /// providing no sorting information.
+    /// This is used from SWI-Prolog to order two blobs of this type
+    /// in the standard order of terms, such as in `compare/3` and
+    /// when sorting lists. The default implementation returns
+    /// `Ordering::Equal`, effectively providing no sorting
+    /// information.
     fn compare(&self, _other: &Self) -> Ordering {
         Ordering::Equal
     }
@@ -684,3 +695,88 @@ pub unsafe trait CloneBlob: CloneBlobImpl {
     /// Return a blob definition for this CloneBlob.
     fn get_blob_definition() -> &'static fli::PL_blob_t;
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::prelude::*;
+    use std::sync::Arc;
+
+    #[arc_blob("blob_a", defaults)]
+    struct BlobA {
+        num: u64,
+    }
+
+    #[arc_blob("blob_b", defaults)]
+    struct BlobB {
+        num: u64,
+    }
+
+    #[clone_blob("blob_c", defaults)]
+    #[derive(Clone)]
+    struct BlobC {
+        num: u64,
+    }
+
+    #[test]
+    fn get_blob_of_wrong_type_fails() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let term = context.new_term_ref();
+        term.unify(&Arc::new(BlobB { num: 42 }))?;
+
+        let retrieved: Arc<BlobB> = term.get()?;
+        assert_eq!(42, retrieved.num);
+        assert!(matches!(term.get::<Arc<BlobA>>(), Err(e) if e.is_failure()));
+        assert!(matches!(term.get::<BlobC>(), Err(e) if e.is_failure()));
+
+        let term = context.new_term_ref();
+        term.unify(&BlobC { num: 24 })?;
+        assert_eq!(24, term.get::<BlobC>()?.num);
+        assert!(matches!(term.get::<Arc<BlobA>>(), Err(e) if e.is_failure()));
+
+        let term = context.new_term_ref();
+        term.unify(&Arc::new(BlobA { num: 7 }))?;
+        assert_eq!(7, term.get::<Arc<BlobA>>()?.num);
+        assert!(matches!(term.get::<Arc<BlobB>>(), Err(e) if e.is_failure()));
+
+        Ok(())
+    }
+
+    #[arc_blob("blob_d")]
+    struct BlobD {
+        num: u64,
+    }
+
+    impl ArcBlobImpl for BlobD {
+        fn compare(&self, other: &Self) -> std::cmp::Ordering {
+            self.num.cmp(&other.num)
+        }
+    }
+
+    #[test]
+    fn compare_distinct_blobs_by_content() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let [order, term1, term2, term3] = context.new_term_refs();
+        term1.unify(&Arc::new(BlobD { num: 42 }))?;
+        term2.unify(&Arc::new(BlobD { num: 42 }))?;
+        term3.unify(&Arc::new(BlobD { num: 43 }))?;
+        assert!(!Arc::ptr_eq(
+            &term1.get::<Arc<BlobD>>()?,
+            &term2.get::<Arc<BlobD>>()?
+        ));
+
+        context.call_once(pred!("compare/3"), [&order, &term1, &term2])?;
+        assert_eq!("=", order.get::<Atom>()?.name());
+
+        let order = context.new_term_ref();
+        context.call_once(pred!("compare/3"), [&order, &term1, &term3])?;
+        assert_eq!("<", order.get::<Atom>()?.name());
+
+        Ok(())
+    }
+}
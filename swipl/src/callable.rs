@@ -227,16 +227,57 @@ impl<'a, C: OpenCall> Context<'a, C> {
         Ok(())
     }
 
+    /// Retrieve up to `n` results, and then cut.
+    ///
+    /// For every result, the given function is called with a frame
+    /// opened on this query. The frame is closed afterwards, or
+    /// discarded if the function returned an error, in which case
+    /// that error is returned. This makes it safe to retrieve the
+    /// first few results of a goal with possibly infinitely many
+    /// solutions. Returns the number of results retrieved.
+    pub fn take_solutions<F: FnMut(&Context<Frame>) -> PrologResult<()>>(
+        self,
+        n: usize,
+        mut f: F,
+    ) -> PrologResult<usize> {
+        let mut count = 0;
+        while count < n {
+            let more = match self.next_solution() {
+                Ok(more) => more,
+                Err(PrologError::Failure) => break,
+                Err(e) => return Err(e),
+            };
+            count += 1;
+
+            let frame = self.open_frame();
+            match f(&frame) {
+                Ok(()) => frame.close(),
+                Err(e) => {
+                    frame.discard();
+                    return Err(e);
+                }
+            }
+
+            if !more {
+                break;
+            }
+        }
+        self.cut();
+
+        Ok(count)
+    }
+
     /// Retrieve one result, ignoring failures, and then cut.
     ///
     /// Exceptions will still be returned as such.
     pub fn ignore(self) -> PrologResult<()> {
-        if let Err(PrologError::Exception) = self.next_solution() {
-            Err(PrologError::Exception)
-        } else {
-            self.cut();
+        match self.next_solution() {
+            Err(e) if e.is_exception() => Err(e),
+            _ => {
+                self.cut();
 
-            Ok(())
+                Ok(())
+            }
         }
     }
 }
@@ -244,6 +285,27 @@ impl<'a, C: OpenCall> Context<'a, C> {
 unsafe impl<T: OpenCall> ContextType for T {}
 impl<T: OpenCall> FrameableContextType for T {}
 
+/// Returns the exit status if the given exception is `unwind(halt(Status))`.
+///
+/// This is the exception SWI-Prolog raises in an engine that calls
+/// `halt/1`, to unwind its stacks before halting.
+unsafe fn halt_status(exception: term_t) -> Option<i32> {
+    let arg = PL_new_term_ref();
+    let mut status = 0;
+    let is_halt = PL_is_functor(exception, Functor::new("unwind", 1).functor_ptr()) != 0
+        && PL_get_arg(1, exception, arg) != 0
+        && PL_is_functor(arg, Functor::new("halt", 1).functor_ptr()) != 0
+        && PL_get_arg(1, arg, arg) != 0
+        && PL_get_integer(arg, &mut status) != 0;
+    PL_reset_term_refs(arg);
+
+    if is_halt {
+        Some(status)
+    } else {
+        None
+    }
+}
+
 unsafe impl OpenCall for OpenQuery {
     fn next_solution(this: &Context<Self>) -> PrologResult<bool> {
         this.assert_activated();
@@ -251,10 +313,14 @@ unsafe impl OpenCall for OpenQuery {
         match result {
             -1 => {
                 let exception = unsafe { PL_exception(this.context.qid) };
+                let halt_status = unsafe { halt_status(exception) };
                 // rethrow this exception but as the special 0 exception which remains alive
                 unsafe { PL_raise_exception(exception) };
 
-                Err(PrologError::Exception)
+                match halt_status {
+                    Some(status) => Err(PrologError::Halt(status)),
+                    None => Err(PrologError::Exception),
+                }
             }
             0 => Err(PrologError::Failure),
             1 => Ok(true),
@@ -325,6 +391,30 @@ impl<const N: usize> Callable<N> for CallablePredicate<N> {
     }
 }
 
+/// A declaration in `prolog!` with an `#[arity(N)]` attribute has its arity checked at compile time.
+///
+/// A matching declaration compiles:
+/// ```
+/// use swipl::prelude::*;
+///
+/// prolog! {
+///     #[arity(2)]
+///     fn atom_length(atom, length);
+/// }
+/// ```
+///
+/// A mismatched declaration does not:
+/// ```compile_fail
+/// use swipl::prelude::*;
+///
+/// prolog! {
+///     #[arity(3)]
+///     fn atom_length(atom, length);
+/// }
+/// ```
+#[cfg(doctest)]
+pub struct PrologMacroArityCheck;
+
 #[cfg(test)]
 mod tests {
     use crate::prelude::*;
@@ -392,4 +482,32 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn take_a_limited_number_of_solutions() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let mut seen = 0;
+        let query = context.open(pred!("repeat/0"), []);
+        let count = query.take_solutions(3, |_| {
+            seen += 1;
+            Ok(())
+        })?;
+        assert_eq!(3, count);
+        assert_eq!(3, seen);
+
+        let x = context.new_term_ref();
+        let mut values = Vec::new();
+        let goal = term! {context: member(#&x, [1, 2])}?;
+        let count = context.open_call(&goal).take_solutions(5, |_| {
+            values.push(x.get::<u64>()?);
+            Ok(())
+        })?;
+        assert_eq!(2, count);
+        assert_eq!(vec![1, 2], values);
+
+        Ok(())
+    }
 }
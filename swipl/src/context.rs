@@ -57,16 +57,23 @@ use super::atom::*;
 use super::callable::*;
 use super::engine::*;
 use super::fli::*;
+use super::functor::*;
 use super::module::*;
+use super::predicate::*;
+use super::record::*;
 use super::result::*;
 use super::stream::*;
 use super::term::*;
+use super::text::*;
 
 #[cfg(feature = "serde")]
-use serde::{Deserialize, Serialize};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use std::cell::Cell;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::convert::{TryFrom, TryInto};
 use std::mem::MaybeUninit;
-use std::convert::TryInto;
+use std::path::Path;
 
 use swipl_macros::pred;
 
@@ -198,6 +205,16 @@ impl<'a, T: ContextType> Context<'a, T> {
         self.engine
     }
 
+    /// Returns an identifier for the underlying engine.
+    ///
+    /// This is derived from the engine pointer, so it is stable for
+    /// as long as the engine exists, and differs between engines
+    /// that exist at the same time. This is useful for correlating
+    /// log lines with engines.
+    pub fn engine_id(&self) -> usize {
+        self.engine as usize
+    }
+
     /// Return the engine pointer as a `TermOrigin`, which is used in the construction of a `Term` in unsafe code.
     pub(crate) fn as_term_origin(&self) -> TermOrigin {
         unsafe { TermOrigin::new(self.engine_ptr()) }
@@ -210,6 +227,7 @@ impl<'a, T: ContextType> Context<'a, T> {
     /// given term_t is indeed from this context. The caller will have
     /// to ensure that the term lives at least as long as this
     /// context.
+    #[cfg_attr(feature = "debug_term_tracking", track_caller)]
     pub unsafe fn wrap_term_ref(&self, term: term_t) -> Term {
         self.assert_activated();
         Term::new(term, self.as_term_origin())
@@ -274,6 +292,15 @@ impl<'a, T: ContextType> Context<'a, T> {
         })
     }
 
+    /// Retrieve the structured error context of the current
+    /// exception.
+    ///
+    /// This returns `None` if there is no exception, or if the
+    /// exception is not of the form `error(Formal, Context)`.
+    pub fn exception_error_context(&self) -> Option<ErrorContext> {
+        self.with_exception(|e| e.and_then(|e| e.get().ok()))
+    }
+
     /// Put the engine in an exceptional state.
     ///
     /// The given term will be copied and put into the exception
@@ -293,14 +320,40 @@ impl<'a, T: ContextType> Context<'a, T> {
     }
 
     /// Retrieve the current output stream.
+    ///
+    /// This is the stream that `current_output/1` returns, so it
+    /// respects redirections like `with_output_to/2`. The stream is
+    /// claimed until the returned object is dropped.
     pub fn current_output<'b>(&self) -> WritablePrologStream<'b> {
         assert_some_engine_is_active();
         unsafe {
-            let current_output = *_PL_streams().offset(4);
+            let current_output = PL_acquire_stream(*_PL_streams().offset(4));
+            assert!(
+                !current_output.is_null(),
+                "could not acquire the current output stream"
+            );
 
             WritablePrologStream::new(current_output)
         }
     }
+
+    /// Retrieve the current input stream.
+    ///
+    /// This is the stream that `current_input/1` returns, so it
+    /// respects redirections like `set_input/1`. The stream is
+    /// claimed until the returned object is dropped.
+    pub fn current_input<'b>(&self) -> ReadablePrologStream<'b> {
+        assert_some_engine_is_active();
+        unsafe {
+            let current_input = PL_acquire_stream(*_PL_streams().offset(3));
+            assert!(
+                !current_input.is_null(),
+                "could not acquire the current input stream"
+            );
+
+            ReadablePrologStream::new(current_input)
+        }
+    }
 }
 
 trait ContextParent {
@@ -503,12 +556,17 @@ enum FrameState {
 pub struct Frame {
     fid: PL_fid_t,
     state: FrameState,
+    #[cfg(feature = "debug_term_tracking")]
+    generation: Cell<u64>,
 }
 
 unsafe impl ContextType for Frame {}
 
 impl Drop for Frame {
     fn drop(&mut self) {
+        #[cfg(feature = "debug_term_tracking")]
+        crate::term::tracking::pop_frame(self.generation.get());
+
         if let FrameState::Active = self.state {
             // unsafe justification: all instantiations of Frame happen in
             // this module.  This module only instantiates the frame as
@@ -560,11 +618,76 @@ impl<'a> Context<'a, Frame> {
         self.assert_activated();
         // unsafe justification: We just checked that this frame right here is currently the active context. Therefore it can be rewinded.
         unsafe { PL_rewind_foreign_frame(self.context.fid) };
+        #[cfg(feature = "debug_term_tracking")]
+        {
+            let generation = crate::term::tracking::rewind_frame(self.context.generation.get());
+            self.context.generation.set(generation);
+        }
 
         self
     }
 }
 
+/// Guard restoring a prolog flag when dropped.
+///
+/// The old value is kept in a record, so no term references are
+/// held on the context while the guard is alive.
+struct FlagRestore<'b, 'a, T: QueryableContextType> {
+    context: &'b Context<'a, T>,
+    name: String,
+    old: Record,
+    restored: bool,
+}
+
+impl<'b, 'a, T: QueryableContextType> FlagRestore<'b, 'a, T> {
+    fn restore(mut self) -> PrologResult<()> {
+        self.restored = true;
+        self.restore_flag()
+    }
+
+    /// Set the flag back to its old value.
+    ///
+    /// A pending exception is cleared while the flag is restored, and
+    /// raised again afterwards. It takes precedence over any error
+    /// from restoring the flag.
+    fn restore_flag(&self) -> PrologResult<()> {
+        let pending = self.context.with_exception(|e| e.map(Record::from_term));
+        self.context.clear_exception();
+
+        let frame = self.context.open_frame();
+        let [flag, old] = frame.new_term_refs();
+        let result = flag
+            .unify(Atomable::from(self.name.as_str()))
+            .and_then(|_| self.old.recorded(&old))
+            .and_then(|_| frame.call_once(pred!("set_prolog_flag/2"), [&flag, &old]));
+
+        let result = match pending {
+            None => result,
+            Some(pending) => {
+                frame.clear_exception();
+                let exception = frame.new_term_ref();
+                pending.recorded(&exception)?;
+                let _ = frame.raise_exception::<()>(&exception);
+
+                Ok(())
+            }
+        };
+        frame.close();
+
+        result
+    }
+}
+
+impl<'b, 'a, T: QueryableContextType> Drop for FlagRestore<'b, 'a, T> {
+    fn drop(&mut self) {
+        if !self.restored {
+            // this may run while unwinding from a panic, where a
+            // second panic would abort, so any failure is ignored.
+            let _ = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| self.restore_flag()));
+        }
+    }
+}
+
 /// A trait marker for context types for which it is safe to open frames.
 pub trait FrameableContextType: ContextType {}
 impl FrameableContextType for Unmanaged {}
@@ -585,6 +708,8 @@ impl<'a, C: FrameableContextType> Context<'a, C> {
         let frame = Frame {
             fid,
             state: FrameState::Active,
+            #[cfg(feature = "debug_term_tracking")]
+            generation: Cell::new(crate::term::tracking::push_frame()),
         };
 
         self.activated.set(false);
@@ -606,6 +731,413 @@ prolog! {
     fn open_call(term);
 }
 
+/// The way double-quoted text is read.
+///
+/// This corresponds to the values of the `double_quotes` prolog flag.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DoubleQuotes {
+    Codes,
+    Chars,
+    Atom,
+    String,
+}
+
+impl DoubleQuotes {
+    fn name(&self) -> &'static str {
+        match self {
+            DoubleQuotes::Codes => "codes",
+            DoubleQuotes::Chars => "chars",
+            DoubleQuotes::Atom => "atom",
+            DoubleQuotes::String => "string",
+        }
+    }
+}
+
+/// The way singleton variables are handled while reading a term.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SingletonPolicy {
+    /// Do not report singleton variables.
+    Ignore,
+    /// Report singleton variables as a warning using `print_message/2`.
+    Warning,
+}
+
+/// The way syntax errors are handled while reading a term.
+///
+/// This corresponds to the `syntax_errors` option of `read_term/2`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorPolicy {
+    /// Raise a syntax error exception.
+    Error,
+    /// Print the syntax error and fail.
+    Fail,
+    /// Fail without printing the syntax error.
+    Quiet,
+}
+
+impl ErrorPolicy {
+    fn name(&self) -> &'static str {
+        match self {
+            ErrorPolicy::Error => "error",
+            ErrorPolicy::Fail => "fail",
+            ErrorPolicy::Quiet => "quiet",
+        }
+    }
+}
+
+/// A character classification, as understood by `char_type/2`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CharClass {
+    /// A letter or digit.
+    Alnum,
+    /// A letter, digit or underscore.
+    Alpha,
+    /// A character that can appear in a C symbol.
+    Csym,
+    /// A character that can start a C symbol.
+    Csymf,
+    /// A digit of any weight.
+    Digit,
+    /// A layout character.
+    Space,
+    /// A space or tab.
+    White,
+    /// An uppercase letter.
+    Upper,
+    /// A lowercase letter.
+    Lower,
+    /// A punctuation character.
+    Punct,
+    /// A visible character that is not a letter or digit.
+    Graph,
+    /// A visible character or a space.
+    Print,
+    /// A control character.
+    Cntrl,
+    /// A character that ends a line.
+    EndOfLine,
+    /// A character that ends a sentence.
+    Period,
+    /// A quote character.
+    Quote,
+    /// A parenthesis.
+    Paren,
+}
+
+impl CharClass {
+    fn name(&self) -> &'static str {
+        match self {
+            CharClass::Alnum => "alnum",
+            CharClass::Alpha => "alpha",
+            CharClass::Csym => "csym",
+            CharClass::Csymf => "csymf",
+            CharClass::Digit => "digit",
+            CharClass::Space => "space",
+            CharClass::White => "white",
+            CharClass::Upper => "upper",
+            CharClass::Lower => "lower",
+            CharClass::Punct => "punct",
+            CharClass::Graph => "graph",
+            CharClass::Print => "print",
+            CharClass::Cntrl => "cntrl",
+            CharClass::EndOfLine => "end_of_line",
+            CharClass::Period => "period",
+            CharClass::Quote => "quote",
+            CharClass::Paren => "paren",
+        }
+    }
+
+    /// Turn this class into a term as understood by `char_type/2`.
+    fn to_term<'a, C: QueryableContextType>(
+        &self,
+        context: &'a Context<C>,
+    ) -> PrologResult<Term<'a>> {
+        match self {
+            // digit only exists with a weight argument
+            CharClass::Digit => term! {context: digit(_)},
+            _ => {
+                let term = context.new_term_ref();
+                term.unify(Atomable::from(self.name()))?;
+
+                Ok(term)
+            }
+        }
+    }
+}
+
+/// An operator type, as understood by `op/3`.
+///
+/// The `f` marks the position of the operator, while `x` and `y`
+/// mark its arguments. An argument marked `x` must have a strictly
+/// lower priority than the operator, while an argument marked `y`
+/// may have an equal priority.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OpType {
+    /// A non-associative infix operator.
+    Xfx,
+    /// A right-associative infix operator.
+    Xfy,
+    /// A left-associative infix operator.
+    Yfx,
+    /// A non-associative prefix operator.
+    Fx,
+    /// An associative prefix operator.
+    Fy,
+    /// A non-associative postfix operator.
+    Xf,
+    /// An associative postfix operator.
+    Yf,
+}
+
+impl OpType {
+    fn name(&self) -> &'static str {
+        match self {
+            OpType::Xfx => "xfx",
+            OpType::Xfy => "xfy",
+            OpType::Yfx => "yfx",
+            OpType::Fx => "fx",
+            OpType::Fy => "fy",
+            OpType::Xf => "xf",
+            OpType::Yf => "yf",
+        }
+    }
+}
+
+/// The result of calling a goal with a depth limit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DepthResult {
+    /// The goal succeeded without exceeding the limit, reaching at most the given depth.
+    Depth(u64),
+    /// The goal failed after exceeding the limit somewhere in its proof.
+    LimitExceeded,
+}
+
+/// The resources consumed by a block of code, as measured by [Context::profile_block].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ProfileDelta {
+    /// The number of logical inferences.
+    pub inferences: i64,
+    /// The CPU time used by the thread, in seconds.
+    pub cpu_time: f64,
+    /// The change in the number of bytes used on the prolog stacks.
+    /// This is negative if stack usage went down.
+    pub stack: i64,
+}
+
+/// A clause read by [Context::read_clause].
+pub struct ClauseReadResult<'a> {
+    /// The clause that was read.
+    pub clause: Term<'a>,
+    /// The names of the variables that only appear once in the
+    /// clause, and which do not start with an underscore. These are
+    /// the variables the loader warns about.
+    pub singletons: Vec<String>,
+}
+
+/// Options for reading a term from a string.
+///
+/// By default, all options are taken from the current prolog
+/// flags. Options set here only apply to the read they're passed to.
+#[derive(Debug, Clone, Default)]
+pub struct ReadOptions {
+    double_quotes: Option<DoubleQuotes>,
+    singletons: Option<SingletonPolicy>,
+    cycles: Option<bool>,
+    syntax_errors: Option<ErrorPolicy>,
+    module: Option<Module>,
+}
+
+impl ReadOptions {
+    /// Create a new ReadOptions.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set how double-quoted text is to be read.
+    pub fn set_double_quotes(&mut self, double_quotes: DoubleQuotes) {
+        self.double_quotes = Some(double_quotes);
+    }
+
+    /// Set how double-quoted text is to be read.
+    pub fn double_quotes(mut self, double_quotes: DoubleQuotes) -> Self {
+        self.set_double_quotes(double_quotes);
+
+        self
+    }
+
+    /// Set how singleton variables are reported.
+    pub fn set_singletons(&mut self, singletons: SingletonPolicy) {
+        self.singletons = Some(singletons);
+    }
+
+    /// Set how singleton variables are reported.
+    pub fn singletons(mut self, singletons: SingletonPolicy) -> Self {
+        self.set_singletons(singletons);
+
+        self
+    }
+
+    /// Set whether cyclic terms written in the `@(Template, Substitutions)` notation are allowed.
+    pub fn set_cycles(&mut self, cycles: bool) {
+        self.cycles = Some(cycles);
+    }
+
+    /// Set whether cyclic terms written in the `@(Template, Substitutions)` notation are allowed.
+    pub fn cycles(mut self, cycles: bool) -> Self {
+        self.set_cycles(cycles);
+
+        self
+    }
+
+    /// Set how syntax errors are handled.
+    pub fn set_syntax_errors(&mut self, syntax_errors: ErrorPolicy) {
+        self.syntax_errors = Some(syntax_errors);
+    }
+
+    /// Set how syntax errors are handled.
+    pub fn syntax_errors(mut self, syntax_errors: ErrorPolicy) -> Self {
+        self.set_syntax_errors(syntax_errors);
+
+        self
+    }
+
+    /// Set the module whose operators and flags are used while reading.
+    pub fn set_module(&mut self, module: Module) {
+        self.module = Some(module);
+    }
+
+    /// Set the module whose operators and flags are used while reading.
+    pub fn module(mut self, module: Module) -> Self {
+        self.set_module(module);
+
+        self
+    }
+
+    /// Turn these options into an option list as understood by `read_term/2`.
+    fn to_term<'a, C: QueryableContextType>(
+        &self,
+        context: &'a Context<C>,
+    ) -> PrologResult<Term<'a>> {
+        let mut options: Vec<Term> = Vec::new();
+        if let Some(double_quotes) = self.double_quotes {
+            let double_quotes = Atomable::from(double_quotes.name());
+            options.push(term! {context: double_quotes(#double_quotes)}?);
+        }
+        if let Some(SingletonPolicy::Warning) = self.singletons {
+            options.push(term! {context: singletons(warning)}?);
+        }
+        if let Some(cycles) = self.cycles {
+            options.push(term! {context: cycles(#cycles)}?);
+        }
+        if let Some(syntax_errors) = self.syntax_errors {
+            let syntax_errors = Atomable::from(syntax_errors.name());
+            options.push(term! {context: syntax_errors(#syntax_errors)}?);
+        }
+        if let Some(module) = self.module {
+            let module = module.name();
+            options.push(term! {context: module(#module)}?);
+        }
+
+        let list = context.new_term_ref();
+        list.unify(options.as_slice())?;
+
+        Ok(list)
+    }
+}
+
+/// Options for creating a saved state.
+///
+/// By default, the defaults of `qsave_program/2` are used.
+#[derive(Debug, Clone, Default)]
+pub struct SaveOptions {
+    goal: Option<String>,
+    toplevel: Option<String>,
+    stand_alone: Option<bool>,
+    autoload: Option<bool>,
+}
+
+impl SaveOptions {
+    /// Create a new SaveOptions.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the goal to run when the saved state is started, as prolog text.
+    pub fn set_goal(&mut self, goal: &str) {
+        self.goal = Some(goal.to_string());
+    }
+
+    /// Set the goal to run when the saved state is started, as prolog text.
+    pub fn goal(mut self, goal: &str) -> Self {
+        self.set_goal(goal);
+
+        self
+    }
+
+    /// Set the toplevel goal of the saved state, as prolog text.
+    pub fn set_toplevel(&mut self, toplevel: &str) {
+        self.toplevel = Some(toplevel.to_string());
+    }
+
+    /// Set the toplevel goal of the saved state, as prolog text.
+    pub fn toplevel(mut self, toplevel: &str) -> Self {
+        self.set_toplevel(toplevel);
+
+        self
+    }
+
+    /// Set whether the saved state is made into a stand-alone executable.
+    pub fn set_stand_alone(&mut self, stand_alone: bool) {
+        self.stand_alone = Some(stand_alone);
+    }
+
+    /// Set whether the saved state is made into a stand-alone executable.
+    pub fn stand_alone(mut self, stand_alone: bool) -> Self {
+        self.set_stand_alone(stand_alone);
+
+        self
+    }
+
+    /// Set whether autoloadable predicates are loaded into the saved state.
+    pub fn set_autoload(&mut self, autoload: bool) {
+        self.autoload = Some(autoload);
+    }
+
+    /// Set whether autoloadable predicates are loaded into the saved state.
+    pub fn autoload(mut self, autoload: bool) -> Self {
+        self.set_autoload(autoload);
+
+        self
+    }
+
+    /// Turn these options into an option list as understood by `qsave_program/2`.
+    fn to_term<'a, C: QueryableContextType>(
+        &self,
+        context: &'a Context<C>,
+    ) -> PrologResult<Term<'a>> {
+        let mut options: Vec<Term> = Vec::new();
+        if let Some(goal) = &self.goal {
+            let goal = context.term_from_string(goal)?;
+            options.push(term! {context: goal(#&goal)}?);
+        }
+        if let Some(toplevel) = &self.toplevel {
+            let toplevel = context.term_from_string(toplevel)?;
+            options.push(term! {context: toplevel(#&toplevel)}?);
+        }
+        if let Some(stand_alone) = self.stand_alone {
+            options.push(term! {context: stand_alone(#stand_alone)}?);
+        }
+        if let Some(autoload) = self.autoload {
+            options.push(term! {context: autoload(#autoload)}?);
+        }
+
+        let list = context.new_term_ref();
+        list.unify(options.as_slice())?;
+
+        Ok(list)
+    }
+}
+
 pub type GenericQueryableContext<'a> = Context<'a, GenericQueryableContextType>;
 
 impl<'a, T: QueryableContextType> Context<'a, T> {
@@ -613,6 +1145,7 @@ impl<'a, T: QueryableContextType> Context<'a, T> {
     ///
     /// The term ref takes on the lifetime of the Context reference,
     /// ensuring that it cannot outlive the context that created it.
+    #[cfg_attr(feature = "debug_term_tracking", track_caller)]
     pub fn new_term_ref(&self) -> Term {
         self.assert_activated();
         unsafe {
@@ -621,11 +1154,105 @@ impl<'a, T: QueryableContextType> Context<'a, T> {
         }
     }
 
+    /// Take the pending exception, if any, and clear it.
+    ///
+    /// This returns a term containing a copy of the exception, and
+    /// leaves the engine in a non-exceptional state so that it can be
+    /// used again. If there is no pending exception, this returns
+    /// `None`.
+    pub fn recover_from_exception(&self) -> Option<Term> {
+        let term = self.new_term_ref();
+        let recovered = self.with_exception(|e| match e {
+            Some(e) => term.unify(e).is_ok(),
+            None => false,
+        });
+
+        if recovered {
+            self.clear_exception();
+
+            Some(term)
+        } else {
+            unsafe { term.reset() };
+
+            None
+        }
+    }
+
+    /// Run the given function, converting a prolog error into a custom error.
+    ///
+    /// If the function returns an exception, the exception is
+    /// recovered from as with
+    /// [recover_from_exception](Self::recover_from_exception), and
+    /// the exception term is passed to the mapping function. If the
+    /// function fails, the mapping function is given `None`. This
+    /// allows translating prolog errors into an application's own
+    /// error type in one place.
+    pub fn run_mapped<R, E>(
+        &self,
+        f: impl FnOnce() -> PrologResult<R>,
+        map: impl FnOnce(Option<Term>) -> E,
+    ) -> Result<R, E> {
+        match f() {
+            Ok(result) => Ok(result),
+            Err(PrologError::Failure) => Err(map(None)),
+            Err(PrologError::Exception | PrologError::Halt(_)) => {
+                Err(map(self.recover_from_exception()))
+            }
+        }
+    }
+
+    /// Retrieve the backtrace of the current exception, if it has one.
+    ///
+    /// SWI-Prolog only adds a backtrace to an `error(Formal, Context)`
+    /// exception if `library(prolog_stack)` is loaded and the
+    /// exception is not caught by prolog code, or caught using
+    /// `catch_with_backtrace/3`. The backtrace is then stored in the
+    /// error context as `prolog_stack(Frames)`. Each frame is
+    /// formatted with `print_prolog_backtrace/2`, innermost frame
+    /// first.
+    ///
+    /// This returns `None` if there is no exception, or if the
+    /// exception does not carry a backtrace.
+    pub fn exception_backtrace(&self) -> Option<Vec<String>> {
+        self.with_exception(|e| {
+            let e = e?;
+            let frame = self.open_frame();
+            let query = frame
+                .term_from_string(
+                    "p(Stack, Lines, findall(L, (member(F, Stack), with_output_to(string(S), print_prolog_backtrace(current_output, [F])), split_string(S, \"\", \"\\s\\n\", [L])), Lines))",
+                )
+                .ok()?;
+            let [stack, lines, goal] = frame.compound_terms(&query).ok()?;
+            let pattern = term! {frame: error(_, context(prolog_stack(#&stack), _))}.ok()?;
+            let result = match e.unify(&pattern) {
+                Ok(()) => frame
+                    .call_once(pred!("call/1"), [&goal])
+                    .and_then(|()| lines.get::<Vec<String>>())
+                    .ok(),
+                Err(_) => None,
+            };
+            frame.discard();
+
+            result
+        })
+    }
+
+    /// Create a new unbound variable in the current context.
+    ///
+    /// This is the same as [new_term_ref](Context::new_term_ref),
+    /// as fresh term references always hold an unbound variable. It
+    /// exists to make code that builds partial terms read more
+    /// clearly.
+    pub fn new_variable(&self) -> Term {
+        self.new_term_ref()
+    }
+
     /// create an array of term references.
     ///
     /// The term refs all take on the lifetime of the Context
     /// reference, ensuring that it cannot outlive the context that
     /// created it.
+    #[cfg_attr(feature = "debug_term_tracking", track_caller)]
     pub fn new_term_refs<const N: usize>(&self) -> [Term; N] {
         // TODO: this should be a compile time thing ideally
         // TODO: swipl 9.1.19 changed the paramater type to usize
@@ -659,7 +1286,15 @@ impl<'a, T: QueryableContextType> Context<'a, T> {
     /// The term refs all take on the lifetime of the Context
     /// reference, ensuring that it cannot outlive the context that
     /// created it.
+    ///
+    /// The term refs are allocated as one contiguous block using a
+    /// single call to `PL_new_term_refs`, which is faster than
+    /// calling [new_term_ref](Self::new_term_ref) repeatedly. Use
+    /// [new_term_refs](Self::new_term_refs) instead if the count is
+    /// known at compile time.
+    #[cfg_attr(feature = "debug_term_tracking", track_caller)]
     pub fn new_term_refs_vec(&self, count: usize) -> Vec<Term> {
+        self.assert_activated();
         let mut term_ptr = unsafe { PL_new_term_refs(count.try_into().unwrap()) };
         let mut result = Vec::with_capacity(count);
         for _ in 0..count {
@@ -727,6 +1362,19 @@ impl<'a, T: QueryableContextType> Context<'a, T> {
         Ok(())
     }
 
+    /// Call the given callable once, returning whether it succeeded.
+    ///
+    /// Unlike [call_once](Self::call_once), failure is not an error
+    /// here, but results in `Ok(false)`. Only exceptions result in an
+    /// `Err`.
+    pub fn call_once_bool<C: Callable<N>, const N: usize>(
+        &self,
+        callable: C,
+        args: [&Term; N],
+    ) -> PrologResult<bool> {
+        Ok(attempt(self.call_once(callable, args))?)
+    }
+
     /// Open a query, optionally passing in a context module.
     pub fn open_with_module<C: Callable<N>, const N: usize>(
         &self,
@@ -744,14 +1392,67 @@ impl<'a, T: QueryableContextType> Context<'a, T> {
     ///
     /// Consider using the `term!` macro instead.
     pub fn term_from_string(&self, s: &str) -> PrologResult<Term> {
+        self.term_from_string_with_opts(s, ReadOptions::new())
+    }
+
+    /// Turn the given string into a prolog term, using the operators of the given module.
+    ///
+    /// Operators can be defined locally to a module, for example with
+    /// `op/3` in a module file that does not export them. Such
+    /// operators are only understood when reading in that module.
+    pub fn term_from_string_in(&self, module: Module, s: &str) -> PrologResult<Term> {
+        self.term_from_string_with_opts(s, ReadOptions::new().module(module))
+    }
+
+    /// Parse each line read from the given reader as a term.
+    ///
+    /// Unlike reading terms from a prolog stream, where terms are
+    /// delimited by a `.`, here every line is a term on its own.
+    /// Lines are parsed with [term_from_string](Self::term_from_string),
+    /// and blank lines are skipped. An I/O error while reading is
+    /// raised as a prolog exception.
+    pub fn terms_from_lines<'b, R: std::io::BufRead + 'b>(
+        &'b self,
+        reader: R,
+    ) -> impl Iterator<Item = PrologResult<Term<'b>>> + 'b {
+        reader.lines().filter_map(move |line| match line {
+            Ok(line) if line.trim().is_empty() => None,
+            Ok(line) => Some(self.term_from_string(&line)),
+            Err(e) => Some(self.try_or_die(Err(e))),
+        })
+    }
+
+    /// Turn the given string into a prolog term, using the given read options.
+    ///
+    /// This uses the prolog predicate `read_term_from_atom/3` for the
+    /// heavy lifting. The options only apply to this read, and leave
+    /// the global prolog flags untouched.
+    pub fn term_from_string_with_opts(&self, s: &str, opts: ReadOptions) -> PrologResult<Term> {
         let term = self.new_term_ref();
         let frame = self.open_frame();
 
         let arg1 = frame.new_term_ref();
-        let arg3 = frame.new_term_ref();
-
         assert!(arg1.unify(s).is_ok());
-        assert!(arg3.unify(Nil).is_ok());
+        let arg3 = opts.to_term(&frame)?;
+
+        read_term_from_atom(&frame, &arg1, &term, &arg3).once()?;
+        frame.close();
+
+        Ok(term)
+    }
+
+    /// Turn the text of the given atom into a prolog term.
+    ///
+    /// This uses the prolog predicate `read_term_from_atom/3` for the
+    /// heavy lifting. Unlike [term_from_string](Self::term_from_string),
+    /// the text does not have to be converted into a rust string first.
+    pub fn term_from_atom(&self, atom: &Atom) -> PrologResult<Term> {
+        let term = self.new_term_ref();
+        let frame = self.open_frame();
+
+        let [arg1, arg3] = frame.new_term_refs();
+        arg1.unify(atom)?;
+        arg3.unify(Nil)?;
 
         read_term_from_atom(&frame, &arg1, &term, &arg3).once()?;
         frame.close();
@@ -776,6 +1477,92 @@ impl<'a, T: QueryableContextType> Context<'a, T> {
         Ok(s)
     }
 
+    /// Write the given term in a canonical textual form.
+    ///
+    /// Atoms and strings are quoted and operators are written in
+    /// prefix form, as with `write_term/2` using `quoted(true)` and
+    /// `ignore_ops(true)`. Dictionary entries are written in the
+    /// standard order of their keys rather than in their internal
+    /// order, which may differ between runs. As a result, equal
+    /// ground terms always produce the same string, making this
+    /// suitable for hashing and deduplication.
+    pub fn canonical_string(&self, term: &Term) -> PrologResult<String> {
+        let frame = self.open_frame();
+        let mut out = String::new();
+        frame.write_canonical(term, &mut out)?;
+        frame.discard();
+
+        Ok(out)
+    }
+
+    fn write_canonical(&self, term: &Term, out: &mut String) -> PrologResult<()> {
+        match term.term_type() {
+            TermType::Dict => {
+                let [tag, pairs] = self.new_term_refs();
+                self.call_once(pred!("dict_pairs/3"), [term, &tag, &pairs])?;
+                if tag.is_var() {
+                    out.push('_');
+                } else {
+                    self.write_canonical(&tag, out)?;
+                }
+                out.push('{');
+                for (i, pair) in self.term_list_iter(&pairs).enumerate() {
+                    if i != 0 {
+                        out.push(',');
+                    }
+                    let [key, value] = self.compound_terms(&pair)?;
+                    self.write_canonical(&key, out)?;
+                    out.push(':');
+                    self.write_canonical(&value, out)?;
+                }
+                out.push('}');
+            }
+            TermType::ListPair => {
+                out.push('[');
+                let mut cur = self.new_term_ref();
+                cur.unify(term)?;
+                let mut first = true;
+                while cur.term_type() == TermType::ListPair {
+                    if !first {
+                        out.push(',');
+                    }
+                    first = false;
+                    let [head, tail] = self.compound_terms(&cur)?;
+                    self.write_canonical(&head, out)?;
+                    cur = tail;
+                }
+                if cur.term_type() != TermType::Nil {
+                    out.push('|');
+                    self.write_canonical(&cur, out)?;
+                }
+                out.push(']');
+            }
+            TermType::CompoundTerm => {
+                let functor: Functor = term.get()?;
+                let name = self.new_term_ref();
+                name.unify(functor.name())?;
+                self.write_canonical(&name, out)?;
+                out.push('(');
+                for (i, arg) in self.compound_terms_vec(term)?.iter().enumerate() {
+                    if i != 0 {
+                        out.push(',');
+                    }
+                    self.write_canonical(arg, out)?;
+                }
+                out.push(')');
+            }
+            _ => {
+                let self_ = self;
+                let string = self.new_term_ref();
+                let options = term! {self_: [quoted(true), ignore_ops(true)]}?;
+                self.call_once(pred!("term_string/3"), [term, &string, &options])?;
+                out.push_str(&string.get::<String>()?);
+            }
+        }
+
+        Ok(())
+    }
+
     /// Open a query for the given term using the `call/1` prolog predicate.
     pub fn open_call(&'a self, t: &Term<'a>) -> Context<'a, impl OpenCall> {
         open_call(self, t)
@@ -789,885 +1576,4854 @@ impl<'a, T: QueryableContextType> Context<'a, T> {
         Ok(())
     }
 
-    /// Turn a result into a `PrologResult`.
+    /// Call the given goal once, with extra arguments appended from a list.
     ///
-    /// For this to work, the `Err` component of the `Result` needs to
-    /// implement the trait `IntoPrologException`. This is currently
-    /// only the case for [std::io::Error].
-    pub fn try_or_die<R, E: IntoPrologException>(&self, r: Result<R, E>) -> PrologResult<R> {
-        match r {
-            Ok(ok) => Ok(ok),
-            Err(e) => {
-                let reset_term = self.new_term_ref();
-                let exception_term = e.into_prolog_exception(self)?;
-                let result = self.raise_exception(&exception_term);
+    /// This uses the prolog predicate `apply/2`. The extra arguments
+    /// are given as a prolog list, which is useful when the arguments
+    /// themselves were constructed in prolog.
+    pub fn apply(&self, goal: &Term, extra_args: &Term) -> PrologResult<()> {
+        self.call_once(pred!("apply/2"), [goal, extra_args])
+    }
 
-                unsafe {
-                    reset_term.reset();
-                }
+    /// Call the given goal once, limiting its recursion depth.
+    ///
+    /// This uses the prolog predicate `call_with_depth_limit/3`. If
+    /// the goal succeeds, the deepest recursion level that was
+    /// reached is returned. If the goal failed after the limit was
+    /// exceeded, [DepthResult::LimitExceeded] is returned. If the
+    /// goal failed without exceeding the limit, this fails as well.
+    pub fn call_with_depth_limit(&self, goal: &Term, limit: u64) -> PrologResult<DepthResult> {
+        let frame = self.open_frame();
+        let [limit_term, result] = frame.new_term_refs();
+        limit_term.unify(limit)?;
+        frame.call_once(
+            pred!("call_with_depth_limit/3"),
+            [goal, &limit_term, &result],
+        )?;
+        let depth = if let Some(depth) = attempt_opt(result.get::<u64>())? {
+            DepthResult::Depth(depth)
+        } else {
+            DepthResult::LimitExceeded
+        };
+        frame.close();
 
-                result
-            }
-        }
+        Ok(depth)
     }
 
-    /// Turn a result into a `PrologResult`.
+    /// Copy a term, leaving out the attributes of any attributed variables.
     ///
-    /// For this to work, the `Err` component of the `Result` needs to
-    /// implement the trait [Error](std::error::Error).
-    pub fn try_or_die_generic<R, E: std::error::Error>(&self, r: Result<R, E>) -> PrologResult<R> {
-        match r {
-            Ok(ok) => Ok(ok),
-            Err(e) => {
-                let reset_term = self.new_term_ref();
-                let msg = format!("{}", e);
+    /// This uses the prolog predicate `copy_term_nat/2`. Attributed
+    /// variables, such as those constrained by a constraint solver,
+    /// become plain variables in the copy. This is useful before
+    /// deserializing or otherwise exporting such a term.
+    pub fn copy_term_nat(&self, term: &Term) -> PrologResult<Term> {
+        let copy = self.new_term_ref();
+        self.call_once(pred!("copy_term_nat/2"), [term, &copy])?;
+
+        Ok(copy)
+    }
 
-                // TODO: term macro doesn't like self, which is
-                // probably only a problem for things inside this
-                // crate but still should probably be resolved.
-                let self_ = self;
-                let exception_term = term! {self_: error(rust_error(#msg), _)}?;
-                let result = self.raise_exception(&exception_term);
+    /// Serialize a term into a byte buffer.
+    ///
+    /// This uses `PL_record_external()`, which produces a compact
+    /// binary representation of the term that is much faster to
+    /// create and read back than text. The buffer is independent of
+    /// the engine, so it can be stored, for example in a cache, and
+    /// read back using [fast_deserialize](Context::fast_deserialize)
+    /// in another engine or process running the same version of
+    /// SWI-Prolog.
+    pub fn fast_serialize(&self, term: &Term) -> PrologResult<Vec<u8>> {
+        term.assert_term_handling_possible();
+        let mut size = 0;
+        let buf = unsafe { PL_record_external(term.term_ptr(), &mut size) };
+        if buf.is_null() {
+            return Err(if unsafe { pl_default_exception() } != 0 {
+                PrologError::Exception
+            } else {
+                PrologError::Failure
+            });
+        }
 
-                unsafe {
-                    reset_term.reset();
-                }
+        let bytes = unsafe { std::slice::from_raw_parts(buf as *const u8, size) }.to_vec();
+        unsafe { PL_erase_external(buf) };
 
-                result
-            }
-        }
+        Ok(bytes)
     }
 
-    /// Iterate over a term list.
+    /// Deserialize a term from a byte buffer created by [fast_serialize](Context::fast_serialize).
     ///
-    /// this returns a TermListIterator made out of the given
-    /// term. The TermListIterator will assume this is a cons cell,
-    /// and unify head and tail on each step of the iterator,
-    /// returning the head term and storing the tail term. If this
-    /// unification fails, the iterator stops.
+    /// This uses `PL_recorded_external()`. A buffer that was created
+    /// by an incompatible version of SWI-Prolog results in an
+    /// exception.
     ///
-    /// Note that the terms created by this iterator are not
-    /// automatically thrown away. It is the caller's responsibility
-    /// to clean up terms if this is required, for example by using a
-    /// frame.
-    pub fn term_list_iter<'b>(&'b self, list: &Term) -> TermListIterator<'b, 'a, T> {
-        self.assert_activated();
-        let cur = self.new_term_ref();
-        cur.unify(list).expect("unifying terms should work");
-        TermListIterator { context: self, cur }
+    /// # Safety
+    /// SWI-Prolog only checks the header of the buffer. The caller
+    /// has to ensure that the buffer was created by
+    /// `fast_serialize` and was not corrupted since, as reading a
+    /// malformed buffer is undefined behavior.
+    pub unsafe fn fast_deserialize(&self, bytes: &[u8]) -> PrologResult<Term> {
+        let term = self.new_term_ref();
+        let result = PL_recorded_external(
+            bytes.as_ptr() as *const std::os::raw::c_char,
+            term.term_ptr(),
+        );
+        if result != 0 {
+            Ok(term)
+        } else if pl_default_exception() != 0 {
+            Err(PrologError::Exception)
+        } else {
+            Err(PrologError::Failure)
+        }
     }
 
-    /// Retrieve a term list as a fixed-size array.
+    /// Run a DCG body over the given list.
     ///
-    /// This is useful when a term contains a list whose supposed size
-    /// is known at compile time. If the actual list is larger than
-    /// this, only the first N elements are used. If the list is
-    /// smaller, the remaining terms in the array remain variables.
-    pub fn term_list_array<const N: usize>(&self, list: &Term) -> [Term; N] {
-        self.assert_activated();
-        // allocate these terms inside the scope of this context
-        let terms = self.new_term_refs();
-
-        let frame = self.open_frame();
-        let terms_iter = terms.iter();
-        let list_iter = frame.term_list_iter(list);
+    /// This uses the prolog predicate `phrase/2`, which requires the
+    /// whole list to be consumed. Failure to parse results in
+    /// `Ok(false)`.
+    pub fn phrase(&self, body: &Term, list: &Term) -> PrologResult<bool> {
+        self.call_once_bool(pred!("phrase/2"), [body, list])
+    }
 
-        for (term, elt) in terms_iter.zip(list_iter) {
-            term.unify(elt).unwrap();
+    /// Run a DCG body over a prefix of the given list.
+    ///
+    /// This uses the prolog predicate `phrase/3`. On success, the
+    /// part of the list that was not consumed is returned. Failure to
+    /// parse results in `Ok(None)`.
+    pub fn phrase_with_rest(&self, body: &Term, list: &Term) -> PrologResult<Option<Term>> {
+        let rest = self.new_term_ref();
+        if self.call_once_bool(pred!("phrase/3"), [body, list, &rest])? {
+            Ok(Some(rest))
+        } else {
+            Ok(None)
         }
+    }
+
+    /// Construct a new term from a [TermTree].
+    ///
+    /// This is an alternative to the `term!` macro for terms whose
+    /// shape is only known at runtime. Any intermediate term
+    /// references are cleaned up before returning.
+    pub fn build_tree(&self, tree: &TermTree) -> PrologResult<Term> {
+        let term = self.new_term_ref();
+        let frame = self.open_frame();
+        let result = term.put(tree);
         frame.close();
+        result?;
 
-        terms
+        Ok(term)
     }
 
-    /// Retrieve a term list as a Vec.
+    /// Evaluate an arithmetic expression.
     ///
-    /// This will iterate over the given prolog list twice - once to
-    /// figure out its size, and then another time to actually
-    /// retrieve the elements. This is done so that we can allocate
-    /// the terms in a way that leaves no unused terms behind on the
-    /// stack (as would normally happen when iterating the list using
-    /// [term_list_iter](Context::term_list_iter)).
+    /// This uses the prolog predicate `is/2` to evaluate the given
+    /// expression term, returning a new term holding the numeric
+    /// result. If the expression cannot be evaluated, the exception
+    /// raised by `is/2` is returned.
+    pub fn eval(&self, expr: &Term) -> PrologResult<Term> {
+        let result = self.new_term_ref();
+        self.call_once(pred!("is/2"), [&result, expr])?;
+
+        Ok(result)
+    }
+
+    /// Evaluate an arithmetic expression into an `i64`.
     ///
-    /// If you know in advance what the size is going to be (or you
-    /// know a reasonable upper bound), consider using
-    /// [term_list_array](Context::term_list_array). If you just wish
-    /// to iterate over the elements, or don't care about garbage
-    /// terms being created, consider using
-    /// [term_list_iter](Context::term_list_iter).
-    pub fn term_list_vec(&self, list: &Term) -> Vec<Term> {
-        self.assert_activated();
+    /// This fails if the expression does not evaluate to an integer
+    /// that fits in an `i64`.
+    pub fn eval_i64(&self, expr: &Term) -> PrologResult<i64> {
         let frame = self.open_frame();
-        let count = frame.term_list_iter(list).count();
-        frame.discard();
+        let result = frame.eval(expr)?.get()?;
+        frame.close();
 
-        // allocate these terms inside the scope of this context
-        let terms = self.new_term_refs_vec(count);
+        Ok(result)
+    }
 
+    /// Evaluate an arithmetic expression into an `f64`.
+    ///
+    /// Integer results are converted to a float.
+    pub fn eval_f64(&self, expr: &Term) -> PrologResult<f64> {
         let frame = self.open_frame();
-        let terms_iter = terms.iter();
-        let list_iter = frame.term_list_iter(list);
-
-        for (term, elt) in terms_iter.zip(list_iter) {
-            term.unify(elt).unwrap();
-        }
+        let result = frame.eval(expr)?.get()?;
         frame.close();
 
-        terms
+        Ok(result)
     }
 
-    /// Retrieve compound terms as a fixed size array.
+    /// Add two integers.
     ///
-    /// This will ensure that the given term is indeed a compound with
-    /// arity N. If this is true, N terms will be allocated in this
-    /// context, unified with the argument terms of the compound, and
-    /// returned as an array. If not, this method will fail.
-    pub fn compound_terms<const N: usize>(&self, compound: &Term) -> PrologResult<[Term; N]> {
-        self.assert_activated();
-        if N > (i32::MAX - 1) as usize {
-            panic!("requested compound term array too large: {}", N);
-        }
+    /// This uses the prolog predicate `plus/3`. As prolog integers
+    /// are unbounded, the addition itself can't overflow, but this
+    /// raises an `int_overflow` evaluation error if the result does not
+    /// fit in an `i64`.
+    pub fn plus(&self, a: i64, b: i64) -> PrologResult<i64> {
+        let frame = self.open_frame();
+        let [a_term, b_term, result] = frame.new_term_refs();
+        a_term.unify(a)?;
+        b_term.unify(b)?;
+        frame.call_once(pred!("plus/3"), [&a_term, &b_term, &result])?;
+        let result = frame.get_i64_or_overflow(&result)?;
+        frame.close();
 
-        let mut size = 0;
-        if unsafe {
-            PL_get_compound_name_arity(compound.term_ptr(), std::ptr::null_mut(), &mut size) != 1
-        } {
-            return Err(PrologError::Failure);
-        }
-        if (size as usize) != N {
-            return Err(PrologError::Failure);
-        }
+        Ok(result)
+    }
 
-        let terms: [Term; N] = self.new_term_refs();
-        for (i, term) in terms.iter().enumerate() {
-            unsafe {
-                assert!(PL_get_arg((i + 1) as i32, compound.term_ptr(), term.term_ptr()) == 1);
-            }
-        }
+    /// Returns the successor of a natural number.
+    ///
+    /// This uses the prolog predicate `succ/2`, which raises an
+    /// exception for negative numbers. This also raises an
+    /// `int_overflow` evaluation error if the result does not fit in
+    /// an `i64`. See [succ](Self::succ) for the term-based variant.
+    pub fn succ_i64(&self, n: i64) -> PrologResult<i64> {
+        let frame = self.open_frame();
+        let [n_term, result] = frame.new_term_refs();
+        n_term.unify(n)?;
+        frame.call_once(pred!("succ/2"), [&n_term, &result])?;
+        let result = frame.get_i64_or_overflow(&result)?;
+        frame.close();
 
-        Ok(terms)
+        Ok(result)
     }
 
-    /// Retrieve compound terms as a Vec.
+    /// Returns the absolute value of an integer.
     ///
-    /// This will ensure that the given term is indeed a compound of
-    /// any arity. If this is true, arity terms will be allocated in
-    /// this context, unified with the argument terms of the compound,
-    /// and returned as a Vec. If not, this method will fail.
-    pub fn compound_terms_vec(&self, compound: &Term) -> PrologResult<Vec<Term>> {
-        self.assert_activated();
+    /// This evaluates `abs/1` using `is/2`. This raises an
+    /// `int_overflow` evaluation error if the result does not fit in
+    /// an `i64`, which is the case for `i64::MIN`.
+    pub fn abs(&self, n: i64) -> PrologResult<i64> {
+        let frame = self.open_frame();
+        let expr = term! {frame: abs(#n)}?;
+        let result = frame.get_i64_or_overflow(&frame.eval(&expr)?)?;
+        frame.close();
 
-        let mut size = 0;
-        if unsafe {
-            PL_get_compound_name_arity(compound.term_ptr(), std::ptr::null_mut(), &mut size) != 1
-        } {
-            return Err(PrologError::Failure);
-        }
+        Ok(result)
+    }
 
-        let terms = self.new_term_refs_vec(size as usize);
-        for (i, term) in terms.iter().enumerate() {
-            unsafe {
-                assert!(PL_get_arg((i + 1) as i32, compound.term_ptr(), term.term_ptr()) == 1);
+    /// Retrieve an integer result, raising an `int_overflow`
+    /// evaluation error if it does not fit in an `i64`.
+    fn get_i64_or_overflow(&self, result: &Term) -> PrologResult<i64> {
+        match attempt_opt(result.get::<i64>())? {
+            Some(result) => Ok(result),
+            None => {
+                let self_ = self;
+                let error = term! {self_: error(evaluation_error(int_overflow), _)}?;
+                self.raise_exception(&error)
             }
         }
-
-        Ok(terms)
     }
 
-    /// Retrieve compound terms as a fixed size Vec.
+    /// Compare two arithmetic expressions by their value.
     ///
-    /// This will ensure that the given term is indeed a compound with
-    /// arity `count`. If this is true, `count` terms will be
-    /// allocated in this context, unified with the argument terms of
-    /// the compound, and returned as an array. If not, this method
-    /// will fail.
-    pub fn compound_terms_vec_sized(
-        &self,
-        compound: &Term,
-        count: usize,
-    ) -> PrologResult<Vec<Term>> {
-        self.assert_activated();
-
-        let mut size = 0;
-        if unsafe {
-            PL_get_compound_name_arity(compound.term_ptr(), std::ptr::null_mut(), &mut size) != 1
-        } {
-            return Err(PrologError::Failure);
-        }
-        if (size as usize) != count {
+    /// Unlike the standard order of terms used by [Term]'s
+    /// `PartialOrd` implementation, this evaluates both expressions,
+    /// so `1+1` compares equal to `2`, and `2` compares equal to
+    /// `2.0`. This uses the prolog predicates `=:=/2` and `</2`.
+    ///
+    /// If the values are not comparable, such as when one of them is
+    /// NaN, this fails.
+    pub fn arith_compare(&self, a: &Term, b: &Term) -> PrologResult<Ordering> {
+        let frame = self.open_frame();
+        let ordering = if attempt(frame.call_once(pred!("=:=", 2), [a, b]))? {
+            Ordering::Equal
+        } else if attempt(frame.call_once(pred!("</2"), [a, b]))? {
+            Ordering::Less
+        } else if attempt(frame.call_once(pred!(">/2"), [a, b]))? {
+            Ordering::Greater
+        } else {
             return Err(PrologError::Failure);
-        }
+        };
+        frame.close();
 
-        let terms = self.new_term_refs_vec(count);
-        for (i, term) in terms.iter().enumerate() {
-            unsafe {
-                assert!(PL_get_arg((i + 1) as i32, compound.term_ptr(), term.term_ptr()) == 1);
-            }
-        }
+        Ok(ordering)
+    }
 
-        Ok(terms)
+    fn convert_string(&self, predicate: CallablePredicate<2>, s: &str) -> PrologResult<String> {
+        let frame = self.open_frame();
+        let [input, output] = frame.new_term_refs();
+        input.unify(s)?;
+        frame.call_once(predicate, [&input, &output])?;
+        let result = output.get()?;
+        frame.close();
+
+        Ok(result)
     }
 
-    #[cfg(feature = "serde")]
-    /// Deserialize a term into a rust value using serde.
-    pub fn deserialize_from_term<'de, DT: Deserialize<'de>>(
-        &'de self,
-        term: &'de Term<'de>,
-    ) -> super::term::de::Result<DT> {
-        super::term::de::from_term(self, term)
+    /// Convert a string to lowercase, the way prolog does it.
+    ///
+    /// This uses the prolog predicate `string_lower/2`, which maps
+    /// each character on its own. The result may therefore differ
+    /// from rust's [str::to_lowercase] for characters whose case
+    /// mapping depends on their context.
+    pub fn string_lower(&self, s: &str) -> PrologResult<String> {
+        self.convert_string(pred!("string_lower/2"), s)
     }
 
-    #[cfg(feature = "serde")]
-    /// Serialize a value into a prolog term using serde.
+    /// Convert a string to uppercase, the way prolog does it.
     ///
-    /// This uses the default serialization configuration, meaning:
-    /// - prolog dictionary tags will remain variables.
-    /// - struct type names are ignored and will not be set as the dictionary tag.
-    pub fn serialize_to_term<ST: Serialize>(
-        &self,
-        term: &Term,
-        obj: &ST,
-    ) -> Result<(), super::term::de::Error> {
-        super::term::ser::to_term(self, term, obj)
+    /// This uses the prolog predicate `string_upper/2`, which maps
+    /// each character on its own. The result may therefore differ
+    /// from rust's [str::to_uppercase] for characters that uppercase
+    /// to multiple characters.
+    pub fn string_upper(&self, s: &str) -> PrologResult<String> {
+        self.convert_string(pred!("string_upper/2"), s)
     }
 
-    #[cfg(feature = "serde")]
-    /// Serialize a value into a prolog term using serde, providing configuration options.
-    pub fn serialize_to_term_with_config<ST: Serialize>(
-        &self,
-        term: &Term,
-        obj: &ST,
-        config: SerializerConfiguration,
-    ) -> Result<(), super::term::de::Error> {
-        super::term::ser::to_term_with_config(self, term, obj, config)
+    /// Compare two strings according to the collation order of the current locale.
+    ///
+    /// This compares the keys produced by the prolog predicate
+    /// `collation_key/2`, which is also what `sort/4` on collation
+    /// keys and `predsort/3` based sorting in prolog would use.
+    pub fn collation_compare(&self, a: &str, b: &str) -> PrologResult<Ordering> {
+        let frame = self.open_frame();
+        let [a_term, b_term, a_key, b_key, order] = frame.new_term_refs();
+        a_term.unify(a)?;
+        b_term.unify(b)?;
+        frame.call_once(pred!("collation_key/2"), [&a_term, &a_key])?;
+        frame.call_once(pred!("collation_key/2"), [&b_term, &b_key])?;
+        frame.call_once(pred!("compare/3"), [&order, &a_key, &b_key])?;
+        let ordering = match order.get::<Atom>()?.name().as_str() {
+            "<" => Ordering::Less,
+            ">" => Ordering::Greater,
+            _ => Ordering::Equal,
+        };
+        frame.close();
+
+        Ok(ordering)
     }
 
-    /// Unify the term with the list functor, returning a term for the head and the tail.
-    pub fn unify_list_functor<'b>(
-        &'b self,
-        term: &Term,
-    ) -> Result<(Term<'b>, Term<'b>), PrologError> {
-        let [head, tail] = self.new_term_refs();
-        match unsafe { PL_unify_list(term.term_ptr(), head.term_ptr(), tail.term_ptr()) } {
-            0 => {
-                unsafe {
-                    head.reset();
-                }
-                if unsafe { pl_default_exception() != 0 } {
-                    Err(PrologError::Exception)
-                } else {
-                    Err(PrologError::Failure)
-                }
+    /// Parse the given string as a goal, run it once, and return the
+    /// values of its named variables.
+    ///
+    /// If the goal succeeds, the result is a map from each variable
+    /// name in the goal to its value. If the goal fails, the result
+    /// is `None`. If a variable is bound to a value that can't be
+    /// represented as a [PrologValue], such as a blob, this raises a
+    /// type error.
+    ///
+    /// Example:
+    /// ```
+    /// # use swipl::prelude::*;
+    /// # fn main() -> PrologResult<()> {
+    /// #  let engine = Engine::new();
+    /// #  let activation = engine.activate();
+    /// #  let context: Context<_> = activation.into();
+    ///
+    ///    let bindings = context.run_once("X = foo(Y), Y = 42")?.unwrap();
+    ///    assert_eq!(PrologValue::Int(42), bindings["Y"]);
+    /// #
+    /// #  Ok(())
+    /// # }
+    /// ```
+    pub fn run_once(&self, goal: &str) -> PrologResult<Option<HashMap<String, PrologValue>>> {
+        let frame = self.open_frame();
+        let goal_string = frame.new_term_ref();
+        let goal_term = frame.new_term_ref();
+        let bindings = frame.new_term_ref();
+
+        goal_string.unify(goal)?;
+        let options = term! {frame: [variable_names(#&bindings)]}?;
+        frame.call_once(pred!("term_string/3"), [&goal_term, &goal_string, &options])?;
+
+        match frame.call_once(pred!("call/1"), [&goal_term]) {
+            Ok(()) => {}
+            Err(PrologError::Failure) => return Ok(None),
+            Err(e) => return Err(e),
+        }
+
+        let mut result = HashMap::new();
+        for binding in frame.term_list_iter(&bindings) {
+            let name: Atom = binding.get_arg(1)?;
+            let value = match attempt_opt(binding.get_arg::<PrologValue>(2))? {
+                Some(value) => value,
+                None => {
+                    let [_, value] = frame.compound_terms(&binding)?;
+                    let error = term! {frame: error(type_error(prolog_value, #&value), _)}?;
+                    return frame.raise_exception(&error);
+                }
+            };
+            result.insert(name.name(), value);
+        }
+        frame.close();
+
+        Ok(Some(result))
+    }
+
+    #[cfg(feature = "serde")]
+    /// Find all solutions of a goal, deserializing the value of the given variable in each.
+    ///
+    /// The goal is parsed from a string, after which the variable
+    /// with the given name is used as the template for `findall/3`.
+    /// If the goal contains no variable with that name, this fails.
+    ///
+    /// Any solution that cannot be deserialized results in a
+    /// `rust_error` exception, like with [try_or_die_generic](Self::try_or_die_generic).
+    ///
+    /// Example:
+    /// ```
+    /// # use swipl::prelude::*;
+    /// # fn main() -> PrologResult<()> {
+    /// #  let engine = Engine::new();
+    /// #  let activation = engine.activate();
+    /// #  let context: Context<_> = activation.into();
+    ///
+    ///    let results: Vec<u64> = context.findall_var("between(1, 3, X)", "X")?;
+    ///    assert_eq!(vec![1, 2, 3], results);
+    /// #
+    /// #  Ok(())
+    /// # }
+    /// ```
+    pub fn findall_var<DT: DeserializeOwned>(
+        &self,
+        goal: &str,
+        var: &str,
+    ) -> PrologResult<Vec<DT>> {
+        self.findall_var_page(goal, var, None)
+    }
+
+    #[cfg(feature = "serde")]
+    /// Find the solutions of a goal in pages, deserializing the value of the given variable in each.
+    ///
+    /// This works like [findall_var](Self::findall_var), but rather
+    /// than collecting all solutions at once, the returned iterator
+    /// collects at most `page_size` solutions at a time using
+    /// `limit/2` and `offset/2`. Every page runs the goal again,
+    /// skipping the solutions of the earlier pages, so no query is
+    /// kept open between pages. For the pages to be consistent, the
+    /// goal should produce its solutions in the same order every
+    /// time it is run.
+    ///
+    /// The iterator stops after the first page with fewer than
+    /// `page_size` solutions, or after the first error.
+    ///
+    /// Panics if `page_size` is 0.
+    pub fn query_paginated<'b, DT: DeserializeOwned>(
+        &'b self,
+        goal: &str,
+        var: &str,
+        page_size: usize,
+    ) -> PaginatedQuery<'b, 'a, T, DT> {
+        assert!(page_size > 0, "page size should be greater than 0");
+        PaginatedQuery {
+            context: self,
+            goal: goal.to_string(),
+            var: var.to_string(),
+            page_size,
+            offset: 0,
+            done: false,
+            _result: std::marker::PhantomData,
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    fn findall_var_page<DT: DeserializeOwned>(
+        &self,
+        goal: &str,
+        var: &str,
+        page: Option<(usize, usize)>,
+    ) -> PrologResult<Vec<DT>> {
+        let frame = self.open_frame();
+        let [goal_string, goal_term, bindings, binding, template, solutions] =
+            frame.new_term_refs();
+
+        goal_string.unify(goal)?;
+        let options = term! {frame: [variable_names(#&bindings)]}?;
+        frame.call_once(pred!("term_string/3"), [&goal_term, &goal_string, &options])?;
+
+        binding.unify(Functor::new("=", 2))?;
+        binding.unify_arg(1, Atomable::from(var))?;
+        binding.unify_arg(2, &template)?;
+        frame.call_once(pred!("memberchk/2"), [&binding, &bindings])?;
+        let goal_term = match page {
+            Some((offset, limit)) => {
+                let [offset_term, limit_term] = frame.new_term_refs();
+                offset_term.unify(offset as u64)?;
+                limit_term.unify(limit as u64)?;
+                term! {frame: limit(#&limit_term, offset(#&offset_term, #&goal_term))}?
+            }
+            None => goal_term,
+        };
+        frame.call_once(pred!("findall/3"), [&template, &goal_term, &solutions])?;
+
+        let mut result = Vec::new();
+        for solution in frame.term_list_iter(&solutions) {
+            result.push(frame.try_or_die_generic(super::term::de::from_term(&frame, &solution))?);
+        }
+        frame.close();
+
+        Ok(result)
+    }
+
+    #[cfg(feature = "serde")]
+    /// Parse a string as a term and deserialize it into a rust value.
+    ///
+    /// This combines [term_from_string](Self::term_from_string) and
+    /// [from_term](super::term::de::from_term). If the term cannot be
+    /// deserialized, this results in a `rust_error` exception, like
+    /// with [try_or_die_generic](Self::try_or_die_generic).
+    pub fn parse_and_deserialize<DT: DeserializeOwned>(&self, s: &str) -> PrologResult<DT> {
+        let frame = self.open_frame();
+        let term = frame.term_from_string(s)?;
+        let result = frame.try_or_die_generic(super::term::de::from_term(&frame, &term))?;
+        frame.close();
+
+        Ok(result)
+    }
+
+    /// Call the given function while holding the prolog mutex with the given name.
+    ///
+    /// This uses `mutex_lock/1` and `mutex_unlock/1`, so the mutex is
+    /// shared with prolog code synchronizing through `with_mutex/2`
+    /// on the same name. The mutex is released even if the function
+    /// panics, after which the panic is resumed.
+    ///
+    /// An error is only returned if locking or unlocking the mutex
+    /// raised an exception.
+    pub fn with_mutex<R>(&self, name: &str, f: impl FnOnce() -> R) -> PrologResult<R> {
+        let frame = self.open_frame();
+        let mutex = frame.new_term_ref();
+        mutex.unify(Atomable::from(name))?;
+        frame.call_once(pred!("mutex_lock/1"), [&mutex])?;
+        frame.close();
+
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(f));
+
+        // the function may have left an exception for us to
+        // propagate, which should not prevent the unlock.
+        let unlock_result = unsafe {
+            with_cleared_exception(|| {
+                let frame = self.open_frame();
+                let mutex = frame.new_term_ref();
+                mutex.unify(Atomable::from(name))?;
+                frame.call_once(pred!("mutex_unlock/1"), [&mutex])?;
+                frame.close();
+
+                Ok(())
+            })
+        };
+
+        match result {
+            Ok(r) => unlock_result.map(|_| r),
+            Err(panic) => std::panic::resume_unwind(panic),
+        }
+    }
+
+    /// Require the given term to be of the given type.
+    ///
+    /// This uses the prolog predicate `must_be/2` from
+    /// `library(error)`. The type is parsed from a string, so
+    /// parameterized types like `list(integer)` or `between(1, 10)`
+    /// can be used as well. If the term is not of the given type,
+    /// this raises the same `type_error`, `domain_error` or
+    /// `instantiation_error` exception as `must_be/2` does, which
+    /// makes it useful for validating the arguments of foreign
+    /// predicates.
+    pub fn must_be(&self, type_: &str, term: &Term) -> PrologResult<()> {
+        let frame = self.open_frame();
+        let type_term = frame.term_from_string(type_)?;
+        frame.call_once(pred!("must_be/2"), [&type_term, term])?;
+        frame.close();
+
+        Ok(())
+    }
+
+    /// Require the given term to be ground.
+    ///
+    /// If the term contains any unbound variables, this raises an
+    /// `error(instantiation_error, _)` exception. This is a common
+    /// guard for the inputs of foreign predicates.
+    pub fn require_ground(&self, term: &Term) -> PrologResult<()> {
+        term.assert_term_handling_possible();
+        if unsafe { PL_is_ground(term.term_ptr()) != 0 } {
+            return Ok(());
+        }
+
+        let self_ = self;
+        let exception_term = term! {self_: error(instantiation_error, _)}?;
+
+        self.raise_exception(&exception_term)
+    }
+
+    /// Turn a result into a `PrologResult`.
+    ///
+    /// For this to work, the `Err` component of the `Result` needs to
+    /// implement the trait `IntoPrologException`. This is currently
+    /// only the case for [std::io::Error].
+    pub fn try_or_die<R, E: IntoPrologException>(&self, r: Result<R, E>) -> PrologResult<R> {
+        match r {
+            Ok(ok) => Ok(ok),
+            Err(e) => {
+                let reset_term = self.new_term_ref();
+                let exception_term = e.into_prolog_exception(self)?;
+                let result = self.raise_exception(&exception_term);
+
+                unsafe {
+                    reset_term.reset();
+                }
+
+                result
+            }
+        }
+    }
+
+    /// Turn a result into a `PrologResult`.
+    ///
+    /// For this to work, the `Err` component of the `Result` needs to
+    /// implement the trait [Error](std::error::Error).
+    pub fn try_or_die_generic<R, E: std::error::Error>(&self, r: Result<R, E>) -> PrologResult<R> {
+        match r {
+            Ok(ok) => Ok(ok),
+            Err(e) => {
+                let reset_term = self.new_term_ref();
+                let msg = format!("{}", e);
+
+                // TODO: term macro doesn't like self, which is
+                // probably only a problem for things inside this
+                // crate but still should probably be resolved.
+                let self_ = self;
+                let exception_term = term! {self_: error(rust_error(#msg), _)}?;
+                let result = self.raise_exception(&exception_term);
+
+                unsafe {
+                    reset_term.reset();
+                }
+
+                result
             }
-            _ => Ok((head, tail)),
         }
     }
 
-    pub fn into_generic(&self) -> GenericQueryableContext {
-        self.assert_activated();
-        self.activated.set(false);
-        unsafe { Context::new_activated(self, GenericQueryableContextType, self.engine) }
+    /// Destructively replace an argument of a compound term.
+    ///
+    /// This uses the prolog predicate `setarg/3`. The argument
+    /// position is 1-indexed. The assignment is backtrackable, which
+    /// means that it is undone when the frame in which it happened
+    /// is rewound or discarded, just like a unification would be.
+    pub fn setarg(&self, index: usize, compound: &Term, value: &Term) -> PrologResult<()> {
+        let frame = self.open_frame();
+        let index_term = frame.new_term_ref();
+        index_term.unify(index as u64)?;
+        frame.call_once(pred!("setarg/3"), [&index_term, compound, value])?;
+        frame.close();
+
+        Ok(())
+    }
+
+    /// Destructively replace an argument of a compound term, without undoing this on backtracking.
+    ///
+    /// This uses the prolog predicate `nb_setarg/3`. The argument
+    /// position is 1-indexed. Unlike [setarg](Context::setarg), the
+    /// assignment survives rewinding or discarding the frame in
+    /// which it happened. Note that the value is copied into the
+    /// compound, so later bindings of variables in the value are not
+    /// reflected in the compound.
+    pub fn nb_setarg(&self, index: usize, compound: &Term, value: &Term) -> PrologResult<()> {
+        let frame = self.open_frame();
+        let index_term = frame.new_term_ref();
+        index_term.unify(index as u64)?;
+        frame.call_once(pred!("nb_setarg/3"), [&index_term, compound, value])?;
+        frame.close();
+
+        Ok(())
+    }
+
+    /// Extract a substring from the given text term.
+    ///
+    /// This uses the prolog predicate `sub_string/5`, returning the
+    /// `len` characters that follow the first `before` characters of
+    /// `s`. Only the substring is converted into a rust string. If
+    /// the given range does not fit within `s`, this fails.
+    pub fn sub_string(&self, s: &Term, before: usize, len: usize) -> PrologResult<String> {
+        let frame = self.open_frame();
+        let [before_term, len_term, after_term, sub_term] = frame.new_term_refs();
+        before_term.unify(before as u64)?;
+        len_term.unify(len as u64)?;
+        frame.call_once(
+            pred!("sub_string/5"),
+            [s, &before_term, &len_term, &after_term, &sub_term],
+        )?;
+        let sub: String = sub_term.get()?;
+        frame.close();
+
+        Ok(sub)
+    }
+
+    /// Read the given prolog flag.
+    ///
+    /// Returns `None` if the flag does not exist or if its value
+    /// cannot be converted into a `G`.
+    pub(crate) fn prolog_flag<G: TermGetable>(&self, name: &str) -> PrologResult<Option<G>> {
+        let frame = self.open_frame();
+        let [flag_term, value_term] = frame.new_term_refs();
+        flag_term.unify(Atomable::from(name))?;
+        let value =
+            if attempt(frame.call_once(pred!("current_prolog_flag/2"), [&flag_term, &value_term]))?
+            {
+                attempt_opt(value_term.get::<G>())?
+            } else {
+                None
+            };
+        frame.close();
+
+        Ok(value)
+    }
+
+    /// Read a prolog flag of the current engine.
+    ///
+    /// Returns `None` if the flag does not exist or if its value
+    /// cannot be converted into a `G`. See
+    /// [set_engine_flag](Context::set_engine_flag) for which flags
+    /// are local to an engine.
+    pub fn get_engine_flag<G: TermGetable>(&self, name: &str) -> PrologResult<Option<G>> {
+        self.prolog_flag(name)
+    }
+
+    /// Set a prolog flag of the current engine.
+    ///
+    /// This uses `set_prolog_flag/2`. In SWI-Prolog, every engine
+    /// has its own copy of most prolog flags, which it inherits from
+    /// the thread that created it. Changing such a flag, like
+    /// `occurs_check`, `unknown` or `last_call_optimisation`, only
+    /// affects the current engine, which allows engines in a pool to
+    /// be configured differently. Flags describing the whole process,
+    /// such as `bounded` or `executable`, are read-only.
+    pub fn set_engine_flag<V: Unifiable>(&self, name: &str, value: V) -> PrologResult<()> {
+        let frame = self.open_frame();
+        let [flag, value_term] = frame.new_term_refs();
+        flag.unify(Atomable::from(name))?;
+        value_term.unify(value)?;
+        frame.call_once(pred!("set_prolog_flag/2"), [&flag, &value_term])?;
+        frame.close();
+
+        Ok(())
+    }
+
+    /// Run the given function with a prolog flag temporarily set to the given value.
+    ///
+    /// The current value of the flag is saved, the flag is set using
+    /// `set_prolog_flag/2`, and after the function returns, the old
+    /// value is restored. The old value is also restored if the
+    /// function panics. If the flag does not exist, this fails.
+    ///
+    /// If the function leaves an exception pending, the flag is still
+    /// restored, and the exception is left pending for the caller.
+    ///
+    /// The function does not receive a context, as it is expected to
+    /// keep using this one.
+    pub fn with_flag<R>(&self, name: &str, value: &Term, f: impl FnOnce() -> R) -> PrologResult<R> {
+        let frame = self.open_frame();
+        let [flag, old] = frame.new_term_refs();
+        flag.unify(Atomable::from(name))?;
+        frame.call_once(pred!("current_prolog_flag/2"), [&flag, &old])?;
+        frame.call_once(pred!("set_prolog_flag/2"), [&flag, value])?;
+        let old = Record::from_term(&old);
+        frame.close();
+
+        let guard = FlagRestore {
+            context: self,
+            name: name.to_owned(),
+            old,
+            restored: false,
+        };
+        let result = f();
+        guard.restore()?;
+
+        Ok(result)
+    }
+
+    /// Returns whether integers are bounded.
+    ///
+    /// This reads the prolog flag `bounded`, which is false if
+    /// SWI-Prolog was compiled with unbounded integer support.
+    pub fn bounded(&self) -> PrologResult<bool> {
+        self.prolog_flag("bounded")?.ok_or(PrologError::Failure)
+    }
+
+    /// Returns the largest integer, if it fits in an `i64`.
+    ///
+    /// This reads the prolog flag `max_integer`.
+    pub fn max_integer(&self) -> PrologResult<Option<i64>> {
+        self.prolog_flag("max_integer")
+    }
+
+    /// Returns the smallest integer, if it fits in an `i64`.
+    ///
+    /// This reads the prolog flag `min_integer`.
+    pub fn min_integer(&self) -> PrologResult<Option<i64>> {
+        self.prolog_flag("min_integer")
+    }
+
+    /// Returns the default encoding used for streams.
+    ///
+    /// This reads the prolog flag `encoding`.
+    pub fn encoding(&self) -> PrologResult<String> {
+        let encoding: Option<PrologText> = self.prolog_flag("encoding")?;
+
+        encoding.map(|e| e.to_string()).ok_or(PrologError::Failure)
+    }
+
+    /// Enable or disable autoloading of library predicates.
+    ///
+    /// This sets the prolog flag `autoload`. When autoloading is
+    /// disabled, calling a library predicate which was not loaded
+    /// explicitly raises an existence error, which makes loading
+    /// deterministic.
+    pub fn set_autoload(&self, enabled: bool) -> PrologResult<()> {
+        let frame = self.open_frame();
+        let [flag, value] = frame.new_term_refs();
+        flag.unify(Atomable::from("autoload"))?;
+        value.unify(enabled)?;
+        frame.call_once(pred!("set_prolog_flag/2"), [&flag, &value])?;
+        frame.close();
+
+        Ok(())
+    }
+
+    /// Map a character to another character when reading terms.
+    ///
+    /// This calls `char_conversion/2`. The conversion only applies
+    /// outside of quoted atoms and strings, and only when the prolog
+    /// flag `char_conversion` is enabled, which can be done with
+    /// [enable_char_conversion](Context::enable_char_conversion).
+    /// Mapping a character to itself removes its conversion.
+    pub fn set_char_conversion(&self, from: char, to: char) -> PrologResult<()> {
+        let frame = self.open_frame();
+        let [from_term, to_term] = frame.new_term_refs();
+        from_term.unify(Atomable::from(from.to_string()))?;
+        to_term.unify(Atomable::from(to.to_string()))?;
+        frame.call_once(pred!("char_conversion/2"), [&from_term, &to_term])?;
+        frame.close();
+
+        Ok(())
+    }
+
+    /// Enable or disable character conversion when reading terms.
+    ///
+    /// This sets the prolog flag `char_conversion`, which is disabled
+    /// by default.
+    pub fn enable_char_conversion(&self, enabled: bool) -> PrologResult<()> {
+        let frame = self.open_frame();
+        let [flag, value] = frame.new_term_refs();
+        flag.unify(Atomable::from("char_conversion"))?;
+        value.unify(enabled)?;
+        frame.call_once(pred!("set_prolog_flag/2"), [&flag, &value])?;
+        frame.close();
+
+        Ok(())
+    }
+
+    /// Check whether the given source file exists.
+    ///
+    /// The spec is parsed as a term and passed to the prolog
+    /// predicate `exists_source/1`, so it may use file search paths,
+    /// like `library(lists)`.
+    pub fn exists_source(&self, spec: &str) -> PrologResult<bool> {
+        let frame = self.open_frame();
+        let spec_term = frame.term_from_string(spec)?;
+        let result = frame.call_once_bool(pred!("exists_source/1"), [&spec_term])?;
+        frame.close();
+
+        Ok(result)
+    }
+
+    /// Format a message term as it would be printed by `print_message/2`.
+    ///
+    /// This translates the message using `translate_message//1`, and
+    /// prints the resulting lines to a string using
+    /// `print_message_lines/3`. No prefix is added to the lines, and
+    /// the trailing newline is removed.
+    pub fn message_to_string(&self, message_term: &Term) -> PrologResult<String> {
+        let frame = self.open_frame();
+        let [lines, tail, output] = frame.new_term_refs();
+        tail.unify(Nil)?;
+        frame.call_once(pred!("translate_message/3"), [message_term, &lines, &tail])?;
+
+        let prefix = Atom::new("");
+        let goal = term! {frame: with_output_to(string(#&output), print_message_lines(current_output, #&prefix, #&lines))}?;
+        frame.call_once(pred!("call/1"), [&goal])?;
+        let message: String = output.get()?;
+        frame.close();
+
+        Ok(message.trim_end_matches('\n').to_string())
+    }
+
+    /// Format a clause as it would be printed by `listing/1`.
+    ///
+    /// This uses the prolog predicate `portray_clause/1`, which names
+    /// variables, puts each goal of the body on its own indented line,
+    /// and ends the clause with a full stop and a newline. Unlike
+    /// [string_from_term](Self::string_from_term), the result can be
+    /// read back as prolog source.
+    pub fn portray_clause_string(&self, clause: &Term) -> PrologResult<String> {
+        let frame = self.open_frame();
+        let output = frame.new_term_ref();
+        let goal = term! {frame: with_output_to(string(#&output), portray_clause(#clause))}?;
+        frame.call_once(pred!("call/1"), [&goal])?;
+        let result: String = output.get()?;
+        frame.close();
+
+        Ok(result)
+    }
+
+    /// Returns all known atoms that start with the given prefix.
+    ///
+    /// This uses the same mechanism the prolog toplevel uses for
+    /// completion, and is useful for implementing completion in
+    /// interactive tools. As every predicate name is an atom, this
+    /// also completes predicate names.
+    pub fn complete_atom(&self, prefix: &str) -> PrologResult<Vec<String>> {
+        let frame = self.open_frame();
+        let [prefix_term, alternatives] = frame.new_term_refs();
+        prefix_term.unify(Atomable::from(prefix))?;
+        frame.call_once(pred!("$atom_completions/2"), [&prefix_term, &alternatives])?;
+        let result = frame
+            .term_list_iter(&alternatives)
+            .map(|alternative| {
+                alternative
+                    .get::<PrologText>()
+                    .map(|text| text.into_inner())
+            })
+            .collect();
+        frame.close();
+
+        result
+    }
+
+    /// Split an atom into its characters.
+    ///
+    /// This uses the prolog predicate `atom_chars/2`.
+    pub fn atom_chars(&self, atom: &Atom) -> PrologResult<Vec<char>> {
+        self.text_to_chars(pred!("atom_chars/2"), atom)
+    }
+
+    /// Split an atom into its character codes.
+    ///
+    /// This uses the prolog predicate `atom_codes/2`.
+    pub fn atom_codes(&self, atom: &Atom) -> PrologResult<Vec<u32>> {
+        self.text_to_codes(pred!("atom_codes/2"), atom)
+    }
+
+    /// Split the text in the given term into its characters.
+    ///
+    /// This uses the prolog predicate `string_chars/2`, which accepts
+    /// any kind of text, including strings, atoms and numbers.
+    pub fn string_chars(&self, string: &Term) -> PrologResult<Vec<char>> {
+        self.text_to_chars(pred!("string_chars/2"), string)
+    }
+
+    /// Split the text in the given term into its character codes.
+    ///
+    /// This uses the prolog predicate `string_codes/2`, which accepts
+    /// any kind of text, including strings, atoms and numbers.
+    pub fn string_codes(&self, string: &Term) -> PrologResult<Vec<u32>> {
+        self.text_to_codes(pred!("string_codes/2"), string)
+    }
+
+    fn text_to_chars<C: Callable<2>, U: Unifiable>(
+        &self,
+        callable: C,
+        text: U,
+    ) -> PrologResult<Vec<char>> {
+        let frame = self.open_frame();
+        let [text_term, list] = frame.new_term_refs();
+        text_term.unify(text)?;
+        frame.call_once(callable, [&text_term, &list])?;
+        let result = frame
+            .term_list_iter(&list)
+            .map(|c| {
+                c.get_atom_name(|name| name.and_then(|name| name.chars().next()))?
+                    .ok_or(PrologError::Failure)
+            })
+            .collect();
+        frame.close();
+
+        result
+    }
+
+    fn text_to_codes<C: Callable<2>, U: Unifiable>(
+        &self,
+        callable: C,
+        text: U,
+    ) -> PrologResult<Vec<u32>> {
+        let frame = self.open_frame();
+        let [text_term, list] = frame.new_term_refs();
+        text_term.unify(text)?;
+        frame.call_once(callable, [&text_term, &list])?;
+        let codes: Vec<u64> = list.get()?;
+        frame.close();
+
+        Ok(codes.into_iter().map(|code| code as u32).collect())
+    }
+
+    /// Create a new code list from the given bytes.
+    ///
+    /// Each byte becomes a code in the range 0 to 255. The list is
+    /// built in one go using `PL_put_list_ncodes`, which is much
+    /// faster than constructing it one element at a time.
+    pub fn new_code_list_from_bytes(&self, bytes: &[u8]) -> PrologResult<Term> {
+        let term = self.new_term_ref();
+        let result = unsafe {
+            PL_put_list_ncodes(
+                term.term_ptr(),
+                bytes.len(),
+                bytes.as_ptr() as *const std::os::raw::c_char,
+            )
+        };
+
+        if result != 0 {
+            Ok(term)
+        } else if unsafe { pl_default_exception() != 0 } {
+            Err(PrologError::Exception)
+        } else {
+            Err(PrologError::Failure)
+        }
+    }
+
+    /// Call the given goal once, capturing its output as bytes.
+    ///
+    /// This uses `with_output_to(codes(Codes), Goal)`. Returns
+    /// whether the goal succeeded, along with the captured output. If
+    /// the goal failed, no output is captured. As each code is turned
+    /// into a single byte, output containing characters beyond code
+    /// 255 results in a `rust_error` exception, like with
+    /// [try_or_die_generic](Self::try_or_die_generic).
+    pub fn call_capturing_codes(&self, goal: &Term) -> PrologResult<(bool, Vec<u8>)> {
+        let frame = self.open_frame();
+        let [codes, sink] = frame.new_term_refs();
+        sink.unify(Functor::new("codes", 1))?;
+        sink.unify_arg(1, &codes)?;
+        if !frame.call_once_bool(pred!("with_output_to/2"), [&sink, goal])? {
+            frame.close();
+            return Ok((false, Vec::new()));
+        }
+
+        let codes: Vec<u64> = codes.get()?;
+        let bytes = frame.try_or_die_generic(
+            codes
+                .into_iter()
+                .map(u8::try_from)
+                .collect::<Result<Vec<u8>, _>>(),
+        )?;
+        frame.close();
+
+        Ok((true, bytes))
+    }
+
+    /// Check whether the given character belongs to the given class.
+    ///
+    /// This uses the prolog predicate `char_type/2`, so that the
+    /// classification exactly matches the one prolog uses.
+    pub fn char_type(&self, c: char, class: CharClass) -> PrologResult<bool> {
+        let frame = self.open_frame();
+        let char_term = frame.new_term_ref();
+        char_term.unify(Atomable::String(c.to_string()))?;
+        let class_term = class.to_term(&frame)?;
+        let result = attempt(frame.call_once(pred!("char_type/2"), [&char_term, &class_term]))?;
+        frame.close();
+
+        Ok(result)
+    }
+
+    /// Define an operator.
+    ///
+    /// This uses the prolog predicate `op/3`. After defining an
+    /// operator, terms using it can be parsed with
+    /// [term_from_string](Self::term_from_string). A priority of 0
+    /// removes the operator.
+    pub fn op(&self, priority: u16, op_type: OpType, name: &str) -> PrologResult<()> {
+        let frame = self.open_frame();
+        let [priority_term, type_term, name_term] = frame.new_term_refs();
+        priority_term.unify(priority as u64)?;
+        type_term.unify(Atomable::from(op_type.name()))?;
+        name_term.unify(Atomable::from(name))?;
+        frame.call_once(pred!("op/3"), [&priority_term, &type_term, &name_term])?;
+        frame.close();
+
+        Ok(())
+    }
+
+    /// Run the stack garbage collector.
+    ///
+    /// This uses the prolog predicate `garbage_collect/0`, which
+    /// reclaims unreachable data on the prolog stacks. It does not
+    /// collect atoms, which is done separately by
+    /// `garbage_collect_atoms/0`. This is useful in long-running
+    /// foreign computations that create a lot of terms they no longer
+    /// need.
+    ///
+    /// Terms that are still referenced by live term references are
+    /// retained. Data that is only referenced from rust in other ways,
+    /// for example through raw `term_t` handles that were reset, must
+    /// not be used after calling this.
+    pub fn garbage_collect(&self) -> PrologResult<()> {
+        self.call_once(pred!("garbage_collect/0"), [])
+    }
+
+    fn profile_snapshot(&self) -> PrologResult<(i64, f64, i64)> {
+        let frame = self.open_frame();
+        let mut values = Vec::with_capacity(3);
+        for key in ["inferences", "cputime", "stack"] {
+            let [key_term, value] = frame.new_term_refs();
+            key_term.unify(Atomable::from(key))?;
+            frame.call_once(pred!("statistics/2"), [&key_term, &value])?;
+            values.push(value);
+        }
+        let snapshot = (values[0].get()?, values[1].get()?, values[2].get()?);
+        frame.close();
+
+        Ok(snapshot)
+    }
+
+    /// Run the given function, measuring the prolog resources it consumed.
+    ///
+    /// The inference count, CPU time and stack usage are retrieved
+    /// with `statistics/2` before and after calling the function, and
+    /// the difference is returned along with the function's result.
+    /// The retrieval itself takes a few inferences, so small
+    /// measurements are not exact.
+    pub fn profile_block<R>(&self, f: impl FnOnce() -> R) -> PrologResult<(R, ProfileDelta)> {
+        let (inferences, cpu_time, stack) = self.profile_snapshot()?;
+        let result = f();
+        let (inferences_after, cpu_time_after, stack_after) = self.profile_snapshot()?;
+
+        Ok((
+            result,
+            ProfileDelta {
+                inferences: inferences_after - inferences,
+                cpu_time: cpu_time_after - cpu_time,
+                stack: stack_after - stack,
+            },
+        ))
+    }
+
+    /// Create a new, uniquely named temporary module.
+    ///
+    /// Clauses asserted in this module are isolated from other
+    /// modules. When the returned [TemporaryModule] is dropped, all
+    /// predicates defined in the module are abolished, making this
+    /// suitable for running goals from multiple tenants.
+    pub fn new_temporary_module(&self) -> PrologResult<TemporaryModule> {
+        TemporaryModule::new(self)
+    }
+
+    /// Find all predicates matching the given name and arity.
+    ///
+    /// This uses the prolog predicate `current_predicate/1` with a
+    /// partially instantiated indicator. If no name or arity is
+    /// given, predicates of any name or arity are found. Predicates
+    /// are searched in all modules, but predicates imported into a
+    /// module from another are only returned for the module defining
+    /// them.
+    ///
+    /// If a matching predicate has an arity larger than a [Functor]
+    /// supports, this raises a representation error.
+    pub fn predicates_matching(
+        &self,
+        name: Option<&str>,
+        arity: Option<u16>,
+    ) -> PrologResult<Vec<Predicate>> {
+        let frame = self.open_frame();
+        let query = frame.term_from_string(
+            "p(M, N, A, (current_predicate(M:N/A), functor(H, N, A), \\+ predicate_property(M:H, imported_from(_))))",
+        )?;
+        let [module, name_term, arity_term, goal] = frame.compound_terms(&query)?;
+        if let Some(name) = name {
+            name_term.unify(Atomable::from(name))?;
+        }
+        if let Some(arity) = arity {
+            arity_term.unify(arity as u64)?;
+        }
+        let list = frame.new_term_ref();
+        let template = term! {frame: p(#&module, #&name_term, #&arity_term)}?;
+        frame.call_once(pred!("findall/3"), [&template, &goal, &list])?;
+
+        let mut predicates = Vec::new();
+        for found in frame.term_list_iter(&list) {
+            let [module, name, arity] = frame.compound_terms(&found)?;
+            let module: Atom = module.get()?;
+            let name: Atom = name.get()?;
+            let arity = frame.functor_arity(arity.get::<u64>()?)?;
+            predicates.push(Predicate::new(
+                Functor::new(name, arity),
+                Module::new(module),
+            ));
+        }
+        frame.close();
+
+        Ok(predicates)
+    }
+
+    /// Check whether the predicate `name/arity` is defined in the given module.
+    ///
+    /// This uses the prolog predicate `current_predicate/1` with a
+    /// module-qualified indicator, so only predicates visible in the
+    /// given module are considered. Predicates that could be
+    /// autoloaded but were not loaded yet are not found.
+    pub fn predicate_defined_in(&self, module: Module, name: &str, arity: u16) -> bool {
+        let frame = self.open_frame();
+        let [indicator, qualified] = frame.new_term_refs();
+        let defined = indicator.unify(Functor::new("/", 2)).is_ok()
+            && indicator.unify_arg(1, Atomable::from(name)).is_ok()
+            && indicator.unify_arg(2, arity as u64).is_ok()
+            && qualified.unify(Functor::new(":", 2)).is_ok()
+            && qualified.unify_arg(1, module.name()).is_ok()
+            && qualified.unify_arg(2, &indicator).is_ok()
+            && frame
+                .call_once_bool(pred!("current_predicate/1"), [&qualified])
+                .unwrap_or(false);
+        frame.discard();
+
+        defined
+    }
+
+    /// Look up a predicate from an indicator like `"lists:append/3"`.
+    ///
+    /// This accepts the same `[module:]name/arity` syntax as the
+    /// string form of the `pred!` macro. If no module is given, the
+    /// predicate is looked up in the `user` module. An indicator that
+    /// does not follow this syntax raises a `domain_error`, and an
+    /// arity larger than a [Functor] supports raises a
+    /// `representation_error`.
+    pub fn predicate_from_indicator(&self, s: &str) -> PrologResult<Predicate> {
+        let parsed = s.rfind('/').and_then(|pos| {
+            let arity = &s[pos + 1..];
+            if arity.is_empty() || !arity.bytes().all(|b| b.is_ascii_digit()) {
+                return None;
+            }
+            let module_and_name = &s[..pos];
+            let (module, name) = match module_and_name.rfind(':') {
+                Some(pos) => (&module_and_name[..pos], &module_and_name[pos + 1..]),
+                None => ("user", module_and_name),
+            };
+
+            if name.is_empty() || module.is_empty() {
+                None
+            } else {
+                Some((module, name, arity))
+            }
+        });
+
+        match parsed {
+            Some((module, name, arity)) => {
+                // digits that don't fit in a u64 are too large an arity as well
+                let arity = self.functor_arity(arity.parse().unwrap_or(u64::MAX))?;
+                Ok(Predicate::new(
+                    Functor::new(name, arity),
+                    Module::new(module),
+                ))
+            }
+            None => {
+                let self_ = self;
+                let error = term! {self_: error(domain_error(predicate_indicator, #s), _)}?;
+                self.raise_exception(&error)
+            }
+        }
+    }
+
+    /// Check that the given arity is supported by [Functor].
+    ///
+    /// If it is not, this raises `representation_error(max_arity)`.
+    fn functor_arity(&self, arity: u64) -> PrologResult<u16> {
+        match u16::try_from(arity) {
+            Ok(arity) if arity as usize <= crate::consts::MAX_ARITY => Ok(arity),
+            _ => {
+                let self_ = self;
+                let error = term! {self_: error(representation_error(max_arity), _)}?;
+                self.raise_exception(&error)
+            }
+        }
+    }
+
+    /// Create a name for a temporary file.
+    ///
+    /// This uses the prolog predicate `tmp_file/2`. The file is
+    /// removed when the prolog process halts.
+    pub fn tmp_file(&self, base: &str) -> PrologResult<String> {
+        let frame = self.open_frame();
+        let [base_term, name_term] = frame.new_term_refs();
+        base_term.unify(Atomable::from(base))?;
+        frame.call_once(pred!("tmp_file/2"), [&base_term, &name_term])?;
+        let name = name_term.get_atom_name(|name| name.map(|name| name.to_owned()))?;
+        frame.close();
+
+        name.ok_or(PrologError::Failure)
+    }
+
+    /// Create a saved state of the program loaded into prolog.
+    ///
+    /// This uses the prolog predicate `qsave_program/2`. The saved
+    /// state contains all loaded code, and can be loaded quickly at
+    /// startup, for example with [Engine::with_state].
+    pub fn qsave_program(&self, path: &Path, options: SaveOptions) -> PrologResult<()> {
+        let frame = self.open_frame();
+        let path_term = frame.new_term_ref();
+        path_term.unify(Atomable::String(path.to_string_lossy().into_owned()))?;
+        let options = options.to_term(&frame)?;
+        frame.call_once(pred!("qsave_program/2"), [&path_term, &options])?;
+        frame.close();
+
+        Ok(())
+    }
+
+    /// Compile a prolog source file into a quick load file.
+    ///
+    /// This uses the prolog predicate `qcompile/1`, which also loads
+    /// the source file. The quick load file is written next to the
+    /// source file, and then moved to the given path. Loading it with
+    /// [load_qlf](Self::load_qlf) is a lot faster than loading the
+    /// source file, as it does not need to be parsed again.
+    pub fn compile_to_qlf(&self, src: &Path, qlf: &Path) -> PrologResult<()> {
+        let frame = self.open_frame();
+        let src_term = frame.new_term_ref();
+        src_term.unify(Atomable::String(src.to_string_lossy().into_owned()))?;
+        frame.call_once(pred!("qcompile/1"), [&src_term])?;
+        frame.close();
+
+        let compiled = src.with_extension("qlf");
+        if compiled != qlf {
+            self.try_or_die(std::fs::rename(compiled, qlf))?;
+        }
+
+        Ok(())
+    }
+
+    /// Load a quick load file, as created by [compile_to_qlf](Self::compile_to_qlf).
+    ///
+    /// This uses the prolog predicate `load_files/2`.
+    pub fn load_qlf(&self, qlf: &Path) -> PrologResult<()> {
+        let frame = self.open_frame();
+        let [qlf_term, options] = frame.new_term_refs();
+        qlf_term.unify(Atomable::String(qlf.to_string_lossy().into_owned()))?;
+        options.unify(Nil)?;
+        frame.call_once(pred!("load_files/2"), [&qlf_term, &options])?;
+        frame.close();
+
+        Ok(())
+    }
+
+    /// Load a prolog source file as a plugin into its own module.
+    ///
+    /// The module is named after the absolute path of the file, so
+    /// loading different plugins never mixes up their predicates.
+    /// The file is loaded with `load_files/2` into this module. If the
+    /// file is not a module file, its clauses are defined in the
+    /// plugin module directly.
+    ///
+    /// A module file still defines its own module globally, under the
+    /// name in its module declaration, and only its exported
+    /// predicates are imported into the plugin module. If a module of
+    /// that name was already loaded from a different file, this
+    /// raises a permission error rather than replacing that module.
+    ///
+    /// Goals can be run in the returned module by passing it as the
+    /// context module to [open_with_module](Self::open_with_module).
+    pub fn load_plugin(&self, path: &Path) -> PrologResult<Module> {
+        let frame = self.open_frame();
+        let [path_term, absolute, options, qualified, load_options] = frame.new_term_refs();
+        path_term.unify(Atomable::String(path.to_string_lossy().into_owned()))?;
+        options.unify(frame.term_from_string("[file_type(prolog), access(read)]")?)?;
+        frame.call_once(
+            pred!("absolute_file_name/3"),
+            [&path_term, &absolute, &options],
+        )?;
+        let module = Module::new(absolute.get::<Atom>()?);
+
+        let [stream, declaration, file] = frame.new_term_refs();
+        let read_declaration = term! {frame: setup_call_cleanup(open(#&absolute, read, #&stream), read_term(#&stream, #&declaration, #&Nil), close(#&stream))}?;
+        frame.call_once(pred!("call/1"), [&read_declaration])?;
+        if attempt_opt(declaration.get::<Functor>())? == Some(Functor::new(":-", 1)) {
+            let [directive] = frame.compound_terms(&declaration)?;
+            if attempt_opt(directive.get::<Functor>())? == Some(Functor::new("module", 2)) {
+                let [name, _] = frame.compound_terms(&directive)?;
+                let file_property = term! {frame: file(#&file)}?;
+                if frame.call_once_bool(pred!("module_property/2"), [&name, &file_property])?
+                    && file.get::<Atom>()? != absolute.get::<Atom>()?
+                {
+                    let error = term! {frame: error(permission_error(load, module, #&name), _)}?;
+                    return frame.raise_exception(&error);
+                }
+            }
+        }
+
+        qualified.unify(Functor::new(":", 2))?;
+        qualified.unify_arg(1, module.name())?;
+        qualified.unify_arg(2, &absolute)?;
+        load_options.unify(Nil)?;
+        frame.call_once(pred!("load_files/2"), [&qualified, &load_options])?;
+        frame.close();
+
+        Ok(module)
+    }
+
+    /// Write the given term to a writer in the fast binary term format.
+    ///
+    /// This uses the prolog predicate `fast_term_serialized/2`, which
+    /// is a lot faster than writing and reading back a textual
+    /// representation of large terms. The writer is expected to
+    /// contain just this one term. Use
+    /// [fast_read_term](Context::fast_read_term) to read it back.
+    pub fn fast_write_term<W: std::io::Write>(
+        &self,
+        term: &Term,
+        mut writer: W,
+    ) -> PrologResult<()> {
+        let frame = self.open_frame();
+        let serialized = frame.new_term_ref();
+        frame.call_once(pred!("fast_term_serialized/2"), [term, &serialized])?;
+        let bytes: Vec<u8> = serialized.get()?;
+        frame.try_or_die(writer.write_all(&bytes).and_then(|_| writer.flush()))?;
+        frame.close();
+
+        Ok(())
+    }
+
+    /// Read a term from a reader containing a term in the fast binary term format.
+    ///
+    /// The whole reader is consumed. See
+    /// [fast_write_term](Context::fast_write_term) for producing
+    /// such a term.
+    pub fn fast_read_term<R: std::io::Read>(&self, mut reader: R) -> PrologResult<Term> {
+        let term = self.new_term_ref();
+        let frame = self.open_frame();
+        let mut bytes = Vec::new();
+        frame.try_or_die(reader.read_to_end(&mut bytes))?;
+        let serialized = frame.new_term_ref();
+        serialized.unify(bytes.as_slice())?;
+        frame.call_once(pred!("fast_term_serialized/2"), [&term, &serialized])?;
+        frame.close();
+
+        Ok(term)
+    }
+
+    /// Iterate over the integers from `low` to `high`, inclusive.
+    ///
+    /// This opens a query for the prolog predicate `between/3`, and
+    /// returns an iterator which retrieves the next solution on each
+    /// step. The query is closed once the iterator is exhausted or
+    /// dropped. Every solution is retrieved into the same term.
+    ///
+    /// If setting up the query fails, the iterator returns that error
+    /// and then ends.
+    ///
+    /// While the iterator is alive, this context cannot be used, as
+    /// is the case for any open query.
+    pub fn between(&self, low: i64, high: i64) -> BetweenIterator {
+        let [low_term, high_term, result] = self.new_term_refs();
+        match low_term.unify(low).and_then(|_| high_term.unify(high)) {
+            Ok(()) => {
+                let query = self.open(pred!("between/3"), [&low_term, &high_term, &result]);
+                BetweenIterator {
+                    query: Some(query),
+                    result,
+                    error: None,
+                }
+            }
+            Err(e) => BetweenIterator {
+                query: None,
+                result,
+                error: Some(e),
+            },
+        }
+    }
+
+    /// Call `aggregate_all/3` with the given aggregation spec and goal.
+    fn aggregate_all<G: TermGetable>(&self, spec: &Term, goal: &Term) -> PrologResult<G> {
+        let frame = self.open_frame();
+        let result = frame.new_term_ref();
+        frame.call_once(pred!("aggregate_all/3"), [spec, goal, &result])?;
+        let value = result.get()?;
+        frame.close();
+
+        Ok(value)
+    }
+
+    /// Call `aggregate_all/3` with the given aggregation spec and goal,
+    /// returning `None` if it fails.
+    ///
+    /// Unlike [aggregate_all](Self::aggregate_all), a result that
+    /// can't be retrieved raises a type error rather than failing.
+    fn aggregate_all_opt_ex<G: TermGetable>(
+        &self,
+        spec: &Term,
+        goal: &Term,
+    ) -> PrologResult<Option<G>> {
+        let frame = self.open_frame();
+        let result = frame.new_term_ref();
+        if !frame.call_once_bool(pred!("aggregate_all/3"), [spec, goal, &result])? {
+            return Ok(None);
+        }
+        let value = result.get_ex()?;
+        frame.close();
+
+        Ok(Some(value))
+    }
+
+    /// Return the length of a proper list.
+    ///
+    /// This uses `PL_skip_list`, which walks the list without
+    /// creating any terms. For partial lists, cyclic lists and terms
+    /// that are not a list at all, this returns `None`.
+    pub fn list_length(&self, term: &Term) -> Option<usize> {
+        self.assert_activated();
+        term.assert_term_handling_possible();
+        let mut len = 0;
+        let result = unsafe { PL_skip_list(term.term_ptr(), 0, &mut len) };
+
+        if result as u32 == PL_LIST {
+            Some(len)
+        } else {
+            None
+        }
+    }
+
+    /// Count the number of solutions of the given goal.
+    ///
+    /// This uses `aggregate_all(count, Goal, Count)`.
+    pub fn count(&self, goal: &Term) -> PrologResult<u64> {
+        let frame = self.open_frame();
+        let spec = term! {frame: count}?;
+        let count = frame.aggregate_all(&spec, goal)?;
+        frame.close();
+
+        Ok(count)
+    }
+
+    /// Sum the template over all solutions of the given goal.
+    ///
+    /// This uses `aggregate_all(sum(Template), Goal, Sum)`. If the
+    /// goal has no solutions, the sum is 0.
+    pub fn sum_i64(&self, template: &Term, goal: &Term) -> PrologResult<i64> {
+        let frame = self.open_frame();
+        let spec = term! {frame: sum(#template)}?;
+        let sum = frame.aggregate_all(&spec, goal)?;
+        frame.close();
+
+        Ok(sum)
+    }
+
+    /// Find the maximum of the template over all solutions of the given goal.
+    ///
+    /// This uses `aggregate_all(max(Template), Goal, Max)`. If the
+    /// goal has no solutions, this returns `None`. If the maximum is
+    /// not an integer that fits in an `i64`, this raises a type error.
+    pub fn max_i64(&self, template: &Term, goal: &Term) -> PrologResult<Option<i64>> {
+        let frame = self.open_frame();
+        let spec = term! {frame: max(#template)}?;
+        let max = frame.aggregate_all_opt_ex(&spec, goal)?;
+        frame.close();
+
+        Ok(max)
+    }
+
+    /// Find the minimum of the template over all solutions of the given goal.
+    ///
+    /// This uses `aggregate_all(min(Template), Goal, Min)`. If the
+    /// goal has no solutions, this returns `None`. If the minimum is
+    /// not an integer that fits in an `i64`, this raises a type error.
+    pub fn min_i64(&self, template: &Term, goal: &Term) -> PrologResult<Option<i64>> {
+        let frame = self.open_frame();
+        let spec = term! {frame: min(#template)}?;
+        let min = frame.aggregate_all_opt_ex(&spec, goal)?;
+        frame.close();
+
+        Ok(min)
+    }
+
+    /// Collect the template for all solutions of the given goal.
+    ///
+    /// This uses `aggregate_all(bag(Template), Goal, Bag)`, and
+    /// retrieves each element of the resulting list.
+    pub fn bag<G: TermGetable>(&self, template: &Term, goal: &Term) -> PrologResult<Vec<G>> {
+        let frame = self.open_frame();
+        let spec = term! {frame: bag(#template)}?;
+        let list = frame.new_term_ref();
+        frame.call_once(pred!("aggregate_all/3"), [&spec, goal, &list])?;
+        let bag = frame
+            .term_list_iter(&list)
+            .map(|element| element.get())
+            .collect::<PrologResult<Vec<G>>>()?;
+        frame.close();
+
+        Ok(bag)
+    }
+
+    /// Collect the distinct unbound variables in the given term.
+    ///
+    /// This uses the prolog predicate `term_variables/2`. The
+    /// variables are returned in depth-first, left-to-right order of
+    /// their first occurrence.
+    pub fn term_variables(&self, term: &Term) -> PrologResult<Vec<Term>> {
+        let list = self.new_term_ref();
+        self.call_once(pred!("term_variables/2"), [term, &list])?;
+
+        Ok(self.term_list_iter(&list).collect())
+    }
+
+    /// Read the next term from a stream, applying term expansion.
+    ///
+    /// This reads a term with `read_term/3`, and passes it through
+    /// `expand_term/2`, just like the loader does for terms read from
+    /// a source file. This means hooks defined with `term_expansion/2`
+    /// and DCG translation are applied. As term expansion may turn
+    /// a term into several clauses, the result can also be a list.
+    ///
+    /// The stream is given as a prolog stream term. At the end of the
+    /// stream, this returns `None`.
+    pub fn read_term_expanded(&self, stream: &Term) -> PrologResult<Option<Term>> {
+        let [read, options, expanded] = self.new_term_refs();
+        options.unify(Nil)?;
+        self.call_once(pred!("read_term/3"), [stream, &read, &options])?;
+        if read.get::<Atom>().ok() == Some(Atom::new("end_of_file")) {
+            return Ok(None);
+        }
+        self.call_once(pred!("expand_term/2"), [&read, &expanded])?;
+
+        Ok(Some(expanded))
+    }
+
+    /// Read the next clause from a stream, the way the loader does.
+    ///
+    /// This uses `read_clause/3`, which reads a term using the
+    /// syntax and error handling of the loader, and prints the
+    /// loader's warnings, such as for singleton variables, as usual.
+    /// The singleton variables are also returned by name along with
+    /// the clause, so that tools emulating the loader can report
+    /// them in their own way.
+    ///
+    /// The stream is given as a prolog stream term. At the end of the
+    /// stream, this returns `None`.
+    pub fn read_clause(&self, stream: &Term) -> PrologResult<Option<ClauseReadResult>> {
+        let [clause, bindings, singletons] = self.new_term_refs();
+        let self_ = self;
+        let options = term! {self_: [variable_names(#&bindings)]}?;
+        self.call_once(pred!("read_clause/3"), [stream, &clause, &options])?;
+        if clause.get::<Atom>().ok() == Some(Atom::new("end_of_file")) {
+            return Ok(None);
+        }
+
+        self.call_once(pred!("term_singletons/2"), [&clause, &singletons])?;
+        let singleton_vars: Vec<Term> = self.term_list_iter(&singletons).collect();
+        let mut singleton_names = Vec::new();
+        for binding in self.term_list_iter(&bindings) {
+            let name = binding.get_arg::<Atom>(1)?.name();
+            let [_, var] = self.compound_terms(&binding)?;
+            if !name.starts_with('_') && singleton_vars.contains(&var) {
+                singleton_names.push(name);
+            }
+        }
+
+        Ok(Some(ClauseReadResult {
+            clause,
+            singletons: singleton_names,
+        }))
+    }
+
+    /// Assert a clause at the end of its predicate in the given module.
+    ///
+    /// Unlike calling `assertz/1`, which stores the clause in the
+    /// module of the context, this always stores it in the given
+    /// module. This is useful together with
+    /// [new_temporary_module](Self::new_temporary_module) to load
+    /// clauses into a sandbox.
+    pub fn assertz_in(&self, module: Module, clause: &Term) -> PrologResult<()> {
+        self.bulk_assert(module, [clause.clone()])?;
+
+        Ok(())
+    }
+
+    /// Returns a new term holding the successor of a natural number.
+    ///
+    /// This uses the prolog predicate `succ/2`, so the result is not
+    /// limited to an `i64`. Negative numbers raise a `type_error`, and
+    /// an unbound term raises an `instantiation_error`. To find the
+    /// predecessor of a number instead, use
+    /// [succ_terms](Self::succ_terms) with the first term unbound.
+    pub fn succ(&self, n: &Term) -> PrologResult<Term> {
+        let successor = self.new_term_ref();
+        self.call_once(pred!("succ/2"), [n, &successor])?;
+
+        Ok(successor)
+    }
+
+    /// Relate a natural number to its successor.
+    ///
+    /// This uses the prolog predicate `succ/2`, so either term may be
+    /// unbound, as long as the other is a natural number. Finding the
+    /// predecessor of 0 fails, while negative numbers raise a
+    /// `type_error`. See [succ](Self::succ) for retrieving the
+    /// successor as a new term, and [succ_i64](Self::succ_i64) for
+    /// computing the successor of an `i64` directly.
+    pub fn succ_terms(&self, n: &Term, successor: &Term) -> PrologResult<()> {
+        self.call_once(pred!("succ/2"), [n, successor])
+    }
+
+    /// Remove all clauses whose head unifies with the given head.
+    ///
+    /// This uses the prolog predicate `retractall/1`. If the
+    /// predicate does not exist yet, it is created as a dynamic
+    /// predicate without clauses.
+    pub fn retractall(&self, head: &Term) -> PrologResult<()> {
+        self.call_once(pred!("retractall/1"), [head])
+    }
+
+    /// Remove the given predicate, including all its clauses and attributes.
+    ///
+    /// This uses the prolog predicate `abolish/1`. Unless the prolog
+    /// flag `iso` is set, only dynamic predicates can be abolished.
+    pub fn abolish(&self, predicate: &Predicate) -> PrologResult<()> {
+        let frame = self.open_frame();
+        let [indicator, qualified] = frame.new_term_refs();
+        indicator.unify(Functor::new("/", 2))?;
+        indicator.unify_arg(1, predicate.name())?;
+        indicator.unify_arg(2, predicate.arity() as u64)?;
+        qualified.unify(Functor::new(":", 2))?;
+        qualified.unify_arg(1, predicate.module().name())?;
+        qualified.unify_arg(2, &indicator)?;
+        frame.call_once(pred!("abolish/1"), [&qualified])?;
+        frame.close();
+
+        Ok(())
+    }
+
+    /// Assert all the given facts into a module.
+    ///
+    /// Each fact is added at the end of its predicate with
+    /// `PL_assert`, directly from the fli. This avoids the overhead of
+    /// calling `assertz/1` for every fact, which matters when loading
+    /// large knowledge bases. Returns the number of facts asserted.
+    ///
+    /// If asserting a fact raises an exception, this stops and
+    /// returns the exception. Facts asserted before that remain.
+    pub fn bulk_assert<'b, I: IntoIterator<Item = Term<'b>>>(
+        &self,
+        module: Module,
+        facts: I,
+    ) -> PrologResult<usize> {
+        self.assert_activated();
+        let mut count = 0;
+        for fact in facts {
+            fact.assert_term_handling_possible();
+            let result = unsafe {
+                PL_assert(
+                    fact.term_ptr(),
+                    module.module_ptr(),
+                    PL_ASSERTZ.try_into().unwrap(),
+                )
+            };
+            if result == 0 {
+                return Err(if unsafe { pl_default_exception() } != 0 {
+                    PrologError::Exception
+                } else {
+                    PrologError::Failure
+                });
+            }
+
+            count += 1;
+        }
+
+        Ok(count)
+    }
+
+    /// Iterate over a term list.
+    ///
+    /// this returns a TermListIterator made out of the given
+    /// term. The TermListIterator will assume this is a cons cell,
+    /// and unify head and tail on each step of the iterator,
+    /// returning the head term and storing the tail term. If this
+    /// unification fails, the iterator stops.
+    ///
+    /// Note that the terms created by this iterator are not
+    /// automatically thrown away. It is the caller's responsibility
+    /// to clean up terms if this is required, for example by using a
+    /// frame.
+    pub fn term_list_iter<'b>(&'b self, list: &Term) -> TermListIterator<'b, 'a, T> {
+        self.assert_activated();
+        let cur = self.new_term_ref();
+        cur.unify(list).expect("unifying terms should work");
+        TermListIterator { context: self, cur }
+    }
+
+    /// Retrieve a term list as a fixed-size array.
+    ///
+    /// This is useful when a term contains a list whose supposed size
+    /// is known at compile time. If the actual list is larger than
+    /// this, only the first N elements are used. If the list is
+    /// smaller, the remaining terms in the array remain variables.
+    pub fn term_list_array<const N: usize>(&self, list: &Term) -> [Term; N] {
+        self.assert_activated();
+        // allocate these terms inside the scope of this context
+        let terms = self.new_term_refs();
+
+        let frame = self.open_frame();
+        let terms_iter = terms.iter();
+        let list_iter = frame.term_list_iter(list);
+
+        for (term, elt) in terms_iter.zip(list_iter) {
+            term.unify(elt).unwrap();
+        }
+        frame.close();
+
+        terms
+    }
+
+    /// Retrieve a term list as a Vec.
+    ///
+    /// This will iterate over the given prolog list twice - once to
+    /// figure out its size, and then another time to actually
+    /// retrieve the elements. This is done so that we can allocate
+    /// the terms in a way that leaves no unused terms behind on the
+    /// stack (as would normally happen when iterating the list using
+    /// [term_list_iter](Context::term_list_iter)).
+    ///
+    /// If you know in advance what the size is going to be (or you
+    /// know a reasonable upper bound), consider using
+    /// [term_list_array](Context::term_list_array). If you just wish
+    /// to iterate over the elements, or don't care about garbage
+    /// terms being created, consider using
+    /// [term_list_iter](Context::term_list_iter).
+    pub fn term_list_vec(&self, list: &Term) -> Vec<Term> {
+        self.assert_activated();
+        let frame = self.open_frame();
+        let count = frame.term_list_iter(list).count();
+        frame.discard();
+
+        // allocate these terms inside the scope of this context
+        let terms = self.new_term_refs_vec(count);
+
+        let frame = self.open_frame();
+        let terms_iter = terms.iter();
+        let list_iter = frame.term_list_iter(list);
+
+        for (term, elt) in terms_iter.zip(list_iter) {
+            term.unify(elt).unwrap();
+        }
+        frame.close();
+
+        terms
+    }
+
+    /// Retrieve compound terms as a fixed size array.
+    ///
+    /// This will ensure that the given term is indeed a compound with
+    /// arity N. If this is true, N terms will be allocated in this
+    /// context, unified with the argument terms of the compound, and
+    /// returned as an array. If not, this method will fail.
+    pub fn compound_terms<const N: usize>(&self, compound: &Term) -> PrologResult<[Term; N]> {
+        self.assert_activated();
+        if N > (i32::MAX - 1) as usize {
+            panic!("requested compound term array too large: {}", N);
+        }
+
+        let mut size = 0;
+        if unsafe {
+            PL_get_compound_name_arity(compound.term_ptr(), std::ptr::null_mut(), &mut size) != 1
+        } {
+            return Err(PrologError::Failure);
+        }
+        if (size as usize) != N {
+            return Err(PrologError::Failure);
+        }
+
+        let terms: [Term; N] = self.new_term_refs();
+        for (i, term) in terms.iter().enumerate() {
+            unsafe {
+                assert!(PL_get_arg((i + 1) as i32, compound.term_ptr(), term.term_ptr()) == 1);
+            }
+        }
+
+        Ok(terms)
+    }
+
+    /// Retrieve compound terms as a Vec.
+    ///
+    /// This will ensure that the given term is indeed a compound of
+    /// any arity. If this is true, arity terms will be allocated in
+    /// this context, unified with the argument terms of the compound,
+    /// and returned as a Vec. If not, this method will fail.
+    pub fn compound_terms_vec(&self, compound: &Term) -> PrologResult<Vec<Term>> {
+        self.assert_activated();
+
+        let mut size = 0;
+        if unsafe {
+            PL_get_compound_name_arity(compound.term_ptr(), std::ptr::null_mut(), &mut size) != 1
+        } {
+            return Err(PrologError::Failure);
+        }
+
+        let terms = self.new_term_refs_vec(size as usize);
+        for (i, term) in terms.iter().enumerate() {
+            unsafe {
+                assert!(PL_get_arg((i + 1) as i32, compound.term_ptr(), term.term_ptr()) == 1);
+            }
+        }
+
+        Ok(terms)
+    }
+
+    /// Retrieve compound terms as a fixed size Vec.
+    ///
+    /// This will ensure that the given term is indeed a compound with
+    /// arity `count`. If this is true, `count` terms will be
+    /// allocated in this context, unified with the argument terms of
+    /// the compound, and returned as an array. If not, this method
+    /// will fail.
+    pub fn compound_terms_vec_sized(
+        &self,
+        compound: &Term,
+        count: usize,
+    ) -> PrologResult<Vec<Term>> {
+        self.assert_activated();
+
+        let mut size = 0;
+        if unsafe {
+            PL_get_compound_name_arity(compound.term_ptr(), std::ptr::null_mut(), &mut size) != 1
+        } {
+            return Err(PrologError::Failure);
+        }
+        if (size as usize) != count {
+            return Err(PrologError::Failure);
+        }
+
+        let terms = self.new_term_refs_vec(count);
+        for (i, term) in terms.iter().enumerate() {
+            unsafe {
+                assert!(PL_get_arg((i + 1) as i32, compound.term_ptr(), term.term_ptr()) == 1);
+            }
+        }
+
+        Ok(terms)
+    }
+
+    #[cfg(feature = "serde")]
+    /// Deserialize a term into a rust value using serde.
+    pub fn deserialize_from_term<'de, DT: Deserialize<'de>>(
+        &'de self,
+        term: &'de Term<'de>,
+    ) -> super::term::de::Result<DT> {
+        super::term::de::from_term(self, term)
+    }
+
+    #[cfg(feature = "serde")]
+    /// Serialize a value into a prolog term using serde.
+    ///
+    /// This uses the default serialization configuration, meaning:
+    /// - prolog dictionary tags will remain variables.
+    /// - struct type names are ignored and will not be set as the dictionary tag.
+    pub fn serialize_to_term<ST: Serialize>(
+        &self,
+        term: &Term,
+        obj: &ST,
+    ) -> Result<(), super::term::de::Error> {
+        super::term::ser::to_term(self, term, obj)
+    }
+
+    #[cfg(feature = "serde")]
+    /// Serialize a value into a prolog term using serde, providing configuration options.
+    pub fn serialize_to_term_with_config<ST: Serialize>(
+        &self,
+        term: &Term,
+        obj: &ST,
+        config: SerializerConfiguration,
+    ) -> Result<(), super::term::de::Error> {
+        super::term::ser::to_term_with_config(self, term, obj, config)
+    }
+
+    /// Unify the term with the list functor, returning a term for the head and the tail.
+    pub fn unify_list_functor<'b>(
+        &'b self,
+        term: &Term,
+    ) -> Result<(Term<'b>, Term<'b>), PrologError> {
+        let [head, tail] = self.new_term_refs();
+        match unsafe { PL_unify_list(term.term_ptr(), head.term_ptr(), tail.term_ptr()) } {
+            0 => {
+                unsafe {
+                    head.reset();
+                }
+                if unsafe { pl_default_exception() != 0 } {
+                    Err(PrologError::Exception)
+                } else {
+                    Err(PrologError::Failure)
+                }
+            }
+            _ => Ok((head, tail)),
+        }
+    }
+
+    /// Unify the term with a list starting with the given items, returning the tail.
+    ///
+    /// The term is unified with `[Item1, ..., ItemN|Tail]`, and
+    /// `Tail` is returned for further extension, for example with
+    /// another call to this function, or by unifying it with `[]` to
+    /// close the list. This allows streaming results into a list
+    /// bound to a term passed in by the caller. If unification fails,
+    /// any bindings made along the way are undone.
+    pub fn unify_list_prefix<'b>(
+        &'b self,
+        list_term: &Term,
+        items: &[&Term],
+    ) -> PrologResult<Term<'b>> {
+        list_term.assert_term_handling_possible();
+        let [tail, head] = self.new_term_refs();
+        unsafe { PL_put_term(tail.term_ptr(), list_term.term_ptr()) };
+
+        let frame = self.open_frame();
+        let mut result = Ok(());
+        for item in items {
+            if unsafe { PL_unify_list(tail.term_ptr(), head.term_ptr(), tail.term_ptr()) } == 0 {
+                result = Err(if unsafe { pl_default_exception() } != 0 {
+                    PrologError::Exception
+                } else {
+                    PrologError::Failure
+                });
+            } else {
+                result = head.unify(*item);
+            }
+
+            if result.is_err() {
+                break;
+            }
+        }
+
+        match result {
+            Ok(()) => {
+                frame.close();
+                unsafe { head.reset() };
+
+                Ok(tail)
+            }
+            Err(e) => {
+                frame.discard();
+                unsafe { tail.reset() };
+
+                Err(e)
+            }
+        }
+    }
+
+    /// Retrieve the entries of a map-like term as key and value terms.
+    ///
+    /// The term may be a dict, a list of `Key-Value` pairs, or an
+    /// association list as created by `library(assoc)`, allowing maps
+    /// from different sources to be handled uniformly. Entries of
+    /// dicts and association lists are ordered by key, while the
+    /// entries of a pair list keep their order. If the term is none
+    /// of these, this fails.
+    pub fn map_entries(&self, term: &Term) -> PrologResult<Vec<(Term, Term)>> {
+        let pairs = self.new_term_ref();
+        match term.term_type() {
+            TermType::Dict => {
+                let tag = self.new_term_ref();
+                self.call_once(pred!("dict_pairs/3"), [term, &tag, &pairs])?;
+            }
+            TermType::ListPair | TermType::Nil => pairs.unify(term)?,
+            _ => {
+                // an empty association list is the atom t, a
+                // non-empty one is a t/5 node.
+                let is_assoc = term.get::<Atom>().ok() == Some(Atom::new("t"))
+                    || term.get::<Functor>().ok() == Some(Functor::new("t", 5));
+                if !is_assoc {
+                    return Err(PrologError::Failure);
+                }
+                self.call_once(pred!("assoc_to_list/2"), [term, &pairs])?;
+            }
+        }
+
+        let mut entries = Vec::new();
+        for pair in self.term_list_iter(&pairs) {
+            if pair.get::<Functor>().ok() != Some(Functor::new("-", 2)) {
+                return Err(PrologError::Failure);
+            }
+            let [key, value] = self.compound_terms(&pair)?;
+            entries.push((key, value));
+        }
+
+        Ok(entries)
+    }
+
+    pub fn into_generic(&self) -> GenericQueryableContext {
+        self.assert_activated();
+        self.activated.set(false);
+        unsafe { Context::new_activated(self, GenericQueryableContextType, self.engine) }
+    }
+}
+
+/// An iterator over a term list.
+///
+/// See [`Context::term_list_iter`] for more information.
+pub struct TermListIterator<'a, 'b, CT: QueryableContextType> {
+    context: &'a Context<'b, CT>,
+    cur: Term<'a>,
+}
+
+impl<'a, 'b, CT: QueryableContextType> TermListIterator<'a, 'b, CT> {
+    /// Return the part of the list that has not been iterated over yet.
+    ///
+    /// Once the iterator is exhausted, this is the tail of the
+    /// list. For a proper list, this will be `[]`.
+    pub fn tail(&self) -> &Term<'a> {
+        &self.cur
+    }
+
+    /// Return the open tail of a partial list.
+    ///
+    /// After iterating over a partial list like `[1,2|T]`, this
+    /// returns the variable `T`. If the remaining tail is not a
+    /// variable (such as with a proper list, which ends in `[]`),
+    /// this returns `None`.
+    pub fn partial_tail(&self) -> Option<&Term<'a>> {
+        if self.cur.is_var() {
+            Some(&self.cur)
+        } else {
+            None
+        }
+    }
+}
+
+impl<'a, 'b, CT: QueryableContextType> Iterator for TermListIterator<'a, 'b, CT> {
+    type Item = Term<'a>;
+
+    fn next(&mut self) -> Option<Term<'a>> {
+        let head = self.context.new_term_ref();
+        let tail = self.context.new_term_ref();
+        let success =
+            unsafe { PL_get_list(self.cur.term_ptr(), head.term_ptr(), tail.term_ptr()) != 0 };
+
+        if success {
+            self.cur = tail;
+            Some(head)
+        } else {
+            None
+        }
+    }
+}
+
+/// An iterator over the solutions of a `between/3` query.
+///
+/// See [`Context::between`] for more information.
+pub struct BetweenIterator<'a> {
+    query: Option<Context<'a, OpenQuery>>,
+    result: Term<'a>,
+    error: Option<PrologError>,
+}
+
+impl<'a> Iterator for BetweenIterator<'a> {
+    type Item = PrologResult<i64>;
+
+    fn next(&mut self) -> Option<PrologResult<i64>> {
+        if let Some(e) = self.error.take() {
+            return Some(Err(e));
+        }
+        let query = self.query.as_ref()?;
+        match query.next_solution() {
+            Ok(more) => {
+                let value = self.result.get::<i64>();
+                if !more {
+                    self.query.take().unwrap().cut();
+                }
+
+                Some(value)
+            }
+            Err(PrologError::Failure) => {
+                self.query.take().unwrap().discard();
+
+                None
+            }
+            Err(e) => {
+                self.query = None;
+
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+/// An iterator over pages of solutions of a goal.
+///
+/// See [`Context::query_paginated`] for more information.
+pub struct PaginatedQuery<'a, 'b, CT: QueryableContextType, DT> {
+    context: &'a Context<'b, CT>,
+    goal: String,
+    var: String,
+    page_size: usize,
+    offset: usize,
+    done: bool,
+    _result: std::marker::PhantomData<DT>,
+}
+
+#[cfg(feature = "serde")]
+impl<'a, 'b, CT: QueryableContextType, DT: DeserializeOwned> Iterator
+    for PaginatedQuery<'a, 'b, CT, DT>
+{
+    type Item = PrologResult<Vec<DT>>;
+
+    fn next(&mut self) -> Option<PrologResult<Vec<DT>>> {
+        if self.done {
+            return None;
+        }
+
+        let page = self.context.findall_var_page(
+            &self.goal,
+            &self.var,
+            Some((self.offset, self.page_size)),
+        );
+        match page {
+            Ok(page) => {
+                self.offset += page.len();
+                if page.len() < self.page_size {
+                    self.done = true;
+                    if page.is_empty() {
+                        return None;
+                    }
+                }
+
+                Some(Ok(page))
+            }
+            Err(error) => {
+                self.done = true;
+
+                Some(Err(error))
+            }
+        }
+    }
+}
+
+/// Trait for turning errors into prolog exceptions
+pub trait IntoPrologException {
+    /// Turns this error into a prolog exception using the given context.
+    ///
+    /// The result is a `Term` containing the prolog exception.
+    fn into_prolog_exception<'a, T: QueryableContextType>(
+        self,
+        context: &'a Context<'_, T>,
+    ) -> PrologResult<Term<'a>>;
+}
+
+impl IntoPrologException for std::io::Error {
+    fn into_prolog_exception<'a, T: QueryableContextType>(
+        self,
+        context: &'a Context<'_, T>,
+    ) -> PrologResult<Term<'a>> {
+        let kind_str = format!("{:?}", self.kind());
+        let kind_atom = Atom::new(&kind_str);
+        let msg = format!("{}", self);
+        term! {context: error(rust_io_error(#kind_atom, #msg), _)}
+    }
+}
+
+/// Extension trait for turning a rust `Result` into a [PrologResult].
+///
+/// This allows using `?` on rust results in code that returns a
+/// `PrologResult`, such as the bodies of foreign predicates defined
+/// with the `predicates!` macro.
+///
+/// # Examples
+/// ```
+/// # use swipl::prelude::*;
+/// predicates! {
+///     semidet fn parse_integer(context, text, number) {
+///         let text: String = text.get()?;
+///         let n: i64 = text.parse().into_prolog(context)?;
+///
+///         number.unify(n)
+///     }
+/// }
+/// ```
+pub trait IntoPrologResult<R> {
+    /// Turn this result into a `PrologResult`.
+    ///
+    /// An `Err` is raised as an exception of the form
+    /// `error(rust_error(Msg), _)` on the given context, where `Msg`
+    /// is a string holding the displayed error.
+    fn into_prolog<T: QueryableContextType>(self, context: &Context<T>) -> PrologResult<R>;
+}
+
+impl<R, E: std::fmt::Display> IntoPrologResult<R> for Result<R, E> {
+    fn into_prolog<T: QueryableContextType>(self, context: &Context<T>) -> PrologResult<R> {
+        match self {
+            Ok(ok) => Ok(ok),
+            Err(e) => {
+                let reset_term = context.new_term_ref();
+                let msg = e.to_string();
+                let exception_term = term! {context: error(rust_error(#msg), _)}?;
+                let result = context.raise_exception(&exception_term);
+
+                unsafe {
+                    reset_term.reset();
+                }
+
+                result
+            }
+        }
+    }
+}
+
+/// Raise the error for a deterministic predicate that failed.
+///
+/// This raises an exception of the form
+/// `error(determinism_error(Name/Arity, det, fail, property_declaration), _)`,
+/// which is what SWI-Prolog raises when a predicate declared with
+/// `det/1` fails. It is used by the `predicates!` macro for
+/// predicates that are defined as `det`, and always returns
+/// `Err(PrologError::Exception)`.
+pub fn raise_determinism_error<C: QueryableContextType>(
+    context: &Context<C>,
+    name: &str,
+    arity: usize,
+) -> PrologResult<()> {
+    let indicator = context.new_term_ref();
+    indicator.unify(Functor::new("/", 2))?;
+    indicator.unify_arg(1, Atomable::from(name))?;
+    indicator.unify_arg(2, arity as u64)?;
+    let exception_term =
+        term! {context: error(determinism_error(#&indicator, det, fail, property_declaration), _)}?;
+
+    context.raise_exception(&exception_term)
+}
+
+/// Call the given function, converting panics into prolog exceptions.
+///
+/// If the inner function panics, an exception of the form
+/// `error(rust_error(panic("..the panic message..")))` will be
+/// raised, and this function will return
+/// `Err(PrologError::Exception)`. Otherwise, This function will
+/// return `Ok(())`.
+///
+/// This is used by various macros to ensure that panics from user
+/// code do not propagate into prolog.
+///
+/// # Safety
+/// This is only safe to use from an environment from which we can
+/// raise a prolog exception.
+pub unsafe fn prolog_catch_unwind<F: FnOnce() -> R + std::panic::UnwindSafe, R>(
+    f: F,
+) -> PrologResult<R> {
+    let result = std::panic::catch_unwind(f);
+    match result {
+        Ok(result) => Ok(result),
+        Err(panic) => {
+            let context = unmanaged_engine_context();
+            let panic_term = context.new_term_ref();
+            let error_term = term! {context: error(rust_error(panic(#&panic_term)), _)}?;
+
+            match panic.downcast_ref::<&str>() {
+                Some(panic_msg) => {
+                    panic_term.unify(panic_msg).unwrap();
+                }
+                None => match panic.downcast_ref::<String>() {
+                    Some(panic_msg) => {
+                        panic_term.unify(panic_msg.as_str()).unwrap();
+                    }
+                    None => {
+                        panic_term.unify("unknown panic type").unwrap();
+                    }
+                },
+            }
+
+            context.raise_exception::<()>(&error_term).unwrap_err();
+            Err(PrologError::Exception)
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct GenericQueryableContextType;
+unsafe impl ContextType for GenericQueryableContextType {}
+impl FrameableContextType for GenericQueryableContextType {}
+impl QueryableContextType for GenericQueryableContextType {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::functor::*;
+    use crate::predicate::*;
+    use crate::predicates;
+
+    #[test]
+    fn get_term_ref_on_fresh_engine() {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let _term = context.new_term_ref();
+    }
+
+    #[test]
+    fn get_term_ref_on_frame() {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context1: Context<_> = activation.into();
+        let _term1 = context1.new_term_ref();
+
+        let context2 = context1.open_frame();
+        let _term2 = context2.new_term_ref();
+        std::mem::drop(context2);
+        let _term3 = context1.new_term_ref();
+    }
+
+    #[test]
+    fn new_variable_is_unbound() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let term = context.new_term_ref();
+        assert_eq!(TermType::Variable, term.term_type());
+
+        let x = context.new_variable();
+        let foo = context.new_term_ref();
+        foo.unify(Functor::new("foo", 1))?;
+        foo.unify_arg(1, &x)?;
+
+        let [arg] = context.compound_terms(&foo)?;
+        assert!(arg.is_var());
+        assert!(x.is_var());
+
+        x.unify(42_u64)?;
+        assert_eq!(42_u64, foo.get_arg(1)?);
+
+        Ok(())
+    }
+
+    #[test]
+    #[should_panic]
+    fn get_term_ref_from_inactive_context_panics() {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context1: Context<_> = activation.into();
+        let _context2 = context1.open_frame();
+
+        let _term = context1.new_term_ref();
+    }
+
+    #[test]
+    fn query_det() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let functor_is = Functor::new("is", 2);
+        let functor_plus = Functor::new("+", 2);
+        let module = Module::new("user");
+        let predicate = Predicate::new(functor_is, module);
+        let callable = CallablePredicate::new(predicate).unwrap();
+
+        let term1 = context.new_term_ref();
+        let term2 = context.new_term_ref();
+
+        term2.unify(functor_plus)?;
+        term2.unify_arg(1, 40_u64)?;
+        term2.unify_arg(2, 2_u64)?;
+
+        let query = context.open(callable, [&term1, &term2]);
+        let next = query.next_solution()?;
+
+        assert!(!next);
+        assert_eq!(42_u64, term1.get()?);
+
+        let next = query.next_solution();
+        assert!(next.is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn query_auto_discard() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let functor_is = Functor::new("is", 2);
+        let functor_plus = Functor::new("+", 2);
+        let module = Module::new("user");
+        let predicate = Predicate::new(functor_is, module);
+        let callable = CallablePredicate::new(predicate).unwrap();
+
+        let term1 = context.new_term_ref();
+        let term2 = context.new_term_ref();
+
+        assert!(term2.unify(functor_plus).is_ok());
+        assert!(term2.unify_arg(1, 40_u64).is_ok());
+        assert!(term2.unify_arg(2, 2_u64).is_ok());
+
+        {
+            let query = context.open(callable, [&term1, &term2]);
+            let next = query.next_solution()?;
+
+            assert!(!next);
+            assert_eq!(42_u64, term1.get().unwrap());
+        }
+
+        // after leaving the block, we have discarded
+        assert!(term1.get::<u64>().unwrap_err().is_failure());
+
+        Ok(())
+    }
+
+    #[test]
+    fn query_manual_discard() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let functor_is = Functor::new("is", 2);
+        let functor_plus = Functor::new("+", 2);
+        let module = Module::new("user");
+        let predicate = Predicate::new(functor_is, module);
+        let callable = CallablePredicate::new(predicate).unwrap();
+
+        let term1 = context.new_term_ref();
+        let term2 = context.new_term_ref();
+
+        term2.unify(functor_plus)?;
+        term2.unify_arg(1, 40_u64)?;
+        term2.unify_arg(2, 2_u64)?;
+
+        {
+            let query = context.open(callable, [&term1, &term2]);
+            let next = query.next_solution()?;
+
+            assert!(!next);
+            assert_eq!(42_u64, term1.get()?);
+            query.discard();
+        }
+
+        // after leaving the block, we have discarded
+        assert!(term1.get::<u64>().unwrap_err().is_failure());
+
+        Ok(())
+    }
+
+    #[test]
+    fn query_cut() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let functor_is = Functor::new("is", 2);
+        let functor_plus = Functor::new("+", 2);
+        let module = Module::new("user");
+        let predicate = Predicate::new(functor_is, module);
+        let callable = CallablePredicate::new(predicate).unwrap();
+
+        let term1 = context.new_term_ref();
+        let term2 = context.new_term_ref();
+
+        term2.unify(functor_plus)?;
+        term2.unify_arg(1, 40_u64)?;
+        term2.unify_arg(2, 2_u64)?;
+
+        {
+            let query = context.open(callable, [&term1, &term2]);
+            let next = query.next_solution()?;
+
+            assert!(!next);
+            assert_eq!(42_u64, term1.get()?);
+            query.cut();
+        }
+
+        // a cut query leaves data intact
+        assert_eq!(42_u64, term1.get()?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn term_from_string_works() {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let term = context.term_from_string("foo(bar(baz,quux))").unwrap();
+        let functor_foo = Functor::new("foo", 1);
+        let functor_bar = Functor::new("bar", 2);
+
+        assert_eq!(functor_foo, term.get().unwrap());
+        assert_eq!(functor_bar, term.get_arg(1).unwrap());
+    }
+
+    #[test]
+    fn term_from_string_with_double_quotes_options() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let opts = ReadOptions::new().double_quotes(DoubleQuotes::String);
+        let term = context.term_from_string_with_opts("\"abc\"", opts)?;
+        assert_eq!("abc", term.get::<String>()?);
+
+        let opts = ReadOptions::new().double_quotes(DoubleQuotes::Codes);
+        let term = context.term_from_string_with_opts("\"abc\"", opts)?;
+        assert_eq!(vec![97_u64, 98, 99], term.get::<Vec<u64>>()?);
+
+        let opts = ReadOptions::new().double_quotes(DoubleQuotes::Atom);
+        let term = context.term_from_string_with_opts("\"abc\"", opts)?;
+        assert_eq!(Atom::new("abc"), term.get::<Atom>()?);
+
+        let term = context.term_from_string("\"abc\"")?;
+        assert_eq!("abc", term.get::<String>()?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn run_once_returns_bindings() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let bindings = context.run_once("append([1],[2],X)")?.unwrap();
+        assert_eq!(1, bindings.len());
+        assert_eq!(
+            PrologValue::List(vec![PrologValue::Int(1), PrologValue::Int(2)]),
+            bindings["X"]
+        );
+
+        assert_eq!(None, context.run_once("append([1],[2],[])")?);
+
+        let bindings = context
+            .run_once("X is 2^100, Y is 1 rdiv 3, Z = point{x: 1, 2: foo}, W = _{}")?
+            .unwrap();
+        assert_eq!(
+            PrologValue::BigInt("1267650600228229401496703205376".to_string()),
+            bindings["X"]
+        );
+        assert_eq!(PrologValue::Rational("1r3".to_string()), bindings["Y"]);
+        let (tag, mut entries) = match bindings["Z"].clone() {
+            PrologValue::Dict(tag, entries) => (tag, entries),
+            value => panic!("expected a dict, got {:?}", value),
+        };
+        assert_eq!(PrologValue::Atom("point".to_string()), *tag);
+        entries.sort_by_key(|(key, _)| format!("{:?}", key));
+        assert_eq!(
+            vec![
+                (PrologValue::Atom("x".to_string()), PrologValue::Int(1)),
+                (PrologValue::Int(2), PrologValue::Atom("foo".to_string())),
+            ],
+            entries
+        );
+        assert_eq!(
+            PrologValue::Dict(Box::new(PrologValue::Var), Vec::new()),
+            bindings["W"]
+        );
+
+        let result = context.run_once("X = S, open_string(\"\", S)");
+        assert!(result.unwrap_err().is_exception());
+        context.clear_exception();
+
+        Ok(())
+    }
+
+    #[test]
+    fn with_mutex_excludes_other_threads() {
+        use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+        use std::sync::Arc;
+
+        let inside = Arc::new(AtomicBool::new(false));
+        let count = Arc::new(AtomicUsize::new(0));
+        let threads: Vec<_> = (0..2)
+            .map(|_| {
+                let inside = inside.clone();
+                let count = count.clone();
+                std::thread::spawn(move || {
+                    let engine = Engine::new();
+                    let activation = engine.activate();
+                    let context: Context<_> = activation.into();
+
+                    for _ in 0..10 {
+                        context
+                            .with_mutex("swipl_rs_test_mutex", || {
+                                assert!(!inside.swap(true, Ordering::SeqCst));
+                                std::thread::sleep(std::time::Duration::from_millis(1));
+                                count.fetch_add(1, Ordering::SeqCst);
+                                inside.store(false, Ordering::SeqCst);
+                            })
+                            .unwrap();
+                    }
+                })
+            })
+            .collect();
+
+        for thread in threads {
+            thread.join().unwrap();
+        }
+
+        assert_eq!(20, count.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn fast_write_and_read_large_term_through_file() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let [low, high, list] = context.new_term_refs();
+        low.unify(1_u64)?;
+        high.unify(100_000_u64)?;
+        context.call_once(pred!("numlist/3"), [&low, &high, &list])?;
+        let term = term! {context: foo(#&list, "bar", baz(_))}?;
+
+        let path = context.tmp_file("swipl_rs_fast")?;
+        let file = std::fs::File::create(&path).unwrap();
+        context.fast_write_term(&term, std::io::BufWriter::new(file))?;
+
+        let file = std::fs::File::open(&path).unwrap();
+        let result = context.fast_read_term(file)?;
+        std::fs::remove_file(&path).unwrap();
+
+        let [result_list, result_string, _] = context.compound_terms(&result)?;
+        assert!(result_list == list);
+        assert_eq!("bar", result_string.get::<String>()?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn setarg_is_undone_on_discard() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let term = term! {context: foo(a, b)}?;
+        let value = term! {context: c}?;
+
+        let frame = context.open_frame();
+        frame.setarg(1, &term, &value)?;
+        assert_eq!("foo(c,b)", frame.string_from_term(&term)?);
+        frame.discard();
+        assert_eq!("foo(a,b)", context.string_from_term(&term)?);
+
+        let frame = context.open_frame();
+        frame.nb_setarg(1, &term, &value)?;
+        frame.discard();
+        assert_eq!("foo(c,b)", context.string_from_term(&term)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn open_call_nondet() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let term = context.term_from_string("member(X, [a,b,c])").unwrap();
+        let term_x = context.new_term_ref();
+        assert!(term.unify_arg(1, &term_x).is_ok());
+
+        let query = context.open_call(&term);
+        assert!(query.next_solution()?);
+        term_x.get_atom_name(|a| assert_eq!("a", a.unwrap()))?;
+
+        assert!(query.next_solution()?);
+        term_x.get_atom_name(|a| assert_eq!("b", a.unwrap()))?;
+
+        assert!(!query.next_solution()?);
+        term_x.get_atom_name(|a| assert_eq!("c", a.unwrap()))?;
+
+        assert!(query.next_solution().unwrap_err().is_failure());
+
+        Ok(())
+    }
+
+    #[test]
+    fn open_query_with_0_arg_predicate() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let functor = Functor::new("true", 0);
+        let module = Module::new("user");
+        let predicate = Predicate::new(functor, module);
+        let callable = CallablePredicate::new(predicate).unwrap();
+
+        let query = context.open(callable, []);
+        assert!(!query.next_solution()?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn freeze_exception_is_delayed_until_next_query() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let term = context.term_from_string("freeze(X, throw(foo))")?;
+        let term_x = context.new_term_ref();
+        term.unify_arg(1, &term_x)?;
+        let query = context.open_call(&term);
+        assert!(!query.next_solution()?);
+        query.cut();
+
+        assert!(term_x.unify(42_u64).is_ok());
+
+        let term = context.new_term_ref();
+        term.unify(true)?;
+        let query = context.open_call(&term);
+        let next = query.next_solution();
+        assert!(next.unwrap_err().is_exception());
+        query.with_exception(|e| {
+            let exception_term = e.unwrap();
+            let atomable: Atomable = exception_term.get().unwrap();
+            assert_eq!("foo", atomable.name());
+
+            assert!(term.get::<u64>().unwrap_err().is_failure());
+        });
+
+        Ok(())
+    }
+
+    prolog! {
+        #[name("is")]
+        fn prolog_arithmetic(term, e);
+    }
+
+    #[test]
+    #[should_panic(expected = "tried to use context which has raised an exception")]
+    fn call_prolog_with_raised_exception_panics() {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let term1 = context.new_term_ref();
+        let term2 = context.new_term_ref();
+
+        let query = prolog_arithmetic(&context, &term1, &term2);
+        assert!(query.next_solution().unwrap_err().is_exception());
+        assert!(query.has_exception());
+        query.discard();
+        let _query2 = prolog_arithmetic(&context, &term1, &term2);
+    }
+
+    predicates! {
+        semidet fn unify_with_42(_context, term) {
+            term.unify(42_u64)
+        }
+    }
+
+    #[test]
+    fn register_foreign_predicate() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+
+        assert!(register_unify_with_42());
+
+        let context: Context<_> = activation.into();
+        let term = context.new_term_ref();
+
+        let functor = Functor::new("unify_with_42", 1);
+        let module = Module::new("user");
+        let predicate = Predicate::new(functor, module);
+        let callable = CallablePredicate::new(predicate).unwrap();
+
+        let query = context.open(callable, [&term]);
+        assert!(!query.next_solution()?);
+        assert_eq!(42, term.get::<u64>().unwrap());
+
+        Ok(())
+    }
+
+    predicates! {
+        nondet fn count_to_three<u64>(_context, term) {
+            setup => {
+                Ok(Some(1))
+            },
+            call(next) => {
+                term.unify(*next)?;
+                *next += 1;
+
+                Ok(*next <= 3)
+            }
+        }
+    }
+
+    #[test]
+    fn register_nondet_foreign_predicate() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+
+        assert!(register_count_to_three());
+
+        let context: Context<_> = activation.into();
+        let term = context.new_term_ref();
+
+        let query = context.open(pred!("count_to_three/1"), [&term]);
+        let mut results = Vec::new();
+        loop {
+            let more = query.next_solution()?;
+            results.push(term.get::<u64>()?);
+            if !more {
+                break;
+            }
+        }
+        query.cut();
+
+        assert_eq!(vec![1, 2, 3], results);
+
+        Ok(())
+    }
+
+    #[cfg(feature = "serde")]
+    prolog! {
+        fn divmod(dividend, divisor) -> (quotient, remainder);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn call_prolog_with_output_arguments() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let dividend = term! {context: 17}?;
+        let divisor = term! {context: 5}?;
+        let (q, r): (u64, u64) = divmod(&context, &dividend, &divisor)?;
+        assert_eq!((3, 2), (q, r));
+
+        Ok(())
+    }
+
+    #[test]
+    fn call_prolog_from_generated_rust_query_opener() {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let term = context.new_term_ref();
+        let expr = context.term_from_string("2+2").unwrap();
+
+        let q = prolog_arithmetic(&context, &term, &expr);
+        assert!(q.next_solution().is_ok());
+        assert_eq!(4, term.get::<u64>().unwrap());
+    }
+
+    #[test]
+    fn build_nested_term_tree() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let tree = TermTree::Compound(
+            "foo".to_string(),
+            vec![
+                TermTree::Compound(
+                    "+".to_string(),
+                    vec![
+                        TermTree::Int(1),
+                        TermTree::Compound(
+                            "*".to_string(),
+                            vec![TermTree::Int(2), TermTree::Int(3)],
+                        ),
+                    ],
+                ),
+                TermTree::List(vec![
+                    TermTree::Atom("bar".to_string()),
+                    TermTree::Float(2.5),
+                    TermTree::Str("baz".to_string()),
+                ]),
+                TermTree::Var,
+            ],
+        );
+        let term = context.build_tree(&tree)?;
+
+        let expr = context.new_term_ref();
+        term.unify_arg(1, &expr)?;
+        assert_eq!("1+2*3", context.string_from_term(&expr)?);
+
+        let list = context.new_term_ref();
+        term.unify_arg(2, &list)?;
+        assert_eq!("[bar,2.5,\"baz\"]", context.string_from_term(&list)?);
+
+        let var = context.new_term_ref();
+        term.unify_arg(3, &var)?;
+        assert!(var.is_var());
+
+        Ok(())
+    }
+
+    #[test]
+    fn eval_arithmetic_expression() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let expr = context.term_from_string("1 + 2 * 3")?;
+        let result = context.eval(&expr)?;
+        assert_eq!(7_i64, result.get()?);
+
+        assert_eq!(7, context.eval_i64(&expr)?);
+        assert_eq!(7.0, context.eval_f64(&expr)?);
+
+        let expr = context.term_from_string("foo + 1")?;
+        assert!(context.eval_i64(&expr).unwrap_err().is_exception());
+
+        Ok(())
+    }
+
+    #[test]
+    fn exception_error_context_has_predicate_indicator() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let term = context.term_from_string("atom_length(X, Y)")?;
+        let result = context.call_term_once(&term);
+        assert!(result.unwrap_err().is_exception());
+
+        let error_context = context.exception_error_context().unwrap();
+        assert_eq!(Some((Atom::new("atom_length"), 2)), error_context.predicate);
+        assert_eq!(None, error_context.arg);
+
+        context.clear_exception();
+        assert_eq!(None, context.exception_error_context());
+
+        Ok(())
+    }
+
+    #[test]
+    fn exception_backtrace_has_frames() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let library = term! {context: library(prolog_stack)}?;
+        context.call_once(pred!("use_module/1"), [&library])?;
+        for clause in [
+            "(backtrace_outer :- backtrace_inner, true)",
+            "(backtrace_inner :- atom_length(_, _), true)",
+        ] {
+            let clause = context.term_from_string(clause)?;
+            context.call_once(pred!("assertz/1"), [&clause])?;
+        }
+
+        let term =
+            context.term_from_string("catch_with_backtrace(backtrace_outer, E, throw(E))")?;
+        let result = context.call_term_once(&term);
+        assert!(result.unwrap_err().is_exception());
+
+        let backtrace = context.exception_backtrace().unwrap();
+        assert!(!backtrace.is_empty());
+        assert!(backtrace
+            .iter()
+            .any(|frame| frame.contains("backtrace_inner")));
+
+        context.clear_exception();
+        assert_eq!(None, context.exception_backtrace());
+
+        Ok(())
+    }
+
+    #[test]
+    fn garbage_collect_reclaims_global_stack() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let [key, before, after] = context.new_term_refs();
+        key.unify(Atom::new("globalused"))?;
+
+        let frame = context.open_frame();
+        let [length, list] = frame.new_term_refs();
+        length.unify(100_000_u64)?;
+        frame.call_once(pred!("length/2"), [&list, &length])?;
+        frame.close();
+
+        context.call_once(pred!("statistics/2"), [&key, &before])?;
+        context.garbage_collect()?;
+        context.call_once(pred!("statistics/2"), [&key, &after])?;
+        assert!(after.get::<u64>()? < before.get::<u64>()?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn run_with_mapped_errors() -> PrologResult<()> {
+        #[derive(Debug, PartialEq)]
+        enum AppError {
+            Type(String),
+            Other,
+            Failed,
+        }
+
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let map = |e: Option<Term>| match e {
+            Some(e) => {
+                let [formal, _] = context.compound_terms(&e).unwrap();
+                if formal.get::<Functor>() == Ok(Functor::new("type_error", 2)) {
+                    AppError::Type(formal.get_arg::<Atom>(1).unwrap().name())
+                } else {
+                    AppError::Other
+                }
+            }
+            None => AppError::Failed,
+        };
+
+        let goal = context.term_from_string("atom_length(1.5, foo)")?;
+        assert_eq!(
+            Err(AppError::Type("integer".to_string())),
+            context.run_mapped(|| context.call_term_once(&goal), map)
+        );
+        assert!(!context.has_exception());
+
+        let goal = context.term_from_string("fail")?;
+        assert_eq!(
+            Err(AppError::Failed),
+            context.run_mapped(|| context.call_term_once(&goal), map)
+        );
+
+        let goal = context.term_from_string("true")?;
+        assert_eq!(
+            Ok(()),
+            context.run_mapped(|| context.call_term_once(&goal), map)
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn iterate_over_term_list() {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let list = context.term_from_string("[5, foo, \"bar\"]").unwrap();
+
+        let mut iter = context.term_list_iter(&list);
+        let first = iter.next().unwrap();
+        let second = iter.next().unwrap();
+        let third = iter.next().unwrap();
+        assert!(iter.next().is_none());
+
+        assert_eq!(5, first.get::<u64>().unwrap());
+        assert_eq!(Atom::new("foo"), second.get::<Atom>().unwrap());
+        assert_eq!("bar", third.get::<String>().unwrap());
+    }
+
+    #[test]
+    fn iterate_over_proper_list_has_no_partial_tail() {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let list = context.term_from_string("[1, 2]").unwrap();
+
+        let mut iter = context.term_list_iter(&list);
+        assert_eq!(1, iter.next().unwrap().get::<u64>().unwrap());
+        assert_eq!(2, iter.next().unwrap().get::<u64>().unwrap());
+        assert!(iter.next().is_none());
+
+        assert!(iter.partial_tail().is_none());
+        assert!(iter.tail().get::<Nil>().is_ok());
+    }
+
+    #[test]
+    fn iterate_over_partial_list_exposes_tail() {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let list = context.term_from_string("[1, 2|T]").unwrap();
+
+        let mut iter = context.term_list_iter(&list);
+        assert_eq!(1, iter.next().unwrap().get::<u64>().unwrap());
+        assert_eq!(2, iter.next().unwrap().get::<u64>().unwrap());
+        assert!(iter.next().is_none());
+
+        let tail = iter.partial_tail().unwrap();
+        tail.unify(Nil).unwrap();
+        assert_eq!(vec![1_u64, 2], list.get::<Vec<u64>>().unwrap());
+    }
+
+    #[test]
+    fn iterate_over_term_that_is_not_a_list() {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let list = context.term_from_string("foo(bar, baz)").unwrap();
+
+        let mut iter = context.term_list_iter(&list);
+        assert!(iter.next().is_none());
+    }
+
+    #[test]
+    fn loop_over_term_list() {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let list = context.term_from_string("[42, 42, 42, 42, 42]").unwrap();
+
+        let mut count = 0;
+        for term in context.term_list_iter(&list) {
+            count += 1;
+            assert_eq!(42, term.get::<u64>().unwrap());
+        }
+
+        assert_eq!(5, count);
+    }
+
+    #[test]
+    fn term_list_to_array() {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let list = context.term_from_string("[5, foo, \"bar\"]").unwrap();
+
+        let terms: [Term; 3] = context.term_list_array(&list);
+        assert_eq!(5, terms[0].get::<u64>().unwrap());
+        assert_eq!(Atom::new("foo"), terms[1].get::<Atom>().unwrap());
+        assert_eq!("bar", terms[2].get::<String>().unwrap());
+    }
+
+    #[test]
+    fn term_list_to_array_large() {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let list = context.term_from_string("[5, foo, \"bar\"]").unwrap();
+
+        let terms: [Term; 4] = context.term_list_array(&list);
+        assert_eq!(5, terms[0].get::<u64>().unwrap());
+        assert_eq!(Atom::new("foo"), terms[1].get::<Atom>().unwrap());
+        assert_eq!("bar", terms[2].get::<String>().unwrap());
+        assert!(terms[3].is_var());
+    }
+
+    #[test]
+    fn term_list_to_array_small() {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let list = context.term_from_string("[5, foo, \"bar\"]").unwrap();
+
+        let terms: [Term; 2] = context.term_list_array(&list);
+        assert_eq!(5, terms[0].get::<u64>().unwrap());
+        assert_eq!(Atom::new("foo"), terms[1].get::<Atom>().unwrap());
+    }
+
+    #[test]
+    fn term_list_to_vec() {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let list = context.term_from_string("[5, foo, \"bar\"]").unwrap();
+        let terms = context.term_list_vec(&list);
+        assert_eq!(3, terms.len());
+        assert_eq!(5, terms[0].get::<u64>().unwrap());
+        assert_eq!(Atom::new("foo"), terms[1].get::<Atom>().unwrap());
+        assert_eq!("bar", terms[2].get::<String>().unwrap());
+    }
+
+    #[test]
+    fn term_compound_to_array() {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let compound = context.term_from_string("moo(5, foo, \"bar\")").unwrap();
+        let terms: [Term; 3] = context.compound_terms(&compound).unwrap();
+        assert_eq!(5, terms[0].get::<u64>().unwrap());
+        assert_eq!(Atom::new("foo"), terms[1].get::<Atom>().unwrap());
+        assert_eq!("bar", terms[2].get::<String>().unwrap());
+    }
+
+    #[test]
+    fn term_compound_to_wrong_size_array() {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let compound = context.term_from_string("moo(5, foo, \"bar\")").unwrap();
+        let terms: Option<[Term; 4]> = attempt_opt(context.compound_terms(&compound)).unwrap();
+        assert!(terms.is_none());
+    }
+
+    #[test]
+    fn term_compound_to_vec() {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let compound = context.term_from_string("moo(5, foo, \"bar\")").unwrap();
+        let terms = context.compound_terms_vec(&compound).unwrap();
+        assert_eq!(3, terms.len());
+        assert_eq!(5, terms[0].get::<u64>().unwrap());
+        assert_eq!(Atom::new("foo"), terms[1].get::<Atom>().unwrap());
+        assert_eq!("bar", terms[2].get::<String>().unwrap());
+    }
+
+    #[test]
+    fn term_compound_to_sized_vec() {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let compound = context.term_from_string("moo(5, foo, \"bar\")").unwrap();
+        let terms = context.compound_terms_vec_sized(&compound, 3).unwrap();
+        assert_eq!(3, terms.len());
+        assert_eq!(5, terms[0].get::<u64>().unwrap());
+        assert_eq!(Atom::new("foo"), terms[1].get::<Atom>().unwrap());
+        assert_eq!("bar", terms[2].get::<String>().unwrap());
+    }
+
+    #[test]
+    fn term_compound_to_wrong_size_vec() {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let compound = context.term_from_string("moo(5, foo, \"bar\")").unwrap();
+        let terms = attempt_opt(context.compound_terms_vec_sized(&compound, 4)).unwrap();
+        assert!(terms.is_none());
+    }
+
+    #[test]
+    fn term_compound_not_a_compound() {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let compound = context.term_from_string("\"moo\"").unwrap();
+        let terms: Option<[Term; 4]> = attempt_opt(context.compound_terms(&compound)).unwrap();
+        assert!(terms.is_none());
+    }
+
+    #[test]
+    fn between_yields_all_integers() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let numbers: Vec<i64> = context.between(1, 5).collect::<PrologResult<_>>()?;
+        assert_eq!(vec![1, 2, 3, 4, 5], numbers);
+
+        let numbers: Vec<i64> = context.between(5, 1).collect::<PrologResult<_>>()?;
+        assert!(numbers.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn extract_sub_string() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let term = term! {context: "hello"}?;
+        assert_eq!("ell", context.sub_string(&term, 1, 3)?);
+        assert_eq!("", context.sub_string(&term, 5, 0)?);
+        assert!(context.sub_string(&term, 3, 3).unwrap_err().is_failure());
+
+        Ok(())
+    }
+
+    #[test]
+    fn read_typed_prolog_flags() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let bounded = context.bounded()?;
+        let big = context.term_from_string("2**100")?;
+        assert_eq!(bounded, context.eval(&big).is_err());
+        context.clear_exception();
+
+        let max = context.max_integer()?;
+        let min = context.min_integer()?;
+        if let (Some(max), Some(min)) = (max, min) {
+            assert!(min < 0 && max > 0);
+        }
+
+        assert!(!context.encoding()?.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn recover_from_exception_and_continue() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        assert!(context.recover_from_exception().is_none());
+
+        let error = term! {context: error(my_error, _)}?;
+        assert!(context.raise_exception::<()>(&error).is_err());
+        assert!(context.has_exception());
+
+        let recovered = context.recover_from_exception().unwrap();
+        assert!(!context.has_exception());
+        let expected = term! {context: error(my_error, _)}?;
+        recovered.unify(&expected)?;
+
+        let result = context.new_term_ref();
+        let expr = context.term_from_string("2+2")?;
+        context.call_once(pred!("is/2"), [&result, &expr])?;
+        assert_eq!(4, result.get::<i64>()?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn term_from_atom_parses_compound() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let atom = Atom::new("foo(1,2)");
+        let term = context.term_from_atom(&atom)?;
+        assert_eq!(Functor::new("foo", 2), term.get::<Functor>()?);
+        let expected = term! {context: foo(1, 2)}?;
+        term.unify(&expected)?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn format_message_to_string() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let message = term! {context: format("~w", [hi])}?;
+        assert_eq!("hi", context.message_to_string(&message)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn classify_characters() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        assert!(context.char_type('a', CharClass::Alpha)?);
+        assert!(context.char_type('a', CharClass::Lower)?);
+        assert!(!context.char_type('a', CharClass::Upper)?);
+        assert!(!context.char_type('a', CharClass::Digit)?);
+        assert!(context.char_type('7', CharClass::Digit)?);
+        assert!(context.char_type(' ', CharClass::Space)?);
+        assert!(!context.char_type('x', CharClass::Space)?);
+        assert!(context.char_type('!', CharClass::Punct)?);
+        assert!(context.char_type('é', CharClass::Alpha)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn collect_term_variables() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let term = context.term_from_string("foo(X, g(Y, X))")?;
+        let variables = context.term_variables(&term)?;
+        assert_eq!(2, variables.len());
+        assert!(variables.iter().all(|v| v.is_var()));
+        assert!(variables[0] != variables[1]);
+
+        variables[0].unify(1_i64)?;
+        variables[1].unify(2_i64)?;
+        let expected = context.term_from_string("foo(1, g(2, 1))")?;
+        term.unify(&expected)?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn require_ground_raises_instantiation_error() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let ground = term! {context: foo(bar, 42)}?;
+        context.require_ground(&ground)?;
+
+        let nonground = term! {context: foo(bar, _)}?;
+        assert!(context
+            .require_ground(&nonground)
+            .unwrap_err()
+            .is_exception());
+        context.with_exception(|e| {
+            let expected = term! {context: error(instantiation_error, _)}.unwrap();
+            assert!(e.unwrap().unify(&expected).is_ok());
+        });
+        context.clear_exception();
+
+        Ok(())
+    }
+
+    #[test]
+    fn aggregate_solutions() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let goal = context.term_from_string("member(_, [a,b,c])")?;
+        assert_eq!(3, context.count(&goal)?);
+
+        let template = context.new_term_ref();
+        let goal = term! {context: member(#&template, [3, 1, 4, 1, 5])}?;
+        assert_eq!(14, context.sum_i64(&template, &goal)?);
+        assert_eq!(Some(5), context.max_i64(&template, &goal)?);
+        assert_eq!(Some(1), context.min_i64(&template, &goal)?);
+        assert_eq!(vec![3, 1, 4, 1, 5], context.bag::<i64>(&template, &goal)?);
+
+        let empty = term! {context: member(#&template, #&Nil)}?;
+        assert_eq!(0, context.count(&empty)?);
+        assert_eq!(0, context.sum_i64(&template, &empty)?);
+        assert_eq!(None, context.max_i64(&template, &empty)?);
+
+        let floats = term! {context: member(#&template, [1.5, 2.5])}?;
+        assert!(context
+            .max_i64(&template, &floats)
+            .unwrap_err()
+            .is_exception());
+        context.clear_exception();
+        assert!(context
+            .min_i64(&template, &floats)
+            .unwrap_err()
+            .is_exception());
+        context.clear_exception();
+        assert!(context.bag::<i64>(&template, &empty)?.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn compare_arithmetic_values() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let sum = context.term_from_string("1+1")?;
+        let two = context.term_from_string("2")?;
+        let three = context.term_from_string("3.0")?;
+        assert_eq!(Ordering::Equal, context.arith_compare(&sum, &two)?);
+        assert!(sum != two);
+        assert_eq!(Ordering::Less, context.arith_compare(&two, &three)?);
+        assert_eq!(Ordering::Greater, context.arith_compare(&three, &sum)?);
+
+        let atom = context.term_from_string("foo")?;
+        assert!(context
+            .arith_compare(&atom, &two)
+            .unwrap_err()
+            .is_exception());
+        context.clear_exception();
+
+        Ok(())
+    }
+
+    #[test]
+    fn bulk_assert_facts() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let frame = context.open_frame();
+        let facts = (0..10_000_u64).map(|i| term! {frame: bulk_fact(#i)}.unwrap());
+        let count = frame.bulk_assert(Module::new("user"), facts)?;
+        frame.close();
+        assert_eq!(10_000, count);
+
+        let goal = term! {context: bulk_fact(_)}?;
+        assert_eq!(10_000, context.count(&goal)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn with_flag_restores_flag() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let value = term! {context: fail}?;
+        let undefined = term! {context: with_flag_undefined_predicate}?;
+        let failed = context.with_flag("unknown", &value, || {
+            context
+                .call_once(pred!("call/1"), [&undefined])
+                .unwrap_err()
+                .is_failure()
+        })?;
+        assert!(failed);
+
+        let unknown: Option<Atom> = context.prolog_flag("unknown")?;
+        assert_eq!(Some(Atom::new("error")), unknown);
+        assert!(context
+            .call_once(pred!("call/1"), [&undefined])
+            .unwrap_err()
+            .is_exception());
+
+        Ok(())
+    }
+
+    #[test]
+    fn with_flag_restores_flag_after_exception() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let value = term! {context: fail}?;
+        let goal = term! {context: throw(with_flag_test_error)}?;
+        let result = context.with_flag("unknown", &value, || {
+            context.call_once(pred!("call/1"), [&goal])
+        })?;
+        assert!(result.unwrap_err().is_exception());
+
+        // the exception is still pending after the flag was restored
+        context.with_exception(|e| {
+            let expected = term! {context: with_flag_test_error}.unwrap();
+            assert!(e.unwrap().unify(&expected).is_ok());
+        });
+        context.clear_exception();
+
+        let unknown: Option<Atom> = context.prolog_flag("unknown")?;
+        assert_eq!(Some(Atom::new("error")), unknown);
+
+        Ok(())
+    }
+
+    #[test]
+    fn with_flag_restores_flag_after_panic() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let value = term! {context: fail}?;
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            context.with_flag("unknown", &value, || panic!("with_flag test panic"))
+        }));
+        assert!(result.is_err());
+
+        let unknown: Option<Atom> = context.prolog_flag("unknown")?;
+        assert_eq!(Some(Atom::new("error")), unknown);
+
+        Ok(())
+    }
+
+    #[test]
+    fn term_from_string_with_read_term_options() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        // capture singleton warnings instead of printing them
+        let hook = context.term_from_string(
+            "(message_hook(Message, warning, _) :- Message =.. [singletons|_], nb_setval(swipl_rs_singletons, Message))",
+        )?;
+        context.call_once(pred!("asserta/1"), [&hook])?;
+
+        let opts = ReadOptions::new().singletons(SingletonPolicy::Warning);
+        context.term_from_string_with_opts("foo(X, Y, Y)", opts)?;
+
+        context.call_once(pred!("retract/1"), [&hook])?;
+        let message = context.new_term_ref();
+        let key = term! {context: swipl_rs_singletons}?;
+        context.call_once(pred!("nb_getval/2"), [&key, &message])?;
+        let message = context.string_from_term(&message)?;
+        assert!(message.contains('X'));
+        assert!(!message.contains('Y'));
+
+        let opts = ReadOptions::new().cycles(true);
+        let term = context.term_from_string_with_opts("@(X, [X=f(X)])", opts)?;
+        assert_eq!(Functor::new("f", 1), term.get::<Functor>()?);
+
+        let opts = ReadOptions::new().syntax_errors(ErrorPolicy::Quiet);
+        assert!(context
+            .term_from_string_with_opts("foo(", opts)
+            .unwrap_err()
+            .is_failure());
+
+        Ok(())
+    }
+
+    #[test]
+    fn apply_goal_with_argument_list() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let result = context.new_term_ref();
+        let goal = term! {context: plus(1)}?;
+        let args = term! {context: [2, #&result]}?;
+        context.apply(&goal, &args)?;
+        assert_eq!(3_u64, result.get()?);
+
+        let args = term! {context: [2, 4]}?;
+        assert!(context.apply(&goal, &args).unwrap_err().is_failure());
+
+        Ok(())
+    }
+
+    #[test]
+    fn call_once_bool_reports_failure_as_false() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let [a, b] = context.new_term_refs();
+        a.unify(1_u64)?;
+        b.unify(2_u64)?;
+        assert!(!context.call_once_bool(pred!("==/2"), [&a, &b])?);
+        assert!(context.call_once_bool(pred!("</2"), [&a, &b])?);
+
+        let error = term! {context: error(foo)}?;
+        assert!(context
+            .call_once_bool(pred!("throw/1"), [&error])
+            .unwrap_err()
+            .is_exception());
+
+        Ok(())
+    }
+
+    #[test]
+    fn convert_text_to_chars_and_codes() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let atom = Atom::new("abc");
+        assert_eq!(vec!['a', 'b', 'c'], context.atom_chars(&atom)?);
+        assert_eq!(vec![97, 98, 99], context.atom_codes(&atom)?);
+
+        let string = context.new_term_ref();
+        string.unify("héllo")?;
+        assert_eq!(
+            vec!['h', 'é', 'l', 'l', 'o'],
+            context.string_chars(&string)?
+        );
+        assert_eq!(
+            vec![104, 233, 108, 108, 111],
+            context.string_codes(&string)?
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn run_dcg_over_code_list() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let rule = context.term_from_string("(phrase_test_greeting --> `hi`)")?;
+        let clause = context.new_term_ref();
+        context.call_once(pred!("expand_term/2"), [&rule, &clause])?;
+        context.call_once(pred!("assertz/1"), [&clause])?;
+
+        let body = context.new_term_ref();
+        body.unify(Atom::new("phrase_test_greeting"))?;
+        let codes = context.term_from_string("`hi`")?;
+        assert!(context.phrase(&body, &codes)?);
+
+        let codes = context.term_from_string("`hi there`")?;
+        assert!(!context.phrase(&body, &codes)?);
+        let rest = context.phrase_with_rest(&body, &codes)?.unwrap();
+        let expected = context.term_from_string("` there`")?;
+        assert!(attempt(rest.unify(&expected))?);
+
+        let codes = context.term_from_string("`hello`")?;
+        assert!(context.phrase_with_rest(&body, &codes)?.is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn create_code_list_from_bytes() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let bytes: Vec<u8> = (0..1024).map(|i| (i % 256) as u8).collect();
+        let list = context.new_code_list_from_bytes(&bytes)?;
+
+        let codes: Vec<u64> = list.get()?;
+        assert_eq!(bytes.len(), codes.len());
+        assert!(bytes.iter().zip(codes).all(|(&b, c)| b as u64 == c));
+
+        Ok(())
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn findall_named_variable() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let results: Vec<u64> = context.findall_var("member(X, [1,2,3])", "X")?;
+        assert_eq!(vec![1, 2, 3], results);
+
+        let results: Vec<String> = context.findall_var("member(X-Y, [a-\"b\", c-\"d\"])", "Y")?;
+        assert_eq!(vec!["b".to_string(), "d".to_string()], results);
+
+        let results: PrologResult<Vec<u64>> = context.findall_var("member(X, [1,2,3])", "Z");
+        assert!(results.unwrap_err().is_failure());
+
+        Ok(())
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn query_solutions_in_pages() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let pages: Vec<Vec<u64>> = context
+            .query_paginated("between(1, 10, X)", "X", 3)
+            .collect::<PrologResult<_>>()?;
+        assert_eq!(
+            vec![vec![1, 2, 3], vec![4, 5, 6], vec![7, 8, 9], vec![10]],
+            pages
+        );
+
+        let pages: Vec<Vec<u64>> = context
+            .query_paginated("between(1, 6, X)", "X", 3)
+            .collect::<PrologResult<_>>()?;
+        assert_eq!(vec![vec![1, 2, 3], vec![4, 5, 6]], pages);
+
+        let mut pages = context.query_paginated::<u64>("between(1, 6, X)", "Z", 3);
+        assert!(pages.next().unwrap().unwrap_err().is_failure());
+        assert!(pages.next().is_none());
+
+        Ok(())
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn parse_string_into_struct() -> PrologResult<()> {
+        #[derive(serde::Deserialize, Debug, PartialEq)]
+        struct Point {
+            x: i64,
+            y: i64,
+        }
+
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let point: Point = context.parse_and_deserialize("point{x:1, y:2}")?;
+        assert_eq!(Point { x: 1, y: 2 }, point);
+
+        let result: PrologResult<Point> = context.parse_and_deserialize("point{x:1}");
+        assert!(result.unwrap_err().is_exception());
+        context.clear_exception();
+
+        Ok(())
     }
-}
 
-/// An iterator over a term list.
-///
-/// See [`Context::term_list_iter`] for more information.
-pub struct TermListIterator<'a, 'b, CT: QueryableContextType> {
-    context: &'a Context<'b, CT>,
-    cur: Term<'a>,
-}
+    #[test]
+    fn engines_have_distinct_stable_ids() {
+        let engine1 = Engine::new();
+        let engine2 = Engine::new();
 
-impl<'a, 'b, CT: QueryableContextType> Iterator for TermListIterator<'a, 'b, CT> {
-    type Item = Term<'a>;
+        let activation = engine1.activate();
+        let context: Context<_> = activation.into();
+        let id1 = context.engine_id();
+        let frame = context.open_frame();
+        assert_eq!(id1, frame.engine_id());
+        frame.close();
+        std::mem::drop(context);
 
-    fn next(&mut self) -> Option<Term<'a>> {
-        let head = self.context.new_term_ref();
-        let tail = self.context.new_term_ref();
-        let success =
-            unsafe { PL_get_list(self.cur.term_ptr(), head.term_ptr(), tail.term_ptr()) != 0 };
+        let activation = engine2.activate();
+        let context: Context<_> = activation.into();
+        let id2 = context.engine_id();
+        std::mem::drop(context);
 
-        if success {
-            self.cur = tail;
-            Some(head)
-        } else {
-            None
-        }
+        let activation = engine1.activate();
+        let context: Context<_> = activation.into();
+        assert_eq!(id1, context.engine_id());
+        assert_ne!(id1, id2);
     }
-}
 
-/// Trait for turning errors into prolog exceptions
-pub trait IntoPrologException {
-    /// Turns this error into a prolog exception using the given context.
-    ///
-    /// The result is a `Term` containing the prolog exception.
-    fn into_prolog_exception<'a, T: QueryableContextType>(
-        self,
-        context: &'a Context<'_, T>,
-    ) -> PrologResult<Term<'a>>;
-}
+    #[test]
+    fn complete_atom_prefix() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
 
-impl IntoPrologException for std::io::Error {
-    fn into_prolog_exception<'a, T: QueryableContextType>(
-        self,
-        context: &'a Context<'_, T>,
-    ) -> PrologResult<Term<'a>> {
-        let kind_str = format!("{:?}", self.kind());
-        let kind_atom = Atom::new(&kind_str);
-        let msg = format!("{}", self);
-        term! {context: error(rust_io_error(#kind_atom, #msg), _)}
-    }
-}
+        let completions = context.complete_atom("appen")?;
+        assert!(completions.iter().any(|c| c == "append"));
+        assert!(completions.iter().all(|c| c.starts_with("appen")));
 
-/// Call the given function, converting panics into prolog exceptions.
-///
-/// If the inner function panics, an exception of the form
-/// `error(rust_error(panic("..the panic message..")))` will be
-/// raised, and this function will return
-/// `Err(PrologError::Exception)`. Otherwise, This function will
-/// return `Ok(())`.
-///
-/// This is used by various macros to ensure that panics from user
-/// code do not propagate into prolog.
-///
-/// # Safety
-/// This is only safe to use from an environment from which we can
-/// raise a prolog exception.
-pub unsafe fn prolog_catch_unwind<F: FnOnce() -> R + std::panic::UnwindSafe, R>(
-    f: F,
-) -> PrologResult<R> {
-    let result = std::panic::catch_unwind(f);
-    match result {
-        Ok(result) => Ok(result),
-        Err(panic) => {
-            let context = unmanaged_engine_context();
-            let panic_term = context.new_term_ref();
-            let error_term = term! {context: error(rust_error(panic(#&panic_term)), _)}?;
+        Ok(())
+    }
 
-            match panic.downcast_ref::<&str>() {
-                Some(panic_msg) => {
-                    panic_term.unify(panic_msg).unwrap();
-                }
-                None => match panic.downcast_ref::<String>() {
-                    Some(panic_msg) => {
-                        panic_term.unify(panic_msg.as_str()).unwrap();
-                    }
-                    None => {
-                        panic_term.unify("unknown panic type").unwrap();
-                    }
-                },
-            }
+    #[test]
+    fn create_many_term_refs_at_once() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
 
-            context.raise_exception::<()>(&error_term).unwrap_err();
-            Err(PrologError::Exception)
+        let terms = context.new_term_refs_vec(100);
+        assert_eq!(100, terms.len());
+        for (i, term) in terms.iter().enumerate() {
+            assert!(term.is_var());
+            term.unify(i as u64)?;
         }
-    }
-}
 
-#[derive(Clone)]
-pub struct GenericQueryableContextType;
-unsafe impl ContextType for GenericQueryableContextType {}
-impl FrameableContextType for GenericQueryableContextType {}
-impl QueryableContextType for GenericQueryableContextType {}
+        for (i, term) in terms.iter().enumerate() {
+            assert_eq!(i as u64, term.get::<u64>()?);
+        }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::functor::*;
-    use crate::predicate::*;
-    use crate::predicates;
+        Ok(())
+    }
 
     #[test]
-    fn get_term_ref_on_fresh_engine() {
+    fn control_autoloading_and_check_sources() -> PrologResult<()> {
         let engine = Engine::new();
         let activation = engine.activate();
         let context: Context<_> = activation.into();
 
-        let _term = context.new_term_ref();
+        assert!(context.exists_source("library(ugraphs)")?);
+        assert!(!context.exists_source("library(swipl_rs_no_such_library)")?);
+
+        // library(heaps) is not used by any other test, so nothing
+        // else autoloads it while autoloading is switched off here.
+        let goal = "catch((empty_heap(_), R = defined), error(existence_error(procedure, _), _), R = undefined)";
+        // with_flag restores the flag even if the goal panics.
+        let enabled = context.new_term_ref();
+        enabled.unify(true)?;
+        let bindings = context.with_flag("autoload", &enabled, || {
+            context.set_autoload(false)?;
+            context.run_once(goal)
+        })?;
+
+        let bindings = bindings?.unwrap();
+        assert_eq!(PrologValue::Atom("undefined".to_string()), bindings["R"]);
+
+        Ok(())
     }
 
     #[test]
-    fn get_term_ref_on_frame() {
+    fn copy_term_without_attributes() -> PrologResult<()> {
         let engine = Engine::new();
         let activation = engine.activate();
-        let context1: Context<_> = activation.into();
-        let _term1 = context1.new_term_ref();
+        let context: Context<_> = activation.into();
 
-        let context2 = context1.open_frame();
-        let _term2 = context2.new_term_ref();
-        std::mem::drop(context2);
-        let _term3 = context1.new_term_ref();
+        let term = context.term_from_string("foo(X, Y, bar)")?;
+        let [x, _, _] = context.compound_terms(&term)?;
+        let [module, value] = context.new_term_refs();
+        module.unify(Atom::new("swipl_rs_test"))?;
+        value.unify(42_u64)?;
+        context.call_once(pred!("put_attr/3"), [&x, &module, &value])?;
+        assert!(context.call_once_bool(pred!("attvar/1"), [&x])?);
+
+        let copy = context.copy_term_nat(&term)?;
+        let [x_copy, y_copy, bar] = context.compound_terms(&copy)?;
+        assert!(x_copy.is_var());
+        assert!(!context.call_once_bool(pred!("attvar/1"), [&x_copy])?);
+        assert!(y_copy.is_var());
+        assert_eq!(Atom::new("bar"), bar.get::<Atom>()?);
+
+        // the original is left alone
+        assert!(context.call_once_bool(pred!("attvar/1"), [&x])?);
+
+        Ok(())
     }
 
     #[test]
-    #[should_panic]
-    fn get_term_ref_from_inactive_context_panics() {
+    fn typed_integer_arithmetic() -> PrologResult<()> {
         let engine = Engine::new();
         let activation = engine.activate();
-        let context1: Context<_> = activation.into();
-        let _context2 = context1.open_frame();
+        let context: Context<_> = activation.into();
 
-        let _term = context1.new_term_ref();
+        assert_eq!(5, context.plus(2, 3)?);
+        assert_eq!(-1, context.plus(2, -3)?);
+        assert_eq!(43, context.succ_i64(42)?);
+        assert_eq!(42, context.abs(-42)?);
+
+        // prolog computes the bignum result, which doesn't fit in an i64
+        let overflows: [&dyn Fn() -> PrologResult<i64>; 3] = [
+            &|| context.plus(i64::MAX, 1),
+            &|| context.succ_i64(i64::MAX),
+            &|| context.abs(i64::MIN),
+        ];
+        for overflow in overflows {
+            assert!(overflow().unwrap_err().is_exception());
+            context.with_exception(|e| {
+                let expected = term! {context: error(evaluation_error(int_overflow), _)}.unwrap();
+                assert!(e.unwrap().unify(&expected).is_ok());
+            });
+            context.clear_exception();
+        }
+        assert!(context.succ_i64(-1).unwrap_err().is_exception());
+        context.clear_exception();
+
+        Ok(())
     }
 
     #[test]
-    fn query_det() -> PrologResult<()> {
+    fn parse_terms_from_lines() -> PrologResult<()> {
         let engine = Engine::new();
         let activation = engine.activate();
         let context: Context<_> = activation.into();
 
-        let functor_is = Functor::new("is", 2);
-        let functor_plus = Functor::new("+", 2);
-        let module = Module::new("user");
-        let predicate = Predicate::new(functor_is, module);
-        let callable = CallablePredicate::new(predicate).unwrap();
-
-        let term1 = context.new_term_ref();
-        let term2 = context.new_term_ref();
+        let input = "foo(bar, 42)\n\n[1, 2, 3]\n\"a string\"\n";
+        let terms: Vec<Term> = context
+            .terms_from_lines(std::io::Cursor::new(input))
+            .collect::<PrologResult<_>>()?;
+        assert_eq!(3, terms.len());
+        assert_eq!("foo(bar,42)", context.string_from_term(&terms[0])?);
+        assert_eq!(vec![1, 2, 3], terms[1].get::<Vec<u64>>()?);
+        assert_eq!("a string", terms[2].get::<String>()?);
 
-        term2.unify(functor_plus)?;
-        term2.unify_arg(1, 40_u64)?;
-        term2.unify_arg(2, 2_u64)?;
+        Ok(())
+    }
 
-        let query = context.open(callable, [&term1, &term2]);
-        let next = query.next_solution()?;
+    #[test]
+    fn canonical_string_is_stable() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
 
-        assert!(!next);
-        assert_eq!(42_u64, term1.get()?);
+        let term1 = context.term_from_string("[1+'A', point{y: \"b\", x: c}]")?;
+        let term2 = context.term_from_string("'[|]'(+(1, 'A'), [point{x: c, y: \"b\"}])")?;
 
-        let next = query.next_solution();
-        assert!(next.is_err());
+        let string1 = context.canonical_string(&term1)?;
+        let string2 = context.canonical_string(&term2)?;
+        assert_eq!("[+(1,'A'),point{x:c,y:\"b\"}]", string1);
+        assert_eq!(string1, string2);
 
         Ok(())
     }
 
     #[test]
-    fn query_auto_discard() -> PrologResult<()> {
+    fn find_predicates_matching_a_name() -> PrologResult<()> {
         let engine = Engine::new();
         let activation = engine.activate();
         let context: Context<_> = activation.into();
 
-        let functor_is = Functor::new("is", 2);
-        let functor_plus = Functor::new("+", 2);
-        let module = Module::new("user");
-        let predicate = Predicate::new(functor_is, module);
-        let callable = CallablePredicate::new(predicate).unwrap();
+        let library = term! {context: library(lists)}?;
+        context.call_once(pred!("use_module/1"), [&library])?;
 
-        let term1 = context.new_term_ref();
-        let term2 = context.new_term_ref();
+        let predicates = context.predicates_matching(Some("append"), None)?;
+        assert!(predicates.iter().all(|p| p.name_string() == "append"));
+        let mut arities: Vec<_> = predicates
+            .iter()
+            .filter(|p| p.module().name_string() == "lists")
+            .map(|p| p.arity())
+            .collect();
+        arities.sort();
+        assert_eq!(vec![2, 3], arities);
 
-        assert!(term2.unify(functor_plus).is_ok());
-        assert!(term2.unify_arg(1, 40_u64).is_ok());
-        assert!(term2.unify_arg(2, 2_u64).is_ok());
+        let predicates = context.predicates_matching(Some("append"), Some(3))?;
+        assert!(predicates.iter().all(|p| p.arity() == 3));
 
-        {
-            let query = context.open(callable, [&term1, &term2]);
-            let next = query.next_solution()?;
+        let goal = context.term_from_string("functor(H, swipl_rs_wide, 70000), assertz(H)")?;
+        context.call_term_once(&goal)?;
+        let result = context.predicates_matching(Some("swipl_rs_wide"), None);
+        assert!(matches!(result, Err(PrologError::Exception)));
+        context.clear_exception();
 
-            assert!(!next);
-            assert_eq!(42_u64, term1.get().unwrap());
-        }
+        Ok(())
+    }
 
-        // after leaving the block, we have discarded
-        assert!(term1.get::<u64>().unwrap_err().is_failure());
+    #[test]
+    fn assertz_into_a_module() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let module = context.new_temporary_module()?;
+        let clause = context.term_from_string("sandboxed_clause(X) :- X = 42")?;
+        context.assertz_in(module.module(), &clause)?;
+
+        let [qualified, head, value] = context.new_term_refs();
+        head.unify(Functor::new("sandboxed_clause", 1))?;
+        head.unify_arg(1, &value)?;
+        qualified.unify(Functor::new(":", 2))?;
+        qualified.unify_arg(1, module.module().name())?;
+        qualified.unify_arg(2, &head)?;
+        context.call_once(pred!("call/1"), [&qualified])?;
+        assert_eq!(42, value.get::<i64>()?);
+
+        let indicator = context.term_from_string("user:sandboxed_clause/1")?;
+        assert!(!context.call_once_bool(pred!("current_predicate/1"), [&indicator])?);
 
         Ok(())
     }
 
     #[test]
-    fn query_manual_discard() -> PrologResult<()> {
+    fn define_operator_and_parse() -> PrologResult<()> {
         let engine = Engine::new();
         let activation = engine.activate();
         let context: Context<_> = activation.into();
 
-        let functor_is = Functor::new("is", 2);
-        let functor_plus = Functor::new("+", 2);
-        let module = Module::new("user");
-        let predicate = Predicate::new(functor_is, module);
-        let callable = CallablePredicate::new(predicate).unwrap();
+        context.op(700, OpType::Xfx, "===>")?;
+        let term = context.term_from_string("a ===> b")?;
+        assert_eq!(Functor::new("===>", 2), term.get::<Functor>()?);
+        assert_eq!(Atom::new("a"), term.get_arg::<Atom>(1)?);
+        assert_eq!(Atom::new("b"), term.get_arg::<Atom>(2)?);
 
-        let term1 = context.new_term_ref();
-        let term2 = context.new_term_ref();
+        Ok(())
+    }
 
-        term2.unify(functor_plus)?;
-        term2.unify_arg(1, 40_u64)?;
-        term2.unify_arg(2, 2_u64)?;
+    #[test]
+    fn must_be_integer() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
 
-        {
-            let query = context.open(callable, [&term1, &term2]);
-            let next = query.next_solution()?;
+        let term = context.new_term_ref();
+        term.unify(42_u64)?;
+        context.must_be("integer", &term)?;
 
-            assert!(!next);
-            assert_eq!(42_u64, term1.get()?);
-            query.discard();
-        }
+        let term = context.new_term_ref();
+        term.unify(Atomable::from("foo"))?;
+        assert!(context
+            .must_be("integer", &term)
+            .unwrap_err()
+            .is_exception());
+        context.with_exception(|e| {
+            let expected = term! {context: error(type_error(integer, foo), _)}.unwrap();
+            assert!(e.unwrap().unify(&expected).is_ok());
+        });
+        context.clear_exception();
 
-        // after leaving the block, we have discarded
-        assert!(term1.get::<u64>().unwrap_err().is_failure());
+        let term = context.new_term_ref();
+        assert!(context
+            .must_be("integer", &term)
+            .unwrap_err()
+            .is_exception());
+        context.with_exception(|e| {
+            let expected = term! {context: error(instantiation_error, _)}.unwrap();
+            assert!(e.unwrap().unify(&expected).is_ok());
+        });
+        context.clear_exception();
 
         Ok(())
     }
 
     #[test]
-    fn query_cut() -> PrologResult<()> {
+    fn parse_with_module_local_operator() -> PrologResult<()> {
         let engine = Engine::new();
         let activation = engine.activate();
         let context: Context<_> = activation.into();
 
-        let functor_is = Functor::new("is", 2);
-        let functor_plus = Functor::new("+", 2);
-        let module = Module::new("user");
-        let predicate = Predicate::new(functor_is, module);
-        let callable = CallablePredicate::new(predicate).unwrap();
+        let goal = context.term_from_string("op(700, xfx, parse_dsl:(~~>))")?;
+        context.call_term_once(&goal)?;
 
-        let term1 = context.new_term_ref();
-        let term2 = context.new_term_ref();
+        let module = Module::new("parse_dsl");
+        let term = context.term_from_string_in(module, "a ~~> b")?;
+        assert_eq!(Functor::new("~~>", 2), term.get::<Functor>()?);
 
-        term2.unify(functor_plus)?;
-        term2.unify_arg(1, 40_u64)?;
-        term2.unify_arg(2, 2_u64)?;
+        assert!(context
+            .term_from_string("a ~~> b")
+            .unwrap_err()
+            .is_exception());
+        context.clear_exception();
 
-        {
-            let query = context.open(callable, [&term1, &term2]);
-            let next = query.next_solution()?;
+        Ok(())
+    }
 
-            assert!(!next);
-            assert_eq!(42_u64, term1.get()?);
-            query.cut();
+    #[test]
+    fn call_deep_goal_with_depth_limit() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        for clause in [
+            "depth_test(0)",
+            "depth_test(N) :- N > 0, M is N - 1, depth_test(M)",
+        ] {
+            let clause = context.term_from_string(clause)?;
+            context.call_once(pred!("assertz/1"), [&clause])?;
         }
 
-        // a cut query leaves data intact
-        assert_eq!(42_u64, term1.get()?);
+        let goal = context.term_from_string("depth_test(100)")?;
+        assert_eq!(
+            DepthResult::LimitExceeded,
+            context.call_with_depth_limit(&goal, 10)?
+        );
+        match context.call_with_depth_limit(&goal, 1000)? {
+            DepthResult::Depth(depth) => assert!(depth > 100 && depth <= 1000),
+            DepthResult::LimitExceeded => panic!("depth limit unexpectedly exceeded"),
+        }
+
+        let goal = context.term_from_string("fail")?;
+        assert!(context
+            .call_with_depth_limit(&goal, 10)
+            .unwrap_err()
+            .is_failure());
 
         Ok(())
     }
 
+    // qsave_program/2 saves the state of the whole process, including
+    // whatever the tests running alongside this one have loaded, and
+    // can take a long time. Run it on its own with `--ignored`.
     #[test]
-    fn term_from_string_works() {
+    #[ignore = "saves the whole process state; run with --ignored"]
+    fn create_saved_state() -> PrologResult<()> {
         let engine = Engine::new();
         let activation = engine.activate();
         let context: Context<_> = activation.into();
 
-        let term = context.term_from_string("foo(bar(baz,quux))").unwrap();
-        let functor_foo = Functor::new("foo", 1);
-        let functor_bar = Functor::new("bar", 2);
+        let clause = context.term_from_string("saved_state_fact(42)")?;
+        context.call_once(pred!("assertz/1"), [&clause])?;
 
-        assert_eq!(functor_foo, term.get().unwrap());
-        assert_eq!(functor_bar, term.get_arg(1).unwrap());
+        let path = context.tmp_file("state")?;
+        context.qsave_program(Path::new(&path), SaveOptions::new().autoload(false))?;
+        assert!(Path::new(&path).exists());
+        std::fs::remove_file(&path).unwrap();
+
+        Ok(())
     }
 
     #[test]
-    fn open_call_nondet() -> PrologResult<()> {
+    fn compile_and_load_qlf() -> PrologResult<()> {
         let engine = Engine::new();
         let activation = engine.activate();
         let context: Context<_> = activation.into();
 
-        let term = context.term_from_string("member(X, [a,b,c])").unwrap();
-        let term_x = context.new_term_ref();
-        assert!(term.unify_arg(1, &term_x).is_ok());
+        let base = context.tmp_file("qlf")?;
+        let src = format!("{}.pl", base);
+        let qlf = format!("{}_compiled.qlf", base);
+        std::fs::write(&src, "qlf_fact(42).\n").unwrap();
 
-        let query = context.open_call(&term);
-        assert!(query.next_solution()?);
-        term_x.get_atom_name(|a| assert_eq!("a", a.unwrap()))?;
+        context.compile_to_qlf(Path::new(&src), Path::new(&qlf))?;
+        assert!(Path::new(&qlf).exists());
 
-        assert!(query.next_solution()?);
-        term_x.get_atom_name(|a| assert_eq!("b", a.unwrap()))?;
+        // qcompile also loaded the source, so unload it again first
+        let src_term = context.new_term_ref();
+        src_term.unify(Atomable::from(src.as_str()))?;
+        context.call_once(pred!("unload_file/1"), [&src_term])?;
 
-        assert!(!query.next_solution()?);
-        term_x.get_atom_name(|a| assert_eq!("c", a.unwrap()))?;
+        context.load_qlf(Path::new(&qlf))?;
+        let value = context.new_term_ref();
+        context.call_once(pred!("qlf_fact/1"), [&value])?;
+        assert_eq!(42, value.get::<u64>()?);
 
-        assert!(query.next_solution().unwrap_err().is_failure());
+        std::fs::remove_file(&src).unwrap();
+        std::fs::remove_file(&qlf).unwrap();
 
         Ok(())
     }
 
     #[test]
-    fn open_query_with_0_arg_predicate() -> PrologResult<()> {
+    fn load_plugin_into_own_module() -> PrologResult<()> {
         let engine = Engine::new();
         let activation = engine.activate();
         let context: Context<_> = activation.into();
 
-        let functor = Functor::new("true", 0);
-        let module = Module::new("user");
-        let predicate = Predicate::new(functor, module);
-        let callable = CallablePredicate::new(predicate).unwrap();
-
-        let query = context.open(callable, []);
-        assert!(!query.next_solution()?);
+        let base = context.tmp_file("plugin")?;
+        let src = format!("{}.pl", base);
+        std::fs::write(
+            &src,
+            ":- module(greeter, [greet/1]).\ngreet(hello).\nhidden(secret).\n",
+        )
+        .unwrap();
+
+        let module = context.load_plugin(Path::new(&src))?;
+        let goal = term! {context: greet(hello)}?;
+        context
+            .open_with_module(pred!("call/1"), Some(module), [&goal])
+            .next_solution()?;
+
+        let indicator = context.term_from_string("greet/1")?;
+        assert!(!context.call_once_bool(pred!("current_predicate/1"), [&indicator])?);
+
+        // another module file declaring the same module is rejected
+        let other = format!("{}_other.pl", base);
+        std::fs::write(&other, ":- module(greeter, [greet/1]).\ngreet(bye).\n").unwrap();
+        assert!(context
+            .load_plugin(Path::new(&other))
+            .unwrap_err()
+            .is_exception());
+        context.clear_exception();
+        context
+            .open_with_module(pred!("call/1"), Some(module), [&goal])
+            .next_solution()?;
+
+        // a non-module file is loaded into the plugin module
+        let plain = format!("{}_plain.pl", base);
+        std::fs::write(&plain, "plain_fact(42).\n").unwrap();
+        let plain_module = context.load_plugin(Path::new(&plain))?;
+        let value = context.new_term_ref();
+        let goal = term! {context: plain_fact(#&value)}?;
+        context
+            .open_with_module(pred!("call/1"), Some(plain_module), [&goal])
+            .next_solution()?;
+        assert_eq!(42, value.get::<u64>()?);
+        let indicator = context.term_from_string("plain_fact/1")?;
+        assert!(!context.call_once_bool(pred!("current_predicate/1"), [&indicator])?);
+
+        std::fs::remove_file(&src).unwrap();
+        std::fs::remove_file(&other).unwrap();
+        std::fs::remove_file(&plain).unwrap();
 
         Ok(())
     }
 
+    predicates! {
+        semidet fn write_to_current_output(context, text) {
+            let text: String = text.get()?;
+            let mut stream = context.current_output();
+            context.try_or_die(std::io::Write::write_all(&mut stream, text.as_bytes()))
+        }
+    }
+
     #[test]
-    fn freeze_exception_is_delayed_until_next_query() -> PrologResult<()> {
+    fn write_to_redirected_current_output() -> PrologResult<()> {
         let engine = Engine::new();
         let activation = engine.activate();
         let context: Context<_> = activation.into();
 
-        let term = context.term_from_string("freeze(X, throw(foo))")?;
-        let term_x = context.new_term_ref();
-        term.unify_arg(1, &term_x)?;
-        let query = context.open_call(&term);
-        assert!(!query.next_solution()?);
-        query.cut();
+        register_write_to_current_output();
+        let output = context.new_term_ref();
+        let goal =
+            term! {context: with_output_to(string(#&output), write_to_current_output("hello"))}?;
+        context.call_term_once(&goal)?;
+        assert_eq!("hello", output.get::<String>()?);
 
-        assert!(term_x.unify(42_u64).is_ok());
+        Ok(())
+    }
 
-        let term = context.new_term_ref();
-        term.unify(true)?;
-        let query = context.open_call(&term);
-        let next = query.next_solution();
-        assert!(next.unwrap_err().is_exception());
-        query.with_exception(|e| {
-            let exception_term = e.unwrap();
-            let atomable: Atomable = exception_term.get().unwrap();
-            assert_eq!("foo", atomable.name());
+    #[test]
+    fn read_from_redirected_current_input() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
 
-            assert!(term.get::<u64>().unwrap_err().is_failure());
-        });
+        let [text, stream, original] = context.new_term_refs();
+        text.unify("some input")?;
+        context.call_once(pred!("current_input/1"), [&original])?;
+        context.call_once(pred!("open_string/2"), [&text, &stream])?;
+        context.call_once(pred!("set_input/1"), [&stream])?;
 
-        Ok(())
-    }
+        let result = context.current_input().read_string();
+        context.call_once(pred!("set_input/1"), [&original])?;
+        context.call_once(pred!("close/1"), [&stream])?;
+        assert_eq!("some input", result.unwrap());
 
-    prolog! {
-        #[name("is")]
-        fn prolog_arithmetic(term, e);
+        Ok(())
     }
 
     #[test]
-    #[should_panic(expected = "tried to use context which has raised an exception")]
-    fn call_prolog_with_raised_exception_panics() {
+    fn parse_predicate_indicators() -> PrologResult<()> {
         let engine = Engine::new();
         let activation = engine.activate();
         let context: Context<_> = activation.into();
 
-        let term1 = context.new_term_ref();
-        let term2 = context.new_term_ref();
-
-        let query = prolog_arithmetic(&context, &term1, &term2);
-        assert!(query.next_solution().unwrap_err().is_exception());
-        assert!(query.has_exception());
-        query.discard();
-        let _query2 = prolog_arithmetic(&context, &term1, &term2);
-    }
+        let predicate = context.predicate_from_indicator("writeq/1")?;
+        assert_eq!("writeq", predicate.name_string());
+        assert_eq!(1, predicate.arity());
+        assert_eq!("user", predicate.module().name_string());
+
+        let predicate = context.predicate_from_indicator("user:nl/0")?;
+        assert_eq!("nl", predicate.name_string());
+        assert_eq!(0, predicate.arity());
+        assert_eq!("user", predicate.module().name_string());
+
+        let predicate = context.predicate_from_indicator("lists:append/3")?;
+        assert_eq!("lists", predicate.module().name_string());
+
+        // a missing slash, a non-numeric arity, or a missing name or module
+        for invalid in [
+            "foo", "foo/bar", "foo/", "foo/-1", "/1", "lists:/2", ":foo/2",
+        ] {
+            let result = context.predicate_from_indicator(invalid);
+            assert!(matches!(result, Err(e) if e.is_exception()));
+            context.with_exception(|e| {
+                let expected =
+                    term! {context: error(domain_error(predicate_indicator, _), _)}.unwrap();
+                assert!(e.unwrap().unify(&expected).is_ok());
+            });
+            context.clear_exception();
+        }
 
-    predicates! {
-        semidet fn unify_with_42(_context, term) {
-            term.unify(42_u64)
+        // an arity too large for a functor
+        for oversized in ["foo/2000", "foo/99999999", "foo/99999999999999999999999"] {
+            let result = context.predicate_from_indicator(oversized);
+            assert!(matches!(result, Err(e) if e.is_exception()));
+            context.with_exception(|e| {
+                let expected = term! {context: error(representation_error(max_arity), _)}.unwrap();
+                assert!(e.unwrap().unify(&expected).is_ok());
+            });
+            context.clear_exception();
         }
+
+        Ok(())
     }
 
     #[test]
-    fn register_foreign_predicate() -> PrologResult<()> {
+    fn read_terms_with_term_expansion() -> PrologResult<()> {
         let engine = Engine::new();
         let activation = engine.activate();
+        let context: Context<_> = activation.into();
 
-        assert!(register_unify_with_42());
+        let clause =
+            context.term_from_string("(term_expansion(shout(X), said(X, loud)) :- true)")?;
+        context.call_once(pred!("assertz/1"), [&clause])?;
+
+        let [text, stream] = context.new_term_refs();
+        text.unify("shout(hello). whisper(hi). X. greeting --> [hello].")?;
+        context.call_once(pred!("open_string/2"), [&text, &stream])?;
+
+        let shout = context.read_term_expanded(&stream)?.unwrap();
+        assert_eq!("said(hello,loud)", context.string_from_term(&shout)?);
+        let whisper = context.read_term_expanded(&stream)?.unwrap();
+        assert_eq!("whisper(hi)", context.string_from_term(&whisper)?);
+        // a variable is not mistaken for the end of the stream.
+        let var = context.read_term_expanded(&stream)?.unwrap();
+        assert!(var.is_var());
+        let greeting = context.read_term_expanded(&stream)?.unwrap();
+        let head = match greeting.get::<Functor>()? == Functor::new(":-", 2) {
+            true => greeting.get_arg::<Functor>(1)?,
+            false => greeting.get::<Functor>()?,
+        };
+        assert_eq!(Functor::new("greeting", 2), head);
+        assert!(context.read_term_expanded(&stream)?.is_none());
+        context.call_once(pred!("close/1"), [&stream])?;
 
-        let context: Context<_> = activation.into();
-        let term = context.new_term_ref();
+        Ok(())
+    }
 
-        let functor = Functor::new("unify_with_42", 1);
-        let module = Module::new("user");
-        let predicate = Predicate::new(functor, module);
-        let callable = CallablePredicate::new(predicate).unwrap();
+    #[test]
+    fn portray_clause_as_listing() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
 
-        let query = context.open(callable, [&term]);
-        assert!(!query.next_solution()?);
-        assert_eq!(42, term.get::<u64>().unwrap());
+        let clause = context.term_from_string("(foo(X) :- bar(X), baz(X))")?;
+        assert_eq!(
+            "foo(A) :-\n    bar(A),\n    baz(A).\n",
+            context.portray_clause_string(&clause)?
+        );
 
         Ok(())
     }
 
     #[test]
-    fn call_prolog_from_generated_rust_query_opener() {
+    fn succ_in_both_directions() -> PrologResult<()> {
         let engine = Engine::new();
         let activation = engine.activate();
         let context: Context<_> = activation.into();
 
-        let term = context.new_term_ref();
-        let expr = context.term_from_string("2+2").unwrap();
+        let n = context.new_term_ref();
+        n.unify(2_u64)?;
+        assert_eq!(3, context.succ(&n)?.get::<u64>()?);
+
+        let n = context.new_term_ref();
+        assert!(context.succ(&n).unwrap_err().is_exception());
+        context.clear_exception();
+
+        let [n, successor] = context.new_term_refs();
+        n.unify(2_u64)?;
+        context.succ_terms(&n, &successor)?;
+        assert_eq!(3, successor.get::<u64>()?);
+
+        let [n, successor] = context.new_term_refs();
+        successor.unify(3_u64)?;
+        context.succ_terms(&n, &successor)?;
+        assert_eq!(2, n.get::<u64>()?);
+
+        let [n, successor] = context.new_term_refs();
+        successor.unify(0_u64)?;
+        assert!(context.succ_terms(&n, &successor).unwrap_err().is_failure());
+        assert!(n.is_var());
+
+        let [n, successor] = context.new_term_refs();
+        n.unify(-1_i64)?;
+        assert!(context
+            .succ_terms(&n, &successor)
+            .unwrap_err()
+            .is_exception());
+        context.clear_exception();
 
-        let q = prolog_arithmetic(&context, &term, &expr);
-        assert!(q.next_solution().is_ok());
-        assert_eq!(4, term.get::<u64>().unwrap());
+        Ok(())
     }
 
     #[test]
-    fn iterate_over_term_list() {
+    fn length_of_lists() -> PrologResult<()> {
         let engine = Engine::new();
         let activation = engine.activate();
         let context: Context<_> = activation.into();
 
-        let list = context.term_from_string("[5, foo, \"bar\"]").unwrap();
+        let term = context.term_from_string("[a, b, c]")?;
+        assert_eq!(Some(3), context.list_length(&term));
 
-        let mut iter = context.term_list_iter(&list);
-        let first = iter.next().unwrap();
-        let second = iter.next().unwrap();
-        let third = iter.next().unwrap();
-        assert!(iter.next().is_none());
+        let term = context.term_from_string("[]")?;
+        assert_eq!(Some(0), context.list_length(&term));
 
-        assert_eq!(5, first.get::<u64>().unwrap());
-        assert_eq!(Atom::new("foo"), second.get::<Atom>().unwrap());
-        assert_eq!("bar", third.get::<String>().unwrap());
+        let term = context.term_from_string("[a, b | _]")?;
+        assert_eq!(None, context.list_length(&term));
+
+        let term = context.term_from_string("(L = [a, b | L])")?;
+        context.call_term_once(&term)?;
+        let [list, _] = context.compound_terms(&term)?;
+        assert_eq!(None, context.list_length(&list));
+
+        let term = context.term_from_string("foo")?;
+        assert_eq!(None, context.list_length(&term));
+
+        Ok(())
     }
 
     #[test]
-    fn iterate_over_term_that_is_not_a_list() {
+    fn retractall_and_abolish() -> PrologResult<()> {
         let engine = Engine::new();
         let activation = engine.activate();
         let context: Context<_> = activation.into();
 
-        let list = context.term_from_string("foo(bar, baz)").unwrap();
+        for i in 0..3_u64 {
+            let fact = term! {context: retract_test(#i)}?;
+            context.call_once(pred!("assertz/1"), [&fact])?;
+        }
+        let head = term! {context: retract_test(_)}?;
+        assert_eq!(3, context.count(&head)?);
 
-        let mut iter = context.term_list_iter(&list);
-        assert!(iter.next().is_none());
+        context.retractall(&head)?;
+        assert_eq!(0, context.count(&head)?);
+
+        let predicate = Predicate::new(Functor::new("retract_test", 1), Module::new("user"));
+        context.abolish(&predicate)?;
+        let indicator = context.term_from_string("retract_test/1")?;
+        assert!(!context.call_once_bool(pred!("current_predicate/1"), [&indicator])?);
+
+        Ok(())
     }
 
     #[test]
-    fn loop_over_term_list() {
+    fn capture_output_as_bytes() -> PrologResult<()> {
         let engine = Engine::new();
         let activation = engine.activate();
         let context: Context<_> = activation.into();
 
-        let list = context.term_from_string("[42, 42, 42, 42, 42]").unwrap();
+        let goal = context.term_from_string("format(\"~c~c~c\", [0, 200, 255])")?;
+        assert_eq!(
+            (true, vec![0, 200, 255]),
+            context.call_capturing_codes(&goal)?
+        );
 
-        let mut count = 0;
-        for term in context.term_list_iter(&list) {
-            count += 1;
-            assert_eq!(42, term.get::<u64>().unwrap());
-        }
+        let goal = context.term_from_string("(write(foo), fail)")?;
+        assert_eq!((false, Vec::new()), context.call_capturing_codes(&goal)?);
 
-        assert_eq!(5, count);
+        Ok(())
     }
 
     #[test]
-    fn term_list_to_array() {
+    fn char_conversion_when_parsing() -> PrologResult<()> {
         let engine = Engine::new();
         let activation = engine.activate();
         let context: Context<_> = activation.into();
 
-        let list = context.term_from_string("[5, foo, \"bar\"]").unwrap();
+        context.set_char_conversion('&', '+')?;
+        context.enable_char_conversion(true)?;
+        let term = context.term_from_string("1&2")?;
+        let quoted = context.term_from_string("'1&2'")?;
+        context.enable_char_conversion(false)?;
+        context.set_char_conversion('&', '&')?;
 
-        let terms: [Term; 3] = context.term_list_array(&list);
-        assert_eq!(5, terms[0].get::<u64>().unwrap());
-        assert_eq!(Atom::new("foo"), terms[1].get::<Atom>().unwrap());
-        assert_eq!("bar", terms[2].get::<String>().unwrap());
+        assert_eq!("1+2", context.string_from_term(&term)?);
+        assert_eq!("1&2", context.string_from_term(&quoted)?);
+        assert!(context.term_from_string("1&2").is_err());
+        context.clear_exception();
+
+        Ok(())
     }
 
     #[test]
-    fn term_list_to_array_large() {
+    fn fast_serialize_roundtrip_across_engines() -> PrologResult<()> {
+        let bytes = {
+            let engine = Engine::new();
+            let activation = engine.activate();
+            let context: Context<_> = activation.into();
+
+            let term = context.term_from_string("foo(X, [1, 2.5, \"bar\"], baz{a: X, b: 'Q'})")?;
+            context.fast_serialize(&term)?
+        };
+
         let engine = Engine::new();
         let activation = engine.activate();
         let context: Context<_> = activation.into();
 
-        let list = context.term_from_string("[5, foo, \"bar\"]").unwrap();
+        let term = unsafe { context.fast_deserialize(&bytes)? };
+        let expected = context.term_from_string("foo(X, [1, 2.5, \"bar\"], baz{a: X, b: 'Q'})")?;
+        let [t1, t2] = context.new_term_refs();
+        t1.unify(&term)?;
+        t2.unify(&expected)?;
+        assert!(context.call_once_bool(pred!("=@=/2"), [&t1, &t2])?);
 
-        let terms: [Term; 4] = context.term_list_array(&list);
-        assert_eq!(5, terms[0].get::<u64>().unwrap());
-        assert_eq!(Atom::new("foo"), terms[1].get::<Atom>().unwrap());
-        assert_eq!("bar", terms[2].get::<String>().unwrap());
-        assert!(terms[3].is_var());
+        Ok(())
     }
 
     #[test]
-    fn term_list_to_array_small() {
+    fn unify_list_prefix_and_close() -> PrologResult<()> {
         let engine = Engine::new();
         let activation = engine.activate();
         let context: Context<_> = activation.into();
 
-        let list = context.term_from_string("[5, foo, \"bar\"]").unwrap();
+        let list = context.new_term_ref();
+        let [a, b, c] = context.new_term_refs();
+        a.unify(1_u64)?;
+        b.unify(2_u64)?;
+        c.unify(3_u64)?;
+
+        let tail = context.unify_list_prefix(&list, &[&a, &b])?;
+        assert!(tail.is_var());
+        let tail = context.unify_list_prefix(&tail, &[&c])?;
+        tail.unify(Nil)?;
+        assert_eq!(vec![1, 2, 3], list.get::<Vec<u64>>()?);
+
+        let term = context.term_from_string("foo(X, [X,5|_])")?;
+        let [x, list] = context.compound_terms(&term)?;
+        assert!(context
+            .unify_list_prefix(&list, &[&a, &b])
+            .unwrap_err()
+            .is_failure());
+        assert!(x.is_var());
 
-        let terms: [Term; 2] = context.term_list_array(&list);
-        assert_eq!(5, terms[0].get::<u64>().unwrap());
-        assert_eq!(Atom::new("foo"), terms[1].get::<Atom>().unwrap());
+        Ok(())
+    }
+
+    predicates! {
+        semidet fn parse_integer_or_raise(context, text, number) {
+            let text: String = text.get()?;
+            let n: i64 = text.parse().into_prolog(context)?;
+
+            number.unify(n)
+        }
     }
 
     #[test]
-    fn term_list_to_vec() {
+    fn raise_rust_error_from_foreign_predicate() -> PrologResult<()> {
         let engine = Engine::new();
         let activation = engine.activate();
         let context: Context<_> = activation.into();
 
-        let list = context.term_from_string("[5, foo, \"bar\"]").unwrap();
-        let terms = context.term_list_vec(&list);
-        assert_eq!(3, terms.len());
-        assert_eq!(5, terms[0].get::<u64>().unwrap());
-        assert_eq!(Atom::new("foo"), terms[1].get::<Atom>().unwrap());
-        assert_eq!("bar", terms[2].get::<String>().unwrap());
+        assert!(register_parse_integer_or_raise());
+
+        let goal = context.term_from_string("parse_integer_or_raise(\"42\", 42)")?;
+        context.call_term_once(&goal)?;
+
+        let goal = context.term_from_string(
+            "catch(parse_integer_or_raise(\"forty-two\", _), error(rust_error(Msg), _), true)",
+        )?;
+        context.call_term_once(&goal)?;
+        let [_, error, _] = context.compound_terms(&goal)?;
+        let [formal, _] = context.compound_terms(&error)?;
+        let [msg] = context.compound_terms(&formal)?;
+        assert_eq!("invalid digit found in string", msg.get::<String>()?);
+
+        Ok(())
     }
 
     #[test]
-    fn term_compound_to_array() {
+    fn map_entries_from_dict_pairs_and_assoc() -> PrologResult<()> {
         let engine = Engine::new();
         let activation = engine.activate();
         let context: Context<_> = activation.into();
 
-        let compound = context.term_from_string("moo(5, foo, \"bar\")").unwrap();
-        let terms: [Term; 3] = context.compound_terms(&compound).unwrap();
-        assert_eq!(5, terms[0].get::<u64>().unwrap());
-        assert_eq!(Atom::new("foo"), terms[1].get::<Atom>().unwrap());
-        assert_eq!("bar", terms[2].get::<String>().unwrap());
+        let dict = context.term_from_string("_{a: 1, b: \"two\"}")?;
+        let pairs = context.term_from_string("[a-1, b-\"two\"]")?;
+        let assoc = context.new_term_ref();
+        context.call_once(pred!("list_to_assoc/2"), [&pairs, &assoc])?;
+
+        for map in [&dict, &pairs, &assoc] {
+            let entries: Vec<(String, String)> = context
+                .map_entries(map)?
+                .iter()
+                .map(|(k, v)| Ok((context.string_from_term(k)?, context.string_from_term(v)?)))
+                .collect::<PrologResult<_>>()?;
+            assert_eq!(
+                vec![
+                    ("a".to_string(), "1".to_string()),
+                    ("b".to_string(), "\"two\"".to_string())
+                ],
+                entries
+            );
+        }
+
+        let empty = context.term_from_string("t")?;
+        assert!(context.map_entries(&empty)?.is_empty());
+
+        let other = context.term_from_string("foo(a, 1)")?;
+        assert!(context.map_entries(&other).unwrap_err().is_failure());
+
+        Ok(())
     }
 
     #[test]
-    fn term_compound_to_wrong_size_array() {
+    fn profile_block_counts_inferences() -> PrologResult<()> {
         let engine = Engine::new();
         let activation = engine.activate();
         let context: Context<_> = activation.into();
 
-        let compound = context.term_from_string("moo(5, foo, \"bar\")").unwrap();
-        let terms: Option<[Term; 4]> = attempt_opt(context.compound_terms(&compound)).unwrap();
-        assert!(terms.is_none());
+        let goal = context.term_from_string("forall(between(1, 1000, X), X > 0)")?;
+        let (result, delta) = context.profile_block(|| context.call_term_once(&goal))?;
+        result?;
+        assert!(delta.inferences >= 1000);
+        assert!(delta.cpu_time >= 0.0);
+
+        Ok(())
     }
 
     #[test]
-    fn term_compound_to_vec() {
+    fn read_clause_with_singletons() -> PrologResult<()> {
         let engine = Engine::new();
         let activation = engine.activate();
         let context: Context<_> = activation.into();
 
-        let compound = context.term_from_string("moo(5, foo, \"bar\")").unwrap();
-        let terms = context.compound_terms_vec(&compound).unwrap();
-        assert_eq!(3, terms.len());
-        assert_eq!(5, terms[0].get::<u64>().unwrap());
-        assert_eq!(Atom::new("foo"), terms[1].get::<Atom>().unwrap());
-        assert_eq!("bar", terms[2].get::<String>().unwrap());
+        let [text, stream] = context.new_term_refs();
+        text.unify("foo(X, Y, _Z) :- bar(Y). baz(A) :- qux(A).")?;
+        context.call_once(pred!("open_string/2"), [&text, &stream])?;
+
+        let foo = context.read_clause(&stream)?.unwrap();
+        assert_eq!(vec!["X".to_string()], foo.singletons);
+        assert_eq!(Functor::new(":-", 2), foo.clause.get::<Functor>()?);
+        assert_eq!(Functor::new("foo", 3), foo.clause.get_arg::<Functor>(1)?);
+
+        let baz = context.read_clause(&stream)?.unwrap();
+        assert!(baz.singletons.is_empty());
+
+        assert!(context.read_clause(&stream)?.is_none());
+        context.call_once(pred!("close/1"), [&stream])?;
+
+        Ok(())
+    }
+
+    predicates! {
+        semidet fn semidet_double(_context, input, output) {
+            let n: i64 = input.get()?;
+
+            output.unify(n * 2)
+        }
+
+        det fn det_double(_context, input, output) {
+            let n: i64 = input.get()?;
+
+            output.unify(n * 2)
+        }
     }
 
     #[test]
-    fn term_compound_to_sized_vec() {
+    fn semidet_fails_and_det_throws() -> PrologResult<()> {
         let engine = Engine::new();
         let activation = engine.activate();
         let context: Context<_> = activation.into();
 
-        let compound = context.term_from_string("moo(5, foo, \"bar\")").unwrap();
-        let terms = context.compound_terms_vec_sized(&compound, 3).unwrap();
-        assert_eq!(3, terms.len());
-        assert_eq!(5, terms[0].get::<u64>().unwrap());
-        assert_eq!(Atom::new("foo"), terms[1].get::<Atom>().unwrap());
-        assert_eq!("bar", terms[2].get::<String>().unwrap());
+        assert!(register_semidet_double());
+        assert!(register_det_double());
+
+        let [input, output] = context.new_term_refs();
+        input.unify(21_i64)?;
+        context.call_once(pred!("det_double/2"), [&input, &output])?;
+        assert_eq!(42, output.get::<i64>()?);
+
+        let [input, output] = context.new_term_refs();
+        input.unify(Atom::new("foo"))?;
+        assert!(!context.call_once_bool(pred!("semidet_double/2"), [&input, &output])?);
+
+        let goal = context.term_from_string(
+            "catch(det_double(foo, _), error(determinism_error(P, det, fail, _), _), true)",
+        )?;
+        context.call_term_once(&goal)?;
+        let [_, error, _] = context.compound_terms(&goal)?;
+        let [formal, _] = context.compound_terms(&error)?;
+        let [indicator, _, _, _] = context.compound_terms(&formal)?;
+        assert_eq!("det_double/2", context.string_from_term(&indicator)?);
+
+        Ok(())
     }
 
     #[test]
-    fn term_compound_to_wrong_size_vec() {
+    fn string_case_conversion_matches_prolog() -> PrologResult<()> {
         let engine = Engine::new();
         let activation = engine.activate();
         let context: Context<_> = activation.into();
 
-        let compound = context.term_from_string("moo(5, foo, \"bar\")").unwrap();
-        let terms = attempt_opt(context.compound_terms_vec_sized(&compound, 4)).unwrap();
-        assert!(terms.is_none());
+        assert_eq!("hello world", context.string_lower("Hello World")?);
+        assert_eq!("HELLO WORLD", context.string_upper("Hello World")?);
+
+        let text = "ÀÉÎ Grüße ΣΑΣ";
+        let [input, lower, upper] = context.new_term_refs();
+        input.unify(text)?;
+        context.call_once(pred!("string_lower/2"), [&input, &lower])?;
+        assert_eq!(lower.get::<String>()?, context.string_lower(text)?);
+        context.call_once(pred!("string_upper/2"), [&input, &upper])?;
+        assert_eq!(upper.get::<String>()?, context.string_upper(text)?);
+
+        assert_eq!(
+            Ordering::Less,
+            context.collation_compare("apple", "banana")?
+        );
+        assert_eq!(
+            Ordering::Greater,
+            context.collation_compare("banana", "apple")?
+        );
+        assert_eq!(
+            Ordering::Equal,
+            context.collation_compare("apple", "apple")?
+        );
+
+        Ok(())
     }
 
     #[test]
-    fn term_compound_not_a_compound() {
+    fn engine_flags_are_local_to_engine() -> PrologResult<()> {
+        let engine1 = Engine::new();
+        let engine2 = Engine::new();
+
+        {
+            let activation = engine1.activate();
+            let context: Context<_> = activation.into();
+            context.set_engine_flag("occurs_check", Atom::new("true"))?;
+            assert_eq!(
+                Some(Atom::new("true")),
+                context.get_engine_flag::<Atom>("occurs_check")?
+            );
+        }
+
+        {
+            let activation = engine2.activate();
+            let context: Context<_> = activation.into();
+            assert_eq!(
+                Some(Atom::new("false")),
+                context.get_engine_flag::<Atom>("occurs_check")?
+            );
+        }
+
+        let activation = engine1.activate();
+        let context: Context<_> = activation.into();
+        assert_eq!(
+            Some(Atom::new("true")),
+            context.get_engine_flag::<Atom>("occurs_check")?
+        );
+        assert!(context.get_engine_flag::<Atom>("no_such_flag")?.is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn predicate_defined_in_module() -> PrologResult<()> {
         let engine = Engine::new();
         let activation = engine.activate();
         let context: Context<_> = activation.into();
 
-        let compound = context.term_from_string("\"moo\"").unwrap();
-        let terms: Option<[Term; 4]> = attempt_opt(context.compound_terms(&compound)).unwrap();
-        assert!(terms.is_none());
+        let module = context.new_temporary_module()?;
+        let other = context.new_temporary_module()?;
+        let clause = term! {context: module_local_fact(42)}?;
+        context
+            .open_with_module(pred!("assertz/1"), Some(module.module()), [&clause])
+            .next_solution()?;
+
+        assert!(context.predicate_defined_in(module.module(), "module_local_fact", 1));
+        assert!(!context.predicate_defined_in(module.module(), "module_local_fact", 2));
+        assert!(!context.predicate_defined_in(other.module(), "module_local_fact", 1));
+        assert!(!context.predicate_defined_in(Module::new("user"), "module_local_fact", 1));
+
+        Ok(())
     }
 }
@@ -398,6 +398,34 @@ impl<'a, T: QueryableContextType> Context<'a, T> {
             index: 0,
         }
     }
+
+    /// Get a value nested in dictionaries by following a path of keys.
+    ///
+    /// Each key is looked up in the dictionary found by the previous
+    /// key, starting with the given dictionary. This avoids
+    /// deserializing the intermediate dictionaries. If at some point
+    /// a key is missing or the value is not a dictionary, `None` is
+    /// returned.
+    ///
+    /// The terms are created in the current context.
+    pub fn dict_get_path<K: IntoKey, I: IntoIterator<Item = K>>(
+        &self,
+        dict: &Term,
+        path: I,
+    ) -> PrologResult<Option<Term>> {
+        let mut current = self.new_term_ref();
+        current.unify(dict)?;
+        for key in path {
+            let value = self.new_term_ref();
+            if !attempt(current.get_dict_key_term(key, &value))? {
+                return Ok(None);
+            }
+
+            current = value;
+        }
+
+        Ok(Some(current))
+    }
 }
 
 /// An iterator over the entries of a dict term.
@@ -700,4 +728,26 @@ mod tests {
 
         assert!(result.is_failure());
     }
+
+    #[test]
+    fn get_nested_dict_value_by_path() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let dict =
+            context.term_from_string("_{config: _{db: _{host: \"localhost\", port: 5432}}}")?;
+
+        let port = context.dict_get_path(&dict, &["config", "db", "port"])?;
+        assert_eq!(Some(5432), port.map(|port| port.get::<u64>()).transpose()?);
+
+        assert!(context
+            .dict_get_path(&dict, &["config", "cache", "port"])?
+            .is_none());
+        assert!(context
+            .dict_get_path(&dict, &["config", "db", "port", "number"])?
+            .is_none());
+
+        Ok(())
+    }
 }
@@ -9,10 +9,23 @@
 //! When using swipl-rs to implement foreign predicates as part of a
 //! loadable module, you generally do not have to worry about prolog
 //! engines, unless you're spawning extra threads.
-use std::sync::atomic;
+use std::cell::Cell;
+use std::io::IsTerminal;
+use std::os::raw::{c_int, c_void};
+use std::panic::AssertUnwindSafe;
+use std::path::PathBuf;
+use std::sync::{atomic, Arc, Mutex, RwLock};
 
+use crate::atom::*;
+use crate::context::*;
 use crate::fli::*;
+use crate::functor::*;
 use crate::init::*;
+use crate::result::*;
+use crate::term::*;
+use crate::text::*;
+use lazy_static::*;
+use swipl_macros::{pred, predicates, term};
 
 /// A Prolog engine.
 ///
@@ -23,8 +36,18 @@ use crate::init::*;
 pub struct Engine {
     engine_ptr: PL_engine_t,
     active: atomic::AtomicBool,
+    /// The prolog thread id of the thread this engine is active on.
+    ///
+    /// This is `NO_THREAD` while the engine is inactive, and
+    /// `ACTIVATING` while it is being activated. Claiming the engine
+    /// is done on this field, so the thread id is always stored
+    /// before `active` is published.
+    thread_id: atomic::AtomicI32,
 }
 
+const NO_THREAD: i32 = -1;
+const ACTIVATING: i32 = -2;
+
 unsafe impl Send for Engine {}
 unsafe impl Sync for Engine {}
 
@@ -87,6 +110,7 @@ impl Engine {
         Engine {
             engine_ptr,
             active: atomic::AtomicBool::new(false),
+            thread_id: atomic::AtomicI32::new(NO_THREAD),
         }
     }
 
@@ -106,6 +130,7 @@ impl Engine {
         Engine {
             engine_ptr: current_engine_ptr(),
             active: atomic::AtomicBool::new(false),
+            thread_id: atomic::AtomicI32::new(NO_THREAD),
         }
     }
 
@@ -125,19 +150,33 @@ impl Engine {
         is_engine_active(self.engine_ptr)
     }
 
-    pub(crate) unsafe fn set_activated(&self) -> EngineActivation {
+    /// Claim this engine for activation, panicking if it is already claimed.
+    fn claim(&self) {
         if self
-            .active
+            .thread_id
             .compare_exchange(
-                false,
-                true,
-                atomic::Ordering::Relaxed,
+                NO_THREAD,
+                ACTIVATING,
+                atomic::Ordering::Acquire,
                 atomic::Ordering::Relaxed,
             )
             .is_err()
         {
             panic!("engine already activated");
         }
+    }
+
+    /// Mark this claimed engine as active on the given prolog thread.
+    fn publish_activation(&self, thread_id: i32) {
+        self.thread_id.store(thread_id, atomic::Ordering::Relaxed);
+        // the release orders the thread id store before the engine
+        // is seen as active.
+        self.active.store(true, atomic::Ordering::Release);
+    }
+
+    pub(crate) unsafe fn set_activated(&self) -> EngineActivation {
+        self.claim();
+        self.publish_activation(PL_thread_self());
 
         EngineActivation {
             engine: self,
@@ -155,27 +194,21 @@ impl Engine {
             panic!("tried to activate engine on a thread that already has an active engine");
         }
 
-        if self
-            .active
-            .compare_exchange(
-                false,
-                true,
-                atomic::Ordering::Relaxed,
-                atomic::Ordering::Relaxed,
-            )
-            .is_err()
-        {
-            panic!("engine already activated");
-        }
+        self.claim();
 
         // unsafe justification: swipl should have been initialized.
         let result = unsafe { PL_set_engine(self.engine_ptr, std::ptr::null_mut()) };
 
         match result as u32 {
-            PL_ENGINE_SET => EngineActivation {
-                engine: self,
-                _x: Default::default(),
-            },
+            PL_ENGINE_SET => {
+                // unsafe justification: this engine was just set as the engine of this thread.
+                self.publish_activation(unsafe { PL_thread_self() });
+
+                EngineActivation {
+                    engine: self,
+                    _x: Default::default(),
+                }
+            }
             PL_ENGINE_INUSE => panic!("engine already activated"),
             PL_ENGINE_INVAL => panic!("engine handle not recognized by swipl"),
             _ => panic!("unknown result from PL_set_engine"),
@@ -183,6 +216,146 @@ impl Engine {
     }
 }
 
+impl<'a, T: QueryableContextType> Context<'a, T> {
+    /// Inject a goal into another engine.
+    ///
+    /// This uses the prolog predicate `thread_signal/2`. The goal is
+    /// run by the given engine at the next point where it is safe to
+    /// do so, interrupting whatever that engine is currently doing. If
+    /// the goal throws an exception, such as with `throw(cancelled)`,
+    /// the query running on that engine is aborted with that
+    /// exception. This makes it possible to cancel long-running or
+    /// divergent queries from another thread.
+    ///
+    /// The engine has to be active on some thread. If it is not, this
+    /// fails.
+    pub fn thread_signal(&self, engine: &Engine, goal: &Term) -> PrologResult<()> {
+        // the acquire pairs with the release in publish_activation, so
+        // the thread id read below belongs to this activation.
+        if !engine.active.load(atomic::Ordering::Acquire) {
+            return Err(PrologError::Failure);
+        }
+
+        let frame = self.open_frame();
+        let thread_id = frame.new_term_ref();
+        thread_id.unify(engine.thread_id.load(atomic::Ordering::Relaxed) as i64)?;
+        frame.call_once(pred!("thread_signal/2"), [&thread_id, goal])?;
+        frame.close();
+
+        Ok(())
+    }
+}
+
+thread_local! {
+    static TTY_RAW_CLOSURE: Cell<*mut c_void> = Cell::new(std::ptr::null_mut());
+}
+
+/// The closure that `$swipl_rs_call_closure/0` will call on this
+/// thread, for the duration of a [Context::with_tty_raw] call.
+struct TtyRawClosureState {
+    previous: *mut c_void,
+}
+
+impl TtyRawClosureState {
+    fn start(closure: *mut c_void) -> Self {
+        let previous = TTY_RAW_CLOSURE.with(|c| c.replace(closure));
+
+        Self { previous }
+    }
+
+    fn take() -> *mut c_void {
+        TTY_RAW_CLOSURE.with(|c| c.replace(std::ptr::null_mut()))
+    }
+}
+
+impl Drop for TtyRawClosureState {
+    fn drop(&mut self) {
+        TTY_RAW_CLOSURE.with(|c| c.set(self.previous));
+    }
+}
+
+predicates! {
+    #[name("$swipl_rs_call_closure")]
+    semidet fn swipl_rs_call_closure(_context) {
+        // the closure is taken out of the slot, so it can only be
+        // called once, and prolog code calling this predicate outside
+        // of with_tty_raw just fails.
+        let closure = TtyRawClosureState::take() as *mut &mut dyn FnMut();
+        if closure.is_null() {
+            return Err(PrologError::Failure);
+        }
+
+        // unsafe justification: the pointer was put in the slot by
+        // with_tty_raw on this thread, which keeps the closure alive
+        // until the goal calling this predicate returns.
+        unsafe { (*closure)() };
+
+        Ok(())
+    }
+}
+
+/// Returns true if the standard input of this process is a terminal.
+///
+/// This can be used to decide whether to run an interactive shell,
+/// or to read goals from a pipe instead.
+pub fn stdin_is_tty() -> bool {
+    std::io::stdin().is_terminal()
+}
+
+impl<'a, T: QueryableContextType> Context<'a, T> {
+    /// Run the given function with the terminal in raw mode.
+    ///
+    /// This uses the prolog predicate `with_tty_raw/1`, which switches
+    /// the terminal to unbuffered input without echo, so that single
+    /// key presses can be read, for example with
+    /// `get_single_char/1`. The terminal mode is restored afterwards.
+    /// If prolog does not control a terminal, the function is just
+    /// called.
+    ///
+    /// As the function is called from prolog, a panic inside it is
+    /// raised as a prolog exception. In that case, or if
+    /// `with_tty_raw/1` itself raises an exception, for example
+    /// because the terminal mode could not be changed, this returns
+    /// `Err(PrologError::Exception)` and the exception is left in the
+    /// context.
+    pub fn with_tty_raw<R>(&self, f: impl FnOnce() -> R) -> PrologResult<R> {
+        register_swipl_rs_call_closure();
+
+        let mut f = Some(f);
+        let mut result = None;
+        let mut call = || result = Some((f.take().unwrap())());
+        let mut call_ref: &mut dyn FnMut() = &mut call;
+
+        let _state =
+            TtyRawClosureState::start(&mut call_ref as *mut &mut dyn FnMut() as *mut c_void);
+
+        let frame = self.open_frame();
+        let goal = frame.new_term_ref();
+        goal.unify(Atomable::from("$swipl_rs_call_closure"))?;
+        frame.call_once(pred!("with_tty_raw/1"), [&goal])?;
+        frame.close();
+
+        Ok(result.expect("closure was not called by with_tty_raw/1"))
+    }
+}
+
+/// Run the given function with the given engine activated on this thread.
+///
+/// The engine is activated for the duration of the call, and
+/// deactivated again afterwards, even if the function panics. This
+/// allows frameworks that manage their own threads to pin work for
+/// an engine onto whatever thread they're running on.
+///
+/// Like [Engine::activate], this will panic if the engine is already
+/// active, either on this or on another thread, or if another engine
+/// is already active on this thread.
+pub fn run_on_engine<R>(engine: &Engine, f: impl FnOnce(&Context<ActivatedEngine>) -> R) -> R {
+    let activation = engine.activate();
+    let context: Context<_> = activation.into();
+
+    f(&context)
+}
+
 /// Checks if the given engine pointer is the engine that is currently active on this thread.
 ///
 /// This will panic is SWI-Prolog was not yet initialized.
@@ -193,20 +366,399 @@ pub fn is_engine_active(engine: PL_engine_t) -> bool {
     current == engine
 }
 
+/// Returns true if some engine is active on this thread.
+///
+/// Unlike [assert_some_engine_is_active], this does not panic. This
+/// allows code which may be called both from prolog and from plain
+/// rust to decide whether it needs to activate an engine
+/// itself. Before SWI-Prolog is initialized, this returns false.
+pub fn has_active_engine() -> bool {
+    Engine::some_engine_active()
+}
+
 /// Panic if no engine is active on this thread.
 pub fn assert_some_engine_is_active() {
-    if !Engine::some_engine_active() {
+    if !has_active_engine() {
         panic!("No SWI-Prolog engine is active");
     }
 }
 
+type HaltHook = Box<dyn Fn(i32) + Send + Sync>;
+
+unsafe extern "C" fn call_halt_hook(status: c_int, closure: *mut c_void) -> c_int {
+    let hook = &*(closure as *const HaltHook);
+    // panics must not unwind into prolog
+    let _ = std::panic::catch_unwind(AssertUnwindSafe(|| hook(status as i32)));
+
+    0
+}
+
+/// Register a callback to be called when SWI-Prolog halts.
+///
+/// The callback receives the exit status. This allows an embedding
+/// application to be notified when prolog code calls `halt/0` or
+/// `halt/1`, for example to flush its own state or log the event.
+/// Callbacks are called in reverse order of registration.
+///
+/// Note that this does not prevent the halt. When `halt/1` is called
+/// from an engine that is not the main engine, SWI-Prolog first
+/// raises an `unwind(halt(Status))` exception, which shows up as a
+/// [PrologError::Halt] in the calling query. Clearing that exception
+/// instead of letting it propagate keeps the process alive.
+pub fn on_halt<F: Fn(i32) + Send + Sync + 'static>(callback: F) {
+    let hook: Box<HaltHook> = Box::new(Box::new(callback));
+    unsafe { PL_on_halt(Some(call_halt_hook), Box::into_raw(hook) as *mut c_void) };
+}
+
+/// The kind of a prolog message, as passed to `print_message/2`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MessageKind {
+    Error,
+    Warning,
+    Informational,
+    Silent,
+    Help,
+    /// A debug message, with its topic.
+    Debug(String),
+    /// Any other kind of message, such as `banner` or `query`.
+    Other(String),
+}
+
+impl MessageKind {
+    fn from_term<C: QueryableContextType>(context: &Context<C>, term: &Term) -> PrologResult<Self> {
+        if let Some(name) = term.get_atom_name(|name| name.map(|name| name.to_owned()))? {
+            return Ok(match name.as_str() {
+                "error" => MessageKind::Error,
+                "warning" => MessageKind::Warning,
+                "informational" => MessageKind::Informational,
+                "silent" => MessageKind::Silent,
+                "help" => MessageKind::Help,
+                _ => MessageKind::Other(name),
+            });
+        }
+
+        if attempt_opt(term.get::<Functor>())? == Some(Functor::new("debug", 1)) {
+            let [topic] = context.compound_terms(term)?;
+            Ok(MessageKind::Debug(context.string_from_term(&topic)?))
+        } else {
+            Ok(MessageKind::Other(context.string_from_term(term)?))
+        }
+    }
+}
+
+type MessageHook = Arc<dyn Fn(MessageKind, String) + Send + Sync>;
+
+lazy_static! {
+    static ref MESSAGE_HOOK: RwLock<Option<MessageHook>> = RwLock::new(None);
+    static ref MESSAGE_HOOK_INSTALLED: Mutex<bool> = Mutex::new(false);
+}
+
+predicates! {
+    #[name("$swipl_rs_message_hook")]
+    semidet fn swipl_rs_message_hook(context, kind, lines) {
+        // the terms used to format the message are discarded along
+        // with the frame, so they don't pile up for every message.
+        let frame = context.open_frame();
+        let kind = MessageKind::from_term(&frame, kind)?;
+
+        let output = frame.new_term_ref();
+        let prefix = Atom::new("");
+        let goal = term! {frame: with_output_to(string(#&output), print_message_lines(current_output, #&prefix, #lines))}?;
+        frame.call_once(pred!("call/1"), [&goal])?;
+        let message: String = output.get()?;
+        let message = message.trim_end_matches('\n').to_string();
+        frame.discard();
+
+        // the lock is not held while calling the hook, as it may
+        // print messages itself, or set another hook.
+        let hook = MESSAGE_HOOK.read().unwrap().clone();
+        if let Some(hook) = hook {
+            // panics must not unwind into prolog
+            let _ = std::panic::catch_unwind(AssertUnwindSafe(|| hook(kind, message)));
+        }
+
+        Ok(())
+    }
+}
+
+/// Register a callback that is called for every message prolog prints.
+///
+/// This installs a clause for `user:message_hook/3`, which passes the
+/// kind of each message along with its text to the callback. This
+/// covers errors, warnings and informational messages alike, allowing
+/// prolog diagnostics to be routed into a logging framework.
+///
+/// The hook does not suppress messages, so prolog still prints them
+/// as usual. Setting a message hook replaces any previously set hook.
+pub fn set_message_hook<
+    C: QueryableContextType,
+    F: Fn(MessageKind, String) + Send + Sync + 'static,
+>(
+    context: &Context<C>,
+    callback: F,
+) -> PrologResult<()> {
+    *MESSAGE_HOOK.write().unwrap() = Some(Arc::new(callback));
+
+    // if installing the clause fails, it is tried again on the next call.
+    let mut installed = MESSAGE_HOOK_INSTALLED.lock().unwrap();
+    if !*installed {
+        register_swipl_rs_message_hook();
+        install_message_hook(context)?;
+        *installed = true;
+    }
+
+    Ok(())
+}
+
+fn install_message_hook<C: QueryableContextType>(context: &Context<C>) -> PrologResult<()> {
+    let frame = context.open_frame();
+    let [head, kind, lines, call, body, clause] = frame.new_term_refs();
+
+    // message_hook(_, Kind, Lines) :-
+    //     '$swipl_rs_message_hook'(Kind, Lines),
+    //     fail.
+    head.unify(Functor::new("message_hook", 3))?;
+    head.unify_arg(2, &kind)?;
+    head.unify_arg(3, &lines)?;
+    call.unify(Functor::new("$swipl_rs_message_hook", 2))?;
+    call.unify_arg(1, &kind)?;
+    call.unify_arg(2, &lines)?;
+    body.unify(Functor::new(",", 2))?;
+    body.unify_arg(1, &call)?;
+    body.unify_arg(2, Atom::new("fail"))?;
+    clause.unify(Functor::new(":-", 2))?;
+    clause.unify_arg(1, &head)?;
+    clause.unify_arg(2, &body)?;
+
+    frame.call_once(pred!("asserta/1"), [&clause])?;
+    frame.close();
+
+    Ok(())
+}
+
+type GoalExpansion = Arc<
+    dyn for<'a> Fn(&'a GenericQueryableContext<'a>, &Term<'a>) -> PrologResult<Option<Term<'a>>>
+        + Send
+        + Sync,
+>;
+
+lazy_static! {
+    static ref GOAL_EXPANSIONS: RwLock<Vec<GoalExpansion>> = RwLock::new(Vec::new());
+    static ref GOAL_EXPANSION_INSTALLED: Mutex<bool> = Mutex::new(false);
+}
+
+predicates! {
+    #[name("$swipl_rs_goal_expansion")]
+    semidet fn swipl_rs_goal_expansion(context, goal, expanded) {
+        // the lock is not held while calling the callbacks, as they
+        // may load code, which expands goals again, or register
+        // another callback.
+        let expansions = GOAL_EXPANSIONS.read().unwrap().clone();
+        for expansion in expansions.iter() {
+            let generic = context.into_generic();
+            // panics must not unwind into prolog
+            let replacement = std::panic::catch_unwind(AssertUnwindSafe(|| expansion(&generic, goal)))
+                .unwrap_or(Err(PrologError::Failure))?;
+            if let Some(replacement) = replacement {
+                return expanded.unify(&replacement);
+            }
+        }
+
+        Err(PrologError::Failure)
+    }
+}
+
+/// Register a callback that rewrites goals while code is being loaded.
+///
+/// This installs a clause for `user:goal_expansion/2`, which passes
+/// every goal prolog expands to the callback. If the callback returns
+/// a replacement term, the goal is replaced by it. If it returns
+/// `None`, the goal is left alone.
+///
+/// Registering more than one callback is allowed. They are tried in
+/// the order in which they were registered, and the first one to
+/// return a replacement wins.
+pub fn register_goal_expansion<
+    C: QueryableContextType,
+    F: for<'a> Fn(&'a GenericQueryableContext<'a>, &Term<'a>) -> PrologResult<Option<Term<'a>>>
+        + Send
+        + Sync
+        + 'static,
+>(
+    context: &Context<C>,
+    callback: F,
+) -> PrologResult<()> {
+    GOAL_EXPANSIONS.write().unwrap().push(Arc::new(callback));
+
+    // if installing the clause fails, it is tried again on the next registration.
+    let mut installed = GOAL_EXPANSION_INSTALLED.lock().unwrap();
+    if !*installed {
+        register_swipl_rs_goal_expansion();
+        install_goal_expansion(context)?;
+        *installed = true;
+    }
+
+    Ok(())
+}
+
+fn install_goal_expansion<C: QueryableContextType>(context: &Context<C>) -> PrologResult<()> {
+    let frame = context.open_frame();
+    let [head, goal, expanded, body, clause] = frame.new_term_refs();
+
+    // goal_expansion(Goal, Expanded) :-
+    //     '$swipl_rs_goal_expansion'(Goal, Expanded).
+    head.unify(Functor::new("goal_expansion", 2))?;
+    head.unify_arg(1, &goal)?;
+    head.unify_arg(2, &expanded)?;
+    body.unify(Functor::new("$swipl_rs_goal_expansion", 2))?;
+    body.unify_arg(1, &goal)?;
+    body.unify_arg(2, &expanded)?;
+    clause.unify(Functor::new(":-", 2))?;
+    clause.unify_arg(1, &head)?;
+    clause.unify_arg(2, &body)?;
+
+    frame.call_once(pred!("assertz/1"), [&clause])?;
+    frame.close();
+
+    Ok(())
+}
+
+type Portray = Arc<
+    dyn for<'a> Fn(&'a GenericQueryableContext<'a>, &Term<'a>) -> PrologResult<Option<String>>
+        + Send
+        + Sync,
+>;
+
+lazy_static! {
+    static ref PORTRAYS: RwLock<Vec<Portray>> = RwLock::new(Vec::new());
+    static ref PORTRAY_INSTALLED: Mutex<bool> = Mutex::new(false);
+}
+
+predicates! {
+    #[name("$swipl_rs_portray")]
+    semidet fn swipl_rs_portray(context, term) {
+        // the lock is not held while calling the callbacks, as they
+        // may print terms, which calls portray again, or register
+        // another callback.
+        let portrays = PORTRAYS.read().unwrap().clone();
+        for portray in portrays.iter() {
+            let generic = context.into_generic();
+            // panics must not unwind into prolog
+            let text = std::panic::catch_unwind(AssertUnwindSafe(|| portray(&generic, term)))
+                .unwrap_or(Err(PrologError::Failure))?;
+            if let Some(text) = text {
+                let mut stream = context.current_output();
+                return context.try_or_die(stream.write_string(&text));
+            }
+        }
+
+        Err(PrologError::Failure)
+    }
+}
+
+/// Register a callback that renders terms when they are printed.
+///
+/// This installs a clause for `user:portray/1`, which is consulted by
+/// `print/1` and `print_message/2` for every subterm they print. If
+/// the callback returns a text, that text is printed instead of the
+/// term. If it returns `None`, the term is printed as usual.
+///
+/// Registering more than one callback is allowed. They are tried in
+/// the order in which they were registered, and the first one to
+/// return a text wins.
+pub fn register_portray<
+    C: QueryableContextType,
+    F: for<'a> Fn(&'a GenericQueryableContext<'a>, &Term<'a>) -> PrologResult<Option<String>>
+        + Send
+        + Sync
+        + 'static,
+>(
+    context: &Context<C>,
+    callback: F,
+) -> PrologResult<()> {
+    PORTRAYS.write().unwrap().push(Arc::new(callback));
+
+    // if installing the clause fails, it is tried again on the next registration.
+    let mut installed = PORTRAY_INSTALLED.lock().unwrap();
+    if !*installed {
+        register_swipl_rs_portray();
+        install_portray(context)?;
+        *installed = true;
+    }
+
+    Ok(())
+}
+
+fn install_portray<C: QueryableContextType>(context: &Context<C>) -> PrologResult<()> {
+    let frame = context.open_frame();
+    let [head, term, body, clause] = frame.new_term_refs();
+
+    // portray(Term) :-
+    //     '$swipl_rs_portray'(Term).
+    head.unify(Functor::new("portray", 1))?;
+    head.unify_arg(1, &term)?;
+    body.unify(Functor::new("$swipl_rs_portray", 1))?;
+    body.unify_arg(1, &term)?;
+    clause.unify(Functor::new(":-", 2))?;
+    clause.unify_arg(1, &head)?;
+    clause.unify_arg(2, &body)?;
+
+    frame.call_once(pred!("assertz/1"), [&clause])?;
+    frame.close();
+
+    Ok(())
+}
+
+/// Returns the SWI-Prolog home directory.
+///
+/// This reads the prolog flag `home`, which points at the directory
+/// holding the prolog runtime and its libraries. This will return
+/// `None` if the flag is not set.
+///
+/// This will panic if no engine is active on this thread.
+pub fn prolog_home() -> Option<PathBuf> {
+    assert_some_engine_is_active();
+    let context = unsafe { unmanaged_engine_context() };
+    let home: Option<PrologText> = context.prolog_flag("home").ok()?;
+
+    home.map(|home| PathBuf::from(home.to_string()))
+}
+
+/// Returns the directories that are searched for library files.
+///
+/// These are all existing directories that the `library` file search
+/// path resolves to, in the order in which prolog searches them.
+pub fn library_directories<C: QueryableContextType>(
+    context: &Context<C>,
+) -> PrologResult<Vec<PathBuf>> {
+    let frame = context.open_frame();
+    let [dir, dirs] = frame.new_term_refs();
+    let dot = Atom::new(".");
+    let goal = term! {frame: absolute_file_name(library(#&dot), #&dir, [file_type(directory), solutions(all), file_errors(fail)])}?;
+    frame.call_once(pred!("findall/3"), [&dir, &goal, &dirs])?;
+
+    let mut result = Vec::new();
+    for term in frame.term_list_iter(&dirs) {
+        let dir: PrologText = term.get()?;
+        result.push(PathBuf::from(dir.to_string()));
+    }
+    frame.close();
+
+    Ok(result)
+}
+
 impl<'a> Drop for EngineActivation<'a> {
     fn drop(&mut self) {
         // unsafe justification: we have an engine context, so swipl was initialized. it should always be fine to set the current thread engine to nothing.
-        self.engine.active.store(false, atomic::Ordering::Release);
+        self.engine.active.store(false, atomic::Ordering::Relaxed);
         unsafe {
             PL_set_engine(std::ptr::null_mut(), std::ptr::null_mut());
         }
+        // releasing the claim last, so the engine can't be activated
+        // elsewhere before it is detached from this thread.
+        self.engine
+            .thread_id
+            .store(NO_THREAD, atomic::Ordering::Release);
     }
 }
 
@@ -237,6 +789,149 @@ mod tests {
         let _activation = engine.activate();
     }
 
+    #[test]
+    fn find_prolog_home_and_library_directories() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let home = prolog_home().unwrap();
+        assert!(home.is_dir());
+
+        let libraries = library_directories(&context)?;
+        assert!(!libraries.is_empty());
+        assert!(libraries.iter().all(|dir| dir.is_dir()));
+
+        Ok(())
+    }
+
+    #[test]
+    fn halt_exception_is_reported_as_halt() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let goal = context.term_from_string("throw(unwind(halt(3)))")?;
+        let result = context.call_term_once(&goal);
+        assert_eq!(Err(PrologError::Halt(3)), result);
+        assert!(result.unwrap_err().is_exception());
+        context.clear_exception();
+
+        let goal = context.term_from_string("throw(halt(3))")?;
+        assert_eq!(Err(PrologError::Exception), context.call_term_once(&goal));
+        context.clear_exception();
+
+        Ok(())
+    }
+
+    #[test]
+    fn halt_in_query_is_reported_as_halt() {
+        // if the halt is not turned into an exception, it exits the
+        // process, so this runs itself as a child process.
+        if std::env::var("SWIPL_RS_HALT_QUERY_TEST").is_ok() {
+            let engine = Engine::new();
+            let activation = engine.activate();
+            let context: Context<_> = activation.into();
+
+            let goal = context.term_from_string("halt(3)").unwrap();
+            let result = context.call_term_once(&goal);
+            context.clear_exception();
+            println!("halt query result: {:?}", result);
+            return;
+        }
+
+        let exe = std::env::current_exe().unwrap();
+        let output = std::process::Command::new(exe)
+            .args([
+                "engine::tests::halt_in_query_is_reported_as_halt",
+                "--exact",
+                "--nocapture",
+            ])
+            .env("SWIPL_RS_HALT_QUERY_TEST", "1")
+            .output()
+            .unwrap();
+
+        assert!(String::from_utf8_lossy(&output.stdout).contains("halt query result: Err(Halt(3))"));
+        assert!(output.status.success());
+    }
+
+    #[test]
+    fn halt_hook_is_called() {
+        // halting exits the process, so this runs itself as a child
+        // process which does the actual halt.
+        if std::env::var("SWIPL_RS_HALT_TEST").is_ok() {
+            on_halt(|status| println!("halt hook called with {}", status));
+            let engine = Engine::new();
+            let _activation = engine.activate();
+            unsafe { PL_halt(3) };
+            unreachable!("halt returned");
+        }
+
+        let exe = std::env::current_exe().unwrap();
+        let output = std::process::Command::new(exe)
+            .args(&[
+                "engine::tests::halt_hook_is_called",
+                "--exact",
+                "--nocapture",
+            ])
+            .env("SWIPL_RS_HALT_TEST", "1")
+            .output()
+            .unwrap();
+
+        assert_eq!(Some(3), output.status.code());
+        assert!(String::from_utf8_lossy(&output.stdout).contains("halt hook called with 3"));
+    }
+
+    #[test]
+    fn message_hook_receives_warnings() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        lazy_static! {
+            static ref MESSAGES: std::sync::Mutex<Vec<(MessageKind, String)>> =
+                std::sync::Mutex::new(Vec::new());
+        }
+
+        set_message_hook(&context, |kind, message| {
+            MESSAGES.lock().unwrap().push((kind, message))
+        })?;
+
+        let goal = context.term_from_string(
+            "print_message(warning, format(\"swipl-rs test warning ~w\", [42]))",
+        )?;
+        context.call_term_once(&goal)?;
+
+        assert!(MESSAGES
+            .lock()
+            .unwrap()
+            .contains(&(MessageKind::Warning, "swipl-rs test warning 42".to_string())));
+
+        let goal = context.term_from_string(
+            "debug(swipl_rs_test), print_message(debug(swipl_rs_test), format(\"swipl-rs test debug ~w\", [43]))",
+        )?;
+        context.call_term_once(&goal)?;
+
+        assert!(MESSAGES.lock().unwrap().contains(&(
+            MessageKind::Debug("swipl_rs_test".to_string()),
+            "swipl-rs test debug 43".to_string()
+        )));
+
+        Ok(())
+    }
+
+    #[test]
+    fn query_whether_engine_is_active() {
+        let engine = Engine::new();
+        assert!(!has_active_engine());
+
+        let activation = engine.activate();
+        assert!(has_active_engine());
+
+        std::mem::drop(activation);
+        assert!(!has_active_engine());
+    }
+
     #[test]
     fn switch_between_engines() {
         let engine1 = Engine::new();
@@ -245,4 +940,223 @@ mod tests {
         std::mem::drop(activation1);
         let _activation2 = engine2.activate();
     }
+
+    #[test]
+    fn goal_expansion_rewrites_loaded_code() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        register_goal_expansion(&context, |context, goal| {
+            if goal.get_atom_name(|name| name == Some("my_goal"))? {
+                let replacement = context.new_term_ref();
+                replacement.unify(Atom::new("true"))?;
+                Ok(Some(replacement))
+            } else {
+                Ok(None)
+            }
+        })?;
+
+        let path = format!("{}.pl", context.tmp_file("goal_expansion")?);
+        std::fs::write(&path, "goal_expansion_test :- my_goal.\n").unwrap();
+        let file = context.new_term_ref();
+        file.unify(Atom::new(&path))?;
+        context.call_once(pred!("consult/1"), [&file])?;
+        std::fs::remove_file(&path).unwrap();
+
+        let query = context.term_from_string("clause(goal_expansion_test, true)")?;
+        assert!(context.call_term_once(&query).is_ok());
+
+        Ok(())
+    }
+
+    #[test]
+    fn goal_expansion_registers_another_expansion() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        static REGISTERED: std::sync::Once = std::sync::Once::new();
+        register_goal_expansion(&context, |context, goal| {
+            if !goal.get_atom_name(|name| name == Some("outer_goal"))? {
+                return Ok(None);
+            }
+
+            let mut result = Ok(());
+            REGISTERED.call_once(|| {
+                result = register_goal_expansion(context, |context, goal| {
+                    if goal.get_atom_name(|name| name == Some("inner_goal"))? {
+                        let replacement = context.new_term_ref();
+                        replacement.unify(Atom::new("true"))?;
+                        Ok(Some(replacement))
+                    } else {
+                        Ok(None)
+                    }
+                });
+            });
+            result?;
+
+            let replacement = context.new_term_ref();
+            replacement.unify(Atom::new("true"))?;
+            Ok(Some(replacement))
+        })?;
+
+        let path = format!("{}.pl", context.tmp_file("goal_expansion")?);
+        std::fs::write(&path, "nested_expansion_test :- outer_goal, inner_goal.\n").unwrap();
+        let file = context.new_term_ref();
+        file.unify(Atom::new(&path))?;
+        context.call_once(pred!("consult/1"), [&file])?;
+        std::fs::remove_file(&path).unwrap();
+
+        let query = context.term_from_string("clause(nested_expansion_test, (true, true))")?;
+        assert!(context.call_term_once(&query).is_ok());
+
+        Ok(())
+    }
+
+    #[test]
+    fn run_closure_on_engine() {
+        let engine = Engine::new();
+
+        let result = run_on_engine(&engine, |context| {
+            assert!(engine.is_active());
+            let term = context.term_from_string("foo(42)").unwrap();
+            term.get_arg::<u64>(1).unwrap()
+        });
+        assert_eq!(42, result);
+        assert!(!engine.is_active());
+        assert!(!Engine::some_engine_active());
+
+        // the engine can be pinned to a thread again afterwards
+        std::thread::spawn(move || {
+            run_on_engine(&engine, |_| assert!(engine.is_active()));
+        })
+        .join()
+        .unwrap();
+    }
+
+    #[test]
+    fn portray_with_rust_callback() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        register_portray(&context, |_context, term| {
+            if attempt_opt(term.get::<Functor>())? == Some(Functor::new("portray_test", 1)) {
+                let num: u64 = term.get_arg(1)?;
+                Ok(Some(format!("<portrayed {}>", num)))
+            } else {
+                Ok(None)
+            }
+        })?;
+
+        let output = context.new_term_ref();
+        let goal =
+            term! {context: with_output_to(string(#&output), print(foo(portray_test(42), bar)))}?;
+        context.call_term_once(&goal)?;
+        assert_eq!("foo(<portrayed 42>,bar)", output.get::<String>()?);
+
+        // a callback may print terms itself, which calls portray again.
+        register_portray(&context, |context, term| {
+            if attempt_opt(term.get::<Functor>())? == Some(Functor::new("portray_nested", 1)) {
+                let [inner] = context.compound_terms(term)?;
+                let output = context.new_term_ref();
+                let goal = term! {context: with_output_to(string(#&output), print(#&inner))}?;
+                context.call_once(pred!("call/1"), [&goal])?;
+                Ok(Some(format!("<nested {}>", output.get::<String>()?)))
+            } else {
+                Ok(None)
+            }
+        })?;
+
+        let output = context.new_term_ref();
+        let goal = term! {context: with_output_to(string(#&output), print(portray_nested(portray_test(7))))}?;
+        context.call_term_once(&goal)?;
+        assert_eq!("<nested <portrayed 7>>", output.get::<String>()?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn signal_divergent_query_on_other_engine() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let clause = context.term_from_string("(signal_test_loop :- signal_test_loop)")?;
+        context.call_once(pred!("assertz/1"), [&clause])?;
+
+        let other = std::sync::Arc::new(Engine::new());
+        let (started_send, started_receive) = std::sync::mpsc::channel();
+        let thread_engine = other.clone();
+        let handle = std::thread::spawn(move || {
+            run_on_engine(&thread_engine, |context| {
+                started_send.send(()).unwrap();
+                let goal = term! {context: signal_test_loop}.unwrap();
+                let result = context.call_term_once(&goal);
+                assert!(result.unwrap_err().is_exception());
+
+                context
+                    .recover_from_exception()
+                    .and_then(|e| e.get::<Atom>().ok())
+                    .map(|a| a.name())
+            })
+        });
+
+        started_receive.recv().unwrap();
+        let goal = term! {context: throw(cancelled)}?;
+        context.thread_signal(&other, &goal)?;
+
+        assert_eq!(Some("cancelled".to_string()), handle.join().unwrap());
+
+        Ok(())
+    }
+
+    #[test]
+    fn with_tty_raw_calls_function() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let mut calls = 0;
+        let result = context.with_tty_raw(|| {
+            calls += 1;
+            42
+        })?;
+        assert_eq!(42, result);
+        assert_eq!(1, calls);
+
+        // outside of with_tty_raw, there is no closure to call.
+        let goal = context.term_from_string("'$swipl_rs_call_closure'")?;
+        assert!(!attempt(context.call_term_once(&goal))?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn stdin_is_not_tty_when_piped() {
+        // the test harness may run with a terminal as stdin, so this
+        // runs itself as a child process with stdin piped or null.
+        if std::env::var("SWIPL_RS_STDIN_TEST").is_ok() {
+            println!("stdin_is_tty: {}", stdin_is_tty());
+            return;
+        }
+
+        let exe = std::env::current_exe().unwrap();
+        for stdin in [std::process::Stdio::piped(), std::process::Stdio::null()] {
+            let output = std::process::Command::new(&exe)
+                .args([
+                    "engine::tests::stdin_is_not_tty_when_piped",
+                    "--exact",
+                    "--nocapture",
+                ])
+                .env("SWIPL_RS_STDIN_TEST", "1")
+                .stdin(stdin)
+                .output()
+                .unwrap();
+
+            assert!(output.status.success());
+            assert!(String::from_utf8_lossy(&output.stdout).contains("stdin_is_tty: false"));
+        }
+    }
 }
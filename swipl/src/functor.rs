@@ -49,6 +49,13 @@ impl Functor {
         unsafe { Functor::wrap(functor) }
     }
 
+    /// Create a new zero-arity functor from the given atom.
+    ///
+    /// This will panic if no prolog engine is active on this thread.
+    pub fn from_atom(atom: Atom) -> Functor {
+        Self::new(atom, 0)
+    }
+
     /// Return the underlying `functor_t` which SWI-Prolog uses to refer to the functor.
     pub fn functor_ptr(&self) -> functor_t {
         self.functor
@@ -351,4 +358,19 @@ mod tests {
         assert_eq!(atom!("foo"), f.name());
         assert_eq!(3, f.arity());
     }
+
+    #[test]
+    fn functor_from_atom() {
+        let engine = Engine::new();
+        let _activation = engine.activate();
+
+        let atom = Atom::new("foo");
+        let f = atom.as_functor(2);
+        assert_eq!(Functor::new("foo", 2), f);
+        assert_eq!(atom, f.name());
+
+        let f = Functor::from_atom(atom);
+        assert_eq!("foo", f.name_string());
+        assert_eq!(0, f.arity());
+    }
 }
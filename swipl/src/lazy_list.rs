@@ -0,0 +1,181 @@
+//! Lazy prolog lists backed by rust iterators.
+//!
+//! A lazy list is a prolog list whose elements are only produced
+//! when prolog code needs them. This allows large or even infinite
+//! sequences to be passed into prolog without materializing them
+//! first.
+//!
+//! The list is implemented using `freeze/2`. Its tail is a variable
+//! which, once prolog code tries to unify it with a list cell, pulls
+//! the next element from the iterator. Elements that have been pulled
+//! are recorded, so that backtracking over the list produces the same
+//! elements again rather than advancing the iterator further.
+use crate::blob::*;
+use crate::context::*;
+use crate::functor::*;
+use crate::record::*;
+use crate::result::*;
+use crate::term::*;
+
+use std::sync::{Arc, Mutex};
+use swipl_macros::{arc_blob, pred, predicates};
+
+type NextElement = Box<dyn FnMut(&Term) -> PrologResult<bool> + Send>;
+
+struct LazyListState {
+    next: NextElement,
+    produced: Vec<Record>,
+    exhausted: bool,
+}
+
+// records are stored off the stack, and are therefore safe to move
+// between threads.
+unsafe impl Send for LazyListState {}
+
+impl LazyListState {
+    /// Put the element at the given index in the given term.
+    ///
+    /// Returns false if the iterator is exhausted before reaching
+    /// this index.
+    fn element(&mut self, index: usize, term: &Term) -> PrologResult<bool> {
+        if let Some(record) = self.produced.get(index) {
+            record.recorded(term)?;
+
+            return Ok(true);
+        }
+
+        if self.exhausted {
+            return Ok(false);
+        }
+
+        if (self.next)(term)? {
+            self.produced.push(Record::from_term(term));
+
+            Ok(true)
+        } else {
+            self.exhausted = true;
+
+            Ok(false)
+        }
+    }
+}
+
+#[arc_blob("lazy_list_source", defaults)]
+struct LazyListSource {
+    state: Mutex<LazyListState>,
+}
+
+fn freeze_lazy_list_tail<C: QueryableContextType>(
+    context: &Context<C>,
+    source: &Term,
+    index: u64,
+    tail: &Term,
+) -> PrologResult<()> {
+    let goal = context.new_term_ref();
+    goal.unify(Functor::new("$swipl_rs_lazy_list", 3))?;
+    goal.unify_arg(1, source)?;
+    goal.unify_arg(2, index)?;
+    goal.unify_arg(3, tail)?;
+
+    context.call_once(pred!("freeze/2"), [tail, &goal])
+}
+
+predicates! {
+    #[name("$swipl_rs_lazy_list")]
+    semidet fn swipl_rs_lazy_list(context, source_term, index_term, list) {
+        let source: Arc<LazyListSource> = source_term.get()?;
+        let index: u64 = index_term.get()?;
+
+        let element = context.new_term_ref();
+        let available = source.state.lock().unwrap().element(index as usize, &element)?;
+        if !available {
+            return list.unify(Nil);
+        }
+
+        let (head, tail) = context.unify_list_functor(list)?;
+        head.unify(&element)?;
+
+        freeze_lazy_list_tail(context, source_term, index + 1, &tail)
+    }
+}
+
+impl<'a, T: QueryableContextType> Context<'a, T> {
+    /// Create a lazy prolog list from the given iterator.
+    ///
+    /// Elements are pulled from the iterator one at a time, as prolog
+    /// code walks the list. The iterator may be infinite, as long as
+    /// prolog code does not try to reach the end of the list.
+    ///
+    /// Pulled elements are recorded for as long as the list is
+    /// alive, so that backtracking sees the same elements again. The
+    /// iterator is dropped once prolog garbage collects the list.
+    pub fn lazy_list<I, U>(&self, iter: I) -> PrologResult<Term>
+    where
+        I: IntoIterator<Item = U>,
+        I::IntoIter: Send + 'static,
+        U: Unifiable,
+    {
+        register_swipl_rs_lazy_list();
+
+        let mut iter = iter.into_iter();
+        let next: NextElement = Box::new(move |term: &Term<'_>| match iter.next() {
+            Some(value) => term.unify(value).map(|_| true),
+            None => Ok(false),
+        });
+        let source = Arc::new(LazyListSource {
+            state: Mutex::new(LazyListState {
+                next,
+                produced: Vec::new(),
+                exhausted: false,
+            }),
+        });
+
+        let list = self.new_term_ref();
+        let frame = self.open_frame();
+        let source_term = frame.new_term_ref();
+        source_term.unify(&source)?;
+        freeze_lazy_list_tail(&frame, &source_term, 0, &list)?;
+        frame.close();
+
+        Ok(list)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::*;
+    use swipl_macros::term;
+
+    #[test]
+    fn take_from_infinite_lazy_list() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let list = context.lazy_list(0_u64..)?;
+        let [prefix, rest] = context.new_term_refs();
+        let length = term! {context: length(#&prefix, 5)}?;
+        context.call_once(pred!("call/1"), [&length])?;
+        context.call_once(pred!("append/3"), [&prefix, &rest, &list])?;
+
+        assert_eq!(vec![0_u64, 1, 2, 3, 4], prefix.get::<Vec<u64>>()?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn finite_lazy_list_ends() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let list = context.lazy_list(vec!["a", "b", "c"].into_iter().map(String::from))?;
+        let length = context.new_term_ref();
+        context.call_once(pred!("length/2"), [&list, &length])?;
+
+        assert_eq!(3_u64, length.get()?);
+
+        Ok(())
+    }
+}
@@ -12,6 +12,7 @@
 pub mod consts;
 pub mod fli;
 
+pub mod arithmetic;
 pub mod atom;
 pub mod blob;
 pub mod callable;
@@ -20,17 +21,26 @@ pub mod dict;
 pub mod engine;
 pub mod functor;
 pub mod init;
+pub mod lazy_list;
 pub mod module;
 pub mod predicate;
+pub mod quasi;
 pub mod record;
 pub mod result;
+#[cfg(feature = "serde")]
+pub mod serde;
 pub mod stream;
 pub mod term;
 pub mod text;
 
 pub mod prelude;
 
+// used by the prolog! macro to refer to serde from dependent crates.
+#[cfg(feature = "serde")]
+#[doc(hidden)]
+pub use ::serde as __serde;
+
 pub use swipl_macros::{
     arc_blob, atom, clone_blob, functor, pred, predicates, prolog, term, wrapped_arc_blob,
-    wrapped_clone_blob,
+    wrapped_clone_blob, FromTerm, IntoTerm,
 };
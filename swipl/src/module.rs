@@ -7,8 +7,14 @@
 //! This module provides functions and types for interacting with
 //! prolog modules.
 use super::atom::*;
+use super::context::*;
 use super::engine::*;
 use super::fli::*;
+use super::functor::*;
+use super::result::*;
+use std::marker::PhantomData;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use swipl_macros::{pred, term};
 
 /// A wrapped fora  prolog module.
 #[derive(Clone, Copy, PartialEq, Eq, Debug)]
@@ -83,6 +89,66 @@ impl Module {
     }
 }
 
+static TEMPORARY_MODULE_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// A uniquely named module which is cleaned up on drop.
+///
+/// This is created through
+/// [Context::new_temporary_module](crate::context::Context::new_temporary_module). Goals
+/// can be run in this module by passing it as the context module to
+/// [Context::open_with_module](crate::context::Context::open_with_module). When
+/// the temporary module is dropped, all predicates defined in it are
+/// abolished.
+pub struct TemporaryModule<'a> {
+    module: Module,
+    _lifetime: PhantomData<&'a ()>,
+}
+
+impl<'a> TemporaryModule<'a> {
+    pub(crate) fn new<C: QueryableContextType>(context: &'a Context<C>) -> PrologResult<Self> {
+        let count = TEMPORARY_MODULE_COUNTER.fetch_add(1, Ordering::Relaxed);
+        let module = Module::new(format!("$swipl_rs_temporary_{}", count).as_str());
+
+        let frame = context.open_frame();
+        let [class, qualified] = frame.new_term_refs();
+        class.unify(Functor::new("class", 1))?;
+        class.unify_arg(1, Atom::new("temporary"))?;
+        qualified.unify(Functor::new(":", 2))?;
+        qualified.unify_arg(1, module.name())?;
+        qualified.unify_arg(2, &class)?;
+        frame.call_once(pred!("set_module/1"), [&qualified])?;
+        frame.close();
+
+        Ok(Self {
+            module,
+            _lifetime: PhantomData,
+        })
+    }
+
+    /// Return the underlying module.
+    pub fn module(&self) -> Module {
+        self.module
+    }
+}
+
+impl<'a> Drop for TemporaryModule<'a> {
+    fn drop(&mut self) {
+        // unsafe justification: the lifetime of the temporary module
+        // is bound to the context it was created in, so an engine is
+        // active here.
+        let context = unsafe { unmanaged_engine_context() };
+        let frame = context.open_frame();
+        let [indicator, qualified] = frame.new_term_refs();
+        qualified.unify(Functor::new(":", 2)).unwrap();
+        qualified.unify_arg(1, self.module.name()).unwrap();
+        qualified.unify_arg(2, &indicator).unwrap();
+        let goal = term! {frame: forall(current_predicate(#&qualified), catch(abolish(#&qualified), _, true))}
+            .unwrap();
+        let _ = frame.call_once(pred!("call/1"), [&goal]);
+        frame.discard();
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -95,4 +161,33 @@ mod tests {
         let module = Module::new("foo");
         assert_eq!("foo", module.name_string());
     }
+
+    #[test]
+    fn temporary_module_is_cleaned_up_on_drop() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let defined = context.new_term_ref();
+        {
+            let module = context.new_temporary_module()?;
+            let clause = term! {context: tenant_fact(42)}?;
+            context
+                .open_with_module(pred!("assertz/1"), Some(module.module()), [&clause])
+                .next_solution()?;
+
+            let indicator = context.term_from_string("tenant_fact/1")?;
+            defined.unify(Functor::new(":", 2))?;
+            defined.unify_arg(1, module.module().name())?;
+            defined.unify_arg(2, &indicator)?;
+            context.call_once(pred!("current_predicate/1"), [&defined])?;
+        }
+
+        assert!(context
+            .call_once(pred!("current_predicate/1"), [&defined])
+            .unwrap_err()
+            .is_failure());
+
+        Ok(())
+    }
 }
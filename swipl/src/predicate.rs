@@ -8,12 +8,87 @@
 //! This module provides functors and types for intearcting with
 //! prolog predicates.
 use std::convert::TryInto;
+use std::path::PathBuf;
 
 use super::atom::*;
+use super::context::*;
 use super::engine::*;
 use super::fli::*;
 use super::functor::*;
 use super::module::*;
+use super::result::*;
+use super::term::*;
+use swipl_macros::{atom, pred, term};
+
+/// A property of a predicate, as queried by `predicate_property/2`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PredicateProperty {
+    /// The predicate is dynamic, meaning its clauses can be modified.
+    Dynamic,
+    /// The predicate may have clauses in multiple files.
+    Multifile,
+    /// The predicate is tabled.
+    Tabled,
+    /// The predicate is a system predicate.
+    BuiltIn,
+    /// The predicate is defined, either through clauses or as a foreign predicate.
+    Defined,
+}
+
+impl PredicateProperty {
+    fn name(&self) -> &'static str {
+        match self {
+            PredicateProperty::Dynamic => "dynamic",
+            PredicateProperty::Multifile => "multifile",
+            PredicateProperty::Tabled => "tabled",
+            PredicateProperty::BuiltIn => "built_in",
+            PredicateProperty::Defined => "defined",
+        }
+    }
+}
+
+/// The specification of an argument of a meta-predicate, as declared with `meta_predicate/1`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum MetaArg {
+    /// A goal which is called with the given number of extra arguments.
+    Goal(u8),
+    /// A module-sensitive argument that is not a goal.
+    Module,
+    /// A goal which may be prefixed with `Var^`, as in `bagof/3`.
+    Caret,
+    /// A DCG body.
+    Dcg,
+    /// An argument that must be instantiated.
+    Instantiated,
+    /// An argument that must be unbound.
+    Unbound,
+    /// An argument of any instantiation.
+    Any,
+    /// An argument of which nothing is declared.
+    Unspecified,
+}
+
+impl MetaArg {
+    fn from_term(term: &Term) -> PrologResult<Self> {
+        if let Some(n) = attempt_opt(term.get::<u64>())? {
+            return n
+                .try_into()
+                .map(MetaArg::Goal)
+                .map_err(|_| PrologError::Failure);
+        }
+
+        term.get_atom_name(|name| match name {
+            Some(":") => Ok(MetaArg::Module),
+            Some("^") => Ok(MetaArg::Caret),
+            Some("//") => Ok(MetaArg::Dcg),
+            Some("+") => Ok(MetaArg::Instantiated),
+            Some("-") => Ok(MetaArg::Unbound),
+            Some("?") => Ok(MetaArg::Any),
+            Some("*") => Ok(MetaArg::Unspecified),
+            _ => Err(PrologError::Failure),
+        })?
+    }
+}
 
 /// A wrapper for a prolog predicate.
 #[derive(Clone, Copy)]
@@ -117,6 +192,11 @@ impl Predicate {
 
     /// Retrieve the module of this predicate.
     ///
+    /// This is the module the predicate is defined in. For a
+    /// predicate that was imported into another module, such as
+    /// `append/3` after `user` loaded `library(lists)`, this is the
+    /// module it was imported from rather than the importing module.
+    ///
     /// this will panic if no prolog engine is active on this thread.
     pub fn module(&self) -> Module {
         assert_some_engine_is_active();
@@ -132,4 +212,246 @@ impl Predicate {
             Module::wrap(module)
         }
     }
+
+    /// Retrieve all clauses of this predicate.
+    ///
+    /// This uses the prolog predicate `clause/2` to find the
+    /// clauses. Each clause is returned as a term of the form `Head
+    /// :- Body`, or as just `Head` for facts. The clause terms are
+    /// created in the given context.
+    ///
+    /// Retrieving the clauses of a foreign predicate or of a system
+    /// predicate will raise a permission error.
+    pub fn clauses<'a, C: QueryableContextType>(
+        &self,
+        context: &'a Context<C>,
+    ) -> PrologResult<Vec<Term<'a>>> {
+        let [head, qualified_head, body, template, goal, list] = context.new_term_refs();
+        self.unify_qualified_head(&head, &qualified_head)?;
+        template.unify(Functor::new(":-", 2))?;
+        template.unify_arg(1, &head)?;
+        template.unify_arg(2, &body)?;
+        goal.unify(Functor::new("clause", 2))?;
+        goal.unify_arg(1, &qualified_head)?;
+        goal.unify_arg(2, &body)?;
+        context.call_once(pred!("findall/3"), [&template, &goal, &list])?;
+
+        let mut clauses = context.term_list_vec(&list);
+        for clause in clauses.iter_mut() {
+            if attempt_opt(clause.get_arg::<Atom>(2))? == Some(atom!("true")) {
+                let fact = context.new_term_ref();
+                clause.unify_arg(1, &fact)?;
+                *clause = fact;
+            }
+        }
+
+        Ok(clauses)
+    }
+
+    /// Check whether this predicate has the given property.
+    ///
+    /// This uses the prolog predicate `predicate_property/2`.
+    pub fn property<C: QueryableContextType>(
+        &self,
+        context: &Context<C>,
+        property: PredicateProperty,
+    ) -> PrologResult<bool> {
+        let frame = context.open_frame();
+        let [head, qualified_head, property_term] = frame.new_term_refs();
+        self.unify_qualified_head(&head, &qualified_head)?;
+        property_term.unify(Atom::new(property.name()))?;
+        let result = attempt(frame.call_once(
+            pred!("predicate_property/2"),
+            [&qualified_head, &property_term],
+        ))?;
+        frame.close();
+
+        Ok(result)
+    }
+
+    /// Retrieve the meta-predicate declaration of this predicate.
+    ///
+    /// This uses `predicate_property/2` with the `meta_predicate/1`
+    /// property. For each argument, its [MetaArg] specification is
+    /// returned. If this predicate is not a meta-predicate, this
+    /// returns `None`.
+    pub fn meta_argument_spec<C: QueryableContextType>(
+        &self,
+        context: &Context<C>,
+    ) -> PrologResult<Option<Vec<MetaArg>>> {
+        let frame = context.open_frame();
+        let [head, qualified_head, spec] = frame.new_term_refs();
+        self.unify_qualified_head(&head, &qualified_head)?;
+        let property = term! {frame: meta_predicate(#&spec)}?;
+        if !frame.call_once_bool(pred!("predicate_property/2"), [&qualified_head, &property])? {
+            frame.close();
+            return Ok(None);
+        }
+
+        let result = frame
+            .compound_terms_vec(&spec)?
+            .iter()
+            .map(MetaArg::from_term)
+            .collect::<PrologResult<Vec<_>>>()?;
+        frame.close();
+
+        Ok(Some(result))
+    }
+
+    /// Retrieve the source locations of the clauses of this predicate.
+    ///
+    /// This uses the prolog predicate `clause_property/2` to find the
+    /// file and line number where each clause was defined. Clauses
+    /// that were not loaded from a file, such as asserted clauses,
+    /// are skipped.
+    pub fn clause_locations<C: QueryableContextType>(
+        &self,
+        context: &Context<C>,
+    ) -> PrologResult<Vec<(PathBuf, u64)>> {
+        let frame = context.open_frame();
+        let [head, qualified_head, clause_ref, file, line, list] = frame.new_term_refs();
+        self.unify_qualified_head(&head, &qualified_head)?;
+        let template = term! {frame: location(#&file, #&line)}?;
+        let goal = term! {frame: (clause(#&qualified_head, _, #&clause_ref), clause_property(#&clause_ref, file(#&file)), clause_property(#&clause_ref, line_count(#&line)))}?;
+        frame.call_once(pred!("findall/3"), [&template, &goal, &list])?;
+
+        let mut locations = Vec::new();
+        for location in frame.term_list_iter(&list) {
+            let [file, line] = frame.compound_terms(&location)?;
+            let file = file.get_atom_name(|name| name.map(PathBuf::from))?;
+            if let Some(file) = file {
+                locations.push((file, line.get()?));
+            }
+        }
+        frame.close();
+
+        Ok(locations)
+    }
+
+    fn unify_qualified_head(&self, head: &Term, qualified_head: &Term) -> PrologResult<()> {
+        head.unify(Functor::new(self.name(), self.arity()))?;
+        qualified_head.unify(Functor::new(":", 2))?;
+        qualified_head.unify_arg(1, self.module().name())?;
+        qualified_head.unify_arg(2, head)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use swipl_macros::term;
+
+    #[test]
+    fn retrieve_clauses_of_predicate() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        for clause in [
+            term! {context: clauses_test(1)}?,
+            term! {context: clauses_test(2)}?,
+        ] {
+            context.call_once(pred!("assertz/1"), [&clause])?;
+        }
+        let clause = context.term_from_string("clauses_test(X) :- X > 2")?;
+        context.call_once(pred!("assertz/1"), [&clause])?;
+
+        let predicate = Predicate::new(Functor::new("clauses_test", 1), Module::new("user"));
+        let clauses = predicate.clauses(&context)?;
+        assert_eq!(3, clauses.len());
+
+        assert_eq!("clauses_test(1)", context.string_from_term(&clauses[0])?);
+        assert_eq!("clauses_test(2)", context.string_from_term(&clauses[1])?);
+        assert_eq!(Functor::new(":-", 2), clauses[2].get::<Functor>()?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn query_predicate_properties() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let predicate = Predicate::new(Functor::new("atom_length", 2), Module::new("user"));
+        assert!(predicate.property(&context, PredicateProperty::BuiltIn)?);
+        assert!(predicate.property(&context, PredicateProperty::Defined)?);
+        assert!(!predicate.property(&context, PredicateProperty::Dynamic)?);
+
+        let fact = term! {context: property_test(1)}?;
+        context.call_once(pred!("assertz/1"), [&fact])?;
+
+        let predicate = Predicate::new(Functor::new("property_test", 1), Module::new("user"));
+        assert!(predicate.property(&context, PredicateProperty::Dynamic)?);
+        assert!(!predicate.property(&context, PredicateProperty::BuiltIn)?);
+        assert!(!predicate.property(&context, PredicateProperty::Tabled)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn retrieve_clause_locations() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let path = format!("{}.pl", context.tmp_file("clause_locations")?);
+        std::fs::write(&path, "location_test(1).\n\nlocation_test(2).\n").unwrap();
+        let file = context.new_term_ref();
+        file.unify(Atom::new(&path))?;
+        context.call_once(pred!("consult/1"), [&file])?;
+        std::fs::remove_file(&path).unwrap();
+
+        let predicate = Predicate::new(Functor::new("location_test", 1), Module::new("user"));
+        let locations = predicate.clause_locations(&context)?;
+        assert_eq!(2, locations.len());
+
+        let file_name = std::path::Path::new(&path).file_name().unwrap();
+        assert!(locations.iter().all(|(file, _)| file.ends_with(file_name)));
+        assert_eq!(1, locations[0].1);
+        assert_eq!(3, locations[1].1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn retrieve_meta_argument_spec() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let library = term! {context: library(apply)}?;
+        context.call_once(pred!("use_module/1"), [&library])?;
+
+        let maplist = Predicate::new(Functor::new("maplist", 2), Module::new("apply"));
+        assert_eq!(
+            Some(vec![MetaArg::Goal(1), MetaArg::Any]),
+            maplist.meta_argument_spec(&context)?
+        );
+
+        let clause = term! {context: meta_spec_test(42)}?;
+        context.call_once(pred!("assertz/1"), [&clause])?;
+        let plain = Predicate::new(Functor::new("meta_spec_test", 1), Module::new("user"));
+        assert_eq!(None, plain.meta_argument_spec(&context)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn module_of_imported_predicate() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let library = term! {context: library(lists)}?;
+        context.call_once(pred!("use_module/1"), [&library])?;
+
+        let predicate = Predicate::new(Functor::new("append", 3), Module::new("user"));
+        assert_eq!("lists", predicate.module().name_string());
+
+        let predicate = Predicate::new(Functor::new("module_test", 1), Module::new("user"));
+        assert_eq!("user", predicate.module().name_string());
+
+        Ok(())
+    }
 }
@@ -1,4 +1,30 @@
 //! Module which re-exports all public symbols in this crate, for easy importing.
+//!
+//! This includes the core types like [Context], [Engine], [Term],
+//! [Atom], [Functor], [Module], [PrologResult] and [PrologError],
+//! all the macros, and when the `serde` feature is enabled,
+//! `from_term` and `to_term`.
+//!
+//! Example:
+//! ```
+//! use swipl::prelude::*;
+//!
+//! fn main() -> PrologResult<()> {
+//!     let engine = Engine::new();
+//!     let activation = engine.activate();
+//!     let context: Context<_> = activation.into();
+//!
+//!     let term = term! {context: foo(bar, 42)}?;
+//!     assert_eq!(functor!("foo/2"), term.get::<Functor>()?);
+//!     assert_eq!(42, term.get_arg::<u64>(2)?);
+//!
+//!     let result = context.new_term_ref();
+//!     context.call_once(pred!(atom_length/2), [&term!{context: hello}?, &result])?;
+//!     assert_eq!(5, result.get::<u64>()?);
+//!
+//!     Ok(())
+//! }
+//! ```
 pub use crate::atom::*;
 pub use crate::blob::*;
 pub use crate::callable::*;
@@ -9,12 +35,18 @@ pub use crate::functor::*;
 pub use crate::init::*;
 pub use crate::module::*;
 pub use crate::predicate::*;
+pub use crate::quasi::*;
 pub use crate::result::*;
 pub use crate::stream::*;
 pub use crate::term::*;
 pub use crate::text::*;
 
+#[cfg(feature = "serde")]
+pub use crate::term::de::from_term;
+#[cfg(feature = "serde")]
+pub use crate::term::ser::{to_term, to_term_with_config};
+
 pub use crate::{
     arc_blob, atom, clone_blob, functor, pred, predicates, prolog, term, term_getable,
-    term_putable, unifiable, wrapped_arc_blob, wrapped_clone_blob,
+    term_putable, unifiable, wrapped_arc_blob, wrapped_clone_blob, FromTerm, IntoTerm,
 };
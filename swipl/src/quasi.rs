@@ -0,0 +1,126 @@
+//! Quasi-quotation handlers implemented in rust.
+//!
+//! SWI-Prolog quasi-quotations allow embedding text in a foreign
+//! syntax in prolog source, such as `{|mydsl||some text|}`. While
+//! reading such a term, prolog calls a handler for the syntax (here
+//! `mydsl`), which turns the quoted text into a term.
+//!
+//! This module allows such handlers to be implemented in rust. A
+//! handler receives the quoted text as a string, and returns the term
+//! that should take the place of the quasi-quotation.
+use crate::atom::*;
+use crate::context::*;
+use crate::functor::*;
+use crate::result::*;
+use crate::term::*;
+
+use lazy_static::*;
+use std::collections::HashMap;
+use std::sync::RwLock;
+use swipl_macros::{pred, predicates, term};
+
+/// A rust function handling a quasi-quotation.
+///
+/// The function is given the quoted text, and is to return the term
+/// that the quasi-quotation is read as.
+pub type QuasiQuotationHandler =
+    for<'a> fn(&'a GenericQueryableContext<'a>, &str) -> PrologResult<Term<'a>>;
+
+lazy_static! {
+    static ref HANDLERS: RwLock<HashMap<String, QuasiQuotationHandler>> =
+        RwLock::new(HashMap::new());
+}
+
+predicates! {
+    #[name("$swipl_rs_quasi_quotation")]
+    semidet fn swipl_rs_quasi_quotation(context, name, text, result) {
+        let handler = name.get_atom_name(|name| {
+            name.and_then(|name| HANDLERS.read().unwrap().get(name).copied())
+        })?;
+        let handler = match handler {
+            Some(handler) => handler,
+            None => return Err(PrologError::Failure),
+        };
+        let text: String = text.get()?;
+
+        let generic = context.into_generic();
+        let term = handler(&generic, &text)?;
+
+        result.unify(&term)
+    }
+}
+
+/// Register a rust function as the handler for the quasi-quotation syntax with the given name.
+///
+/// This defines the syntax in the `user` module, after which any
+/// term read in that module can use `{|name||text|}`. Registering a
+/// handler for a name that was registered before replaces the
+/// previous handler.
+pub fn register_quasi_quotation<C: QueryableContextType>(
+    context: &Context<C>,
+    name: &str,
+    handler: QuasiQuotationHandler,
+) -> PrologResult<()> {
+    HANDLERS.write().unwrap().insert(name.to_string(), handler);
+    register_swipl_rs_quasi_quotation();
+
+    let frame = context.open_frame();
+    let library = term! {frame: library(quasi_quotations)}?;
+    frame.call_once(pred!("use_module/1"), [&library])?;
+
+    let [name_term, head, content, stream, text, result, call, clause] = frame.new_term_refs();
+    name_term.unify(Atomable::from(name))?;
+
+    // name(Content, _SyntaxArgs, _VariableNames, Result) :-
+    //     with_quasi_quotation_input(Content, Stream, read_string(Stream, _, Text)),
+    //     '$swipl_rs_quasi_quotation'(name, Text, Result).
+    head.unify(Functor::new(name, 4))?;
+    frame.call_once(pred!("retractall/1"), [&head])?;
+    head.unify_arg(1, &content)?;
+    head.unify_arg(4, &result)?;
+    call.unify(Functor::new("$swipl_rs_quasi_quotation", 3))?;
+    call.unify_arg(1, &name_term)?;
+    call.unify_arg(2, &text)?;
+    call.unify_arg(3, &result)?;
+    let body = term! {frame: (with_quasi_quotation_input(#&content, #&stream, read_string(#&stream, _, #&text)), #&call)}?;
+    clause.unify(Functor::new(":-", 2))?;
+    clause.unify_arg(1, &head)?;
+    clause.unify_arg(2, &body)?;
+
+    frame.call_once(pred!("assertz/1"), [&clause])?;
+    frame.call_once(pred!("quasi_quotation_syntax/1"), [&name_term])?;
+    frame.close();
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::*;
+
+    fn atom_quasi_quotation<'a>(
+        context: &'a GenericQueryableContext<'a>,
+        text: &str,
+    ) -> PrologResult<Term<'a>> {
+        let term = context.new_term_ref();
+        term.unify(Atomable::from(text))?;
+
+        Ok(term)
+    }
+
+    #[test]
+    fn register_and_read_quasi_quotation() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        register_quasi_quotation(&context, "rust_atom", atom_quasi_quotation)?;
+
+        let term = context.term_from_string("foo({|rust_atom||hello world|})")?;
+        let [arg] = context.compound_terms(&term)?;
+        assert_eq!(Atom::new("hello world"), arg.get::<Atom>()?);
+
+        Ok(())
+    }
+}
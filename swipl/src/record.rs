@@ -5,6 +5,7 @@
 //! erased. This module wraps such records, making the erase happen
 //! automatically on drop of a wrapper object.
 
+use super::context::*;
 use super::fli;
 use super::result::*;
 use super::term::*;
@@ -82,6 +83,57 @@ unifiable! {
     }
 }
 
+/// A snapshot of the bindings of a set of variables.
+///
+/// This is created by [Context::snapshot_bindings], and holds the
+/// term references of the variables along with a record of their
+/// values at that time, including any sharing between them. It can
+/// be restored with [Context::restore_bindings].
+#[derive(Clone)]
+pub struct BindingSnapshot<'a> {
+    vars: Vec<Term<'a>>,
+    record: Record,
+}
+
+impl<'a, T: QueryableContextType> Context<'a, T> {
+    /// Take a snapshot of the current bindings of the given variables.
+    ///
+    /// The values of the variables are recorded together, so
+    /// variables that share a subterm will still share it when the
+    /// snapshot is restored. This makes it possible to return to an
+    /// earlier state after the bindings were undone, for example by
+    /// discarding a frame.
+    pub fn snapshot_bindings<'b>(&self, vars: &[&Term<'b>]) -> BindingSnapshot<'b> {
+        let list = self.new_term_ref();
+        list.unify(vars).expect("unifying a fresh term should work");
+        let record = list.record();
+        unsafe {
+            list.reset();
+        }
+
+        BindingSnapshot {
+            vars: vars.iter().map(|&var| var.clone()).collect(),
+            record,
+        }
+    }
+
+    /// Restore the bindings of the variables in the given snapshot.
+    ///
+    /// The variables are unified with the values from the snapshot,
+    /// so this will usually be done after their bindings were undone.
+    /// If the variables are bound to values that do not unify with
+    /// the snapshot, this fails.
+    pub fn restore_bindings(&self, snapshot: &BindingSnapshot) -> PrologResult<()> {
+        let frame = self.open_frame();
+        let values = frame.new_term_ref();
+        snapshot.record.recorded(&values)?;
+        values.unify(snapshot.vars.as_slice())?;
+        frame.close();
+
+        Ok(())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -223,4 +275,30 @@ mod tests {
 
         assert!(!attempt(term2.unify(&record)).unwrap());
     }
+
+    #[test]
+    fn snapshot_and_restore_bindings() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let [x, y] = context.new_term_refs();
+        let frame = context.open_frame();
+        let shared = term! {frame: foo(#&y)}?;
+        x.unify(&shared)?;
+        let snapshot = frame.snapshot_bindings(&[&x, &y]);
+        frame.discard();
+        assert!(x.is_var());
+
+        let frame = context.open_frame();
+        x.unify(42_u64)?;
+        frame.discard();
+
+        context.restore_bindings(&snapshot)?;
+        assert!(y.is_var());
+        y.unify(1_u64)?;
+        assert_eq!("foo(1)", context.string_from_term(&x)?);
+
+        Ok(())
+    }
 }
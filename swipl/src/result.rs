@@ -7,21 +7,35 @@
 //! early.
 //!
 //! This module also provides some transformations on prolog results.
+use std::convert::TryInto;
 use thiserror::Error;
 
-use crate::context::{Context, QueryableContextType};
+use crate::atom::Atom;
+use crate::context::{unmanaged_engine_context, Context, QueryableContextType};
+use crate::functor::Functor;
+use crate::term::*;
+use crate::term_getable;
 
 /// A prolog error.
 ///
 /// This is either a failure or an exception. In case of an exception,
 /// whowever returned the exception was also supposed to raise an
 /// exception on the context.
+///
+/// When prolog code calls `halt/1` in an engine, SWI-Prolog raises
+/// the exception `unwind(halt(Status))` to unwind the engine. Queries
+/// return this as a [PrologError::Halt] with the exit status, so that
+/// an embedding application can recognize it. Like any other
+/// exception, it remains raised on the context, and clearing it
+/// keeps the process alive.
 #[derive(Error, Debug, PartialEq, Eq)]
 pub enum PrologError {
     #[error("prolog function failed")]
     Failure,
     #[error("prolog function threw an exception")]
     Exception,
+    #[error("prolog halted with status {0}")]
+    Halt(i32),
 }
 
 impl PrologError {
@@ -31,8 +45,16 @@ impl PrologError {
     }
 
     /// Returns true if this error is an exception.
+    ///
+    /// This is also true for [PrologError::Halt], as halting is done
+    /// by raising an exception.
     pub fn is_exception(&self) -> bool {
-        matches!(self, PrologError::Exception)
+        matches!(self, PrologError::Exception | PrologError::Halt(_))
+    }
+
+    /// Returns true if this error is an exception raised by `halt/1`.
+    pub fn is_halt(&self) -> bool {
+        matches!(self, PrologError::Halt(_))
     }
 }
 
@@ -78,7 +100,7 @@ pub fn attempt(r: PrologResult<()>) -> BoolPrologResult {
     match r {
         Ok(()) => Ok(true),
         Err(PrologError::Failure) => Ok(false),
-        Err(PrologError::Exception) => Err(PrologException),
+        Err(PrologError::Exception | PrologError::Halt(_)) => Err(PrologException),
     }
 }
 
@@ -105,7 +127,7 @@ pub fn attempt_opt<R>(r: PrologResult<R>) -> OptPrologResult<R> {
     match r {
         Ok(r) => Ok(Some(r)),
         Err(PrologError::Failure) => Ok(None),
-        Err(PrologError::Exception) => Err(PrologException),
+        Err(PrologError::Exception | PrologError::Halt(_)) => Err(PrologException),
     }
 }
 
@@ -141,7 +163,7 @@ pub fn result_to_string_result<C: QueryableContextType, T>(
     match r {
         Ok(r) => Ok(r),
         Err(PrologError::Failure) => Err(PrologStringError::Failure),
-        Err(PrologError::Exception) => {
+        Err(PrologError::Exception | PrologError::Halt(_)) => {
             let r = c.with_exception(|e| {
                 let e = e.expect("prolog exception but no exception in prolog engine");
                 c.string_from_term(e)
@@ -157,7 +179,7 @@ pub fn result_to_string_result<C: QueryableContextType, T>(
                 Err(PrologError::Failure) => Err(PrologStringError::Exception(
                     "prolog failed while retrieving string from previous error".to_string(),
                 )),
-                Err(PrologError::Exception) => Err(PrologStringError::Exception(
+                Err(_) => Err(PrologStringError::Exception(
                     "prolog threw exception while retrieving string from previous error"
                         .to_string(),
                 )),
@@ -173,3 +195,80 @@ pub fn unwrap_result<C: QueryableContextType, T>(c: &Context<C>, r: PrologResult
         Err(PrologStringError::Exception(s)) => panic!("{}", s),
     }
 }
+
+/// Structured information from the context argument of an
+/// `error(Formal, Context)` exception term.
+///
+/// SWI-Prolog builtins usually raise errors with a context of the
+/// form `context(Module:Name/Arity, Message)`. This can be retrieved
+/// from any such exception term using [Term::get], or from the
+/// currently raised exception using
+/// [Context::exception_error_context].
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ErrorContext {
+    /// The name and arity of the predicate that raised the error.
+    pub predicate: Option<(Atom, u16)>,
+    /// The argument position the error refers to, if the context
+    /// message was given as `argument(N)`.
+    pub arg: Option<usize>,
+    /// The context message, if it was an atom or a string.
+    pub message: Option<String>,
+}
+
+fn get_error_context<C: QueryableContextType>(
+    context: &Context<C>,
+    term: &Term,
+) -> Option<ErrorContext> {
+    if term.get::<Functor>().ok()? != Functor::new("error", 2) {
+        return None;
+    }
+
+    let mut result = ErrorContext::default();
+    let error_context = context.new_term_ref();
+    term.unify_arg(2, &error_context).ok()?;
+    if error_context.get::<Functor>() != Ok(Functor::new("context", 2)) {
+        return Some(result);
+    }
+
+    let mut indicator = context.new_term_ref();
+    error_context.unify_arg(1, &indicator).ok()?;
+    if indicator.get::<Functor>() == Ok(Functor::new(":", 2)) {
+        let unqualified = context.new_term_ref();
+        indicator.unify_arg(2, &unqualified).ok()?;
+        indicator = unqualified;
+    }
+    if indicator.get::<Functor>() == Ok(Functor::new("/", 2)) {
+        let name = indicator.get_arg::<Atom>(1);
+        let arity = indicator.get_arg::<u64>(2);
+        if let (Ok(name), Ok(arity)) = (name, arity) {
+            result.predicate = arity.try_into().ok().map(|arity| (name, arity));
+        }
+    }
+
+    let message = context.new_term_ref();
+    error_context.unify_arg(2, &message).ok()?;
+    if message.is_atom() {
+        result.message = message.get::<Atom>().ok().map(|a| a.name());
+    } else if message.is_string() {
+        result.message = message.get::<String>().ok();
+    } else if message.get::<Functor>() == Ok(Functor::new("argument", 1)) {
+        result.arg = message
+            .get_arg::<u64>(1)
+            .ok()
+            .and_then(|arg| arg.try_into().ok());
+    }
+
+    Some(result)
+}
+
+term_getable! {
+    (ErrorContext, "error_context", term) => {
+        // unsafe justification: This context will only exist inside this implementation. We know we are in some valid context for term handling, so that's great.
+        let context = unsafe { unmanaged_engine_context() };
+        let frame = context.open_frame();
+        let result = get_error_context(&frame, term);
+        frame.close();
+
+        result
+    }
+}
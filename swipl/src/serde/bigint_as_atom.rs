@@ -0,0 +1,97 @@
+//! Serialize large integers as atoms.
+//!
+//! Integers like `u128` and `i128` may not fit in the integers
+//! supported by SWI-Prolog, for example on builds without unbounded
+//! integer support. This module serializes such an integer as an atom
+//! holding its decimal representation, so that no precision is lost.
+//! When deserializing, the atom (or string) is parsed back into an
+//! integer.
+//!
+//! Any type that can be formatted with [Display] and parsed with
+//! [FromStr] can be used with this module.
+//!
+//! # Examples
+//! ```
+//! # use serde::{Deserialize, Serialize};
+//! #[derive(Serialize, Deserialize)]
+//! struct Account {
+//!     // serialized as an atom like '340282366920938463463374607431768211455'
+//!     #[serde(with = "swipl::serde::bigint_as_atom")]
+//!     balance: u128,
+//! }
+//! ```
+use crate::term::ser::ATOM_STRUCT_NAME;
+use ::serde::de::{self, Visitor};
+use ::serde::{Deserializer, Serializer};
+use std::fmt::{self, Display};
+use std::marker::PhantomData;
+use std::str::FromStr;
+
+/// Serialize an integer as an atom holding its decimal representation.
+pub fn serialize<T: Display, S: Serializer>(value: &T, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_newtype_struct(ATOM_STRUCT_NAME, &value.to_string())
+}
+
+struct DecimalVisitor<T>(PhantomData<T>);
+
+impl<'de, T: FromStr> Visitor<'de> for DecimalVisitor<T> {
+    type Value = T;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("an atom holding a decimal integer")
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Self::Value, E> {
+        v.parse()
+            .map_err(|_| E::custom(format!("invalid decimal integer: {}", v)))
+    }
+}
+
+/// Deserialize an integer from an atom holding its decimal representation.
+pub fn deserialize<'de, T: FromStr, D: Deserializer<'de>>(deserializer: D) -> Result<T, D::Error> {
+    deserializer.deserialize_str(DecimalVisitor(PhantomData))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::prelude::*;
+    use crate::term::de::from_term;
+    use crate::term::ser::to_term;
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Big {
+        #[serde(with = "crate::serde::bigint_as_atom")]
+        unsigned: u128,
+        #[serde(with = "crate::serde::bigint_as_atom")]
+        signed: i128,
+    }
+
+    #[test]
+    fn roundtrip_big_integers_as_atoms() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let big = Big {
+            unsigned: u128::MAX,
+            signed: i128::MIN,
+        };
+
+        let term = context.new_term_ref();
+        to_term(&context, &term, &big).unwrap();
+
+        let expected = context.term_from_string(
+            "_{unsigned:'340282366920938463463374607431768211455',signed:'-170141183460469231731687303715884105728'}",
+        )?;
+        term.unify(&expected)?;
+
+        let result: Big = from_term(&context, &term).unwrap();
+        assert_eq!(big, result);
+
+        let term = context.term_from_string("_{unsigned:foo,signed:'1'}")?;
+        assert!(from_term::<_, Big>(&context, &term).is_err());
+
+        Ok(())
+    }
+}
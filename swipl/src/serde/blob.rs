@@ -0,0 +1,216 @@
+//! Serialize arc blobs as references to the blob.
+//!
+//! Normally, a blob-backed type has no serde representation. With
+//! this module, a field holding an `Arc` of an [arc
+//! blob](crate::blob::ArcBlob) is serialized as the blob atom
+//! itself, and deserialized back into a reference to the same
+//! value. This allows blob-wrapped resources to flow through
+//! [to_term](crate::term::ser::to_term) and
+//! [from_term](crate::term::de::from_term).
+//!
+//! As a blob reference only has meaning inside prolog, serializing to
+//! any other format fails.
+//!
+//! Clone blobs can instead be stored as a blob wherever they are
+//! serialized, by passing the `serde` option to the
+//! [clone_blob](crate::prelude::clone_blob) attribute macro. This
+//! implements [Serialize] and [Deserialize] for the type itself,
+//! using [serialize_clone_blob] and [deserialize_clone_blob]. Each
+//! time it is serialized, the value is cloned into a new blob.
+//!
+//! # Examples
+//! ```
+//! # use serde::{Deserialize, Serialize};
+//! # use std::sync::Arc;
+//! # use swipl::prelude::*;
+//! #[arc_blob("connection", defaults)]
+//! struct Connection {
+//!     id: u64,
+//! }
+//!
+//! #[derive(Serialize, Deserialize)]
+//! struct Session {
+//!     user: String,
+//!     #[serde(with = "swipl::serde::blob")]
+//!     connection: Arc<Connection>,
+//! }
+//!
+//! #[clone_blob("settings", defaults, serde)]
+//! #[derive(Clone)]
+//! struct Settings {
+//!     verbose: bool,
+//! }
+//!
+//! #[derive(Serialize, Deserialize)]
+//! struct Job {
+//!     name: String,
+//!     // no attribute needed, this is stored as a settings blob
+//!     settings: Settings,
+//! }
+//! ```
+use crate::atom::Atom;
+use crate::blob::{ArcBlob, CloneBlob};
+use crate::context::*;
+use crate::term::ser::SerializingSwiplTermState;
+use crate::term::{TermGetable, Unifiable};
+use ::serde::{de, ser, Deserialize, Deserializer, Serialize, Serializer};
+use std::sync::Arc;
+
+fn serialize_blob<U: Unifiable, S: Serializer>(blob: &U, serializer: S) -> Result<S::Ok, S::Error> {
+    if !SerializingSwiplTermState::is_serializing_swipl_term() {
+        return Err(ser::Error::custom(
+            "blobs can only be serialized into prolog terms",
+        ));
+    }
+
+    let context = unsafe { unmanaged_engine_context() };
+    let term = context.new_term_ref();
+    let atom = term
+        .unify(blob)
+        .and_then(|_| term.get::<Atom>())
+        .map_err(|_| ser::Error::custom("could not create blob"));
+    unsafe { term.reset() };
+
+    // the atom holds a reference, keeping the blob alive until it is
+    // part of the serialized term.
+    atom?.serialize(serializer)
+}
+
+fn deserialize_blob<'de, T: TermGetable, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<T, D::Error> {
+    let atom = Atom::deserialize(deserializer)?;
+
+    let context = unsafe { unmanaged_engine_context() };
+    let term = context.new_term_ref();
+    let blob = term
+        .unify(&atom)
+        .and_then(|_| term.get::<T>())
+        .map_err(|_| de::Error::custom(format!("expected a {} blob", T::name())));
+    unsafe { term.reset() };
+
+    blob
+}
+
+/// Serialize an arc blob as a reference to the blob.
+pub fn serialize<T: ArcBlob, S: Serializer>(
+    blob: &Arc<T>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serialize_blob(blob, serializer)
+}
+
+/// Deserialize a reference to an arc blob.
+pub fn deserialize<'de, T: ArcBlob, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Arc<T>, D::Error> {
+    deserialize_blob(deserializer)
+}
+
+/// Serialize a clone blob as a blob holding a clone of the value.
+///
+/// This is used by the serde implementation generated by the
+/// [clone_blob](crate::prelude::clone_blob) macro with the `serde`
+/// option.
+pub fn serialize_clone_blob<T: CloneBlob + Unifiable, S: Serializer>(
+    blob: &T,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serialize_blob(blob, serializer)
+}
+
+/// Deserialize a clone blob into a clone of the value it holds.
+///
+/// This is used by the serde implementation generated by the
+/// [clone_blob](crate::prelude::clone_blob) macro with the `serde`
+/// option.
+pub fn deserialize_clone_blob<'de, T: CloneBlob + TermGetable, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<T, D::Error> {
+    deserialize_blob(deserializer)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::prelude::*;
+    use crate::term::de::from_term;
+    use crate::term::ser::to_term;
+    use serde::{Deserialize, Serialize};
+    use std::sync::Arc;
+
+    #[arc_blob("serde_resource", defaults)]
+    struct Resource {
+        id: u64,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    struct Holder {
+        name: String,
+        #[serde(with = "crate::serde::blob")]
+        resource: Arc<Resource>,
+    }
+
+    #[test]
+    fn roundtrip_struct_with_blob_field() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let holder = Holder {
+            name: "db".to_string(),
+            resource: Arc::new(Resource { id: 42 }),
+        };
+
+        let term = context.new_term_ref();
+        to_term(&context, &term, &holder).unwrap();
+
+        let resource: Arc<Resource> = term.get_dict_key("resource")?;
+        assert!(Arc::ptr_eq(&holder.resource, &resource));
+
+        let result: Holder = from_term(&context, &term).unwrap();
+        assert_eq!("db", result.name);
+        assert_eq!(42, result.resource.id);
+        assert!(Arc::ptr_eq(&holder.resource, &result.resource));
+
+        Ok(())
+    }
+
+    #[clone_blob("serde_settings", defaults, serde)]
+    #[derive(Clone, Debug, PartialEq)]
+    struct Settings {
+        verbose: bool,
+        level: u8,
+    }
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Job {
+        name: String,
+        settings: Settings,
+    }
+
+    #[test]
+    fn roundtrip_struct_with_serde_clone_blob() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let job = Job {
+            name: "build".to_string(),
+            settings: Settings {
+                verbose: true,
+                level: 3,
+            },
+        };
+
+        let term = context.new_term_ref();
+        to_term(&context, &term, &job).unwrap();
+
+        let settings: Settings = term.get_dict_key("settings")?;
+        assert_eq!(job.settings, settings);
+
+        let result: Job = from_term(&context, &term).unwrap();
+        assert_eq!(job, result);
+
+        Ok(())
+    }
+}
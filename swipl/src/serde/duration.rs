@@ -0,0 +1,126 @@
+//! Serialize durations as a number of seconds.
+//!
+//! A [Duration] is serialized as a float holding the number of
+//! seconds. When deserializing, the following representations are
+//! accepted:
+//! - a number of seconds, either an integer or a float,
+//! - `duration(Seconds)`,
+//! - a clock time like `Hours:Minutes:Seconds` or `Minutes:Seconds`.
+//!
+//! # Examples
+//! ```
+//! # use serde::{Deserialize, Serialize};
+//! # use std::time::Duration;
+//! #[derive(Serialize, Deserialize)]
+//! struct Timeout {
+//!     // deserialized from terms like 90.5 or 0:1:30.5
+//!     #[serde(with = "swipl::serde::duration")]
+//!     after: Duration,
+//! }
+//! ```
+use ::serde::de::{self, SeqAccess, Visitor};
+use ::serde::{Deserialize, Deserializer, Serializer};
+use std::fmt;
+use std::time::Duration;
+
+/// The parts of a clock time, from most to least significant.
+struct ClockParts(Vec<f64>);
+
+impl<'de> Deserialize<'de> for ClockParts {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(ClockPartsVisitor)
+    }
+}
+
+struct ClockPartsVisitor;
+
+impl<'de> Visitor<'de> for ClockPartsVisitor {
+    type Value = ClockParts;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a number of seconds or a clock time like H:M:S")
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<Self::Value, E> {
+        Ok(ClockParts(vec![v as f64]))
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<Self::Value, E> {
+        Ok(ClockParts(vec![v as f64]))
+    }
+
+    fn visit_f64<E: de::Error>(self, v: f64) -> Result<Self::Value, E> {
+        Ok(ClockParts(vec![v]))
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        // A:B is a binary compound, where B may itself be a clock time.
+        let first: f64 = seq
+            .next_element()?
+            .ok_or_else(|| de::Error::invalid_length(0, &self))?;
+        let rest: ClockParts = seq
+            .next_element()?
+            .ok_or_else(|| de::Error::invalid_length(1, &self))?;
+        let mut parts = vec![first];
+        parts.extend(rest.0);
+
+        Ok(ClockParts(parts))
+    }
+}
+
+/// Serialize a duration as a float holding the number of seconds.
+pub fn serialize<S: Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_f64(duration.as_secs_f64())
+}
+
+/// Deserialize a duration from a number of seconds or a clock time.
+pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+    let parts = ClockParts::deserialize(deserializer)?;
+    let seconds = parts.0.iter().fold(0.0, |acc, part| acc * 60.0 + part);
+    if !seconds.is_finite() || seconds < 0.0 {
+        return Err(de::Error::custom(format!("invalid duration: {}", seconds)));
+    }
+
+    Ok(Duration::from_secs_f64(seconds))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::prelude::*;
+    use crate::term::de::from_term;
+    use serde::Deserialize;
+    use std::time::Duration;
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct Timeout {
+        #[serde(with = "crate::serde::duration")]
+        after: Duration,
+    }
+
+    #[test]
+    fn deserialize_durations() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let expected = Timeout {
+            after: Duration::from_secs_f64(90.5),
+        };
+        for text in [
+            "_{after: 90.5}",
+            "_{after: 0:1:30.5}",
+            "_{after: 1:30.5}",
+            "_{after: duration(90.5)}",
+        ] {
+            let term = context.term_from_string(text)?;
+            let result: Timeout = from_term(&context, &term).unwrap();
+            assert_eq!(expected, result);
+        }
+
+        let term = context.term_from_string("_{after: 90}")?;
+        let result: Timeout = from_term(&context, &term).unwrap();
+        assert_eq!(Duration::from_secs(90), result.after);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,183 @@
+//! Serialize C-like enums as integers.
+//!
+//! By default, a unit variant of an enum is serialized as an atom
+//! holding the variant name. This module instead serializes it as the
+//! index of the variant in the enum declaration, starting at 0, which
+//! is useful for numeric protocols. When deserializing, an integer
+//! that does not correspond to a variant results in an error.
+//!
+//! Note that the index is the position of the variant, not an
+//! explicit discriminant given with `= value`. Only unit variants are
+//! supported.
+//!
+//! # Examples
+//! ```
+//! # use serde::{Deserialize, Serialize};
+//! #[derive(Serialize, Deserialize)]
+//! enum Level {
+//!     Low,
+//!     Medium,
+//!     High,
+//! }
+//!
+//! #[derive(Serialize, Deserialize)]
+//! struct Alarm {
+//!     // serialized as an integer like 2
+//!     #[serde(with = "swipl::serde::enum_as_int")]
+//!     level: Level,
+//! }
+//! ```
+use ::serde::de::value::{Error, U32Deserializer};
+use ::serde::de::{self, IntoDeserializer};
+use ::serde::ser::{self, Impossible};
+use ::serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// A serializer which only accepts unit variants, returning their index.
+struct VariantIndexSerializer;
+
+macro_rules! unsupported {
+    ($($method:ident($($arg:ty),*) -> $ret:ty;)*) => {
+        $(
+            fn $method(self, $(_: $arg),*) -> Result<$ret, Error> {
+                Err(ser::Error::custom("expected a unit variant"))
+            }
+        )*
+    };
+}
+
+impl Serializer for VariantIndexSerializer {
+    type Ok = u32;
+    type Error = Error;
+    type SerializeSeq = Impossible<u32, Error>;
+    type SerializeTuple = Impossible<u32, Error>;
+    type SerializeTupleStruct = Impossible<u32, Error>;
+    type SerializeTupleVariant = Impossible<u32, Error>;
+    type SerializeMap = Impossible<u32, Error>;
+    type SerializeStruct = Impossible<u32, Error>;
+    type SerializeStructVariant = Impossible<u32, Error>;
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        variant_index: u32,
+        _variant: &'static str,
+    ) -> Result<u32, Error> {
+        Ok(variant_index)
+    }
+
+    fn serialize_some<T: ?Sized + Serialize>(self, _value: &T) -> Result<u32, Error> {
+        Err(ser::Error::custom("expected a unit variant"))
+    }
+
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        _value: &T,
+    ) -> Result<u32, Error> {
+        Err(ser::Error::custom("expected a unit variant"))
+    }
+
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<u32, Error> {
+        Err(ser::Error::custom("expected a unit variant"))
+    }
+
+    unsupported! {
+        serialize_bool(bool) -> u32;
+        serialize_i8(i8) -> u32;
+        serialize_i16(i16) -> u32;
+        serialize_i32(i32) -> u32;
+        serialize_i64(i64) -> u32;
+        serialize_u8(u8) -> u32;
+        serialize_u16(u16) -> u32;
+        serialize_u32(u32) -> u32;
+        serialize_u64(u64) -> u32;
+        serialize_f32(f32) -> u32;
+        serialize_f64(f64) -> u32;
+        serialize_char(char) -> u32;
+        serialize_str(&str) -> u32;
+        serialize_bytes(&[u8]) -> u32;
+        serialize_none() -> u32;
+        serialize_unit() -> u32;
+        serialize_unit_struct(&'static str) -> u32;
+        serialize_seq(Option<usize>) -> Self::SerializeSeq;
+        serialize_tuple(usize) -> Self::SerializeTuple;
+        serialize_tuple_struct(&'static str, usize) -> Self::SerializeTupleStruct;
+        serialize_tuple_variant(&'static str, u32, &'static str, usize) -> Self::SerializeTupleVariant;
+        serialize_map(Option<usize>) -> Self::SerializeMap;
+        serialize_struct(&'static str, usize) -> Self::SerializeStruct;
+        serialize_struct_variant(&'static str, u32, &'static str, usize) -> Self::SerializeStructVariant;
+    }
+}
+
+/// Serialize a unit variant as its index in the enum declaration.
+pub fn serialize<T: Serialize, S: Serializer>(value: &T, serializer: S) -> Result<S::Ok, S::Error> {
+    let index = value
+        .serialize(VariantIndexSerializer)
+        .map_err(ser::Error::custom)?;
+
+    serializer.serialize_u32(index)
+}
+
+/// Deserialize a unit variant from its index in the enum declaration.
+pub fn deserialize<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+where
+    T: Deserialize<'de>,
+    D: Deserializer<'de>,
+{
+    let index = u32::deserialize(deserializer)?;
+    let index_deserializer: U32Deserializer<Error> = index.into_deserializer();
+
+    T::deserialize(index_deserializer).map_err(de::Error::custom)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::prelude::*;
+    use crate::term::de::from_term;
+    use crate::term::ser::to_term;
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    enum Level {
+        Low,
+        Medium,
+        High,
+    }
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Alarm {
+        #[serde(with = "crate::serde::enum_as_int")]
+        level: Level,
+    }
+
+    #[test]
+    fn roundtrip_enum_as_int() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        for (level, index) in [(Level::Low, 0), (Level::Medium, 1), (Level::High, 2)] {
+            let alarm = Alarm { level };
+
+            let term = context.new_term_ref();
+            to_term(&context, &term, &alarm).unwrap();
+
+            let expected = context.term_from_string(&format!("_{{level:{}}}", index))?;
+            term.unify(&expected)?;
+
+            let result: Alarm = from_term(&context, &term).unwrap();
+            assert_eq!(alarm, result);
+        }
+
+        let term = context.term_from_string("_{level:3}")?;
+        assert!(from_term::<_, Alarm>(&context, &term).is_err());
+
+        Ok(())
+    }
+}
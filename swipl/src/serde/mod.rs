@@ -0,0 +1,12 @@
+//! Serde adapters for representing rust data in prolog-specific ways.
+//!
+//! The modules in here are meant to be used with serde's `with`
+//! field attribute, for values whose default serialization does not
+//! fit the intended prolog representation.
+pub mod bigint_as_atom;
+pub mod blob;
+pub mod duration;
+pub mod enum_as_int;
+pub mod one_or_many;
+pub mod pairs;
+pub mod tuple_as_list;
@@ -0,0 +1,126 @@
+//! Deserialize a single term as a one-element sequence.
+//!
+//! Prolog code often accepts either a single value or a list of
+//! values, such as an option that takes `foo` as well as
+//! `[foo,bar]`. This module deserializes such a term into a `Vec`,
+//! wrapping a term that is not a list into a vector of one element.
+//! A vector is always serialized as a list.
+//!
+//! Note that a single term is only recognized as such when
+//! deserializing from a prolog term. Other deserializers will treat
+//! the value as a single element.
+//!
+//! # Examples
+//! ```
+//! # use serde::{Deserialize, Serialize};
+//! #[derive(Serialize, Deserialize)]
+//! struct Options {
+//!     // deserialized from either a term like foo or a list like [foo,bar]
+//!     #[serde(with = "swipl::serde::one_or_many")]
+//!     names: Vec<String>,
+//! }
+//! ```
+use crate::term::de::ONE_OR_MANY_STRUCT_NAME;
+use ::serde::de::{SeqAccess, Visitor};
+use ::serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+use std::marker::PhantomData;
+
+/// Serialize a vector as a list.
+pub fn serialize<'a, V, T, S>(values: &'a V, serializer: S) -> Result<S::Ok, S::Error>
+where
+    &'a V: IntoIterator<Item = &'a T>,
+    T: Serialize + 'a,
+    S: Serializer,
+{
+    serializer.collect_seq(values)
+}
+
+struct OneOrManyVisitor<T>(PhantomData<T>);
+
+impl<'de, T: Deserialize<'de>> Visitor<'de> for OneOrManyVisitor<T> {
+    type Value = Vec<T>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a single value or a list of values")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let mut values = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+        while let Some(value) = seq.next_element()? {
+            values.push(value);
+        }
+
+        Ok(values)
+    }
+
+    fn visit_newtype_struct<D: Deserializer<'de>>(
+        self,
+        deserializer: D,
+    ) -> Result<Self::Value, D::Error> {
+        Ok(vec![T::deserialize(deserializer)?])
+    }
+}
+
+/// Deserialize a vector from either a list or a single term.
+pub fn deserialize<'de, T, D>(deserializer: D) -> Result<Vec<T>, D::Error>
+where
+    T: Deserialize<'de>,
+    D: Deserializer<'de>,
+{
+    deserializer.deserialize_newtype_struct(ONE_OR_MANY_STRUCT_NAME, OneOrManyVisitor(PhantomData))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::prelude::*;
+    use crate::term::de::from_term;
+    use crate::term::ser::to_term;
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Names {
+        #[serde(with = "crate::serde::one_or_many")]
+        names: Vec<Atom>,
+    }
+
+    #[test]
+    fn deserialize_one_or_many() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let term = context.term_from_string("_{names:foo}")?;
+        let result: Names = from_term(&context, &term).unwrap();
+        assert_eq!(vec![Atom::new("foo")], result.names);
+
+        let term = context.term_from_string("_{names:[foo,bar]}")?;
+        let result: Names = from_term(&context, &term).unwrap();
+        assert_eq!(vec![Atom::new("foo"), Atom::new("bar")], result.names);
+
+        let term = context.term_from_string("_{names:[]}")?;
+        let result: Names = from_term(&context, &term).unwrap();
+        assert!(result.names.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn serialize_one_or_many_as_list() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let names = Names {
+            names: vec![Atom::new("foo")],
+        };
+
+        let term = context.new_term_ref();
+        to_term(&context, &term, &names).unwrap();
+
+        let expected = context.term_from_string("_{names:[foo]}")?;
+        term.unify(&expected)?;
+
+        Ok(())
+    }
+}
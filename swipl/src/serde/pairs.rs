@@ -0,0 +1,104 @@
+//! Serialize maps as prolog pair lists.
+//!
+//! By default, maps are serialized as prolog dictionaries, which only
+//! allow atom and small integer keys. This module instead serializes
+//! a map as a list of `Key-Value` pairs, which allows keys to be
+//! arbitrary terms.
+//!
+//! The map type can be anything that can be iterated over as
+//! key-value pairs and collected from them, such as a `HashMap` or a
+//! `BTreeMap`.
+//!
+//! # Examples
+//! ```
+//! # use std::collections::HashMap;
+//! # use serde::{Deserialize, Serialize};
+//! #[derive(Serialize, Deserialize, PartialEq, Eq, Hash)]
+//! struct Point(i64, i64);
+//!
+//! #[derive(Serialize, Deserialize)]
+//! struct Grid {
+//!     // serialized as a list like ['Point'(1,2)-"a"]
+//!     #[serde(with = "swipl::serde::pairs")]
+//!     cells: HashMap<Point, String>,
+//! }
+//! ```
+use ::serde::ser::SerializeTupleStruct;
+use ::serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::iter::FromIterator;
+
+struct Pair<'a, K, V>(&'a K, &'a V);
+
+impl<'a, K: Serialize, V: Serialize> Serialize for Pair<'a, K, V> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut pair = serializer.serialize_tuple_struct("-", 2)?;
+        pair.serialize_field(self.0)?;
+        pair.serialize_field(self.1)?;
+
+        pair.end()
+    }
+}
+
+/// Serialize a map as a list of `Key-Value` pairs.
+pub fn serialize<'a, M, K, V, S>(map: &'a M, serializer: S) -> Result<S::Ok, S::Error>
+where
+    &'a M: IntoIterator<Item = (&'a K, &'a V)>,
+    K: Serialize + 'a,
+    V: Serialize + 'a,
+    S: Serializer,
+{
+    serializer.collect_seq(map.into_iter().map(|(k, v)| Pair(k, v)))
+}
+
+/// Deserialize a map from a list of `Key-Value` pairs.
+pub fn deserialize<'de, M, K, V, D>(deserializer: D) -> Result<M, D::Error>
+where
+    M: FromIterator<(K, V)>,
+    K: Deserialize<'de>,
+    V: Deserialize<'de>,
+    D: Deserializer<'de>,
+{
+    let pairs: Vec<(K, V)> = Vec::deserialize(deserializer)?;
+
+    Ok(pairs.into_iter().collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::prelude::*;
+    use crate::term::de::from_term;
+    use crate::term::ser::to_term;
+    use serde::{Deserialize, Serialize};
+    use std::collections::HashMap;
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Hash)]
+    struct Point(i64, i64);
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Grid {
+        #[serde(with = "crate::serde::pairs")]
+        cells: HashMap<Point, String>,
+    }
+
+    #[test]
+    fn roundtrip_map_with_compound_keys() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let mut cells = HashMap::new();
+        cells.insert(Point(1, 2), "a".to_string());
+        let grid = Grid { cells };
+
+        let term = context.new_term_ref();
+        to_term(&context, &term, &grid).unwrap();
+
+        let expected = context.term_from_string("_{cells:['Point'(1,2)-\"a\"]}")?;
+        term.unify(&expected)?;
+
+        let result: Grid = from_term(&context, &term).unwrap();
+        assert_eq!(grid, result);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,105 @@
+//! Serialize tuples as prolog lists.
+//!
+//! By default, tuples are serialized as a comma-separated compound
+//! like `(a,b,c)`. This module instead serializes a tuple as a list
+//! like `[a,b,c]`, which is useful when passing it to a predicate
+//! that expects a list.
+//!
+//! Tuples of up to 12 elements are supported. As the deserializer
+//! already accepts a list of the right length for a tuple,
+//! deserializing does not need any special handling.
+//!
+//! # Examples
+//! ```
+//! # use serde::{Deserialize, Serialize};
+//! #[derive(Serialize, Deserialize)]
+//! struct Color {
+//!     // serialized as a list like [255,128,0]
+//!     #[serde(with = "swipl::serde::tuple_as_list")]
+//!     rgb: (u8, u8, u8),
+//! }
+//! ```
+use ::serde::ser::SerializeSeq;
+use ::serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// A tuple which can be serialized as a list.
+pub trait ListTuple {
+    /// Serialize this tuple as a sequence of its elements.
+    fn serialize_as_list<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error>;
+}
+
+macro_rules! list_tuple {
+    ($len:expr => $($name:ident $index:tt)+) => {
+        impl<$($name: Serialize),+> ListTuple for ($($name,)+) {
+            fn serialize_as_list<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                let mut seq = serializer.serialize_seq(Some($len))?;
+                $(seq.serialize_element(&self.$index)?;)+
+
+                seq.end()
+            }
+        }
+    };
+}
+
+list_tuple!(1 => T0 0);
+list_tuple!(2 => T0 0 T1 1);
+list_tuple!(3 => T0 0 T1 1 T2 2);
+list_tuple!(4 => T0 0 T1 1 T2 2 T3 3);
+list_tuple!(5 => T0 0 T1 1 T2 2 T3 3 T4 4);
+list_tuple!(6 => T0 0 T1 1 T2 2 T3 3 T4 4 T5 5);
+list_tuple!(7 => T0 0 T1 1 T2 2 T3 3 T4 4 T5 5 T6 6);
+list_tuple!(8 => T0 0 T1 1 T2 2 T3 3 T4 4 T5 5 T6 6 T7 7);
+list_tuple!(9 => T0 0 T1 1 T2 2 T3 3 T4 4 T5 5 T6 6 T7 7 T8 8);
+list_tuple!(10 => T0 0 T1 1 T2 2 T3 3 T4 4 T5 5 T6 6 T7 7 T8 8 T9 9);
+list_tuple!(11 => T0 0 T1 1 T2 2 T3 3 T4 4 T5 5 T6 6 T7 7 T8 8 T9 9 T10 10);
+list_tuple!(12 => T0 0 T1 1 T2 2 T3 3 T4 4 T5 5 T6 6 T7 7 T8 8 T9 9 T10 10 T11 11);
+
+/// Serialize a tuple as a list of its elements.
+pub fn serialize<T: ListTuple, S: Serializer>(tuple: &T, serializer: S) -> Result<S::Ok, S::Error> {
+    tuple.serialize_as_list(serializer)
+}
+
+/// Deserialize a tuple from a list of its elements.
+pub fn deserialize<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+where
+    T: Deserialize<'de>,
+    D: Deserializer<'de>,
+{
+    T::deserialize(deserializer)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::prelude::*;
+    use crate::term::de::from_term;
+    use crate::term::ser::to_term;
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Triple {
+        #[serde(with = "crate::serde::tuple_as_list")]
+        values: (u64, String, bool),
+    }
+
+    #[test]
+    fn roundtrip_tuple_as_list() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let triple = Triple {
+            values: (42, "foo".to_string(), true),
+        };
+
+        let term = context.new_term_ref();
+        to_term(&context, &term, &triple).unwrap();
+
+        let expected = context.term_from_string("_{values:[42,\"foo\",true]}")?;
+        term.unify(&expected)?;
+
+        let result: Triple = from_term(&context, &term).unwrap();
+        assert_eq!(triple, result);
+
+        Ok(())
+    }
+}
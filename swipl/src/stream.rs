@@ -135,6 +135,38 @@ unsafe fn write_to_prolog_stream(stream: *mut fli::IOSTREAM, buf: &[u8]) -> io::
     Ok(count)
 }
 
+/// Write text to the stream, character by character.
+///
+/// Unlike the `Write` implementation, which writes bytes, this lets
+/// the stream encode each character according to its encoding.
+unsafe fn write_string_to_prolog_stream(stream: *mut fli::IOSTREAM, s: &str) -> io::Result<()> {
+    ensure_writable_prolog_stream(stream)?;
+
+    for c in s.chars() {
+        if fli::Sputcode(c as i32, stream) < 0 {
+            fli::Sclearerr(stream);
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Tried to write a character that is out of range for the encoding of this stream",
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+impl<'a> WritablePrologStream<'a> {
+    /// Write a string to this stream.
+    ///
+    /// The string is encoded according to the encoding of the
+    /// stream.
+    pub fn write_string(&mut self, s: &str) -> io::Result<()> {
+        assert_some_engine_is_active();
+
+        unsafe { write_string_to_prolog_stream(self.stream, s) }
+    }
+}
+
 impl<'a> Write for WritablePrologStream<'a> {
     fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
         assert_some_engine_is_active();
@@ -157,6 +189,18 @@ impl<'a> Write for WritablePrologStream<'a> {
     }
 }
 
+impl PrologStream {
+    /// Write a string to this stream.
+    ///
+    /// The string is encoded according to the encoding of the
+    /// stream.
+    pub fn write_string(&mut self, s: &str) -> io::Result<()> {
+        assert_some_engine_is_active();
+
+        unsafe { write_string_to_prolog_stream(self.stream, s) }
+    }
+}
+
 impl Write for PrologStream {
     fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
         assert_some_engine_is_active();
@@ -266,3 +310,86 @@ impl<'a> Read for ReadablePrologStream<'a> {
         unsafe { read_from_prolog_stream(self.stream, buf) }
     }
 }
+
+impl<'a> ReadablePrologStream<'a> {
+    /// Read the remainder of this stream into a string.
+    ///
+    /// The stream is decoded according to its encoding, character by
+    /// character.
+    pub fn read_string(&mut self) -> io::Result<String> {
+        assert_some_engine_is_active();
+        unsafe { ensure_readable_prolog_stream(self.stream)? };
+
+        let mut result = String::new();
+        loop {
+            let code = unsafe { fli::Sgetcode(self.stream) };
+            if code < 0 {
+                break;
+            }
+
+            match char::from_u32(code as u32) {
+                Some(c) => result.push(c),
+                None => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        "read a character code that is not a valid unicode character",
+                    ))
+                }
+            }
+        }
+
+        if unsafe { fli::Sferror(self.stream) } != 0 {
+            unsafe { fli::Sclearerr(self.stream) };
+            return Err(io::Error::new(
+                io::ErrorKind::Other,
+                "error while reading from prolog stream",
+            ));
+        }
+
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::context::*;
+    use crate::result::*;
+    use swipl_macros::term;
+
+    #[test]
+    fn write_and_read_multibyte_string() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let text = "héllo wörld ✓";
+        let file = context.tmp_file("stream")?;
+
+        let stream = context.new_term_ref();
+        let open = term! {context: open(#&file, write, #&stream, [encoding(utf8)])}?;
+        context.call_term_once(&open)?;
+        {
+            let mut writable: WritablePrologStream = stream.get()?;
+            writable.write_string(text).unwrap();
+        }
+        let close = term! {context: close(#&stream)}?;
+        context.call_term_once(&close)?;
+
+        let stream = context.new_term_ref();
+        let open = term! {context: open(#&file, read, #&stream, [encoding(utf8)])}?;
+        context.call_term_once(&open)?;
+        let read = {
+            let mut readable: ReadablePrologStream = stream.get()?;
+            readable.read_string().unwrap()
+        };
+        let close = term! {context: close(#&stream)}?;
+        context.call_term_once(&close)?;
+
+        std::fs::remove_file(&file).unwrap();
+
+        assert_eq!(text, read);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,149 @@
+//! Conversion between rust maps and prolog association lists.
+//!
+//! A [HashMap] or [BTreeMap] is unified with a term as an AVL tree
+//! from `library(assoc)`, as constructed by `list_to_assoc/2`.
+//! Retrieving a map from a term goes through `assoc_to_list/2`.
+//!
+//! Unlike going through serde with [to_term](super::ser::to_term)
+//! and [from_term](super::de::from_term), this does not require the
+//! key and value types to implement serde traits, only [Unifiable]
+//! and [TermGetable].
+use super::*;
+use std::collections::{BTreeMap, HashMap};
+use std::hash::Hash;
+use std::iter::FromIterator;
+use swipl_macros::pred;
+
+fn unify_assoc<'b, K: 'b + Unifiable, V: 'b + Unifiable, I: IntoIterator<Item = (&'b K, &'b V)>>(
+    term: &Term,
+    entries: I,
+) -> bool {
+    // unsafe justification: This context will only exist inside this implementation. We know we are in some valid context for term handling, so that's great.
+    let context = unsafe { unmanaged_engine_context() };
+    let frame = context.open_frame();
+    let [pairs, assoc] = frame.new_term_refs();
+
+    let mut cur = pairs.clone();
+    for (key, value) in entries {
+        let pair = frame.new_term_ref();
+        let success = pair.unify(Functor::new("-", 2)).is_ok()
+            && pair.unify_arg(1, key).is_ok()
+            && pair.unify_arg(2, value).is_ok();
+        match (success, frame.unify_list_functor(&cur)) {
+            (true, Ok((head, tail))) if head.unify(&pair).is_ok() => cur = tail,
+            _ => {
+                frame.discard();
+                return false;
+            }
+        }
+    }
+
+    let success = cur.unify(Nil).is_ok()
+        && frame
+            .call_once(pred!("list_to_assoc/2"), [&pairs, &assoc])
+            .is_ok()
+        && term.unify(&assoc).is_ok();
+    if success {
+        frame.close();
+    } else {
+        frame.discard();
+    }
+
+    success
+}
+
+fn get_assoc<K: TermGetable, V: TermGetable, M: FromIterator<(K, V)>>(term: &Term) -> Option<M> {
+    // unsafe justification: This context will only exist inside this implementation. We know we are in some valid context for term handling, so that's great.
+    let context = unsafe { unmanaged_engine_context() };
+    let frame = context.open_frame();
+    let pairs = frame.new_term_ref();
+    if frame
+        .call_once(pred!("assoc_to_list/2"), [term, &pairs])
+        .is_err()
+    {
+        frame.discard();
+        return None;
+    }
+
+    let mut iter = frame.term_list_iter(&pairs);
+    let result: Option<M> = (&mut iter)
+        .map(|pair| {
+            let [key, value] = frame.compound_terms(&pair).ok()?;
+            Some((key.get().ok()?, value.get().ok()?))
+        })
+        .collect();
+    frame.discard();
+
+    result
+}
+
+unsafe impl<K: Unifiable, V: Unifiable, S> Unifiable for HashMap<K, V, S> {
+    fn unify(&self, term: &Term) -> bool {
+        term.assert_term_handling_possible();
+
+        unify_assoc(term, self.iter())
+    }
+}
+
+unsafe impl<K: Unifiable, V: Unifiable> Unifiable for BTreeMap<K, V> {
+    fn unify(&self, term: &Term) -> bool {
+        term.assert_term_handling_possible();
+
+        unify_assoc(term, self.iter())
+    }
+}
+
+unsafe impl<K: TermGetable + Eq + Hash, V: TermGetable> TermGetable for HashMap<K, V> {
+    fn get(term: &Term) -> Option<Self> {
+        term.assert_term_handling_possible();
+
+        get_assoc(term)
+    }
+
+    fn name() -> &'static str {
+        "assoc"
+    }
+}
+
+unsafe impl<K: TermGetable + Ord, V: TermGetable> TermGetable for BTreeMap<K, V> {
+    fn get(term: &Term) -> Option<Self> {
+        term.assert_term_handling_possible();
+
+        get_assoc(term)
+    }
+
+    fn name() -> &'static str {
+        "assoc"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn roundtrip_map_through_assoc() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let map = HashMap::from([(1_u64, "one".to_string()), (2, "two".to_string())]);
+        let term = context.new_term_ref();
+        term.unify(&map)?;
+
+        let [key, value] = context.new_term_refs();
+        key.unify(2_u64)?;
+        context.call_once(pred!("get_assoc/3"), [&term, &key, &value])?;
+        assert_eq!("two", value.get::<String>()?);
+
+        let result: HashMap<u64, String> = term.get()?;
+        assert_eq!(map, result);
+        let result: BTreeMap<u64, String> = term.get()?;
+        assert_eq!(
+            vec![(1, "one".to_string()), (2, "two".to_string())],
+            result.into_iter().collect::<Vec<_>>()
+        );
+
+        Ok(())
+    }
+}
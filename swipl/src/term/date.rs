@@ -0,0 +1,213 @@
+//! Conversion between rust and SWI-Prolog date and time terms.
+//!
+//! SWI-Prolog represents dates and times with three different
+//! compounds. `date(Y,M,D)` is a date without a time,
+//! `time(H,Mn,S)` is a time without a date, and
+//! `date(Y,M,D,H,Mn,S,Off,TZ,DST)` is a full timestamp as produced by
+//! `stamp_date_time/3`. A [PrologDate] can be retrieved from any of
+//! these, with the fields that are missing from the term set to their
+//! defaults. It is always unified as the full timestamp compound.
+use super::*;
+use crate::{term_getable, unifiable};
+use std::convert::TryInto;
+
+/// A date and time, as represented by SWI-Prolog date compounds.
+///
+/// The default value is midnight of January 1st 1970, in UTC, with
+/// an unknown time zone and daylight saving time status.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PrologDate {
+    pub year: i64,
+    pub month: u8,
+    pub day: u8,
+    pub hour: u8,
+    pub minute: u8,
+    pub second: f64,
+    /// The offset to UTC in seconds. Following SWI-Prolog, this is
+    /// positive for time zones west of Greenwich.
+    pub utc_offset: i64,
+    /// The name of the time zone, if known.
+    pub time_zone: Option<String>,
+    /// Whether daylight saving time is in effect, if known.
+    pub dst: Option<bool>,
+}
+
+impl Default for PrologDate {
+    fn default() -> Self {
+        Self {
+            year: 1970,
+            month: 1,
+            day: 1,
+            hour: 0,
+            minute: 0,
+            second: 0.0,
+            utc_offset: 0,
+            time_zone: None,
+            dst: None,
+        }
+    }
+}
+
+fn get_small(term: &Term, index: usize) -> Option<u8> {
+    term.get_arg::<u64>(index).ok()?.try_into().ok()
+}
+
+fn get_date(term: &Term) -> Option<PrologDate> {
+    let functor: Functor = term.get().ok()?;
+    let mut date = PrologDate::default();
+    if functor == Functor::new("time", 3) {
+        date.hour = get_small(term, 1)?;
+        date.minute = get_small(term, 2)?;
+        date.second = term.get_arg(3).ok()?;
+
+        return Some(date);
+    }
+
+    if functor != Functor::new("date", 3) && functor != Functor::new("date", 9) {
+        return None;
+    }
+
+    date.year = term.get_arg(1).ok()?;
+    date.month = get_small(term, 2)?;
+    date.day = get_small(term, 3)?;
+    if functor.arity() == 9 {
+        date.hour = get_small(term, 4)?;
+        date.minute = get_small(term, 5)?;
+        date.second = term.get_arg(6).ok()?;
+        date.utc_offset = term.get_arg(7).ok()?;
+        let time_zone: Atom = term.get_arg(8).ok()?;
+        date.time_zone = match time_zone.name().as_str() {
+            "-" => None,
+            name => Some(name.to_string()),
+        };
+        let dst: Atom = term.get_arg(9).ok()?;
+        date.dst = match dst.name().as_str() {
+            "true" => Some(true),
+            "false" => Some(false),
+            "-" => None,
+            _ => return None,
+        };
+    }
+
+    Some(date)
+}
+
+fn unify_date(term: &Term, date: &PrologDate) -> bool {
+    let time_zone = Atom::new(date.time_zone.as_deref().unwrap_or("-"));
+    let dst = Atom::new(match date.dst {
+        Some(true) => "true",
+        Some(false) => "false",
+        None => "-",
+    });
+
+    // unsafe justification: This context will only exist inside this function. We know we are in some valid context for term handling, so that's great.
+    let context = unsafe { unmanaged_engine_context() };
+    let frame = context.open_frame();
+    let success = term.unify(Functor::new("date", 9)).is_ok()
+        && term.unify_arg(1, date.year).is_ok()
+        && term.unify_arg(2, date.month as u64).is_ok()
+        && term.unify_arg(3, date.day as u64).is_ok()
+        && term.unify_arg(4, date.hour as u64).is_ok()
+        && term.unify_arg(5, date.minute as u64).is_ok()
+        && term.unify_arg(6, date.second).is_ok()
+        && term.unify_arg(7, date.utc_offset).is_ok()
+        && term.unify_arg(8, &time_zone).is_ok()
+        && term.unify_arg(9, &dst).is_ok();
+    if success {
+        frame.close();
+    } else {
+        frame.discard();
+    }
+
+    success
+}
+
+unifiable! {
+    (self: PrologDate, term) => {
+        unify_date(term, self)
+    }
+}
+
+term_getable! {
+    (PrologDate, "date", term) => {
+        get_date(term)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use swipl_macros::pred;
+
+    #[test]
+    fn get_date_from_date_only() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let term = context.term_from_string("date(2024, 2, 29)")?;
+        let date: PrologDate = term.get()?;
+        assert_eq!(
+            PrologDate {
+                year: 2024,
+                month: 2,
+                day: 29,
+                ..Default::default()
+            },
+            date
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn get_date_from_time_only() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let term = context.term_from_string("time(13, 37, 4.5)")?;
+        let date: PrologDate = term.get()?;
+        assert_eq!(
+            PrologDate {
+                hour: 13,
+                minute: 37,
+                second: 4.5,
+                ..Default::default()
+            },
+            date
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn roundtrip_full_date() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let [stamp, date_term, time_zone] = context.new_term_refs();
+        stamp.unify(0.0)?;
+        time_zone.unify(Atom::new("UTC"))?;
+        context.call_once(pred!("stamp_date_time/3"), [&stamp, &date_term, &time_zone])?;
+        let date: PrologDate = date_term.get()?;
+        assert_eq!(
+            PrologDate {
+                time_zone: Some("UTC".to_string()),
+                ..Default::default()
+            },
+            date
+        );
+
+        let term = context.new_term_ref();
+        term.unify(&date)?;
+        assert_eq!(
+            "date(1970,1,1,0,0,0.0,0,'UTC',-)",
+            context.string_from_term(&term)?
+        );
+        assert_eq!(date, term.get::<PrologDate>()?);
+
+        Ok(())
+    }
+}
@@ -1,16 +1,31 @@
 //! Deserialization of rust values from prolog terms.
-use super::ser::ATOM_STRUCT_NAME;
+use super::ser::{NoneRepr, ATOM_STRUCT_NAME};
 use super::*;
 use crate::dict::*;
 use crate::functor::*;
 use crate::text::*;
-use crate::{atom, functor};
+use crate::{atom, functor, term};
+use serde::de::DeserializeOwned;
 use serde::de::{self, DeserializeSeed, EnumAccess, MapAccess, SeqAccess, VariantAccess, Visitor};
 use serde::Deserialize;
 use std::cell::Cell;
+use std::collections::HashMap;
 use std::fmt::{self, Display};
 
+/// Newtype struct name used by [crate::serde::one_or_many] to ask the
+/// deserializer to treat a non-list term as a one-element sequence.
+pub(crate) const ONE_OR_MANY_STRUCT_NAME: &str = "$swipl::private::one_or_many";
+
 /// Deserialize a term into a rust value using serde.
+///
+/// Prolog lists deserialize into any rust sequence type, including
+/// sets like `HashSet` and `BTreeSet`. As prolog lists may contain
+/// duplicates, deserializing into a set silently drops them.
+///
+/// Enums are matched by variant name. C-like enums which are sent as
+/// their integer discriminant instead can be deserialized by deriving
+/// `Deserialize_repr` from the `serde_repr` crate, which reads them
+/// as an integer.
 pub fn from_term<'a, C: QueryableContextType, T>(
     context: &'a Context<C>,
     term: &Term<'a>,
@@ -18,24 +33,473 @@ pub fn from_term<'a, C: QueryableContextType, T>(
 where
     T: Deserialize<'a>,
 {
-    let deserializer = Deserializer {
+    from_term_with_limits(context, term, DeserializeLimits::new())
+}
+
+/// Deserialize a term into a rust value using serde, enforcing the given limits.
+///
+/// This is like [from_term], but fails with [Error::LimitExceeded]
+/// if the term exceeds any of the given limits. Use this when
+/// deserializing terms from untrusted sources.
+pub fn from_term_with_limits<'a, C: QueryableContextType, T>(
+    context: &'a Context<C>,
+    term: &Term<'a>,
+    limits: DeserializeLimits,
+) -> Result<T>
+where
+    T: Deserialize<'a>,
+{
+    from_term_with_configuration(
         context,
-        term: term.clone(),
-    };
+        term,
+        DeserializerConfiguration::new().limits(limits),
+    )
+}
+
+/// Deserialize a term into a rust value using serde, using the given configuration.
+///
+/// This is like [from_term], but allows overriding the defaults
+/// described in [DeserializerConfiguration].
+pub fn from_term_with_configuration<'a, C: QueryableContextType, T>(
+    context: &'a Context<C>,
+    term: &Term<'a>,
+    configuration: DeserializerConfiguration,
+) -> Result<T>
+where
+    T: Deserialize<'a>,
+{
+    let deserializer = Deserializer::new_with_configuration(context, term.clone(), configuration);
 
     Deserialize::deserialize(deserializer)
 }
 
+type DynDeserializer<T> = Box<
+    dyn for<'a, 'b> Fn(&'a Context<'b, GenericQueryableContextType>, &Term<'a>) -> Result<Box<T>>
+        + Send
+        + Sync,
+>;
+
+/// A registry of types that can be deserialized into a boxed trait object.
+///
+/// Each type is registered under a tag, along with a function that
+/// boxes a deserialized value as the trait object. [from_term_dyn]
+/// reads the tag from a term, and deserializes the term using the
+/// type registered under that tag.
+pub struct TypeRegistry<T: ?Sized> {
+    types: HashMap<String, DynDeserializer<T>>,
+}
+
+impl<T: ?Sized> Default for TypeRegistry<T> {
+    fn default() -> Self {
+        Self {
+            types: HashMap::new(),
+        }
+    }
+}
+
+impl<T: ?Sized + 'static> TypeRegistry<T> {
+    /// Create a new, empty registry.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a type under the given tag.
+    ///
+    /// The given function turns a deserialized value into the boxed
+    /// trait object. Registering a tag that was registered before
+    /// replaces the previous type.
+    pub fn register<U: DeserializeOwned + 'static>(
+        &mut self,
+        tag: &str,
+        into_boxed: fn(U) -> Box<T>,
+    ) {
+        self.types.insert(
+            tag.to_string(),
+            Box::new(
+                move |context: &Context<GenericQueryableContextType>, term: &Term| {
+                    Ok(into_boxed(from_term(context, term)?))
+                },
+            ),
+        );
+    }
+}
+
+/// Retrieve the type tag of a term, along with the term holding the value.
+fn type_tag<'a, C: QueryableContextType>(
+    context: &'a Context<C>,
+    term: &Term<'a>,
+) -> Result<(String, Term<'a>)> {
+    if term.is_dict() {
+        if let Some(tag) = attempt_opt(term.get_dict_key::<_, Atom>("type"))? {
+            return Ok((tag.name(), term.clone()));
+        }
+        if let Some(Some(tag)) = attempt_opt(term.get_dict_tag())? {
+            return Ok((tag.name(), term.clone()));
+        }
+    } else if let Some(functor) = attempt_opt(term.get::<Functor>())? {
+        if functor.arity() == 1 {
+            let [value] = attempt_opt(context.compound_terms(term))?.unwrap();
+            return Ok((functor.name().to_string(), value));
+        }
+    }
+
+    Err(Error::Message(
+        "expected a dict with a type key or tag, or a unary compound".to_string(),
+    ))
+}
+
+/// Deserialize a term into a boxed trait object, using the type registered for its tag.
+///
+/// The tag is read from one of the following:
+/// - the `type` key of a dict, in which case the whole dict is deserialized,
+/// - the tag of a dict, in which case the whole dict is deserialized,
+/// - the functor of a compound of arity 1, in which case its argument is deserialized.
+///
+/// As the `type` key is deserialized along with the other keys,
+/// types that are registered for dicts with a `type` key should not
+/// deny unknown fields. If the tag is not registered, this results in
+/// an error.
+pub fn from_term_dyn<C: QueryableContextType, T: ?Sized>(
+    context: &Context<C>,
+    term: &Term,
+    registry: &TypeRegistry<T>,
+) -> Result<Box<T>> {
+    let (tag, value) = type_tag(context, term)?;
+    let deserialize = registry
+        .types
+        .get(&tag)
+        .ok_or_else(|| Error::Message(format!("no type registered for tag {}", tag)))?;
+    let generic = context.into_generic();
+
+    deserialize(&generic, &value)
+}
+
+/// Limits on the terms accepted by the deserializer.
+///
+/// When deserializing terms from untrusted sources, these limits
+/// guard against terms that are too deeply nested or too large to
+/// handle safely. Exceeding any of them results in
+/// [Error::LimitExceeded].
+///
+/// By default, no limits are set.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DeserializeLimits {
+    max_depth: Option<usize>,
+    max_elements: Option<usize>,
+    max_string_len: Option<usize>,
+}
+
+impl DeserializeLimits {
+    /// Create a new DeserializeLimits without any limits set.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the maximum nesting depth of lists, dictionaries and compound terms.
+    ///
+    /// A depth of 1 allows a flat list, but not a list of lists.
+    pub fn set_max_depth(&mut self, max_depth: usize) {
+        self.max_depth = Some(max_depth);
+    }
+
+    /// Set the maximum nesting depth of lists, dictionaries and compound terms.
+    ///
+    /// A depth of 1 allows a flat list, but not a list of lists.
+    pub fn max_depth(mut self, max_depth: usize) -> Self {
+        self.set_max_depth(max_depth);
+        self
+    }
+
+    /// Set the maximum number of elements in a single list, dictionary or compound term.
+    pub fn set_max_elements(&mut self, max_elements: usize) {
+        self.max_elements = Some(max_elements);
+    }
+
+    /// Set the maximum number of elements in a single list, dictionary or compound term.
+    pub fn max_elements(mut self, max_elements: usize) -> Self {
+        self.set_max_elements(max_elements);
+        self
+    }
+
+    /// Set the maximum length in bytes of deserialized strings.
+    pub fn set_max_string_len(&mut self, max_string_len: usize) {
+        self.max_string_len = Some(max_string_len);
+    }
+
+    /// Set the maximum length in bytes of deserialized strings.
+    pub fn max_string_len(mut self, max_string_len: usize) -> Self {
+        self.set_max_string_len(max_string_len);
+        self
+    }
+}
+
+/// Configuration object for the deserializer.
+///
+/// By default, deserialization is done with the following options:
+/// - no limits are enforced.
+/// - compound terms are deserialized as a sequence of their
+///   arguments when no particular type is asked for, as happens for
+///   untagged enums.
+/// - `None` is read from the atom `none`, and `Some(x)` from
+///   `some(x)` or just `x`.
+///
+/// This object allows you to override these options.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DeserializerConfiguration {
+    limits: DeserializeLimits,
+    untagged_variants: &'static [&'static str],
+    none_repr: NoneRepr,
+}
+
+impl Default for DeserializerConfiguration {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DeserializerConfiguration {
+    /// Create a new DeserializerConfiguration.
+    pub fn new() -> Self {
+        Self {
+            limits: DeserializeLimits::new(),
+            untagged_variants: &[],
+            none_repr: NoneRepr::Atom("none"),
+        }
+    }
+
+    /// Set the limits to enforce while deserializing.
+    pub fn set_limits(&mut self, limits: DeserializeLimits) {
+        self.limits = limits;
+    }
+
+    /// Set the limits to enforce while deserializing.
+    pub fn limits(mut self, limits: DeserializeLimits) -> Self {
+        self.set_limits(limits);
+        self
+    }
+
+    /// Set the functor names of unary compounds that represent
+    /// variants of untagged enums.
+    ///
+    /// An untagged enum, declared with `#[serde(untagged)]`, is
+    /// deserialized by trying each variant in turn against the term,
+    /// without telling the deserializer about the variant names. A
+    /// unary compound with one of the given names, like
+    /// `circle(1.0)`, is then deserialized as just its argument, so
+    /// that it matches a newtype variant like `Circle(f64)`. Other
+    /// unary compounds are still deserialized as a sequence of one
+    /// element.
+    pub fn set_untagged_variants(&mut self, untagged_variants: &'static [&'static str]) {
+        self.untagged_variants = untagged_variants;
+    }
+
+    /// Set the functor names of unary compounds that represent
+    /// variants of untagged enums.
+    ///
+    /// See [set_untagged_variants](Self::set_untagged_variants).
+    pub fn untagged_variants(mut self, untagged_variants: &'static [&'static str]) -> Self {
+        self.set_untagged_variants(untagged_variants);
+        self
+    }
+
+    /// Set the way `Option::None` is represented.
+    ///
+    /// This should match the representation the terms were
+    /// serialized with, as set by
+    /// [SerializerConfiguration::none_repr](super::SerializerConfiguration::none_repr).
+    /// By default, `None` is read from the atom `none`.
+    pub fn set_none_repr(&mut self, none_repr: NoneRepr) {
+        self.none_repr = none_repr;
+    }
+
+    /// Set the way `Option::None` is represented.
+    ///
+    /// See [set_none_repr](Self::set_none_repr).
+    pub fn none_repr(mut self, none_repr: NoneRepr) -> Self {
+        self.set_none_repr(none_repr);
+        self
+    }
+}
+
+/// The configuration in effect, along with the current nesting depth.
+#[derive(Clone, Copy)]
+struct DeserializeState {
+    configuration: DeserializerConfiguration,
+    depth: usize,
+}
+
+impl DeserializeState {
+    fn new(configuration: DeserializerConfiguration) -> Self {
+        Self {
+            configuration,
+            depth: 0,
+        }
+    }
+
+    /// Returns the state for the elements of a nested term.
+    fn enter(self) -> Result<Self> {
+        match self.configuration.limits.max_depth {
+            Some(max_depth) if self.depth >= max_depth => Err(Error::LimitExceeded),
+            _ => Ok(Self {
+                depth: self.depth + 1,
+                ..self
+            }),
+        }
+    }
+
+    /// Count one more element, checking that there are not too many.
+    fn count_element(&self, elements: &mut usize) -> Result<()> {
+        *elements += 1;
+        match self.configuration.limits.max_elements {
+            Some(max_elements) if *elements > max_elements => Err(Error::LimitExceeded),
+            _ => Ok(()),
+        }
+    }
+
+    fn check_string_len(&self, len: usize) -> Result<()> {
+        match self.configuration.limits.max_string_len {
+            Some(max_string_len) if len > max_string_len => Err(Error::LimitExceeded),
+            _ => Ok(()),
+        }
+    }
+
+    /// Check the length of the text of an atom, before it is copied.
+    ///
+    /// This uses the length in characters, which is never more than
+    /// the length in bytes, so this only rejects text that is
+    /// certainly too long. The exact length is checked once the text
+    /// has been copied.
+    fn check_atom_len(&self, atom: atom_t) -> Result<()> {
+        if self.configuration.limits.max_string_len.is_some() {
+            self.check_string_len(atom_text_len(atom))?;
+        }
+
+        Ok(())
+    }
+
+    /// Check the length of the text in a term, before it is copied.
+    ///
+    /// See [check_atom_len](Self::check_atom_len).
+    fn check_text_len(&self, term: &Term) -> Result<()> {
+        if self.configuration.limits.max_string_len.is_some() {
+            if let Some(len) = term_text_len(term) {
+                self.check_string_len(len)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Returns the length in characters of the text of an atom, without copying it.
+fn atom_text_len(atom: atom_t) -> usize {
+    let mut len = 0;
+    // unsafe justification: both functions return a pointer into the
+    // atom itself, and one of them succeeds for any text atom.
+    unsafe {
+        if PL_atom_nchars(atom, &mut len).is_null() {
+            PL_atom_wchars(atom, &mut len);
+        }
+    }
+
+    len
+}
+
+/// Returns the length in characters of the atom or string in a term, without copying it.
+fn term_text_len(term: &Term) -> Option<usize> {
+    let mut len = 0;
+    match term.term_type() {
+        TermType::Atom => {
+            let mut atom = 0;
+            // unsafe justification: the atom is only used while the
+            // term keeps it alive, so it need not be registered.
+            if unsafe { PL_get_atom(term.term_ptr(), &mut atom) } == 0 {
+                return None;
+            }
+
+            Some(atom_text_len(atom))
+        }
+        TermType::String => {
+            let mut chars = std::ptr::null_mut();
+            // unsafe justification: PL_get_string returns a pointer
+            // into narrow strings themselves, and PL_get_wchars does
+            // the same for wide strings.
+            let result = unsafe {
+                PL_get_string(term.term_ptr(), &mut chars, &mut len) != 0
+                    || PL_get_wchars(
+                        term.term_ptr(),
+                        &mut len,
+                        &mut std::ptr::null_mut(),
+                        CVT_STRING | BUF_DISCARDABLE,
+                    ) != 0
+            };
+
+            result.then_some(len)
+        }
+        _ => None,
+    }
+}
+
 /// A serde deserializer for turning prolog terms into rust values.
 pub struct Deserializer<'de, C: QueryableContextType> {
     context: &'de Context<'de, C>,
     term: Term<'de>,
+    state: DeserializeState,
 }
 
 impl<'de, C: QueryableContextType> Deserializer<'de, C> {
+    /// Retrieve a float from the term.
+    ///
+    /// Besides prolog floats and integers, this accepts the rational
+    /// and string representations written by [FloatRepr](super::FloatRepr).
+    fn get_float(&self) -> Result<Option<f64>> {
+        match self.term.term_type() {
+            TermType::Rational => {
+                let frame = self.context.open_frame();
+                let result = attempt_opt(
+                    term! {frame: float(#&self.term)}.and_then(|e| frame.eval_f64(&e)),
+                )?;
+                frame.close();
+
+                Ok(result)
+            }
+            TermType::String => {
+                self.state.check_text_len(&self.term)?;
+                Ok(attempt_opt(self.term.get::<String>())?.and_then(|s| s.parse().ok()))
+            }
+            _ => Ok(attempt_opt(self.term.get::<f64>())?),
+        }
+    }
+
     /// Create a new deserializer.
     pub fn new(context: &'de Context<'de, C>, term: Term<'de>) -> Self {
-        Self { context, term }
+        Self::new_with_configuration(context, term, DeserializerConfiguration::new())
+    }
+
+    /// Create a new deserializer which enforces the given limits.
+    pub fn new_with_limits(
+        context: &'de Context<'de, C>,
+        term: Term<'de>,
+        limits: DeserializeLimits,
+    ) -> Self {
+        Self::new_with_configuration(
+            context,
+            term,
+            DeserializerConfiguration::new().limits(limits),
+        )
+    }
+
+    /// Create a new deserializer using the given configuration.
+    pub fn new_with_configuration(
+        context: &'de Context<'de, C>,
+        term: Term<'de>,
+        configuration: DeserializerConfiguration,
+    ) -> Self {
+        Self {
+            context,
+            term,
+            state: DeserializeState::new(configuration),
+        }
     }
 }
 
@@ -49,6 +513,50 @@ pub enum Error {
     ValueNotOfExpectedType(&'static str),
     ValueOutOfRange,
     UnificationFailed,
+    LimitExceeded,
+    /// An error that occurred while deserializing a part of the term.
+    ///
+    /// The path points at the part of the term where deserialization
+    /// failed, such as `baa.c[2]` for the third element of the list
+    /// under key `c` in the dictionary under key `baa`.
+    AtPath {
+        path: String,
+        source: Box<Error>,
+    },
+}
+
+impl Error {
+    /// Mark this error as having occurred under the given path segment.
+    ///
+    /// Prolog exceptions and exceeded limits are left as is, so they
+    /// can still be matched on directly.
+    fn at_path(self, segment: String) -> Self {
+        match self {
+            Self::PrologError(_) | Self::LimitExceeded => self,
+            Self::AtPath { path, source } => {
+                let separator = if path.starts_with('[') { "" } else { "." };
+                Self::AtPath {
+                    path: format!("{}{}{}", segment, separator, path),
+                    source,
+                }
+            }
+            error => Self::AtPath {
+                path: segment,
+                source: Box::new(error),
+            },
+        }
+    }
+
+    fn at_index(self, index: usize) -> Self {
+        self.at_path(format!("[{}]", index))
+    }
+
+    fn at_key(self, key: &Key) -> Self {
+        match key {
+            Key::Atom(atom) => self.at_path(atom.name()),
+            Key::Int(int) => self.at_path(int.to_string()),
+        }
+    }
 }
 
 impl From<PrologException> for Error {
@@ -69,11 +577,20 @@ impl Display for Error {
             }
             Self::ValueOutOfRange => formatter.write_str("value out of range"),
             Self::UnificationFailed => formatter.write_str("unification failed"),
+            Self::LimitExceeded => formatter.write_str("deserialization limit exceeded"),
+            Self::AtPath { path, source } => write!(formatter, "at {}: {}", path, source),
         }
     }
 }
 
-impl std::error::Error for Error {}
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::AtPath { source, .. } => Some(source.as_ref()),
+            _ => None,
+        }
+    }
+}
 
 impl de::Error for Error {
     fn custom<T>(msg: T) -> Self
@@ -89,8 +606,10 @@ pub type Result<T> = std::result::Result<T, Error>;
 
 struct DictMapAccess<'de, C: QueryableContextType> {
     context: &'de Context<'de, C>,
+    state: DeserializeState,
+    elements: usize,
     iter: DictIterator<'de, 'de, C>,
-    next_value: Option<Term<'de>>,
+    next_value: Option<(Key, Term<'de>)>,
 }
 
 impl<'de, C: QueryableContextType> MapAccess<'de> for DictMapAccess<'de, C> {
@@ -102,9 +621,13 @@ impl<'de, C: QueryableContextType> MapAccess<'de> for DictMapAccess<'de, C> {
     {
         match self.iter.next() {
             Some((key, value)) => {
-                self.next_value = Some(value);
+                self.state.count_element(&mut self.elements)?;
+                self.next_value = Some((key.clone(), value));
 
-                let inner_de = KeyDeserializer { key };
+                let inner_de = KeyDeserializer {
+                    key,
+                    state: self.state,
+                };
                 seed.deserialize(inner_de).map(Some)
             }
             None => Ok(None),
@@ -118,12 +641,13 @@ impl<'de, C: QueryableContextType> MapAccess<'de> for DictMapAccess<'de, C> {
         let mut next_value = None;
         std::mem::swap(&mut next_value, &mut self.next_value);
         match next_value {
-            Some(value) => {
+            Some((key, value)) => {
                 let inner_de = Deserializer {
                     context: self.context,
                     term: value,
+                    state: self.state,
                 };
-                seed.deserialize(inner_de)
+                seed.deserialize(inner_de).map_err(|e| e.at_key(&key))
             }
             None => panic!("MapAccess used out of order"),
         }
@@ -132,6 +656,8 @@ impl<'de, C: QueryableContextType> MapAccess<'de> for DictMapAccess<'de, C> {
 
 struct CompoundTermSeqAccess<'a, C: QueryableContextType> {
     context: &'a Context<'a, C>,
+    state: DeserializeState,
+    elements: usize,
     terms: Vec<Term<'a>>,
 }
 
@@ -143,11 +669,16 @@ impl<'de, C: QueryableContextType> SeqAccess<'de> for CompoundTermSeqAccess<'de,
         T: DeserializeSeed<'de>,
     {
         if let Some(term) = self.terms.pop() {
+            self.state.count_element(&mut self.elements)?;
+            let index = self.elements - 1;
             let inner_de = Deserializer {
                 context: self.context,
                 term,
+                state: self.state,
             };
-            seed.deserialize(inner_de).map(Some)
+            seed.deserialize(inner_de)
+                .map(Some)
+                .map_err(|e| e.at_index(index))
         } else {
             Ok(None)
         }
@@ -156,6 +687,7 @@ impl<'de, C: QueryableContextType> SeqAccess<'de> for CompoundTermSeqAccess<'de,
 
 struct CompoundTermEnumAccess<'a, C: QueryableContextType> {
     context: &'a Context<'a, C>,
+    state: DeserializeState,
     variant_name: String,
     term: Term<'a>,
 }
@@ -180,7 +712,7 @@ impl<'de, C: QueryableContextType> VariantAccess<'de> for CompoundTermEnumAccess
     type Error = Error;
 
     fn unit_variant(self) -> Result<()> {
-        if self.term.is_atom() {
+        if self.term.is_atom() || self.term.is_string() {
             Ok(())
         } else if let Some(f) = attempt_opt(self.term.get::<Functor>())? {
             if f.arity() == 0 {
@@ -201,6 +733,7 @@ impl<'de, C: QueryableContextType> VariantAccess<'de> for CompoundTermEnumAccess
             seed.deserialize(Deserializer {
                 context: self.context,
                 term,
+                state: self.state.enter()?,
             })
         } else {
             Err(Error::ValueOutOfRange)
@@ -214,26 +747,37 @@ impl<'de, C: QueryableContextType> VariantAccess<'de> for CompoundTermEnumAccess
         let inner_de = Deserializer {
             context: self.context,
             term: self.term,
+            state: self.state,
         };
 
         de::Deserializer::deserialize_tuple(inner_de, len, visitor)
     }
 
-    fn struct_variant<V>(self, _fields: &'static [&'static str], visitor: V) -> Result<V::Value>
+    fn struct_variant<V>(self, fields: &'static [&'static str], visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
+        let is_compound = self.term.term_type() == TermType::CompoundTerm;
         let inner_de = Deserializer {
             context: self.context,
             term: self.term,
+            state: self.state,
         };
 
-        de::Deserializer::deserialize_map(inner_de, visitor)
+        if is_compound {
+            // A compound term like `goat(42)` is matched positionally
+            // against the declared field order.
+            de::Deserializer::deserialize_tuple(inner_de, fields.len(), visitor)
+        } else {
+            de::Deserializer::deserialize_map(inner_de, visitor)
+        }
     }
 }
 
 struct CommaCompoundTermSeqAccess<'a, C: QueryableContextType> {
     context: &'a Context<'a, C>,
+    state: DeserializeState,
+    elements: usize,
     term: Term<'a>,
 }
 
@@ -244,26 +788,36 @@ impl<'de, C: QueryableContextType> SeqAccess<'de> for CommaCompoundTermSeqAccess
     where
         T: DeserializeSeed<'de>,
     {
+        self.state.count_element(&mut self.elements)?;
+        let index = self.elements - 1;
         if attempt_opt(self.term.get::<Functor>())? == Some(functor!(",/2")) {
             let [head, tail] = attempt_opt(self.context.compound_terms(&self.term))?.unwrap();
             self.term = tail;
             let inner_de = Deserializer {
                 context: self.context,
                 term: head,
+                state: self.state,
             };
-            seed.deserialize(inner_de).map(Some)
+            seed.deserialize(inner_de)
+                .map(Some)
+                .map_err(|e| e.at_index(index))
         } else {
             let inner_de = Deserializer {
                 context: self.context,
                 term: self.term.clone(),
+                state: self.state,
             };
-            seed.deserialize(inner_de).map(Some)
+            seed.deserialize(inner_de)
+                .map(Some)
+                .map_err(|e| e.at_index(index))
         }
     }
 }
 
 struct ListSeqAccess<'a, C: QueryableContextType> {
     context: &'a Context<'a, C>,
+    state: DeserializeState,
+    elements: usize,
     iter: TermListIterator<'a, 'a, C>,
 }
 
@@ -275,17 +829,38 @@ impl<'de, C: QueryableContextType> SeqAccess<'de> for ListSeqAccess<'de, C> {
         T: DeserializeSeed<'de>,
     {
         if let Some(term) = self.iter.next() {
+            self.state.count_element(&mut self.elements)?;
+            let index = self.elements - 1;
             let inner_de = Deserializer {
                 context: self.context,
                 term,
+                state: self.state,
             };
-            seed.deserialize(inner_de).map(Some)
+            seed.deserialize(inner_de)
+                .map(Some)
+                .map_err(|e| e.at_index(index))
         } else {
             Ok(None)
         }
     }
 }
 
+impl<'de, C: QueryableContextType> Deserializer<'de, C> {
+    /// Refuse to deserialize variables with delayed goals.
+    ///
+    /// Silently treating such a variable like a plain unbound
+    /// variable would lose the goals attached to it.
+    fn check_delayed_goals(&self) -> Result<()> {
+        if self.term.is_var() && self.term.has_delayed_goals() {
+            Err(Error::Message(
+                "cannot deserialize a variable with delayed goals".to_string(),
+            ))
+        } else {
+            Ok(())
+        }
+    }
+}
+
 impl<'de, C: QueryableContextType> de::Deserializer<'de> for Deserializer<'de, C> {
     type Error = Error;
     fn deserialize_any<V>(self, visitor: V) -> Result<V::Value>
@@ -307,21 +882,51 @@ impl<'de, C: QueryableContextType> de::Deserializer<'de> for Deserializer<'de, C
                 if f.name() == atom!(",") && f.arity() == 2 {
                     visitor.visit_seq(CommaCompoundTermSeqAccess {
                         context: self.context,
+                        state: self.state.enter()?,
+                        elements: 0,
                         term: self.term,
                     })
+                } else if f.arity() == 1
+                    && self
+                        .state
+                        .configuration
+                        .untagged_variants
+                        .iter()
+                        .any(|name| f.name().eq_str(name))
+                {
+                    // A unary compound named after a variant of an
+                    // untagged enum is treated as a wrapper around its
+                    // argument, which is how newtype variants are
+                    // represented. This allows the untagged enum to
+                    // match on the inner value.
+                    let [term] = attempt_opt(self.context.compound_terms(&self.term))?.unwrap();
+                    let inner_de = Deserializer {
+                        context: self.context,
+                        term,
+                        state: self.state.enter()?,
+                    };
+
+                    de::Deserializer::deserialize_any(inner_de, visitor)
                 } else {
                     let mut terms =
                         attempt_opt(self.context.compound_terms_vec(&self.term))?.unwrap();
                     terms.reverse();
                     visitor.visit_seq(CompoundTermSeqAccess {
                         context: self.context,
+                        state: self.state.enter()?,
+                        elements: 0,
                         terms,
                     })
                 }
             }
             TermType::ListPair => self.deserialize_seq(visitor),
             TermType::Dict => self.deserialize_map(visitor),
-            TermType::Variable => todo!("variables are not yet supported"),
+            TermType::Variable => {
+                self.check_delayed_goals()?;
+                Err(Error::Message(
+                    "cannot deserialize an unbound variable without a known type".to_string(),
+                ))
+            }
             _ => Err(Error::UnsupportedValue),
         }
     }
@@ -454,7 +1059,7 @@ impl<'de, C: QueryableContextType> de::Deserializer<'de> for Deserializer<'de, C
     where
         V: Visitor<'de>,
     {
-        match attempt_opt(self.term.get::<f64>())? {
+        match self.get_float()? {
             // a little bit suspicious as this loses precision
             Some(f) => visitor.visit_f32(f as f32),
             None => Err(Error::ValueNotOfExpectedType("f32")),
@@ -464,7 +1069,7 @@ impl<'de, C: QueryableContextType> de::Deserializer<'de> for Deserializer<'de, C
     where
         V: Visitor<'de>,
     {
-        match attempt_opt(self.term.get::<f64>())? {
+        match self.get_float()? {
             // a little bit suspicious as this loses precision
             Some(f) => visitor.visit_f64(f),
             None => Err(Error::ValueNotOfExpectedType("f64")),
@@ -520,8 +1125,12 @@ impl<'de, C: QueryableContextType> de::Deserializer<'de> for Deserializer<'de, C
     where
         V: Visitor<'de>,
     {
+        self.state.check_text_len(&self.term)?;
         match attempt_opt(self.term.get::<PrologText>())? {
-            Some(s) => visitor.visit_string(s.into_inner()),
+            Some(s) => {
+                self.state.check_string_len(s.len())?;
+                visitor.visit_string(s.into_inner())
+            }
             None => Err(Error::ValueNotOfExpectedType("string")),
         }
     }
@@ -541,8 +1150,34 @@ impl<'de, C: QueryableContextType> de::Deserializer<'de> for Deserializer<'de, C
     where
         V: Visitor<'de>,
     {
-        // us being here indicates a value was present.
-        visitor.visit_some(self)
+        // us being here indicates a value was present. It may still
+        // be the configured representation for `None`, or the
+        // `some/1` wrapper around a present value.
+        self.check_delayed_goals()?;
+        // an unbound variable holds no value, so it is always `None`.
+        let none_repr = self.state.configuration.none_repr;
+        let is_none = match none_repr {
+            NoneRepr::Atom(name) => {
+                self.term.is_var()
+                    || self.term.term_type() == TermType::Atom
+                        && attempt_opt(self.term.get_atom_name(|n| n == Some(name)))? == Some(true)
+            }
+            NoneRepr::Variable | NoneRepr::Omit => self.term.is_var(),
+        };
+        if is_none {
+            visitor.visit_none()
+        } else if matches!(none_repr, NoneRepr::Atom(_))
+            && attempt_opt(self.term.get::<Functor>())? == Some(functor!("some/1"))
+        {
+            let [term] = attempt_opt(self.context.compound_terms(&self.term))?.unwrap();
+            visitor.visit_some(Deserializer {
+                context: self.context,
+                term,
+                state: self.state,
+            })
+        } else {
+            visitor.visit_some(self)
+        }
     }
     fn deserialize_unit<V>(self, visitor: V) -> Result<V::Value>
     where
@@ -554,11 +1189,25 @@ impl<'de, C: QueryableContextType> de::Deserializer<'de> for Deserializer<'de, C
             Err(Error::ValueNotOfExpectedType("unit"))
         }
     }
-    fn deserialize_unit_struct<V>(self, _name: &'static str, visitor: V) -> Result<V::Value>
+    fn deserialize_unit_struct<V>(self, name: &'static str, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        self.deserialize_unit(visitor)
+        // PhantomData carries no data, so whatever the term holds,
+        // there is nothing to check.
+        if name == "PhantomData" {
+            return visitor.visit_unit();
+        }
+
+        // unit structs are serialized either as their name or as nil,
+        // depending on the serializer configuration. accept both.
+        let is_name = self.term.term_type() == TermType::Atom
+            && attempt_opt(self.term.get_atom_name(|n| n == Some(name)))? == Some(true);
+        if is_name || self.term.term_type() == TermType::Nil {
+            visitor.visit_unit()
+        } else {
+            Err(Error::ValueNotOfExpectedType("unit struct"))
+        }
     }
     fn deserialize_newtype_struct<V>(self, name: &'static str, visitor: V) -> Result<V::Value>
     where
@@ -566,20 +1215,30 @@ impl<'de, C: QueryableContextType> de::Deserializer<'de> for Deserializer<'de, C
     {
         if name == ATOM_STRUCT_NAME {
             if DeserializingAtomState::is_deserializing_atom() {
-                let atom = attempt_opt(self.term.get::<Atom>())?;
-                match atom {
-                    Some(atom) => {
-                        if cfg!(target_pointer_width = "32") {
-                            visitor.visit_u32(atom.atom_ptr() as u32)
-                        } else {
-                            visitor.visit_u64(atom.atom_ptr() as u64)
-                        }
-                    }
-                    None => Err(Error::ValueNotOfExpectedType("atom")),
+                self.state.check_text_len(&self.term)?;
+                self.term.assert_term_handling_possible();
+                let mut atom: atom_t = 0;
+                // unsafe justification: this does not touch the
+                // reference count. The atom is kept alive by the
+                // term, and the visitor registers it if it needs to
+                // own it.
+                if unsafe { PL_get_atom(self.term.term_ptr(), &mut atom) } == 0 {
+                    return Err(Error::ValueNotOfExpectedType("atom"));
+                }
+
+                if cfg!(target_pointer_width = "32") {
+                    visitor.visit_u32(atom as u32)
+                } else {
+                    visitor.visit_u64(atom as u64)
                 }
             } else {
                 self.deserialize_string(visitor)
             }
+        } else if name == ONE_OR_MANY_STRUCT_NAME {
+            match self.term.term_type() {
+                TermType::ListPair | TermType::Nil => self.deserialize_seq(visitor),
+                _ => visitor.visit_newtype_struct(self),
+            }
         } else {
             visitor.visit_newtype_struct(self)
         }
@@ -588,10 +1247,13 @@ impl<'de, C: QueryableContextType> de::Deserializer<'de> for Deserializer<'de, C
     where
         V: Visitor<'de>,
     {
+        let state = self.state.enter()?;
         let cleanup_term = self.context.new_term_ref();
         let iter = self.context.term_list_iter(&self.term);
         let result = visitor.visit_seq(ListSeqAccess {
             context: self.context,
+            state,
+            elements: 0,
             iter,
         });
         unsafe {
@@ -604,11 +1266,14 @@ impl<'de, C: QueryableContextType> de::Deserializer<'de> for Deserializer<'de, C
     where
         V: Visitor<'de>,
     {
+        let state = self.state.enter()?;
         let cleanup_term = self.context.new_term_ref();
         let result;
         if attempt_opt(self.term.get::<Functor>())? == Some(functor!(",/2")) {
             result = visitor.visit_seq(CommaCompoundTermSeqAccess {
                 context: self.context,
+                state,
+                elements: 0,
                 term: self.term,
             });
         } else if let Some(mut terms) =
@@ -617,6 +1282,8 @@ impl<'de, C: QueryableContextType> de::Deserializer<'de> for Deserializer<'de, C
             terms.reverse();
             result = visitor.visit_seq(CompoundTermSeqAccess {
                 context: self.context,
+                state,
+                elements: 0,
                 terms,
             });
         } else if self.term.term_type() == TermType::ListPair
@@ -630,6 +1297,8 @@ impl<'de, C: QueryableContextType> de::Deserializer<'de> for Deserializer<'de, C
 
                 result = visitor.visit_seq(CompoundTermSeqAccess {
                     context: self.context,
+                    state,
+                    elements: 0,
                     terms,
                 });
             }
@@ -667,6 +1336,8 @@ impl<'de, C: QueryableContextType> de::Deserializer<'de> for Deserializer<'de, C
         if self.term.term_type() == TermType::Dict {
             visitor.visit_map(DictMapAccess {
                 context: self.context,
+                state: self.state.enter()?,
+                elements: 0,
                 iter: self.context.dict_entries(&self.term),
                 next_value: None,
             })
@@ -694,13 +1365,27 @@ impl<'de, C: QueryableContextType> de::Deserializer<'de> for Deserializer<'de, C
     where
         V: Visitor<'de>,
     {
+        let mut term = self.term;
+        // variants may be module-qualified, as in module:variant(..)
+        if attempt_opt(term.get::<Functor>())? == Some(Functor::new(":", 2)) {
+            let [_, variant] = attempt_opt(self.context.compound_terms(&term))?.unwrap();
+            term = variant;
+        }
+
+        self.state.check_text_len(&term)?;
         let variant_name;
-        if let Some(Some(atom)) = attempt_opt(self.term.get_dict_tag())? {
-            variant_name = atom;
-        } else if let Some(functor) = attempt_opt(self.term.get::<Functor>())? {
-            variant_name = functor.name();
-        } else if let Some(atom) = attempt_opt(self.term.get::<Atom>())? {
-            variant_name = atom;
+        if let Some(Some(atom)) = attempt_opt(term.get_dict_tag())? {
+            variant_name = atom.to_string();
+        } else if let Some(functor) = attempt_opt(term.get::<Functor>())? {
+            variant_name = functor.name().to_string();
+        } else if let Some(atom) = attempt_opt(term.get::<Atom>())? {
+            variant_name = atom.to_string();
+        } else if term.term_type() == TermType::String {
+            // the tag may arrive as a string rather than an atom
+            match attempt_opt(term.get::<String>())? {
+                Some(string) => variant_name = string,
+                None => return Err(Error::ValueOutOfRange),
+            }
         } else {
             return Err(Error::ValueOutOfRange);
         }
@@ -708,16 +1393,21 @@ impl<'de, C: QueryableContextType> de::Deserializer<'de> for Deserializer<'de, C
         // TODO more efficient string handling without atom reserving
         visitor.visit_enum(CompoundTermEnumAccess {
             context: self.context,
-            variant_name: variant_name.to_string(),
-            term: self.term,
+            state: self.state,
+            variant_name,
+            term,
         })
     }
     fn deserialize_identifier<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
+        self.state.check_text_len(&self.term)?;
         match attempt_opt(self.term.get::<PrologText>())? {
-            Some(text) => visitor.visit_string(text.to_string()),
+            Some(text) => {
+                self.state.check_string_len(text.len())?;
+                visitor.visit_string(text.into_inner())
+            }
             None => Err(Error::ValueNotOfExpectedType("identifier")),
         }
     }
@@ -731,6 +1421,23 @@ impl<'de, C: QueryableContextType> de::Deserializer<'de> for Deserializer<'de, C
 
 struct KeyDeserializer {
     key: Key,
+    state: DeserializeState,
+}
+
+impl KeyDeserializer {
+    fn key_string(&self) -> Result<String> {
+        match &self.key {
+            Key::Atom(a) => {
+                self.state.check_atom_len(a.atom_ptr())?;
+                let s = a.to_string();
+                self.state.check_string_len(s.len())?;
+
+                Ok(s)
+            }
+            // dubious, maybe error
+            Key::Int(i) => Ok(i.to_string()),
+        }
+    }
 }
 
 impl<'de> de::Deserializer<'de> for KeyDeserializer {
@@ -901,11 +1608,7 @@ impl<'de> de::Deserializer<'de> for KeyDeserializer {
     where
         V: Visitor<'de>,
     {
-        match self.key {
-            Key::Atom(a) => visitor.visit_string(a.to_string()),
-            // dubious, maybe error
-            Key::Int(i) => visitor.visit_string(i.to_string()),
-        }
+        visitor.visit_string(self.key_string()?)
     }
     fn deserialize_bytes<V>(self, _visitor: V) -> Result<V::Value>
     where
@@ -946,6 +1649,7 @@ impl<'de> de::Deserializer<'de> for KeyDeserializer {
             if DeserializingAtomState::is_deserializing_atom() {
                 match self.key {
                     Key::Atom(atom) => {
+                        self.state.check_atom_len(atom.atom_ptr())?;
                         if cfg!(target_pointer_width = "32") {
                             visitor.visit_u32(atom.atom_ptr() as u32)
                         } else {
@@ -1016,11 +1720,7 @@ impl<'de> de::Deserializer<'de> for KeyDeserializer {
     where
         V: Visitor<'de>,
     {
-        match self.key {
-            Key::Atom(a) => visitor.visit_string(a.to_string()),
-            // dubious, maybe error
-            Key::Int(i) => visitor.visit_string(i.to_string()),
-        }
+        visitor.visit_string(self.key_string()?)
     }
     fn deserialize_ignored_any<V>(self, _visitor: V) -> Result<V::Value>
     where
@@ -1324,9 +2024,62 @@ impl<'de> Visitor<'de> for AtomVisitor {
     }
 }
 
+/// Deserializing a [BorrowedAtom] avoids touching the reference count of the atom.
+///
+/// This is only possible when deserializing from a prolog term. See
+/// [BorrowedAtom] for the constraints on its lifetime.
+impl<'de> Deserialize<'de> for BorrowedAtom<'de> {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        let _state = DeserializingAtomState::start();
+        deserializer.deserialize_newtype_struct(ATOM_STRUCT_NAME, BorrowedAtomVisitor)
+    }
+}
+
+struct BorrowedAtomVisitor;
+
+impl<'de> Visitor<'de> for BorrowedAtomVisitor {
+    type Value = BorrowedAtom<'de>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(formatter, "an atom in a prolog term")
+    }
+
+    #[cfg(target_pointer_width = "32")]
+    #[allow(clippy::useless_conversion)]
+    fn visit_u32<E>(self, v: u32) -> std::result::Result<BorrowedAtom<'de>, E>
+    where
+        E: de::Error,
+    {
+        if !DeserializingAtomState::is_deserializing_atom() {
+            panic!("visiting atom as pointer but not in deserializing state");
+        }
+        // unsafe justification: the atom is kept alive by the term
+        // it was retrieved from, which lives for 'de.
+        Ok(unsafe { BorrowedAtom::wrap((v as usize).into()) })
+    }
+
+    #[cfg(target_pointer_width = "64")]
+    #[allow(clippy::useless_conversion)]
+    fn visit_u64<E>(self, v: u64) -> std::result::Result<BorrowedAtom<'de>, E>
+    where
+        E: de::Error,
+    {
+        if !DeserializingAtomState::is_deserializing_atom() {
+            panic!("visiting atom as pointer but not in deserializing state");
+        }
+        // unsafe justification: the atom is kept alive by the term
+        // it was retrieved from, which lives for 'de.
+        Ok(unsafe { BorrowedAtom::wrap((v as usize).into()) })
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::{pred, term};
     use serde::Deserialize;
 
     #[derive(Deserialize, Debug, PartialEq)]
@@ -1540,4 +2293,445 @@ mod tests {
             result
         );
     }
+
+    #[test]
+    fn deserialize_an_enum_from_a_string_tag() {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let term = context.term_from_string("\"cow\"").unwrap();
+        assert!(term.is_string());
+        let result: Animal = from_term(&context, &term).unwrap();
+        assert_eq!(Animal::Cow, result);
+
+        let term = context.term_from_string("\"unicorn\"").unwrap();
+        assert!(from_term::<_, Animal>(&context, &term).is_err());
+    }
+
+    #[test]
+    fn deserialize_a_struct_variant_from_a_compound() {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let term = context.term_from_string("goat(42)").unwrap();
+        let result: Animal = from_term(&context, &term).unwrap();
+        assert_eq!(Animal::Goat { horns: 42 }, result);
+
+        let term = context.term_from_string("goat(42, 43)").unwrap();
+        assert!(from_term::<_, Animal>(&context, &term).is_err());
+    }
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    #[serde(untagged)]
+    enum Shape {
+        Circle(f64),
+        Point { x: i64, y: i64 },
+    }
+
+    #[test]
+    fn deserialize_an_untagged_enum() {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+        let configuration = DeserializerConfiguration::new().untagged_variants(&["circle"]);
+
+        let term = context.term_from_string("circle(1.0)").unwrap();
+        let result: Shape = from_term_with_configuration(&context, &term, configuration).unwrap();
+        assert_eq!(Shape::Circle(1.0), result);
+
+        let term = context.term_from_string("point{x:1,y:2}").unwrap();
+        let result: Shape = from_term_with_configuration(&context, &term, configuration).unwrap();
+        assert_eq!(Shape::Point { x: 1, y: 2 }, result);
+
+        let term = context.term_from_string("square(1, 2, 3)").unwrap();
+        assert!(from_term_with_configuration::<_, Shape>(&context, &term, configuration).is_err());
+        assert!(!context.has_exception());
+
+        // other unary compounds remain a sequence of their argument.
+        let term = context.term_from_string("oval(1.0)").unwrap();
+        assert!(from_term_with_configuration::<_, Shape>(&context, &term, configuration).is_err());
+        let term = context.term_from_string("circle(1.0)").unwrap();
+        assert!(from_term::<_, Shape>(&context, &term).is_err());
+        assert!(!context.has_exception());
+    }
+
+    #[test]
+    fn deserialize_nonzero_integers() {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let term = context.term_from_string("[1, 42]").unwrap();
+        let result: Vec<std::num::NonZeroU64> = from_term(&context, &term).unwrap();
+        assert_eq!(
+            vec![
+                std::num::NonZeroU64::new(1).unwrap(),
+                std::num::NonZeroU64::new(42).unwrap()
+            ],
+            result
+        );
+
+        let term = context.term_from_string("0").unwrap();
+        assert!(from_term::<_, std::num::NonZeroU64>(&context, &term).is_err());
+    }
+
+    #[test]
+    fn deserialize_sets_from_list_with_duplicates() {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let term = context.term_from_string("[1,2,2,3]").unwrap();
+
+        let result: std::collections::HashSet<u64> = from_term(&context, &term).unwrap();
+        assert_eq!(std::collections::HashSet::from([1, 2, 3]), result);
+
+        let result: std::collections::BTreeSet<u64> = from_term(&context, &term).unwrap();
+        assert_eq!(vec![1, 2, 3], result.into_iter().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn deserialize_within_limits() {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let limits = DeserializeLimits::new()
+            .max_depth(2)
+            .max_elements(3)
+            .max_string_len(5);
+        let term = context
+            .term_from_string("[[\"a\", \"bb\"], [\"hello\"]]")
+            .unwrap();
+        let result: Vec<Vec<String>> = from_term_with_limits(&context, &term, limits).unwrap();
+        assert_eq!(
+            vec![
+                vec!["a".to_string(), "bb".to_string()],
+                vec!["hello".to_string()]
+            ],
+            result
+        );
+    }
+
+    #[test]
+    fn deserialize_exceeding_max_depth() {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let limits = DeserializeLimits::new().max_depth(2);
+        let term = context.term_from_string("[[[1]]]").unwrap();
+        let result: Result<Vec<Vec<Vec<u64>>>> = from_term_with_limits(&context, &term, limits);
+        assert!(matches!(result, Err(Error::LimitExceeded)));
+
+        let term = context.term_from_string("_{a: _{b: _{c: 1}}}").unwrap();
+        let result: Result<HashMap<String, HashMap<String, HashMap<String, u64>>>> =
+            from_term_with_limits(&context, &term, limits);
+        assert!(matches!(result, Err(Error::LimitExceeded)));
+    }
+
+    #[test]
+    fn deserialize_exceeding_max_elements() {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let limits = DeserializeLimits::new().max_elements(3);
+        let term = context.term_from_string("[1, 2, 3, 4]").unwrap();
+        let result: Result<Vec<u64>> = from_term_with_limits(&context, &term, limits);
+        assert!(matches!(result, Err(Error::LimitExceeded)));
+
+        let term = context
+            .term_from_string("_{a: 1, b: 2, c: 3, d: 4}")
+            .unwrap();
+        let result: Result<HashMap<String, u64>> = from_term_with_limits(&context, &term, limits);
+        assert!(matches!(result, Err(Error::LimitExceeded)));
+    }
+
+    #[test]
+    fn deserialize_exceeding_max_string_len() {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let limits = DeserializeLimits::new().max_string_len(5);
+        let term = context.term_from_string("\"too long\"").unwrap();
+        let result: Result<String> = from_term_with_limits(&context, &term, limits);
+        assert!(matches!(result, Err(Error::LimitExceeded)));
+
+        let term = context.term_from_string("'too long'").unwrap();
+        let result: Result<String> = from_term_with_limits(&context, &term, limits);
+        assert!(matches!(result, Err(Error::LimitExceeded)));
+        let result: Result<Atom> = from_term_with_limits(&context, &term, limits);
+        assert!(matches!(result, Err(Error::LimitExceeded)));
+
+        let term = context.term_from_string("_{too_long: 1}").unwrap();
+        let result: Result<HashMap<String, u64>> = from_term_with_limits(&context, &term, limits);
+        assert!(matches!(result, Err(Error::LimitExceeded)));
+        let result: Result<HashMap<Atom, u64>> = from_term_with_limits(&context, &term, limits);
+        assert!(matches!(result, Err(Error::LimitExceeded)));
+    }
+
+    #[derive(serde_repr::Deserialize_repr, Debug, PartialEq)]
+    #[repr(u8)]
+    enum Priority {
+        Low = 0,
+        Medium = 1,
+        High = 2,
+    }
+
+    #[test]
+    fn deserialize_integer_into_repr_enum() {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let term = context.term_from_string("2").unwrap();
+        let result: Priority = from_term(&context, &term).unwrap();
+        assert_eq!(Priority::High, result);
+
+        let term = context.term_from_string("[0, 1]").unwrap();
+        let result: Vec<Priority> = from_term(&context, &term).unwrap();
+        assert_eq!(vec![Priority::Low, Priority::Medium], result);
+
+        let term = context.term_from_string("3").unwrap();
+        let result: Result<Priority> = from_term(&context, &term);
+        assert!(result.is_err());
+    }
+
+    fn atom_references<C: QueryableContextType>(context: &Context<C>, atom: &Atom) -> u64 {
+        let [atom_term, count] = context.new_term_refs();
+        atom_term.unify(atom).unwrap();
+        context
+            .call_once(pred!("$atom_references/2"), [&atom_term, &count])
+            .unwrap();
+
+        count.get().unwrap()
+    }
+
+    #[test]
+    fn deserialize_borrowed_atoms_without_refcounting() {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let atom = Atom::new("swipl_rs_borrowed_atom_test");
+        let term = context
+            .term_from_string(
+                "findall(swipl_rs_borrowed_atom_test, between(1, 1000, _), L), L = Result",
+            )
+            .unwrap();
+        context.call_term_once(&term).unwrap();
+        let [_, unification] = context.compound_terms(&term).unwrap();
+        let [list, _] = context.compound_terms(&unification).unwrap();
+
+        let before = atom_references(&context, &atom);
+        let owned: Vec<Atom> = from_term(&context, &list).unwrap();
+        assert_eq!(before + 1000, atom_references(&context, &atom));
+        std::mem::drop(owned);
+        assert_eq!(before, atom_references(&context, &atom));
+
+        let operations = atom::REFCOUNT_OPERATIONS.with(|ops| ops.get());
+        let borrowed: Vec<BorrowedAtom> = from_term(&context, &list).unwrap();
+        assert_eq!(operations, atom::REFCOUNT_OPERATIONS.with(|ops| ops.get()));
+        assert_eq!(before, atom_references(&context, &atom));
+        assert_eq!(1000, borrowed.len());
+        assert!(borrowed.iter().all(|a| *a == atom));
+        assert_eq!("swipl_rs_borrowed_atom_test", borrowed[0].name());
+
+        let owned = borrowed[0].to_atom();
+        assert_eq!(before + 1, atom_references(&context, &atom));
+        std::mem::drop(owned);
+    }
+
+    #[derive(Deserialize, Debug)]
+    struct PathOuter {
+        #[allow(dead_code)]
+        baa: PathInner,
+    }
+
+    #[derive(Deserialize, Debug)]
+    struct PathInner {
+        #[allow(dead_code)]
+        c: Vec<u64>,
+    }
+
+    #[test]
+    fn deserialize_error_reports_path() {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let term = context
+            .term_from_string("_{baa: _{c: [1, 2, foo]}}")
+            .unwrap();
+        match from_term::<_, PathOuter>(&context, &term) {
+            Err(Error::AtPath { path, source }) => {
+                assert_eq!("baa.c[2]", path);
+                assert!(matches!(*source, Error::ValueNotOfExpectedType("u64")));
+            }
+            result => panic!("unexpected result: {:?}", result),
+        }
+    }
+
+    #[test]
+    fn deserialize_variable_with_delayed_goals_fails() {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let term = context.term_from_string("foo(X, Y)").unwrap();
+        let [frozen, plain] = context.compound_terms(&term).unwrap();
+        let goal = term! {context: true}.unwrap();
+        context
+            .call_once(pred!("freeze/2"), [&frozen, &goal])
+            .unwrap();
+
+        assert!(term.has_delayed_goals());
+        assert!(frozen.has_delayed_goals());
+        assert!(!plain.has_delayed_goals());
+
+        let result: Option<u64> = from_term(&context, &plain).unwrap();
+        assert_eq!(None, result);
+        let result: Result<Option<u64>> = from_term(&context, &frozen);
+        assert!(matches!(result, Err(Error::Message(_))));
+    }
+
+    #[derive(Deserialize, Debug)]
+    #[serde(untagged)]
+    #[allow(dead_code)]
+    enum NumberOrText {
+        Number(u64),
+        Text(String),
+    }
+
+    #[test]
+    fn deserialize_any_variable_fails() {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        // untagged enums go through deserialize_any, which can't tell
+        // what an unbound variable is supposed to be.
+        let term = context.new_term_ref();
+        let result: Result<NumberOrText> = from_term(&context, &term);
+        assert!(matches!(result, Err(Error::Message(_))));
+
+        let result: Result<Vec<NumberOrText>> =
+            from_term(&context, &context.term_from_string("[1, _]").unwrap());
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn deserialize_none_with_configured_repr() {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let configuration = DeserializerConfiguration::new().none_repr(NoneRepr::Atom("nil"));
+        let term = context.term_from_string("nil").unwrap();
+        let result: Option<Atom> =
+            from_term_with_configuration(&context, &term, configuration).unwrap();
+        assert_eq!(None, result);
+
+        let term = context.term_from_string("none").unwrap();
+        let result: Option<Atom> =
+            from_term_with_configuration(&context, &term, configuration).unwrap();
+        assert_eq!(Some(atom!("none")), result);
+
+        let configuration = DeserializerConfiguration::new().none_repr(NoneRepr::Variable);
+        let term = context.term_from_string("some(foo)").unwrap();
+        let result: Option<(Atom,)> =
+            from_term_with_configuration(&context, &term, configuration).unwrap();
+        assert_eq!(Some((atom!("foo"),)), result);
+    }
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct Marked {
+        value: u64,
+        marker: std::marker::PhantomData<String>,
+    }
+
+    #[test]
+    fn deserialize_a_struct_with_phantom_data() {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        for marker in ["[]", "'PhantomData'", "42", "foo(bar)", "_"] {
+            let term = context
+                .term_from_string(&format!("_{{value: 1, marker: {}}}", marker))
+                .unwrap();
+            let result: Marked = from_term(&context, &term).unwrap();
+            assert_eq!(
+                Marked {
+                    value: 1,
+                    marker: std::marker::PhantomData
+                },
+                result
+            );
+        }
+    }
+
+    trait Figure {
+        fn area(&self) -> f64;
+    }
+
+    #[derive(Deserialize)]
+    struct Circle {
+        radius: f64,
+    }
+
+    impl Figure for Circle {
+        fn area(&self) -> f64 {
+            std::f64::consts::PI * self.radius * self.radius
+        }
+    }
+
+    #[derive(Deserialize)]
+    struct Square {
+        side: f64,
+    }
+
+    impl Figure for Square {
+        fn area(&self) -> f64 {
+            self.side * self.side
+        }
+    }
+
+    #[test]
+    fn deserialize_trait_objects_by_tag() {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let mut registry = TypeRegistry::<dyn Figure>::new();
+        registry.register::<Circle>("circle", |c| Box::new(c));
+        registry.register::<Square>("square", |s| Box::new(s));
+
+        let term = context
+            .term_from_string("_{type: circle, radius: 1.0}")
+            .unwrap();
+        let figure = from_term_dyn(&context, &term, &registry).unwrap();
+        assert_eq!(std::f64::consts::PI, figure.area());
+
+        let term = context.term_from_string("square{side: 3.0}").unwrap();
+        let figure = from_term_dyn(&context, &term, &registry).unwrap();
+        assert_eq!(9.0, figure.area());
+
+        let term = context.term_from_string("square(_{side: 2.0})").unwrap();
+        let figure = from_term_dyn(&context, &term, &registry).unwrap();
+        assert_eq!(4.0, figure.area());
+
+        let term = context.term_from_string("triangle{side: 2.0}").unwrap();
+        assert!(matches!(
+            from_term_dyn(&context, &term, &registry),
+            Err(Error::Message(_))
+        ));
+
+        let term = context.term_from_string("42").unwrap();
+        assert!(from_term_dyn(&context, &term, &registry).is_err());
+    }
 }
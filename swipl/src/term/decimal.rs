@@ -0,0 +1,146 @@
+//! Support for exact decimals through the `rust_decimal` crate.
+//!
+//! A [Decimal] is stored in prolog as a rational number, which is
+//! exactly equal to the decimal. Retrieving a decimal from an integer
+//! or rational term only succeeds if the number can be represented
+//! exactly as a decimal, that is, if its denominator only has the
+//! prime factors 2 and 5.
+//!
+//! Alternatively, [DecimalString] stores a decimal as a prolog string
+//! containing its textual representation.
+use super::*;
+use crate::{term_getable, unifiable};
+use rust_decimal::Decimal;
+use std::str::FromStr;
+use swipl_macros::pred;
+
+/// The largest scale supported by [Decimal].
+const MAX_SCALE: u32 = 28;
+
+/// A decimal that is stored in prolog as a string.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DecimalString(pub Decimal);
+
+fn rational_to_decimal(numerator: i128, denominator: i128) -> Option<Decimal> {
+    let mut rest = denominator;
+    let mut twos = 0;
+    let mut fives = 0;
+    while rest % 2 == 0 {
+        rest /= 2;
+        twos += 1;
+    }
+    while rest % 5 == 0 {
+        rest /= 5;
+        fives += 1;
+    }
+    if rest != 1 {
+        return None;
+    }
+
+    let scale = std::cmp::max(twos, fives);
+    if scale > MAX_SCALE {
+        return None;
+    }
+
+    let mantissa = numerator.checked_mul(10_i128.pow(scale) / denominator)?;
+
+    Decimal::try_from_i128_with_scale(mantissa, scale).ok()
+}
+
+unifiable! {
+    (self: Decimal, term) => {
+        let context = unsafe { unmanaged_engine_context() };
+        let frame = context.open_frame();
+        let expr = format!("{} rdiv {}", self.mantissa(), 10_i128.pow(self.scale()));
+        let result = frame
+            .term_from_string(&expr)
+            .and_then(|expr| frame.eval(&expr))
+            .and_then(|rational| term.unify(&rational));
+        frame.close();
+
+        result.is_ok()
+    }
+}
+
+term_getable! {
+    (Decimal, "decimal", term) => {
+        let term_type = term.term_type();
+        if term_type != TermType::Integer && term_type != TermType::Rational {
+            return None;
+        }
+
+        let context = unsafe { unmanaged_engine_context() };
+        let frame = context.open_frame();
+        let [numerator, denominator] = frame.new_term_refs();
+        let result = frame
+            .call_once(pred!("rational/3"), [term, &numerator, &denominator])
+            .and_then(|_| {
+                let numerator = frame.string_from_term(&numerator)?;
+                let denominator = frame.string_from_term(&denominator)?;
+
+                Ok((numerator, denominator))
+            });
+        frame.close();
+
+        let (numerator, denominator) = result.ok()?;
+        rational_to_decimal(numerator.parse().ok()?, denominator.parse().ok()?)
+    }
+}
+
+unifiable! {
+    (self: DecimalString, term) => {
+        term.unify(self.0.to_string().as_str()).is_ok()
+    }
+}
+
+term_getable! {
+    (DecimalString, "decimal", term) => {
+        let text: PrologText = term.get().ok()?;
+
+        Decimal::from_str(&text).ok().map(DecimalString)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn roundtrip_decimal_as_rational() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let decimal = Decimal::from_str("12.3456").unwrap();
+        let term = context.new_term_ref();
+        term.unify(decimal)?;
+        assert_eq!(TermType::Rational, term.term_type());
+
+        let expected = context.term_from_string("123456 rdiv 10000")?;
+        let expected = context.eval(&expected)?;
+        assert!(term == expected);
+
+        let retrieved: Decimal = term.get()?;
+        assert_eq!("12.3456", retrieved.to_string());
+
+        let third = context.eval(&context.term_from_string("1 rdiv 3")?)?;
+        assert!(third.get::<Decimal>().unwrap_err().is_failure());
+
+        Ok(())
+    }
+
+    #[test]
+    fn roundtrip_decimal_as_string() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let decimal = DecimalString(Decimal::from_str("-0.0042").unwrap());
+        let term = context.new_term_ref();
+        term.unify(decimal)?;
+        assert_eq!("-0.0042", term.get::<String>()?);
+        assert_eq!(decimal, term.get()?);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,240 @@
+//! Direct conversion between `serde_json` values and prolog terms.
+//!
+//! This follows the conventions of SWI-Prolog's `json_read_dict/2`:
+//! - JSON objects are dictionaries with an unbound tag,
+//! - JSON arrays are lists,
+//! - JSON strings are strings,
+//! - JSON numbers are integers or floats,
+//! - `true`, `false` and `null` are the atoms `true`, `false` and `null`.
+//!
+//! Unlike going through serde with [to_term](super::ser::to_term) and
+//! [from_term](super::de::from_term), this converts a [Value]
+//! directly. The conversion goes through the [Json] wrapper, as
+//! implementing the term traits for [Value] itself would make
+//! `term.get()` ambiguous in code comparing the result against a
+//! number or a string.
+//!
+//! When retrieving a value, atoms other than `true`, `false` and
+//! `null` are converted to JSON strings, and integer dictionary keys
+//! are converted to their textual representation.
+use super::*;
+use crate::dict::*;
+use crate::{term_getable, unifiable};
+use serde_json::{Map, Number, Value};
+
+/// A JSON value that can be unified with or retrieved from a term.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Json(pub Value);
+
+impl From<Value> for Json {
+    fn from(value: Value) -> Self {
+        Json(value)
+    }
+}
+
+impl From<Json> for Value {
+    fn from(json: Json) -> Self {
+        json.0
+    }
+}
+
+fn unify_json<C: QueryableContextType>(
+    context: &Context<C>,
+    term: &Term,
+    value: &Value,
+) -> PrologResult<()> {
+    match value {
+        Value::Null => term.unify(Atomable::from("null")),
+        Value::Bool(b) => term.unify(Atomable::from(if *b { "true" } else { "false" })),
+        Value::Number(n) => {
+            if let Some(i) = n.as_i64() {
+                term.unify(i)
+            } else if let Some(u) = n.as_u64() {
+                term.unify(u)
+            } else if let Some(f) = n.as_f64() {
+                term.unify(f)
+            } else {
+                Err(PrologError::Failure)
+            }
+        }
+        Value::String(s) => term.unify(s.as_str()),
+        Value::Array(values) => {
+            let mut cur = term.clone();
+            for value in values {
+                let (head, tail) = context.unify_list_functor(&cur)?;
+                unify_json(context, &head, value)?;
+                cur = tail;
+            }
+
+            cur.unify(Nil)
+        }
+        Value::Object(entries) => {
+            let mut builder = DictBuilder::new();
+            for (key, value) in entries {
+                let value_term = context.new_term_ref();
+                unify_json(context, &value_term, value)?;
+                builder.add_entry(key.as_str(), value_term);
+            }
+
+            term.unify(builder)
+        }
+    }
+}
+
+fn get_json<C: QueryableContextType>(context: &Context<C>, term: &Term) -> PrologResult<Value> {
+    match term.term_type() {
+        TermType::Atom => {
+            let name = term
+                .get_atom_name(|name| name.map(|name| name.to_owned()))?
+                .ok_or(PrologError::Failure)?;
+            Ok(match name.as_str() {
+                "null" => Value::Null,
+                "true" => Value::Bool(true),
+                "false" => Value::Bool(false),
+                _ => Value::String(name),
+            })
+        }
+        TermType::Nil => Ok(Value::Array(Vec::new())),
+        TermType::String => Ok(Value::String(term.get::<String>()?)),
+        TermType::Integer => match attempt_opt(term.get::<i64>())? {
+            Some(i) => Ok(Value::Number(i.into())),
+            None => Ok(Value::Number(term.get::<u64>()?.into())),
+        },
+        TermType::Float => Number::from_f64(term.get::<f64>()?)
+            .map(Value::Number)
+            .ok_or(PrologError::Failure),
+        TermType::ListPair => {
+            let mut iter = context.term_list_iter(term);
+            let mut values = Vec::new();
+            for element in &mut iter {
+                values.push(get_json(context, &element)?);
+            }
+            if iter.tail().term_type() != TermType::Nil {
+                return Err(PrologError::Failure);
+            }
+
+            Ok(Value::Array(values))
+        }
+        TermType::Dict => {
+            let mut entries = Map::new();
+            for (key, value) in context.dict_entries(term) {
+                let key = match key {
+                    Key::Atom(atom) => atom.name(),
+                    Key::Int(i) => i.to_string(),
+                };
+                entries.insert(key, get_json(context, &value)?);
+            }
+
+            Ok(Value::Object(entries))
+        }
+        _ => Err(PrologError::Failure),
+    }
+}
+
+unifiable! {
+    (self: Json, term) => {
+        // unsafe justification: we are in a valid context for term
+        // handling, and this context does not outlive this
+        // implementation.
+        let context = unsafe { unmanaged_engine_context() };
+        let frame = context.open_frame();
+        match unify_json(&frame, term, &self.0) {
+            Ok(()) => {
+                frame.close();
+
+                true
+            }
+            Err(PrologError::Failure) => {
+                frame.discard();
+
+                false
+            }
+            Err(PrologError::Exception | PrologError::Halt(_)) => {
+                // closing rather than discarding the frame keeps the
+                // exception around for Term::unify to report.
+                frame.close();
+
+                false
+            }
+        }
+    }
+}
+
+term_getable! {
+    (Json, "json", term) => {
+        // unsafe justification: we are in a valid context for term
+        // handling, and this context does not outlive this
+        // implementation.
+        let context = unsafe { unmanaged_engine_context() };
+        let frame = context.open_frame();
+        match get_json(&frame, term) {
+            Ok(value) => {
+                frame.discard();
+
+                Some(Json(value))
+            }
+            Err(PrologError::Failure) => {
+                frame.discard();
+
+                None
+            }
+            Err(PrologError::Exception | PrologError::Halt(_)) => {
+                // closing rather than discarding the frame keeps the
+                // exception around for the getter to report.
+                frame.close();
+
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn roundtrip_nested_json_value() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let value = json!({
+            "name": "swipl",
+            "version": 3,
+            "ratio": 0.5,
+            "enabled": true,
+            "parent": null,
+            "tags": ["prolog", "rust", []],
+            "nested": {"depth": -2, "empty": {}}
+        });
+
+        let term = context.new_term_ref();
+        term.unify(Json(value.clone()))?;
+        assert!(term.is_dict());
+        assert_eq!("swipl", term.get_dict_key::<_, String>("name")?);
+
+        let result: Json = term.get()?;
+        assert_eq!(value, result.0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn unify_json_value_with_prolog_term() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let term = context.term_from_string("_{a: [1, \"two\", null], b: false}")?;
+        assert!(attempt(
+            term.unify(Json(json!({"a": [1, "two", null], "b": false})))
+        )?);
+        assert!(!attempt(
+            term.unify(Json(json!({"a": [1, "two", null], "b": true})))
+        )?);
+
+        Ok(())
+    }
+}
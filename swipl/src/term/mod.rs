@@ -33,32 +33,56 @@ use super::atom::*;
 use super::context::*;
 use super::engine::*;
 use super::fli::*;
+use super::functor::*;
 use super::record::*;
 use super::result::*;
+use super::text::*;
 use std::cmp::{Ordering, PartialOrd};
-use std::convert::TryInto;
+use std::convert::{TryFrom, TryInto};
 use std::fmt;
 use std::fmt::Debug;
-use std::os::raw::c_char;
+use std::num::{NonZeroI64, NonZeroU64};
+use std::os::raw::{c_char, c_void};
 
-use swipl_macros::term;
+use swipl_macros::{pred, term};
 
+mod assoc;
+mod date;
 #[cfg(feature = "serde")]
 pub mod de;
+#[cfg(feature = "rust_decimal")]
+pub mod decimal;
+#[cfg(feature = "json")]
+pub mod json;
+mod range;
 #[cfg(feature = "serde")]
 pub mod ser;
+#[cfg(feature = "debug_term_tracking")]
+pub(crate) mod tracking;
+pub mod tree;
+
+pub use date::PrologDate;
+pub use range::Between;
+pub use tree::{PrologValue, TermTree};
+
+#[cfg(feature = "rust_decimal")]
+pub use decimal::DecimalString;
 
 #[cfg(feature = "serde")]
-pub use de::Deserializer;
+pub use de::{DeserializeLimits, Deserializer, DeserializerConfiguration};
 
 #[cfg(feature = "serde")]
-pub use ser::{Serializer, SerializerConfiguration};
+pub use ser::{FloatRepr, NoneRepr, Serializer, SerializerConfiguration, UnitStructRepr};
 
 /// A term reference.
 #[derive(Clone)]
 pub struct Term<'a> {
     term: term_t,
     origin: TermOrigin<'a>,
+    #[cfg(feature = "debug_term_tracking")]
+    generation: u64,
+    #[cfg(feature = "debug_term_tracking")]
+    location: &'static std::panic::Location<'static>,
 }
 
 impl<'a> Debug for Term<'a> {
@@ -84,9 +108,44 @@ pub enum TermType {
     Unknown,
 }
 
+/// The coarse classes of terms in the standard order of terms.
+///
+/// In the standard order, every variable comes before every number,
+/// which come before every atom, and so on. The classes compare in
+/// the same order, so terms can be bucketed by class before a finer
+/// comparison within each class.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum TermOrderClass {
+    Variable,
+    /// Integers, rationals and floats.
+    Number,
+    /// Atoms, including blobs and `[]`.
+    Atom,
+    String,
+    /// Compound terms, including lists and dicts.
+    Compound,
+}
+
+/// Encodings for passing rust text to prolog.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    Utf8,
+    Latin1,
+    /// Each byte is a character of its own.
+    Octet,
+}
+
 impl<'a> Term<'a> {
+    #[cfg_attr(feature = "debug_term_tracking", track_caller)]
     pub(crate) unsafe fn new(term: term_t, origin: TermOrigin<'a>) -> Self {
-        Term { term, origin }
+        Term {
+            term,
+            origin,
+            #[cfg(feature = "debug_term_tracking")]
+            generation: tracking::current_generation(),
+            #[cfg(feature = "debug_term_tracking")]
+            location: std::panic::Location::caller(),
+        }
     }
 
     /// Return the underying `term_t` from the SWI-Prolog fli.
@@ -94,6 +153,20 @@ impl<'a> Term<'a> {
         self.term
     }
 
+    /// Return the identity of this term reference as an integer.
+    ///
+    /// Two terms have the same identity if they are the same term
+    /// reference, regardless of their content. This makes the
+    /// identity usable as a key in a map, for example to keep track
+    /// of visited terms in graph algorithms.
+    ///
+    /// The identity is only meaningful within the frame and engine
+    /// this term was created in. Once the frame is closed, the same
+    /// identity may be reused for a different term reference.
+    pub fn raw_identity(&self) -> usize {
+        self.term as usize
+    }
+
     pub fn origin_engine_ptr(&self) -> PL_engine_t {
         self.origin.origin_engine_ptr()
     }
@@ -117,6 +190,20 @@ impl<'a> Term<'a> {
         }
     }
 
+    /// Returns the class of the value in this term in the standard order of terms.
+    pub fn order_rank(&self) -> TermOrderClass {
+        match self.term_type() {
+            TermType::Variable => TermOrderClass::Variable,
+            TermType::Integer | TermType::Rational | TermType::Float => TermOrderClass::Number,
+            TermType::Atom | TermType::Nil | TermType::Blob => TermOrderClass::Atom,
+            TermType::String => TermOrderClass::String,
+            TermType::CompoundTerm | TermType::ListPair | TermType::Dict => {
+                TermOrderClass::Compound
+            }
+            TermType::Unknown => panic!("term has an unknown type"),
+        }
+    }
+
     /// Returns true if this term reference holds a variable.
     pub fn is_var(&self) -> bool {
         self.assert_term_handling_possible();
@@ -151,10 +238,60 @@ impl<'a> Term<'a> {
     }
 
     /// Assert that the engine where this term was originally created is the active engine on this thread.
+    ///
+    /// In debug builds with the `debug_term_stack_check` feature
+    /// enabled, this also checks that the term reference has not been
+    /// freed by closing, discarding or rewinding the frame it was
+    /// created in. Term references are allocated on a stack, so a
+    /// term reference that lies at or above the next free term
+    /// reference is no longer valid. This check can miss cases where
+    /// the freed slot has since been reused by a new term reference.
+    ///
+    /// With the `debug_term_tracking` feature enabled, this instead
+    /// checks that the frame the term reference was created in is
+    /// still open, and panics with the location where the term
+    /// reference was created if it is not. Unlike the check above,
+    /// this also catches freed slots that have since been reused, so
+    /// the check above is skipped when both features are enabled.
     pub fn assert_term_handling_possible(&self) {
         if !self.origin.is_engine_active() {
             panic!("term is not part of the active engine");
         }
+
+        #[cfg(feature = "debug_term_tracking")]
+        {
+            if !tracking::is_live(self.generation) {
+                panic!(
+                    "term was created at {}, but is used after the frame it was created in was closed, discarded or rewound",
+                    self.location
+                );
+            }
+        }
+
+        #[cfg(all(
+            debug_assertions,
+            feature = "debug_term_stack_check",
+            not(feature = "debug_term_tracking")
+        ))]
+        {
+            // unsafe justification: the engine is active, and the
+            // term reference we create is immediately released again.
+            let top = unsafe { PL_new_term_ref() };
+            if top == 0 {
+                // there is no room to do the check. make sure this is
+                // raised, so the operation on this term reports it.
+                if unsafe { pl_default_exception() } == 0 {
+                    unsafe { PL_resource_error(b"term_references\0".as_ptr() as *const c_char) };
+                }
+
+                return;
+            }
+
+            unsafe { PL_reset_term_refs(top) };
+            if self.term >= top {
+                panic!("term is used after the frame it was created in was closed, discarded or rewound");
+            }
+        }
     }
 
     /// Unify this term with some unifiable data.
@@ -293,6 +430,55 @@ impl<'a> Term<'a> {
         }
     }
 
+    /// Retrieve data from the term reference, distinguishing a type
+    /// mismatch from an exception.
+    ///
+    /// Any data type for which [TermGetable] has been implemented may
+    /// be retrieved in this way.
+    ///
+    /// This will return an `Err(PrologError::Exception)` if during
+    /// getting, an error was raised somewhere in the SWI-Prolog
+    /// fli. If getting is not possible (because the term holds a
+    /// variable, or an incompatible data type), `Ok(None)` will be
+    /// returned. Otherwise the result is an `Ok(Some(data))`, with
+    /// the requested data in it.
+    pub fn try_get<G: TermGetable>(&self) -> PrologResult<Option<G>> {
+        let opt = G::get(self);
+
+        if unsafe { pl_default_exception() != 0 } {
+            Err(PrologError::Exception)
+        } else {
+            Ok(opt)
+        }
+    }
+
+    /// Retrieve an integer from the term reference, parsing text in the given radix.
+    ///
+    /// If the term holds an integer, it is returned directly. If the
+    /// term holds an atom or a string, its text is parsed as an
+    /// integer in the given radix. The text may start with a `-`
+    /// sign, and for radix 16, 8 and 2, with the respective `0x`,
+    /// `0o` or `0b` prefix.
+    ///
+    /// This will return an `Err(PrologError::Failure)` if the term
+    /// holds anything else, or if its text is not a valid integer in
+    /// the given radix that fits in an `i64`. Rather than an
+    /// `Option<i64>`, this returns a [PrologResult] like the other
+    /// getters, so an exception raised while retrieving the text is
+    /// reported instead of being mistaken for invalid text. Use
+    /// [attempt_opt] to get an `Option`.
+    ///
+    /// This will panic if the radix is not in the range 2 to 36.
+    pub fn get_integer_radix(&self, radix: u32) -> PrologResult<i64> {
+        assert!((2..=36).contains(&radix), "radix out of range: {}", radix);
+        if self.is_integer() {
+            return self.get();
+        }
+
+        let text: PrologText = self.get()?;
+        parse_integer_radix(&text, radix).ok_or(PrologError::Failure)
+    }
+
     /// Retrieve data from the nth position of the given term. This
     /// assumes that the given term contains a functor.
     ///
@@ -406,6 +592,48 @@ impl<'a> Term<'a> {
         Ok(func(arg))
     }
 
+    /// Unify this term with a string, passing its text to prolog in the given encoding.
+    ///
+    /// Unifying with a `&str` directly always passes the text as
+    /// UTF-8. With [Encoding::Latin1], the text is converted to
+    /// latin-1 instead, which fails if it contains characters that
+    /// do not fit in a byte. With [Encoding::Octet], every byte of
+    /// the UTF-8 encoded text becomes a character of its own, which
+    /// is what predicates that work on raw bytes expect.
+    pub fn unify_string_encoded(&self, s: &str, encoding: Encoding) -> PrologResult<()> {
+        self.assert_term_handling_possible();
+        let latin1: Vec<u8>;
+        let (bytes, rep) = match encoding {
+            Encoding::Utf8 => (s.as_bytes(), REP_UTF8),
+            Encoding::Latin1 => {
+                latin1 = s
+                    .chars()
+                    .map(|c| u8::try_from(c as u32))
+                    .collect::<Result<_, _>>()
+                    .map_err(|_| PrologError::Failure)?;
+                (latin1.as_slice(), REP_ISO_LATIN_1)
+            }
+            Encoding::Octet => (s.as_bytes(), REP_ISO_LATIN_1),
+        };
+
+        let result = unsafe {
+            PL_unify_chars(
+                self.term,
+                (PL_STRING | rep).try_into().unwrap(),
+                bytes.len(),
+                bytes.as_ptr() as *const c_char,
+            )
+        };
+
+        if result != 0 {
+            Ok(())
+        } else if unsafe { pl_default_exception() != 0 } {
+            Err(PrologError::Exception)
+        } else {
+            Err(PrologError::Failure)
+        }
+    }
+
     /// Retrieve an atom from this term, and call the given function with a borrow to it.
     ///
     /// We skip reference-counting for this atom which may be slightly
@@ -455,6 +683,213 @@ impl<'a> Term<'a> {
         Ok(func(arg))
     }
 
+    /// Retrieve the name of the functor of this term.
+    ///
+    /// For compound terms, this is the functor name. For atoms, it
+    /// is the atom itself. For any other kind of term, this returns
+    /// `None`.
+    pub fn functor_name(&self) -> Option<Atom> {
+        self.assert_term_handling_possible();
+        let mut name = 0;
+        let mut arity = 0;
+        let result = unsafe { PL_get_name_arity(self.term, &mut name, &mut arity) };
+
+        if result == 0 {
+            None
+        } else {
+            // unsafe justification: PL_get_name_arity gave us a valid
+            // atom, which we register to keep it alive while it is
+            // wrapped.
+            unsafe {
+                register_atom(name);
+                Some(Atom::wrap(name))
+            }
+        }
+    }
+
+    /// Retrieve the length in characters of the text in this term.
+    ///
+    /// This uses the prolog predicate `string_length/2`, so the text
+    /// is not copied into rust. This returns `None` if this term is
+    /// not a string or an atom.
+    pub fn string_length(&self) -> Option<usize> {
+        self.assert_term_handling_possible();
+        if !matches!(self.term_type(), TermType::String | TermType::Atom) {
+            return None;
+        }
+
+        // unsafe justification: This context will only exist inside this function. We know we are in some valid context for term handling, so that's great.
+        let context = unsafe { unmanaged_engine_context() };
+        let frame = context.open_frame();
+        let length = frame.new_term_ref();
+        let result = frame
+            .call_once(pred!("string_length/2"), [self, &length])
+            .and_then(|_| length.get::<u64>())
+            .ok()
+            .map(|length| length as usize);
+        frame.discard();
+
+        result
+    }
+
+    /// Compute a hash of the content of this term.
+    ///
+    /// This uses the prolog predicate `term_hash/2`. Structurally
+    /// equal terms have the same hash. Only ground terms are hashed,
+    /// so for any term containing variables this returns `None`.
+    pub fn term_hash(&self) -> Option<u64> {
+        self.assert_term_handling_possible();
+        // unsafe justification: This context will only exist inside this function. We know we are in some valid context for term handling, so that's great.
+        let context = unsafe { unmanaged_engine_context() };
+        let frame = context.open_frame();
+        let hash = frame.new_term_ref();
+        let result = frame
+            .call_once(pred!("term_hash/2"), [self, &hash])
+            .and_then(|_| hash.get::<u64>())
+            .ok();
+        frame.discard();
+
+        result
+    }
+
+    /// Returns true if this term contains variables with delayed goals.
+    ///
+    /// Such goals are attached to attributed variables by predicates
+    /// like `freeze/2`, `dif/2` or `when/2`, as well as by constraint
+    /// solvers. This uses the prolog predicate `copy_term/3` to
+    /// collect them.
+    pub fn has_delayed_goals(&self) -> bool {
+        self.assert_term_handling_possible();
+        // unsafe justification: This context will only exist inside this function. We know we are in some valid context for term handling, so that's great.
+        let context = unsafe { unmanaged_engine_context() };
+        let frame = context.open_frame();
+        let [copy, goals] = frame.new_term_refs();
+        let result = frame
+            .call_once(pred!("copy_term/3"), [self, &copy, &goals])
+            .is_ok()
+            && goals.term_type() != TermType::Nil;
+        frame.discard();
+
+        result
+    }
+
+    /// Unify this term with the address of a pointer, as an integer.
+    ///
+    /// This is an escape hatch for passing opaque pointers between C
+    /// libraries through prolog. Nothing keeps the pointed-to data
+    /// alive, and nothing stops prolog code from doing arithmetic on
+    /// the address, so retrieving the pointer with
+    /// [get_pointer](Self::get_pointer) and dereferencing it is only
+    /// sound if the caller knows the data is still there. Prefer
+    /// [blobs](crate::blob) wherever possible.
+    pub fn unify_pointer(&self, ptr: *const c_void) -> PrologResult<()> {
+        self.unify(ptr as usize as u64)
+    }
+
+    /// Retrieve a pointer from an integer term holding its address.
+    ///
+    /// This is the counterpart of [unify_pointer](Self::unify_pointer),
+    /// and comes with the same caveats. This fails if the term is not
+    /// an integer, or if it does not fit in a pointer on this
+    /// platform.
+    pub fn get_pointer(&self) -> PrologResult<*mut c_void> {
+        let address: u64 = self.get()?;
+        let address: usize = address.try_into().map_err(|_| PrologError::Failure)?;
+
+        Ok(address as *mut c_void)
+    }
+
+    /// Returns true if this term is a proper list whose elements all have the given type.
+    ///
+    /// Partial lists, which end in a variable, and other improper
+    /// lists are never a list of anything. The empty list is a list
+    /// of every type. Nothing is unified or retrieved, so this is a
+    /// cheap check before deserializing untrusted input.
+    pub fn is_list_of(&self, ty: TermType) -> bool {
+        self.assert_term_handling_possible();
+        // checking for a proper list first ensures the loop below
+        // terminates, even if the list is cyclic.
+        let mut len = 0;
+        if unsafe { PL_skip_list(self.term, 0, &mut len) } as u32 != PL_LIST {
+            return false;
+        }
+
+        // unsafe justification: This context will only exist inside this function. We know we are in some valid context for term handling, so that's great.
+        let context = unsafe { unmanaged_engine_context() };
+        let frame = context.open_frame();
+        let [list, head] = frame.new_term_refs();
+        list.unify(self).unwrap();
+        let result = loop {
+            if unsafe { PL_get_nil(list.term_ptr()) != 0 } {
+                break true;
+            }
+
+            // unsafe justification: passing the same term as list and
+            // tail advances it to the tail of the list.
+            if unsafe { PL_get_list(list.term_ptr(), head.term_ptr(), list.term_ptr()) == 0 }
+                || head.term_type() != ty
+            {
+                break false;
+            }
+        };
+        frame.discard();
+
+        result
+    }
+
+    /// Interpret this term as a boolean, accepting the various ways prolog code represents one.
+    ///
+    /// Unlike retrieving a `bool` using [get](Term::get), which only
+    /// accepts the atoms `true`, `false`, `on` and `off`, this also
+    /// accepts the atoms `yes` and `no`, and the integers 1 and 0.
+    /// For anything else, this returns `None`.
+    pub fn as_bool_lenient(&self) -> Option<bool> {
+        self.assert_term_handling_possible();
+        if let Ok(b) = self.get::<bool>() {
+            return Some(b);
+        }
+
+        match self.term_type() {
+            TermType::Atom => self
+                .get_atom_name(|name| match name {
+                    Some("yes") => Some(true),
+                    Some("no") => Some(false),
+                    _ => None,
+                })
+                .ok()
+                .flatten(),
+            TermType::Integer => match self.get::<i64>() {
+                Ok(1) => Some(true),
+                Ok(0) => Some(false),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// Retrieve the variable number of a `'$VAR'(N)` term.
+    ///
+    /// Such terms are created by `numbervars/3`, and are written as
+    /// variable names like `A` or `B1`. If this term is not of this
+    /// form, `None` is returned.
+    pub fn get_numbervar(&self) -> Option<u64> {
+        self.assert_term_handling_possible();
+        let functor = Functor::new("$VAR", 1);
+        if unsafe { PL_is_functor(self.term, functor.functor_ptr()) } == 0 {
+            return None;
+        }
+
+        self.get_arg::<u64>(1).ok()
+    }
+
+    /// Unify this term with a `'$VAR'(N)` term.
+    ///
+    /// This is the counterpart of [get_numbervar](Term::get_numbervar).
+    pub fn unify_numbervar(&self, number: u64) -> PrologResult<()> {
+        self.unify(Functor::new("$VAR", 1))?;
+        self.unify_arg(1, number)
+    }
+
     /// Put data into the term reference using a borrow.
     ///
     /// Any data type for which [TermPutable] has been implemented may
@@ -493,12 +928,48 @@ impl<'a> Term<'a> {
         self.put(&val)
     }
 
+    /// Put a fresh unbound variable into the term reference.
+    ///
+    /// This is a nonlogical operation. The term reference will
+    /// replace its content in a way that does not play nicely with
+    /// backtracking.
+    pub fn put_variable(&self) {
+        self.assert_term_handling_possible();
+        unsafe { PL_put_variable(self.term) };
+    }
+
     /// Return a record of the term.
     pub fn record(&self) -> Record {
         Record::from_term(self)
     }
 }
 
+fn parse_integer_radix(text: &str, radix: u32) -> Option<i64> {
+    let (negative, mut digits) = match text.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, text),
+    };
+    let prefix = match radix {
+        16 => Some("0x"),
+        8 => Some("0o"),
+        2 => Some("0b"),
+        _ => None,
+    };
+    if let Some(prefix) = prefix {
+        if digits.len() > 2 && digits.as_bytes()[..2].eq_ignore_ascii_case(prefix.as_bytes()) {
+            digits = &digits[2..];
+        }
+    }
+    if digits.starts_with('+') {
+        return None;
+    }
+
+    let magnitude = u64::from_str_radix(digits, radix).ok()? as i128;
+    let value = if negative { -magnitude } else { magnitude };
+
+    value.try_into().ok()
+}
+
 impl<'a> PartialEq for Term<'a> {
     fn eq(&self, other: &Term) -> bool {
         self.assert_term_handling_possible();
@@ -935,6 +1406,44 @@ term_putable! {
     }
 }
 
+unifiable! {
+    (self:NonZeroU64, term) => {
+        attempt(term.unify(self.get())).unwrap_or(false)
+    }
+}
+
+term_getable! {
+    (NonZeroU64, "integer", term) => {
+        let num: u64 = attempt_opt(term.get()).unwrap_or(None)?;
+        NonZeroU64::new(num)
+    }
+}
+
+term_putable! {
+    (self:NonZeroU64, term) => {
+        term.put_val(self.get()).unwrap_or(());
+    }
+}
+
+unifiable! {
+    (self:NonZeroI64, term) => {
+        attempt(term.unify(self.get())).unwrap_or(false)
+    }
+}
+
+term_getable! {
+    (NonZeroI64, "integer", term) => {
+        let num: i64 = attempt_opt(term.get()).unwrap_or(None)?;
+        NonZeroI64::new(num)
+    }
+}
+
+term_putable! {
+    (self:NonZeroI64, term) => {
+        term.put_val(self.get()).unwrap_or(());
+    }
+}
+
 unifiable! {
     (self:f64, term) => {
         let result = unsafe { PL_unify_float(term.term, *self) };
@@ -1056,6 +1565,74 @@ term_putable! {
 
 }
 
+/// Call the given function with the bytes of a string or code list
+/// term.
+///
+/// The term text is retrieved in latin-1, so that each character
+/// corresponds with exactly one byte. This returns `None` if the term
+/// is not a string or a code list, or if it contains characters that
+/// do not fit in a byte.
+#[cfg(feature = "bytes")]
+fn with_term_bytes<R>(term: &Term, f: impl FnOnce(&[u8]) -> R) -> Option<R> {
+    let mut len: usize = 0;
+    let mut s: *mut c_char = std::ptr::null_mut();
+    let flags = CVT_STRING | CVT_LIST | BUF_DISCARDABLE | REP_ISO_LATIN_1;
+    let result = unsafe { PL_get_nchars(term.term_ptr(), &mut len, &mut s, flags) };
+    if result == 0 {
+        return None;
+    }
+
+    let slice = unsafe { std::slice::from_raw_parts(s as *const u8, len) };
+
+    Some(f(slice))
+}
+
+#[cfg(feature = "bytes")]
+unifiable! {
+    (self: bytes::Bytes, term) => {
+        let slice: &[u8] = self;
+        slice.unify(term)
+    }
+}
+
+#[cfg(feature = "bytes")]
+term_getable! {
+    (bytes::Bytes, "string", term) => {
+        with_term_bytes(term, bytes::Bytes::copy_from_slice)
+    }
+}
+
+#[cfg(feature = "bytes")]
+term_putable! {
+    (self: bytes::Bytes, term) => {
+        let slice: &[u8] = self;
+        slice.put(term);
+    }
+}
+
+#[cfg(feature = "bytes")]
+unifiable! {
+    (self: bytes::BytesMut, term) => {
+        let slice: &[u8] = self;
+        slice.unify(term)
+    }
+}
+
+#[cfg(feature = "bytes")]
+term_getable! {
+    (bytes::BytesMut, "string", term) => {
+        with_term_bytes(term, |b| bytes::BytesMut::from(b))
+    }
+}
+
+#[cfg(feature = "bytes")]
+term_putable! {
+    (self: bytes::BytesMut, term) => {
+        let slice: &[u8] = self;
+        slice.put(term);
+    }
+}
+
 /// Unit struct representing an empty list in SWI-Prolog.
 pub struct Nil;
 unifiable! {
@@ -1520,4 +2097,416 @@ mod tests {
         assert_eq!(42, result.unwrap());
         assert!(!context.has_exception());
     }
+
+    #[test]
+    fn try_get_distinguishes_mismatch_from_exception() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let term = term! {context: 42}?;
+        assert_eq!(Some(42), term.try_get::<u64>()?);
+
+        let term = term! {context: foo}?;
+        assert_eq!(None, term.try_get::<u64>()?);
+        assert!(!context.has_exception());
+
+        let term = context.term_from_string("1180591620717411303424")?;
+        assert!(term.try_get::<u64>().unwrap_err().is_exception());
+        assert!(context.has_exception());
+
+        Ok(())
+    }
+
+    #[test]
+    fn unify_and_get_nonzero_integers() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let term = term! {context: 0}?;
+        assert_eq!(None, term.try_get::<NonZeroU64>()?);
+        assert_eq!(None, term.try_get::<NonZeroI64>()?);
+
+        let term = term! {context: 42}?;
+        assert_eq!(NonZeroU64::new(42), term.try_get::<NonZeroU64>()?);
+        assert_eq!(NonZeroI64::new(42), term.try_get::<NonZeroI64>()?);
+
+        let term = term! {context: -42}?;
+        assert_eq!(None, term.try_get::<NonZeroU64>()?);
+        assert_eq!(NonZeroI64::new(-42), term.try_get::<NonZeroI64>()?);
+
+        let term = context.new_term_ref();
+        term.unify(NonZeroU64::new(7).unwrap())?;
+        assert_eq!(7_u64, term.get()?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn put_variable_replaces_value() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let term = term! {context: foo}?;
+        assert!(!term.is_var());
+        term.put_variable();
+        assert!(term.is_var());
+        assert_eq!(TermType::Variable, term.term_type());
+
+        Ok(())
+    }
+
+    #[test]
+    fn get_integer_in_radix() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let term = term! {context: ff}?;
+        assert_eq!(255, term.get_integer_radix(16)?);
+
+        let term = context.term_from_string("'0xFF'")?;
+        assert_eq!(255, term.get_integer_radix(16)?);
+
+        let term = term! {context: "-101"}?;
+        assert_eq!(-5, term.get_integer_radix(2)?);
+
+        let term = term! {context: 42}?;
+        assert_eq!(42, term.get_integer_radix(16)?);
+
+        let term = term! {context: fg}?;
+        assert!(term.get_integer_radix(16).unwrap_err().is_failure());
+
+        // the prefix check must not split a multi-byte character
+        let term = context.term_from_string("'aé'")?;
+        assert!(term.get_integer_radix(16).unwrap_err().is_failure());
+        let term = context.term_from_string("'0é1'")?;
+        assert!(term.get_integer_radix(8).unwrap_err().is_failure());
+
+        let term = term! {context: foo(bar)}?;
+        assert!(term.get_integer_radix(16).unwrap_err().is_failure());
+
+        Ok(())
+    }
+
+    #[cfg(feature = "bytes")]
+    #[test]
+    fn roundtrip_bytes() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let buf = bytes::Bytes::from_static(&[0, 1, 42, 255]);
+        let term = context.new_term_ref();
+        term.unify(&buf)?;
+        assert_eq!(buf, term.get::<bytes::Bytes>()?);
+
+        let term = context.new_term_ref();
+        term.put(&bytes::BytesMut::from(&b"hello"[..]))?;
+        assert_eq!(&b"hello"[..], &term.get::<bytes::BytesMut>()?[..]);
+
+        let codes = context.term_from_string("[104, 105]")?;
+        assert_eq!(&b"hi"[..], &codes.get::<bytes::Bytes>()?[..]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn unify_arg_of_partially_bound_compound() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let term = term! {context: foo(a, _)}?;
+        term.unify_arg(2, Atom::new("b"))?;
+        assert_eq!(Atom::new("b"), term.get_arg::<Atom>(2)?);
+        assert!(term.unify_arg(1, Atom::new("b")).unwrap_err().is_failure());
+        assert!(term.unify_arg(3, Atom::new("b")).unwrap_err().is_failure());
+
+        let atom_term = term! {context: foo}?;
+        assert!(atom_term
+            .unify_arg(1, Atom::new("b"))
+            .unwrap_err()
+            .is_failure());
+
+        Ok(())
+    }
+
+    #[derive(crate::FromTerm, crate::IntoTerm, Debug, PartialEq)]
+    struct Point {
+        x: i64,
+        y: i64,
+    }
+
+    #[derive(crate::FromTerm, crate::IntoTerm, Debug, PartialEq)]
+    struct NamedPair(Atom, u64);
+
+    #[test]
+    fn roundtrip_derived_structs() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let term = context.term_from_string("point(1,2)")?;
+        let point: Point = term.get()?;
+        assert_eq!(Point { x: 1, y: 2 }, point);
+
+        let term2 = context.new_term_ref();
+        term2.unify(&point)?;
+        assert_eq!("point(1,2)", context.string_from_term(&term2)?);
+
+        let term3 = context.term_from_string("point(1,2,3)")?;
+        assert!(term3.get::<Point>().unwrap_err().is_failure());
+
+        let pair = NamedPair(Atom::new("a"), 42);
+        let term4 = context.new_term_ref();
+        term4.unify(&pair)?;
+        assert_eq!("named_pair(a,42)", context.string_from_term(&term4)?);
+        assert_eq!(pair, term4.get()?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn get_functor_name() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let term = context.term_from_string("foo(1,2,3)")?;
+        assert_eq!(Some(Atom::new("foo")), term.functor_name());
+
+        let term = context.term_from_string("bar")?;
+        assert_eq!(Some(Atom::new("bar")), term.functor_name());
+
+        let term = context.term_from_string("42")?;
+        assert_eq!(None, term.functor_name());
+
+        Ok(())
+    }
+
+    #[test]
+    fn numbervar_round_trip() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let term = context.term_from_string("'$VAR'(0)")?;
+        assert_eq!(Some(0), term.get_numbervar());
+
+        let term = context.term_from_string("foo(0)")?;
+        assert_eq!(None, term.get_numbervar());
+
+        let term = context.new_term_ref();
+        term.unify_numbervar(27)?;
+        assert_eq!(Some(27), term.get_numbervar());
+
+        Ok(())
+    }
+
+    #[cfg(all(
+        debug_assertions,
+        feature = "debug_term_stack_check",
+        not(feature = "debug_term_tracking")
+    ))]
+    #[test]
+    #[should_panic(expected = "term is used after the frame it was created in was closed")]
+    fn use_term_after_frame_close_panics() {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let frame = context.open_frame();
+        let term_ptr = frame.new_term_ref().term_ptr();
+        frame.close();
+
+        // unsafe justification: this deliberately creates a dangling
+        // term reference to trigger the check.
+        let term = unsafe { Term::new(term_ptr, TermOrigin::new(context.engine_ptr())) };
+        term.is_var();
+    }
+
+    #[test]
+    fn hash_ground_and_nonground_terms() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let term1 = context.term_from_string("foo(bar, [1, 2, \"baz\"])")?;
+        let term2 = context.term_from_string("foo(bar, [1, 2, \"baz\"])")?;
+        let term3 = context.term_from_string("foo(bar, [1, 2, \"quux\"])")?;
+        let hash = term1.term_hash();
+        assert!(hash.is_some());
+        assert_eq!(hash, term2.term_hash());
+        assert_ne!(hash, term3.term_hash());
+
+        let nonground = context.term_from_string("foo(bar, X)")?;
+        assert_eq!(None, nonground.term_hash());
+
+        Ok(())
+    }
+
+    #[test]
+    fn retrieve_string_length() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let string = context.term_from_string("\"héllo wörld\"")?;
+        assert_eq!(Some(11), string.string_length());
+        let atom = context.term_from_string("'ünïcode'")?;
+        assert_eq!(Some(7), atom.string_length());
+        let number = context.term_from_string("42")?;
+        assert_eq!(None, number.string_length());
+
+        Ok(())
+    }
+
+    #[test]
+    fn interpret_terms_as_lenient_bools() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        for (text, expected) in [
+            ("true", Some(true)),
+            ("false", Some(false)),
+            ("on", Some(true)),
+            ("off", Some(false)),
+            ("yes", Some(true)),
+            ("no", Some(false)),
+            ("1", Some(true)),
+            ("0", Some(false)),
+            ("2", None),
+            ("maybe", None),
+            ("\"true\"", None),
+            ("f(true)", None),
+        ] {
+            let term = context.term_from_string(text)?;
+            assert_eq!(expected, term.as_bool_lenient(), "for {}", text);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn raw_identity_of_term_refs() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let [term1, term2] = context.new_term_refs();
+        term1.unify(42_u64)?;
+        term2.unify(42_u64)?;
+        let copy = term1.clone();
+
+        assert_eq!(term1.raw_identity(), copy.raw_identity());
+        assert_ne!(term1.raw_identity(), term2.raw_identity());
+
+        Ok(())
+    }
+
+    #[test]
+    fn check_list_element_types() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let term = context.term_from_string("[1, 2, 3]")?;
+        assert!(term.is_list_of(TermType::Integer));
+        assert!(!term.is_list_of(TermType::Atom));
+
+        let term = context.term_from_string("[1, foo, 3]")?;
+        assert!(!term.is_list_of(TermType::Integer));
+
+        let term = context.term_from_string("[1, 2 | _]")?;
+        assert!(!term.is_list_of(TermType::Integer));
+
+        let term = context.term_from_string("[]")?;
+        assert!(term.is_list_of(TermType::Integer));
+
+        let term = context.term_from_string("X = [1, 2 | X]")?;
+        context.call_term_once(&term)?;
+        let [cyclic, _] = context.compound_terms(&term)?;
+        assert!(!cyclic.is_list_of(TermType::Integer));
+
+        Ok(())
+    }
+
+    #[test]
+    fn roundtrip_pointer_as_integer() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let value = 42_u64;
+        let ptr = &value as *const u64 as *const c_void;
+
+        let term = context.new_term_ref();
+        term.unify_pointer(ptr)?;
+        assert!(term.is_integer());
+        assert_eq!(ptr as usize as u64, term.get::<u64>()?);
+        assert_eq!(ptr as *mut c_void, term.get_pointer()?);
+
+        let term = context.term_from_string("foo")?;
+        assert!(term.get_pointer().unwrap_err().is_failure());
+
+        Ok(())
+    }
+
+    #[test]
+    fn order_rank_follows_standard_order() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let terms = context.term_from_string("[f(x), \"str\", foo, 4.2, _]")?;
+        let mut terms: Vec<Term> = context.term_list_iter(&terms).collect();
+        terms.sort();
+        let ranks: Vec<TermOrderClass> = terms.iter().map(|t| t.order_rank()).collect();
+        assert_eq!(
+            vec![
+                TermOrderClass::Variable,
+                TermOrderClass::Number,
+                TermOrderClass::Atom,
+                TermOrderClass::String,
+                TermOrderClass::Compound
+            ],
+            ranks
+        );
+
+        let term = context.term_from_string("[]")?;
+        assert_eq!(TermOrderClass::Atom, term.order_rank());
+
+        Ok(())
+    }
+
+    #[test]
+    fn unify_string_with_encoding() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let term = context.new_term_ref();
+        term.unify_string_encoded("café", Encoding::Latin1)?;
+        assert_eq!("café", term.get::<String>()?);
+
+        let term = context.new_term_ref();
+        term.unify_string_encoded("café", Encoding::Utf8)?;
+        assert_eq!("café", term.get::<String>()?);
+
+        let term = context.new_term_ref();
+        term.unify_string_encoded("café", Encoding::Octet)?;
+        assert_eq!("cafÃ©", term.get::<String>()?);
+
+        let term = context.new_term_ref();
+        assert!(term
+            .unify_string_encoded("€", Encoding::Latin1)
+            .unwrap_err()
+            .is_failure());
+
+        Ok(())
+    }
 }
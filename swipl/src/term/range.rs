@@ -0,0 +1,153 @@
+//! Conversion between rust ranges and prolog bounds.
+//!
+//! A [Range] or [RangeInclusive] of `i64` is unified with a term as a
+//! `Low-High` pair of inclusive bounds, so `1..5` becomes `1-4` and
+//! `1..=5` becomes `1-5`. Alternatively, [Between] stores a range as
+//! a `between(Low, High)` compound, whose arguments can be passed
+//! directly to `between/3`.
+use super::*;
+use crate::{term_getable, unifiable};
+use std::ops::{Range, RangeInclusive};
+
+/// A range that is stored in prolog as a `between(Low, High)` compound.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Between<R>(pub R);
+
+fn unify_bounds(term: &Term, name: &str, bounds: Option<(i64, i64)>) -> bool {
+    let (low, high) = match bounds {
+        Some(bounds) => bounds,
+        None => return false,
+    };
+
+    // unsafe justification: This context will only exist inside this function. We know we are in some valid context for term handling, so that's great.
+    let context = unsafe { unmanaged_engine_context() };
+    let frame = context.open_frame();
+    let success = term.unify(Functor::new(name, 2)).is_ok()
+        && term.unify_arg(1, low).is_ok()
+        && term.unify_arg(2, high).is_ok();
+    if success {
+        frame.close();
+    } else {
+        frame.discard();
+    }
+
+    success
+}
+
+fn get_bounds(term: &Term, name: &str) -> Option<(i64, i64)> {
+    if term.get::<Functor>().ok()? != Functor::new(name, 2) {
+        return None;
+    }
+
+    Some((term.get_arg(1).ok()?, term.get_arg(2).ok()?))
+}
+
+fn exclusive_bounds(range: &Range<i64>) -> Option<(i64, i64)> {
+    Some((range.start, range.end.checked_sub(1)?))
+}
+
+fn exclusive_range((low, high): (i64, i64)) -> Option<Range<i64>> {
+    Some(low..high.checked_add(1)?)
+}
+
+unifiable! {
+    (self: Range<i64>, term) => {
+        unify_bounds(term, "-", exclusive_bounds(self))
+    }
+}
+
+term_getable! {
+    (Range<i64>, "range", term) => {
+        exclusive_range(get_bounds(term, "-")?)
+    }
+}
+
+unifiable! {
+    (self: RangeInclusive<i64>, term) => {
+        unify_bounds(term, "-", Some((*self.start(), *self.end())))
+    }
+}
+
+term_getable! {
+    (RangeInclusive<i64>, "range", term) => {
+        let (low, high) = get_bounds(term, "-")?;
+
+        Some(low..=high)
+    }
+}
+
+unifiable! {
+    (self: Between<Range<i64>>, term) => {
+        unify_bounds(term, "between", exclusive_bounds(&self.0))
+    }
+}
+
+term_getable! {
+    (Between<Range<i64>>, "between", term) => {
+        exclusive_range(get_bounds(term, "between")?).map(Between)
+    }
+}
+
+unifiable! {
+    (self: Between<RangeInclusive<i64>>, term) => {
+        unify_bounds(term, "between", Some((*self.0.start(), *self.0.end())))
+    }
+}
+
+term_getable! {
+    (Between<RangeInclusive<i64>>, "between", term) => {
+        let (low, high) = get_bounds(term, "between")?;
+
+        Some(Between(low..=high))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn roundtrip_ranges_as_pairs() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let term = context.new_term_ref();
+        term.unify(1_i64..5)?;
+        assert_eq!("1-4", context.string_from_term(&term)?);
+        assert_eq!(1..5, term.get::<Range<i64>>()?);
+
+        let term = context.new_term_ref();
+        term.unify(1_i64..=5)?;
+        assert_eq!("1-5", context.string_from_term(&term)?);
+        assert_eq!(1..=5, term.get::<RangeInclusive<i64>>()?);
+
+        let term = context.new_term_ref();
+        // the end of this range can not be made inclusive without underflowing.
+        #[allow(clippy::reversed_empty_ranges)]
+        let range = 0_i64..i64::MIN;
+        assert!(term.unify(range).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn roundtrip_ranges_as_between() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let term = context.new_term_ref();
+        term.unify(Between(1_i64..5))?;
+        assert_eq!("between(1,4)", context.string_from_term(&term)?);
+        assert_eq!(Between(1..5), term.get::<Between<Range<i64>>>()?);
+        assert!(term.get::<Range<i64>>().unwrap_err().is_failure());
+
+        let term = context.new_term_ref();
+        term.unify(Between(1_i64..=5))?;
+        assert_eq!("between(1,5)", context.string_from_term(&term)?);
+        assert_eq!(Between(1..=5), term.get::<Between<RangeInclusive<i64>>>()?);
+
+        Ok(())
+    }
+}
@@ -63,17 +63,80 @@ fn attempt_unify<U: Unifiable>(term: &Term, v: U) -> Result<(), Error> {
     }
 }
 
+fn attempt_unify_arg<U: Unifiable>(term: &Term, index: usize, v: U) -> Result<(), Error> {
+    if attempt(term.unify_arg(index, v))? {
+        Ok(())
+    } else {
+        Err(Error::UnificationFailed)
+    }
+}
+
+/// The way floating point numbers are represented in prolog.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FloatRepr {
+    /// Store floats as prolog floats.
+    Native,
+    /// Store floats as the rational number that is exactly equal to
+    /// the float.
+    Rational,
+    /// Store floats as a string containing the shortest decimal
+    /// representation that round-trips to the same float.
+    String,
+}
+
+/// The way `Option::None` is represented in prolog.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NoneRepr {
+    /// Leave `None` as an unbound variable. `Some(x)` is stored as
+    /// just `x`.
+    Variable,
+    /// Store `None` as the given atom. `Some(x)` is stored as
+    /// `some(x)`.
+    ///
+    /// To deserialize an atom other than `none` back into `None`,
+    /// configure the deserializer with the same representation.
+    Atom(&'static str),
+    /// Drop the field from the surrounding dictionary. `Some(x)` is
+    /// stored as just `x`.
+    ///
+    /// Outside of a dictionary, `None` is left as an unbound
+    /// variable.
+    Omit,
+}
+
+/// The way unit structs, like `struct Marker;`, are represented in prolog.
+///
+/// Deserialization accepts both representations. Note that unit enum
+/// variants are not affected by this, as they are always stored as
+/// the atom of their variant name. The unit type `()` is always
+/// stored as `[]`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnitStructRepr {
+    /// Store unit structs as the atom of their name.
+    Name,
+    /// Store unit structs as `[]`, just like `()`.
+    Nil,
+}
+
 /// Configuration object for the serializer.
 ///
 /// By default, serialization is done with the following options:
 /// - prolog dictionary tags will remain variables.
 /// - struct type names are ignored and will not be set as the dictionary tag.
+/// - floats are stored as prolog floats.
+/// - `None` is stored as the atom `none`, and `Some(x)` as `some(x)`.
+/// - unit structs are stored as the atom of their name.
+/// - enum variants are not module-qualified.
 ///
 /// This object allows you to override these options.
 #[derive(Debug, Clone)]
 pub struct SerializerConfiguration {
     default_tag: Option<Atom>,
     tag_struct_dicts: bool,
+    float_repr: FloatRepr,
+    none_repr: NoneRepr,
+    unit_struct_repr: UnitStructRepr,
+    variant_module: Option<Atom>,
 }
 
 impl Default for SerializerConfiguration {
@@ -88,6 +151,10 @@ impl SerializerConfiguration {
         Self {
             default_tag: None,
             tag_struct_dicts: false,
+            float_repr: FloatRepr::Native,
+            none_repr: NoneRepr::Atom("none"),
+            unit_struct_repr: UnitStructRepr::Name,
+            variant_module: None,
         }
     }
 
@@ -125,6 +192,77 @@ impl SerializerConfiguration {
         self.set_tag_struct_dicts();
         self
     }
+
+    /// Set the way floats are represented.
+    ///
+    /// By default, floats are stored as prolog floats.
+    pub fn set_float_repr(&mut self, float_repr: FloatRepr) {
+        self.float_repr = float_repr;
+    }
+
+    /// Set the way floats are represented.
+    ///
+    /// By default, floats are stored as prolog floats.
+    pub fn float_repr(mut self, float_repr: FloatRepr) -> Self {
+        self.set_float_repr(float_repr);
+        self
+    }
+
+    /// Set the way `Option::None` is represented.
+    ///
+    /// By default, `None` is stored as the atom `none`.
+    pub fn set_none_repr(&mut self, none_repr: NoneRepr) {
+        self.none_repr = none_repr;
+    }
+
+    /// Set the way `Option::None` is represented.
+    ///
+    /// By default, `None` is stored as the atom `none`.
+    pub fn none_repr(mut self, none_repr: NoneRepr) -> Self {
+        self.set_none_repr(none_repr);
+        self
+    }
+
+    /// Set the way unit structs are represented.
+    ///
+    /// By default, unit structs are stored as the atom of their name.
+    pub fn set_unit_struct_repr(&mut self, unit_struct_repr: UnitStructRepr) {
+        self.unit_struct_repr = unit_struct_repr;
+    }
+
+    /// Set the way unit structs are represented.
+    ///
+    /// By default, unit structs are stored as the atom of their name.
+    pub fn unit_struct_repr(mut self, unit_struct_repr: UnitStructRepr) -> Self {
+        self.set_unit_struct_repr(unit_struct_repr);
+        self
+    }
+
+    /// Qualify enum variants with the given module.
+    ///
+    /// Variants are then stored as `module:variant(..)`, which is
+    /// useful when passing them to module-sensitive predicates, such
+    /// as meta-predicates calling the variant as a goal. The
+    /// deserializer accepts both qualified and unqualified variants.
+    pub fn set_variant_module<A: AsAtom>(&mut self, module: A) {
+        self.variant_module = Some(module.as_atom());
+    }
+
+    /// Qualify enum variants with the given module.
+    ///
+    /// Variants are then stored as `module:variant(..)`, which is
+    /// useful when passing them to module-sensitive predicates, such
+    /// as meta-predicates calling the variant as a goal. The
+    /// deserializer accepts both qualified and unqualified variants.
+    pub fn variant_module<A: AsAtom>(mut self, module: A) -> Self {
+        self.set_variant_module(module.as_atom());
+
+        self
+    }
+
+    fn omits_none(&self) -> bool {
+        self.none_repr == NoneRepr::Omit
+    }
 }
 
 /// A serde serializer for turning rust values into prolog terms.
@@ -135,6 +273,24 @@ pub struct Serializer<'a, C: QueryableContextType> {
 }
 
 impl<'a, C: QueryableContextType> Serializer<'a, C> {
+    /// Return the term to serialize an enum variant into.
+    ///
+    /// If a variant module is configured, this unifies the term with
+    /// `module:_` and returns the unqualified part.
+    fn variant_term(&self) -> Result<Term<'a>, Error> {
+        match &self.configuration.variant_module {
+            None => Ok(self.term.clone()),
+            Some(module) => {
+                let variant = self.context.new_term_ref();
+                attempt_unify(&self.term, Functor::new(":", 2))?;
+                attempt_unify_arg(&self.term, 1, module)?;
+                attempt_unify_arg(&self.term, 2, &variant)?;
+
+                Ok(variant)
+            }
+        }
+    }
+
     /// Create a new serializer with the default configuration.
     pub fn new(context: &'a Context<'a, C>, term: Term<'a>) -> Self {
         Self {
@@ -200,7 +356,25 @@ impl<'a, C: QueryableContextType> serde::Serializer for Serializer<'a, C> {
         self.serialize_f64(v as f64)
     }
     fn serialize_f64(self, v: f64) -> Result<Self::Ok, Self::Error> {
-        attempt_unify(&self.term, v)
+        match self.configuration.float_repr {
+            FloatRepr::Native => attempt_unify(&self.term, v),
+            FloatRepr::Rational => {
+                let context = self.context;
+                let reset_term = context.new_term_ref();
+                let rational =
+                    attempt_opt(term! {context: rational(#v)}.and_then(|e| context.eval(&e)))?;
+                let result = match rational {
+                    Some(rational) => attempt_unify(&self.term, &rational),
+                    None => Err(Error::UnificationFailed),
+                };
+                unsafe {
+                    reset_term.reset();
+                }
+
+                result
+            }
+            FloatRepr::String => attempt_unify(&self.term, v.to_string().as_str()),
+        }
     }
     fn serialize_char(self, v: char) -> Result<Self::Ok, Self::Error> {
         attempt_unify(&self.term, Atomable::String(v.to_string()))
@@ -212,13 +386,18 @@ impl<'a, C: QueryableContextType> serde::Serializer for Serializer<'a, C> {
         attempt_unify(&self.term, v)
     }
     fn serialize_none(self) -> Result<Self::Ok, Self::Error> {
-        attempt_unify(&self.term, atom!("none"))
+        match self.configuration.none_repr {
+            NoneRepr::Atom(name) => attempt_unify(&self.term, Atom::new(name)),
+            NoneRepr::Variable | NoneRepr::Omit => Ok(()),
+        }
     }
     fn serialize_some<T: ?Sized>(self, value: &T) -> Result<Self::Ok, Self::Error>
     where
         T: Serialize,
     {
-        if attempt(self.term.unify(functor!("some/1")))? {
+        if !matches!(self.configuration.none_repr, NoneRepr::Atom(_)) {
+            value.serialize(self)
+        } else if attempt(self.term.unify(functor!("some/1")))? {
             let [term] = attempt_opt(self.context.compound_terms(&self.term))?.expect("having just unified the functor some/1, retrieving its argument list should have been possible");
             let inner_serializer =
                 Serializer::new_with_config(self.context, term.clone(), self.configuration.clone());
@@ -235,7 +414,10 @@ impl<'a, C: QueryableContextType> serde::Serializer for Serializer<'a, C> {
         attempt_unify(&self.term, Nil)
     }
     fn serialize_unit_struct(self, name: &'static str) -> Result<Self::Ok, Self::Error> {
-        attempt_unify(&self.term, Atom::new(name))
+        match self.configuration.unit_struct_repr {
+            UnitStructRepr::Name => attempt_unify(&self.term, Atom::new(name)),
+            UnitStructRepr::Nil => attempt_unify(&self.term, Nil),
+        }
     }
     fn serialize_unit_variant(
         self,
@@ -243,7 +425,7 @@ impl<'a, C: QueryableContextType> serde::Serializer for Serializer<'a, C> {
         _variant_index: u32,
         variant: &'static str,
     ) -> Result<Self::Ok, Self::Error> {
-        attempt_unify(&self.term, Atom::new(variant))
+        attempt_unify(&self.variant_term()?, Atom::new(variant))
     }
     fn serialize_newtype_struct<T: ?Sized>(
         self,
@@ -278,8 +460,9 @@ impl<'a, C: QueryableContextType> serde::Serializer for Serializer<'a, C> {
     where
         T: Serialize,
     {
-        if attempt(self.term.unify(Functor::new(variant, 1)))? {
-            let [term] = attempt_opt(self.context.compound_terms(&self.term))?.expect("having just unified the functor with arity 1, retrieving its argument list should have been possible");
+        let variant_term = self.variant_term()?;
+        if attempt(variant_term.unify(Functor::new(variant, 1)))? {
+            let [term] = attempt_opt(self.context.compound_terms(&variant_term))?.expect("having just unified the functor with arity 1, retrieving its argument list should have been possible");
             let inner_serializer =
                 Serializer::new_with_config(self.context, term.clone(), self.configuration.clone());
             let result = value.serialize(inner_serializer);
@@ -292,9 +475,12 @@ impl<'a, C: QueryableContextType> serde::Serializer for Serializer<'a, C> {
         }
     }
     fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq, Self::Error> {
+        let [head, tail] = self.context.new_term_refs();
+        attempt_unify(&tail, &self.term)?;
         Ok(SerializeSeq {
             context: self.context,
-            term: self.term.clone(),
+            head,
+            tail,
             configuration: self.configuration.clone(),
         })
     }
@@ -332,11 +518,12 @@ impl<'a, C: QueryableContextType> serde::Serializer for Serializer<'a, C> {
         len: usize,
     ) -> Result<Self::SerializeTupleVariant, Self::Error> {
         // we serialize based on the variant name.
-        attempt_unify(&self.term, Functor::new(variant, len as u16))?;
+        let variant_term = self.variant_term()?;
+        attempt_unify(&variant_term, Functor::new(variant, len as u16))?;
 
         Ok(SerializeNamedTuple {
             context: self.context,
-            term: self.term.clone(),
+            term: variant_term,
             pos: 0,
             configuration: self.configuration.clone(),
         })
@@ -375,7 +562,7 @@ impl<'a, C: QueryableContextType> serde::Serializer for Serializer<'a, C> {
     ) -> Result<Self::SerializeStructVariant, Self::Error> {
         Ok(SerializeMap::new(
             self.context,
-            self.term,
+            self.variant_term()?,
             Some(variant),
             self.configuration,
         ))
@@ -385,7 +572,7 @@ impl<'a, C: QueryableContextType> serde::Serializer for Serializer<'a, C> {
 thread_local! {
     static SERIALIZING_SWIPL_TERM: Cell<bool> = Cell::new(false);
 }
-struct SerializingSwiplTermState;
+pub(crate) struct SerializingSwiplTermState;
 
 impl SerializingSwiplTermState {
     fn start() -> Self {
@@ -399,7 +586,7 @@ impl SerializingSwiplTermState {
         Self
     }
 
-    fn is_serializing_swipl_term() -> bool {
+    pub(crate) fn is_serializing_swipl_term() -> bool {
         SERIALIZING_SWIPL_TERM.with(|sst| sst.get())
     }
 }
@@ -621,9 +808,18 @@ impl<'a> ser::Serializer for AtomEmitter<'a> {
     }
 }
 
+/// Serializer for sequences, building a prolog list.
+///
+/// Elements are unified into the list as they arrive, so no
+/// intermediate collection is built. The list is built using a fixed
+/// pair of term references for the head and tail, and any term
+/// references created while serializing an element are released
+/// afterwards. Serializing a long sequence therefore does not grow
+/// the number of term references in use.
 pub struct SerializeSeq<'a, C: QueryableContextType> {
     context: &'a Context<'a, C>,
-    term: Term<'a>,
+    head: Term<'a>,
+    tail: Term<'a>,
     configuration: SerializerConfiguration,
 }
 
@@ -636,20 +832,38 @@ impl<'a, C: QueryableContextType> ser::SerializeSeq for SerializeSeq<'a, C> {
     where
         T: Serialize,
     {
-        if let Some((head, tail)) = attempt_opt(self.context.unify_list_functor(&self.term))? {
-            let inner_serializer =
-                Serializer::new_with_config(self.context, head, self.configuration.clone());
-            value.serialize(inner_serializer)?;
-            self.term = tail;
+        // unsafe justification: head and tail are term references
+        // in the active context. Passing the same term as list and
+        // tail advances it to the tail of the new list cell.
+        let result = unsafe {
+            crate::fli::PL_unify_list(
+                self.tail.term_ptr(),
+                self.head.term_ptr(),
+                self.tail.term_ptr(),
+            )
+        };
+        if result == 0 {
+            if unsafe { crate::fli::pl_default_exception() } != 0 {
+                return Err(PrologException.into());
+            }
 
-            Ok(())
-        } else {
-            Err(Error::UnificationFailed)
+            return Err(Error::UnificationFailed);
         }
+
+        let reset_term = self.context.new_term_ref();
+        let inner_serializer = Serializer::new_with_config(
+            self.context,
+            self.head.clone(),
+            self.configuration.clone(),
+        );
+        let result = value.serialize(inner_serializer);
+        unsafe { reset_term.reset() };
+
+        result
     }
 
     fn end(self) -> Result<Self::Ok, Self::Error> {
-        attempt_unify(&self.term, Nil)
+        attempt_unify(&self.tail, Nil)
     }
 }
 
@@ -820,6 +1034,9 @@ impl<'a, C: QueryableContextType> ser::SerializeMap for SerializeMap<'a, C> {
         value.serialize(serializer)?;
         let mut key = None;
         std::mem::swap(&mut key, &mut self.last_key);
+        if self.configuration.omits_none() && val_term.is_var() {
+            return Ok(());
+        }
         self.builder
             .add_entry(key.expect("key should have been set"), val_term);
 
@@ -854,6 +1071,9 @@ impl<'a, C: QueryableContextType> ser::SerializeStruct for SerializeMap<'a, C> {
             self.configuration.clone(),
         );
         value.serialize(serializer)?;
+        if self.configuration.omits_none() && value_term.is_var() {
+            return Ok(());
+        }
         self.builder.add_entry(key, value_term);
 
         Ok(())
@@ -887,6 +1107,9 @@ impl<'a, C: QueryableContextType> ser::SerializeStructVariant for SerializeMap<'
             self.configuration.clone(),
         );
         value.serialize(serializer)?;
+        if self.configuration.omits_none() && value_term.is_var() {
+            return Ok(());
+        }
         self.builder.add_entry(key, value_term);
 
         Ok(())
@@ -1539,4 +1762,249 @@ mod tests {
         let r: EnumStruct = context.deserialize_from_term(&term).unwrap();
         assert_eq!(r, v);
     }
+
+    #[test]
+    fn serialize_float_as_rational_is_exact() {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let num: f64 = 0.1;
+        let config = SerializerConfiguration::new().float_repr(FloatRepr::Rational);
+
+        let term = context.new_term_ref();
+        to_term_with_config(&context, &term, &num, config).unwrap();
+        assert_eq!(TermType::Rational, term.term_type());
+
+        let term_string = context.string_from_term(&term).unwrap();
+        assert_eq!("3602879701896397r36028797018963968", term_string);
+
+        let expr = term! {context: float(#&term)}.unwrap();
+        assert_eq!(num.to_bits(), context.eval_f64(&expr).unwrap().to_bits());
+
+        let back: f64 = crate::term::de::from_term(&context, &term).unwrap();
+        assert_eq!(num.to_bits(), back.to_bits());
+    }
+
+    #[test]
+    fn serialize_float_as_string() {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let num: f64 = 0.1;
+        let config = SerializerConfiguration::new().float_repr(FloatRepr::String);
+
+        let term = context.new_term_ref();
+        to_term_with_config(&context, &term, &num, config).unwrap();
+
+        let s: String = term.get().unwrap();
+        assert_eq!("0.1", s);
+        assert_eq!(num.to_bits(), s.parse::<f64>().unwrap().to_bits());
+
+        let back: f64 = crate::term::de::from_term(&context, &term).unwrap();
+        assert_eq!(num.to_bits(), back.to_bits());
+
+        let term = context.new_term_ref();
+        term.unify("not a float").unwrap();
+        assert!(crate::term::de::from_term::<_, f64>(&context, &term).is_err());
+    }
+
+    #[derive(Serialize, serde::Deserialize, Debug, PartialEq)]
+    struct Optionals {
+        a: Option<i64>,
+        b: Option<i64>,
+    }
+
+    fn roundtrip_optionals(config: SerializerConfiguration, expected: &str) {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let value = Optionals {
+            a: Some(1),
+            b: None,
+        };
+
+        let none_repr = config.none_repr;
+        let term = context.new_term_ref();
+        to_term_with_config(&context, &term, &value, config).unwrap();
+
+        let expected = context.term_from_string(expected).unwrap();
+        term.unify(&expected).unwrap();
+
+        let de_config = DeserializerConfiguration::new().none_repr(none_repr);
+        let result: Optionals =
+            crate::term::de::from_term_with_configuration(&context, &term, de_config).unwrap();
+        assert_eq!(value, result);
+    }
+
+    #[test]
+    fn serialize_none_as_atom() {
+        roundtrip_optionals(SerializerConfiguration::new(), "_{a: some(1), b: none}");
+    }
+
+    #[test]
+    fn serialize_none_as_variable() {
+        let config = SerializerConfiguration::new().none_repr(NoneRepr::Variable);
+        roundtrip_optionals(config, "_{a: 1, b: B}");
+    }
+
+    #[test]
+    fn serialize_none_omitted() {
+        let config = SerializerConfiguration::new().none_repr(NoneRepr::Omit);
+        roundtrip_optionals(config, "_{a: 1}");
+    }
+
+    #[test]
+    fn serialize_none_as_custom_atom() {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let value: Option<i64> = None;
+        let config = SerializerConfiguration::new().none_repr(NoneRepr::Atom("null"));
+
+        let term = context.new_term_ref();
+        to_term_with_config(&context, &term, &value, config).unwrap();
+
+        assert_eq!(atom!("null"), term.get::<Atom>().unwrap());
+    }
+
+    #[test]
+    fn roundtrip_none_as_custom_atom() {
+        let config = SerializerConfiguration::new().none_repr(NoneRepr::Atom("nil"));
+        roundtrip_optionals(config, "_{a: some(1), b: nil}");
+    }
+
+    #[test]
+    fn serialize_large_slice() {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let values: Vec<u64> = (0..100_000).collect();
+        let slice: &[u64] = &values;
+
+        let term = context.new_term_ref();
+        to_term(&context, &term, &slice).unwrap();
+
+        let result: Vec<u64> = context.deserialize_from_term(&term).unwrap();
+        assert_eq!(values, result);
+    }
+
+    #[derive(Serialize, serde::Deserialize, Debug, PartialEq)]
+    struct Marker;
+
+    #[derive(Serialize, serde::Deserialize, Debug, PartialEq)]
+    enum Signal {
+        Stop,
+        Go,
+    }
+
+    #[test]
+    fn roundtrip_unit_struct_as_name() {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let term = context.new_term_ref();
+        to_term(&context, &term, &Marker).unwrap();
+        assert_eq!(atom!("Marker"), term.get::<Atom>().unwrap());
+
+        let result: Marker = crate::term::de::from_term(&context, &term).unwrap();
+        assert_eq!(Marker, result);
+    }
+
+    #[test]
+    fn roundtrip_unit_struct_as_nil() {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let config = SerializerConfiguration::new().unit_struct_repr(UnitStructRepr::Nil);
+        let term = context.new_term_ref();
+        to_term_with_config(&context, &term, &Marker, config).unwrap();
+        assert!(term.get::<Nil>().is_ok());
+
+        let result: Marker = crate::term::de::from_term(&context, &term).unwrap();
+        assert_eq!(Marker, result);
+    }
+
+    #[test]
+    fn roundtrip_unit_variants() {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let value = vec![Signal::Stop, Signal::Go];
+        let term = context.new_term_ref();
+        to_term(&context, &term, &value).unwrap();
+
+        let expected = context.term_from_string("['Stop', 'Go']").unwrap();
+        term.unify(&expected).unwrap();
+
+        let result: Vec<Signal> = crate::term::de::from_term(&context, &term).unwrap();
+        assert_eq!(value, result);
+    }
+
+    #[test]
+    fn serialize_vec_as_predicate_argument() {
+        use crate::pred;
+
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let [input, output] = context.new_term_refs();
+        to_term(&context, &input, &vec![3, 1, 2]).unwrap();
+        assert_eq!("[3,1,2]", context.string_from_term(&input).unwrap());
+
+        context
+            .call_once(pred!("sort/2"), [&input, &output])
+            .unwrap();
+
+        let sorted: Vec<u64> = crate::term::de::from_term(&context, &output).unwrap();
+        assert_eq!(vec![1, 2, 3], sorted);
+    }
+
+    #[test]
+    fn serialize_enum_variants_module_qualified() {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let config = SerializerConfiguration::new().variant_module("mymod");
+        let variants = [
+            (EnumStruct::Variant1, "mymod:'Variant1'"),
+            (
+                EnumStruct::Variant2("hello".to_string()),
+                "mymod:'Variant2'(\"hello\")",
+            ),
+            (
+                EnumStruct::Variant3("hello".to_string(), 103),
+                "mymod:'Variant3'(\"hello\",103)",
+            ),
+        ];
+        for (v, expected) in variants {
+            let term = context.new_term_ref();
+            to_term_with_config(&context, &term, &v, config.clone()).unwrap();
+            assert_eq!(expected, context.string_from_term(&term).unwrap());
+
+            let r: EnumStruct = context.deserialize_from_term(&term).unwrap();
+            assert_eq!(r, v);
+        }
+
+        let v = EnumStruct::Variant4 {
+            foo: "hello".to_string(),
+            bar: 103,
+        };
+        let term = context.new_term_ref();
+        to_term_with_config(&context, &term, &v, config).unwrap();
+        assert_eq!(Functor::new(":", 2), term.get::<Functor>().unwrap());
+        assert_eq!(Atom::new("mymod"), term.get_arg::<Atom>(1).unwrap());
+
+        let r: EnumStruct = context.deserialize_from_term(&term).unwrap();
+        assert_eq!(r, v);
+    }
 }
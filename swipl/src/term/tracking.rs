@@ -0,0 +1,134 @@
+//! Tracking of the frames that term references were created in.
+//!
+//! With the `debug_term_tracking` feature enabled, every frame that
+//! is opened gets a unique generation, and every term reference
+//! remembers the generation of the innermost frame that was open
+//! when it was created, along with the location in the source where
+//! it was created. Rewinding a frame gives it a new generation, as
+//! the term references created in it are freed.
+//!
+//! A term reference is live as long as the frame of its generation
+//! is still open. Term references created outside of any frame have
+//! generation 0, and are always considered live.
+//!
+//! As these checks take a lock on every frame operation and on every
+//! use of a term, this feature is meant for debug builds only.
+use crate::engine::current_engine_ptr;
+use lazy_static::*;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+static NEXT_GENERATION: AtomicU64 = AtomicU64::new(1);
+
+lazy_static! {
+    static ref LIVE_FRAMES: Mutex<HashMap<usize, Vec<u64>>> = Mutex::new(HashMap::new());
+}
+
+fn current_engine_key() -> usize {
+    // unsafe justification: frames and terms are only handled while
+    // an engine is active on this thread.
+    unsafe { current_engine_ptr() as usize }
+}
+
+/// Register a newly opened frame on the current engine, returning its generation.
+pub(crate) fn push_frame() -> u64 {
+    let generation = NEXT_GENERATION.fetch_add(1, Ordering::Relaxed);
+    LIVE_FRAMES
+        .lock()
+        .unwrap()
+        .entry(current_engine_key())
+        .or_default()
+        .push(generation);
+
+    generation
+}
+
+/// Unregister the frame with the given generation on the current engine.
+pub(crate) fn pop_frame(generation: u64) {
+    let mut live_frames = LIVE_FRAMES.lock().unwrap();
+    let key = current_engine_key();
+    if let Some(frames) = live_frames.get_mut(&key) {
+        frames.retain(|g| *g != generation);
+        if frames.is_empty() {
+            live_frames.remove(&key);
+        }
+    }
+}
+
+/// Give the frame with the given generation a new generation, returning it.
+pub(crate) fn rewind_frame(generation: u64) -> u64 {
+    let new_generation = NEXT_GENERATION.fetch_add(1, Ordering::Relaxed);
+    let mut live_frames = LIVE_FRAMES.lock().unwrap();
+    if let Some(frames) = live_frames.get_mut(&current_engine_key()) {
+        for g in frames.iter_mut() {
+            if *g == generation {
+                *g = new_generation;
+            }
+        }
+    }
+
+    new_generation
+}
+
+/// Return the generation of the innermost open frame on the current engine.
+pub(crate) fn current_generation() -> u64 {
+    LIVE_FRAMES
+        .lock()
+        .unwrap()
+        .get(&current_engine_key())
+        .and_then(|frames| frames.last().copied())
+        .unwrap_or(0)
+}
+
+/// Check whether the frame with the given generation is still open on the current engine.
+pub(crate) fn is_live(generation: u64) -> bool {
+    generation == 0
+        || LIVE_FRAMES
+            .lock()
+            .unwrap()
+            .get(&current_engine_key())
+            .map(|frames| frames.contains(&generation))
+            .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::prelude::*;
+
+    #[test]
+    #[should_panic(expected = "was created at")]
+    fn stale_term_panics_with_creation_location() {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        // an unmanaged context is not bound to the frame, so the
+        // borrow checker does not stop the term from outliving it.
+        let unmanaged = unsafe { unmanaged_engine_context() };
+        let frame = context.open_frame();
+        let term = unmanaged.new_term_ref();
+        frame.discard();
+
+        let _ = term.unify(42_u64);
+    }
+
+    #[test]
+    fn terms_of_open_frames_are_live() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let outer = context.new_term_ref();
+        let frame = context.open_frame();
+        let inner = frame.new_term_ref();
+        inner.unify(42_u64)?;
+        let frame = frame.rewind();
+        let rewound = frame.new_term_ref();
+        rewound.unify(43_u64)?;
+        frame.close();
+        outer.unify(44_u64)?;
+
+        Ok(())
+    }
+}
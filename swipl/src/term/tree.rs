@@ -0,0 +1,199 @@
+//! Dynamically shaped terms.
+//!
+//! The [term!](crate::prelude::term!) macro is the most convenient
+//! way to construct a term whose shape is known at compile time. When
+//! the shape of a term is only known at runtime, such as when
+//! generating prolog code, a [TermTree] can be built up instead and
+//! then be put into a term reference in one go.
+//!
+//! A [TermTree] can also be retrieved from a term, which allows
+//! inspecting terms of unknown shape from rust. A [PrologValue] does
+//! the same, but also covers big integers, rationals and dicts.
+use super::*;
+use crate::functor::Functor;
+use crate::{term_getable, term_putable};
+
+/// A tree representation of a prolog term.
+///
+/// When retrieved from a term, integers that don't fit in an `i64`,
+/// rationals, dicts and blobs are not supported.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TermTree {
+    Atom(String),
+    Int(i64),
+    Float(f64),
+    Str(String),
+    /// A compound term with the given name and arguments. If there
+    /// are no arguments, this will be an atom.
+    Compound(String, Vec<TermTree>),
+    List(Vec<TermTree>),
+    /// A fresh variable.
+    Var,
+}
+
+/// A prolog value that was retrieved from a term.
+///
+/// Unlike [TermTree], this covers every kind of value a term can
+/// hold, except for blobs. Retrieving a term that contains a blob
+/// fails.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PrologValue {
+    Atom(String),
+    Int(i64),
+    /// An integer that does not fit in an `i64`, in decimal notation.
+    BigInt(String),
+    /// A rational number, written as `<numerator>r<denominator>`.
+    Rational(String),
+    Float(f64),
+    Str(String),
+    /// A compound term with the given name and arguments. If there
+    /// are no arguments, this will be an atom.
+    Compound(String, Vec<PrologValue>),
+    List(Vec<PrologValue>),
+    /// A dict with the given tag and key-value pairs. The tag is
+    /// either an atom or a variable, and the keys are atoms or
+    /// integers.
+    Dict(Box<PrologValue>, Vec<(PrologValue, PrologValue)>),
+    /// An unbound variable.
+    Var,
+}
+
+impl TermTree {
+    /// Put this tree into the given raw term reference.
+    ///
+    /// # Safety
+    /// This creates term references for compound arguments and list
+    /// elements without any context keeping track of them. The caller
+    /// has to ensure an engine is active and that these are cleaned
+    /// up, for example by using a frame.
+    unsafe fn put_term_ref(&self, term: term_t) {
+        match self {
+            TermTree::Atom(name) => {
+                PL_put_chars(
+                    term,
+                    (PL_ATOM | REP_UTF8).try_into().unwrap(),
+                    name.len(),
+                    name.as_bytes().as_ptr() as *const c_char,
+                );
+            }
+            TermTree::Int(num) => {
+                PL_put_int64(term, *num);
+            }
+            TermTree::Float(num) => {
+                PL_put_float(term, *num);
+            }
+            TermTree::Str(s) => {
+                PL_put_chars(
+                    term,
+                    (PL_STRING | REP_UTF8).try_into().unwrap(),
+                    s.len(),
+                    s.as_bytes().as_ptr() as *const c_char,
+                );
+            }
+            TermTree::Compound(name, args) => {
+                let functor = Functor::new(name.as_str(), args.len().try_into().unwrap());
+                if args.is_empty() {
+                    PL_put_functor(term, functor.functor_ptr());
+                } else {
+                    let arg_terms = PL_new_term_refs(args.len().try_into().unwrap());
+                    for (i, arg) in args.iter().enumerate() {
+                        arg.put_term_ref(arg_terms + i);
+                    }
+                    PL_cons_functor_v(term, functor.functor_ptr(), arg_terms);
+                }
+            }
+            TermTree::List(elements) => {
+                PL_put_nil(term);
+                let head = PL_new_term_ref();
+                for element in elements.iter().rev() {
+                    element.put_term_ref(head);
+                    PL_cons_list(term, head, term);
+                }
+            }
+            TermTree::Var => {
+                PL_put_variable(term);
+            }
+        }
+    }
+}
+
+term_putable! {
+    (self:TermTree, term) => {
+        // unsafe justification: the term_putable! macro has checked
+        // that the engine for this term is active. Intermediate term
+        // references are left to the caller to clean up.
+        unsafe { self.put_term_ref(term.term_ptr()) }
+    }
+}
+
+term_getable! {
+    (TermTree, "term_tree", term) => {
+        match term.term_type() {
+            TermType::Variable => Some(TermTree::Var),
+            TermType::Atom => term.get::<Atom>().ok().map(|a| TermTree::Atom(a.name())),
+            TermType::Integer => term.get::<i64>().ok().map(TermTree::Int),
+            TermType::Float => term.get::<f64>().ok().map(TermTree::Float),
+            TermType::String => term.get::<String>().ok().map(TermTree::Str),
+            TermType::Nil => Some(TermTree::List(Vec::new())),
+            TermType::ListPair => term.get::<Vec<TermTree>>().ok().map(TermTree::List),
+            TermType::CompoundTerm => {
+                let functor: Functor = term.get().ok()?;
+                let mut args = Vec::with_capacity(functor.arity() as usize);
+                for i in 1..=functor.arity() as usize {
+                    args.push(term.get_arg(i).ok()?);
+                }
+
+                Some(TermTree::Compound(functor.name_string(), args))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Write the given number term as text.
+fn number_text(term: &Term) -> Option<String> {
+    // unsafe justification: string_from_term runs in a frame of its
+    // own, so no term references are left behind.
+    let context = unsafe { unmanaged_engine_context() };
+    context.string_from_term(term).ok()
+}
+
+term_getable! {
+    (PrologValue, "prolog_value", term) => {
+        match term.term_type() {
+            TermType::Variable => Some(PrologValue::Var),
+            TermType::Atom => term.get::<Atom>().ok().map(|a| PrologValue::Atom(a.name())),
+            TermType::Integer => match term.get::<i64>() {
+                Ok(num) => Some(PrologValue::Int(num)),
+                Err(_) => number_text(term).map(PrologValue::BigInt),
+            },
+            TermType::Rational => number_text(term).map(PrologValue::Rational),
+            TermType::Float => term.get::<f64>().ok().map(PrologValue::Float),
+            TermType::String => term.get::<String>().ok().map(PrologValue::Str),
+            TermType::Nil => Some(PrologValue::List(Vec::new())),
+            TermType::ListPair => term.get::<Vec<PrologValue>>().ok().map(PrologValue::List),
+            TermType::Dict => {
+                let functor: Functor = term.get().ok()?;
+                let tag = term.get_arg(1).ok()?;
+                let mut entries = Vec::with_capacity((functor.arity() as usize - 1) / 2);
+                for i in (2..functor.arity() as usize).step_by(2) {
+                    let value = term.get_arg(i).ok()?;
+                    let key = term.get_arg(i + 1).ok()?;
+                    entries.push((key, value));
+                }
+
+                Some(PrologValue::Dict(Box::new(tag), entries))
+            }
+            TermType::CompoundTerm => {
+                let functor: Functor = term.get().ok()?;
+                let mut args = Vec::with_capacity(functor.arity() as usize);
+                for i in 1..=functor.arity() as usize {
+                    args.push(term.get_arg(i).ok()?);
+                }
+
+                Some(PrologValue::Compound(functor.name_string(), args))
+            }
+            _ => None,
+        }
+    }
+}